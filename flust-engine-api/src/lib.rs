@@ -1,17 +1,53 @@
 use std::ffi::{c_void, CStr};
 
-use dpi::PhysicalSize;
+use dpi::{PhysicalPosition, PhysicalSize};
+
+/// `GL_RGBA8`, see [`FlutterOpenGLHandler::framebuffer_format`].
+pub const GL_RGBA8: u32 = 0x8058;
 
 pub trait FlutterOpenGLHandler {
     fn present(&self) -> bool;
 
+    /// Presents a frame of which only `damage` changed since the previous
+    /// one, each rect being given by its top left corner and its size in
+    /// physical pixels (e.g.: to limit the damage of the window surface).
+    /// `damage` is empty when unknown. The whole frame is presented with
+    /// [`FlutterOpenGLHandler::present`] by default.
+    fn present_with_damage(&self, _damage: &[(PhysicalPosition<f64>, PhysicalSize<f64>)]) -> bool {
+        self.present()
+    }
+
     fn make_current(&self) -> bool;
 
     fn clear_current(&self) -> bool;
 
     fn fbo_with_frame_info_callback(&self, size: PhysicalSize<u32>) -> u32;
 
+    /// The internal format of the framebuffers returned by
+    /// [`FlutterOpenGLHandler::fbo_with_frame_info_callback`], reported to the
+    /// engine when it renders into them through a backing store (i.e.: once
+    /// the compositor fell back to the window framebuffer). [`GL_RGBA8`] by
+    /// default.
+    fn framebuffer_format(&self) -> u32 {
+        GL_RGBA8
+    }
+
+    /// The age of the back buffer the next frame is rendered into (see
+    /// `EGL_EXT_buffer_age`), so that the engine only repaints what changed
+    /// since it was presented. 0 (the default) when its contents are unknown,
+    /// in which case the whole frame is repainted.
+    fn buffer_age(&self) -> u32 {
+        0
+    }
+
     fn make_resource_current(&self) -> bool;
 
     fn gl_proc_resolver(&self, proc: &CStr) -> *mut c_void;
 }
+
+pub trait FlutterSoftwareHandler {
+    /// Presents a frame rendered by the engine on the CPU. `allocation` holds
+    /// `height` rows of `row_bytes` bytes each, in the native 32-bit pixel
+    /// format (i.e.: BGRA on little-endian systems).
+    fn present(&self, allocation: &[u8], row_bytes: usize, height: usize) -> bool;
+}
@@ -1,5 +1,6 @@
 use bindgen::EnumVariation;
-use flust_tools::Flutter;
+use flust_tools::engine_library::EngineLibraryCache;
+use flust_tools::{Build, Flutter};
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
@@ -66,11 +67,9 @@ impl Cargo {
 
         // TODO: Remove hard-coded "debug" Flutter build-mode (once a reasonable
         // strategy of auto-detection has been found).
-        dirs::cache_dir()?
-            .join("flutter-engine-lib")
-            .join("by-engine-version")
-            .join(engine_version)
-            .join("debug")
+        EngineLibraryCache::new()
+            .ok()?
+            .library_dir(&engine_version, Build::Debug)
             .into_os_string()
             .into_string()
             .ok()
@@ -86,6 +85,8 @@ impl BindingsBuilder {
             .default_enum_style(EnumVariation::Rust {
                 non_exhaustive: false,
             })
+            // Flags and actions of semantics nodes are combined into bitmasks.
+            .bitfield_enum("FlutterSemanticsFlag|FlutterSemanticsAction")
             .clang_args(Self::clang_args())
             .generate()?;
 
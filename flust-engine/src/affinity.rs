@@ -0,0 +1,203 @@
+//! Pinning of the engine threads to CPUs, e.g.: the raster thread to the
+//! performance cores of a big.LITTLE board (see
+//! [`crate::builder::FlutterEngineBuilder::with_thread_affinity`]).
+use std::{fmt, io};
+
+use parking_lot::RwLock;
+use tracing::warn;
+
+use crate::CreateError;
+
+/// The threads of the engine that can be pinned to CPUs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineThread {
+    /// The thread running the Dart code of the app.
+    Ui,
+    /// The thread rasterizing the frames (i.e.: the render thread, which the
+    /// raster task runner is backed by).
+    Raster,
+}
+
+impl EngineThread {
+    /// The engine thread whose priority is set by `thread_priority_setter`
+    /// (which is called on the thread when the engine creates it).
+    pub(crate) fn from_priority(priority: flust_engine_sys::FlutterThreadPriority) -> Option<Self> {
+        match priority {
+            flust_engine_sys::FlutterThreadPriority::kDisplay => Some(EngineThread::Ui),
+            flust_engine_sys::FlutterThreadPriority::kRaster => Some(EngineThread::Raster),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for EngineThread {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineThread::Ui => write!(f, "UI"),
+            EngineThread::Raster => write!(f, "raster"),
+        }
+    }
+}
+
+/// The CPUs each engine thread is pinned to (unpinned when unset).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ThreadAffinity {
+    ui: Option<Vec<usize>>,
+    raster: Option<Vec<usize>>,
+}
+
+/// The affinity of the UI thread of the last engine created with one. The
+/// engine doesn't pass a user data to `thread_priority_setter`, so it can't be
+/// looked up from the engine. The raster thread is the render thread of each
+/// engine instead, which is pinned when spawned (see
+/// [`crate::tasks::RenderThread::spawn`]).
+pub(crate) static THREAD_AFFINITY: RwLock<ThreadAffinity> = RwLock::new(ThreadAffinity {
+    ui: None,
+    raster: None,
+});
+
+impl ThreadAffinity {
+    /// Pins `thread` to `cpus` (or unpins it when empty).
+    pub(crate) fn set(&mut self, thread: EngineThread, cpus: Vec<usize>) {
+        let cpus = (!cpus.is_empty()).then_some(cpus);
+        match thread {
+            EngineThread::Ui => self.ui = cpus,
+            EngineThread::Raster => self.raster = cpus,
+        }
+    }
+
+    pub(crate) fn cpus(&self, thread: EngineThread) -> Option<&[usize]> {
+        match thread {
+            EngineThread::Ui => self.ui.as_deref(),
+            EngineThread::Raster => self.raster.as_deref(),
+        }
+    }
+
+    /// The affinity of the threads created by the engine (i.e.: only the UI
+    /// thread, as the raster thread is the render thread), which is applied
+    /// by `thread_priority_setter`.
+    pub(crate) fn engine_threads(&self) -> Option<Self> {
+        self.ui.as_ref().map(|ui| Self {
+            ui: Some(ui.clone()),
+            raster: None,
+        })
+    }
+
+    /// Makes sure the threads are only pinned to the `available` CPUs (i.e.:
+    /// CPUs `0..available`).
+    pub(crate) fn validate(&self, available: usize) -> Result<(), CreateError> {
+        for thread in [EngineThread::Ui, EngineThread::Raster] {
+            let unavailable = self
+                .cpus(thread)
+                .into_iter()
+                .flatten()
+                .find(|&&cpu| cpu >= available);
+            if let Some(&cpu) = unavailable {
+                return Err(CreateError::ThreadAffinityCpuUnavailable {
+                    thread,
+                    cpu,
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Pins the current thread (which is `thread`) to its CPUs, if any, with
+    /// `set_affinity`. Failing to do so isn't fatal, as the engine works the
+    /// same (only slower).
+    pub(crate) fn apply(
+        &self,
+        thread: EngineThread,
+        set_affinity: impl FnOnce(&[usize]) -> io::Result<()>,
+    ) {
+        let Some(cpus) = self.cpus(thread) else {
+            return;
+        };
+
+        if let Err(err) = set_affinity(cpus) {
+            warn!(
+                "Unable to pin the {} thread to CPUs {:?}: {}",
+                thread, cpus, err
+            );
+        }
+    }
+}
+
+/// The number of CPUs configured on the system, including the offline ones
+/// (which threads can still be pinned to).
+#[cfg(target_os = "linux")]
+pub(crate) fn available_cpus() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) } {
+        count if count > 0 => count as usize,
+        _ => 1,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn available_cpus() -> usize {
+    std::thread::available_parallelism().map_or(1, |count| count.get())
+}
+
+/// Pins the current thread to `cpus`.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_current_thread_affinity(cpus: &[usize]) -> io::Result<()> {
+    let mut cpu_set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        unsafe { libc::CPU_SET(cpu, &mut cpu_set) };
+    }
+
+    // A pid of 0 is the calling thread.
+    let result =
+        unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &cpu_set) };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn set_current_thread_affinity(_cpus: &[usize]) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_configured_threads_are_pinned() {
+        let mut affinity = ThreadAffinity::default();
+        affinity.set(EngineThread::Raster, vec![4, 5]);
+        affinity.set(EngineThread::Ui, vec![]);
+
+        assert!(affinity.validate(6).is_ok());
+        assert!(matches!(
+            affinity.validate(4),
+            Err(CreateError::ThreadAffinityCpuUnavailable {
+                thread: EngineThread::Raster,
+                cpu: 4,
+                available: 4,
+            })
+        ));
+
+        let mut pinned = vec![];
+        for priority in [
+            flust_engine_sys::FlutterThreadPriority::kBackground,
+            flust_engine_sys::FlutterThreadPriority::kDisplay,
+            flust_engine_sys::FlutterThreadPriority::kRaster,
+        ] {
+            let Some(thread) = EngineThread::from_priority(priority) else {
+                continue;
+            };
+            affinity.apply(thread, |cpus| {
+                pinned.push((thread, cpus.to_vec()));
+                Ok(())
+            });
+        }
+        assert_eq!(pinned, vec![(EngineThread::Raster, vec![4, 5])]);
+    }
+}
@@ -1,20 +1,85 @@
 use parking_lot::Mutex;
 
-use std::path::PathBuf;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
+use tracing::warn;
+
+use crate::affinity::{available_cpus, EngineThread, ThreadAffinity};
+use crate::diagnostics::EngineErrorHandler;
+use crate::ffi::FlutterLocale;
+use crate::semantics::FlutterSemanticsHandler;
 use crate::tasks::TaskRunnerHandler;
-use crate::{CreateError, FlutterEngine, FlutterVsyncHandler};
+use crate::{CreateError, FlutterEngine, FlutterVsyncHandler, PlatformResolvedLocaleHandler};
+
+/// Checks whether the renderer meets the requirements of Impeller, returning
+/// the reason why it doesn't otherwise.
+pub type ImpellerRequirementsCheck = Box<dyn FnOnce() -> Result<(), String>>;
+
+const ENABLE_IMPELLER_SWITCH: &str = "--enable-impeller";
+
+/// How Dart code is run, which is determined by the linked engine library:
+/// debug libraries run JIT compiled code (from the kernel blob in the assets),
+/// while profile and release libraries run AOT compiled code (from the AOT
+/// library).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeMode {
+    Jit,
+    Aot,
+}
+
+impl RuntimeMode {
+    /// The runtime mode of the linked engine library.
+    pub fn of_engine_library() -> Self {
+        Self::from_runs_aot_compiled_dart_code(FlutterEngine::runs_aot_compiled_dart_code())
+    }
+
+    fn from_runs_aot_compiled_dart_code(runs_aot_compiled_dart_code: bool) -> Self {
+        if runs_aot_compiled_dart_code {
+            Self::Aot
+        } else {
+            Self::Jit
+        }
+    }
+}
+
+impl fmt::Display for RuntimeMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeMode::Jit => write!(f, "JIT"),
+            RuntimeMode::Aot => write!(f, "AOT"),
+        }
+    }
+}
 
 pub struct FlutterEngineBuilder {
     pub(crate) platform_handler: Option<Arc<dyn TaskRunnerHandler + Send + Sync>>,
     pub(crate) vsync_handler: Option<Arc<Mutex<dyn FlutterVsyncHandler + Send>>>,
+    pub(crate) semantics_handler: Option<Arc<Mutex<dyn FlutterSemanticsHandler + Send>>>,
+    pub(crate) engine_error_handler: Option<Arc<Mutex<dyn EngineErrorHandler + Send>>>,
     pub(crate) compositor_enabled: bool,
+    pub(crate) compositor_fallback: bool,
+    pub(crate) software_rendering: bool,
+    pub(crate) enable_impeller: bool,
+    pub(crate) impeller_requirements_check: Option<ImpellerRequirementsCheck>,
+    pub(crate) runtime_mode: Option<RuntimeMode>,
+    pub(crate) paths_relative_to_executable: bool,
     pub(crate) aot_library: PathBuf,
+    pub(crate) aot_data: Option<Vec<u8>>,
     pub(crate) assets: PathBuf,
     pub(crate) icu_data: PathBuf,
     pub(crate) persistent_cache: PathBuf,
     pub(crate) args: Vec<String>,
+    pub(crate) log_tag: Option<String>,
+    pub(crate) pre_engine_restart_handler: Option<Box<dyn Fn() + Send>>,
+    pub(crate) platform_resolved_locale_handler: Option<Box<PlatformResolvedLocaleHandler>>,
+    pub(crate) platform_task_budget: Option<Duration>,
+    pub(crate) thread_affinity: ThreadAffinity,
 }
 
 impl FlutterEngineBuilder {
@@ -23,12 +88,26 @@ impl FlutterEngineBuilder {
         Self {
             platform_handler: None,
             vsync_handler: None,
+            semantics_handler: None,
+            engine_error_handler: None,
             compositor_enabled: false,
+            compositor_fallback: false,
+            software_rendering: false,
+            enable_impeller: false,
+            impeller_requirements_check: None,
+            runtime_mode: None,
+            paths_relative_to_executable: false,
             aot_library: Default::default(),
+            aot_data: None,
             assets: Default::default(),
             icu_data: Default::default(),
             persistent_cache: Default::default(),
             args: vec![],
+            log_tag: None,
+            pre_engine_restart_handler: None,
+            platform_resolved_locale_handler: None,
+            platform_task_budget: None,
+            thread_affinity: ThreadAffinity::default(),
         }
     }
 
@@ -48,16 +127,155 @@ impl FlutterEngineBuilder {
         self
     }
 
+    pub fn with_semantics_handler(
+        mut self,
+        handler: Arc<Mutex<dyn FlutterSemanticsHandler + Send>>,
+    ) -> Self {
+        self.semantics_handler = Some(handler);
+        self
+    }
+
+    /// Sets the handler notified of engine-internal failures detected after
+    /// the engine is running. See [`crate::diagnostics`] for the failure
+    /// classes that can be detected.
+    pub fn with_engine_error_handler(
+        mut self,
+        handler: Arc<Mutex<dyn EngineErrorHandler + Send>>,
+    ) -> Self {
+        self.engine_error_handler = Some(handler);
+        self
+    }
+
+    /// Sets the function called on the platform thread when the engine is
+    /// about to restart (e.g.: on hot restart), so that state tied to the
+    /// previous Dart isolate (e.g.: the text input client or the pressed keys)
+    /// can be reset.
+    pub fn with_pre_engine_restart_handler(mut self, handler: impl Fn() + Send + 'static) -> Self {
+        self.pre_engine_restart_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the function picking the locale the app should use among the
+    /// ones it supports (i.e.: the `supportedLocales` of the app), returning
+    /// its index, so that the first frame is already localized. Returning
+    /// `None` leaves the locale unresolved.
+    ///
+    /// The engine doesn't tell which engine the locales are resolved for, so
+    /// the handler is shared by every engine of the process (the last one set
+    /// wins).
+    pub fn with_platform_resolved_locale_handler(
+        mut self,
+        handler: impl Fn(&[FlutterLocale]) -> Option<usize> + Send + Sync + 'static,
+    ) -> Self {
+        self.platform_resolved_locale_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Bounds the time spent running platform tasks (including the callbacks
+    /// of [`FlutterEngine::run_on_platform_thread`]) per call of
+    /// [`FlutterEngine::execute_platform_tasks`]. The tasks left once it's
+    /// exceeded are deferred to the next call, so that the event loop handles
+    /// input and frame events in between. This trades throughput for latency:
+    /// a flood of tasks takes longer to be worked through, but doesn't cause
+    /// jank. By default (`None`), all the expired tasks are run at once.
+    pub fn with_platform_task_budget(mut self, budget: Option<Duration>) -> Self {
+        self.platform_task_budget = budget;
+        self
+    }
+
+    /// Pins `thread` to `cpus` (i.e.: CPU indices, as in `/proc/cpuinfo`),
+    /// e.g.: the raster thread to the performance cores of a big.LITTLE
+    /// board, for steadier frame times. An empty set leaves the thread
+    /// unpinned. Building the engine fails if a CPU isn't available, while
+    /// failing to pin a thread (e.g.: on other systems than Linux) only logs
+    /// a warning.
+    ///
+    /// Like the locale handler, the affinity of the UI thread is shared by
+    /// every engine of the process (the last one set wins).
+    pub fn with_thread_affinity(
+        mut self,
+        thread: EngineThread,
+        cpus: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        self.thread_affinity.set(thread, cpus.into_iter().collect());
+        self
+    }
+
     pub fn with_compositor_enabled(mut self, enabled: bool) -> Self {
         self.compositor_enabled = enabled;
         self
     }
 
+    /// Falls back to rendering straight into the window framebuffer of each
+    /// view (i.e.: through its [`flust_engine_api::FlutterOpenGLHandler`], as
+    /// without the compositor) when the compositor fails to create a backing
+    /// store before presenting its first frame (e.g.: because of GL limits),
+    /// with a warning. Platform views can't be composited once fallen back.
+    ///
+    /// Without the fallback, or if presenting the first frame fails, the
+    /// failure is reported as [`EngineFailure::CompositorUnavailable`].
+    ///
+    /// [`EngineFailure::CompositorUnavailable`]: crate::diagnostics::EngineFailure::CompositorUnavailable
+    pub fn with_compositor_fallback(mut self, enabled: bool) -> Self {
+        self.compositor_fallback = enabled;
+        self
+    }
+
+    /// Renders on the CPU instead of through OpenGL, presenting frames to the
+    /// [`flust_engine_api::FlutterSoftwareHandler`] of the implicit view (see
+    /// [`crate::view::FlutterView::new_software`]). Meant for systems without
+    /// a usable GPU. The compositor is disabled (with a warning) when
+    /// software rendering is enabled.
+    pub fn with_software_rendering(mut self, enabled: bool) -> Self {
+        self.software_rendering = enabled;
+        self
+    }
+
+    /// Opts into the Impeller renderer instead of Skia.
+    pub fn with_enable_impeller(mut self, enabled: bool) -> Self {
+        self.enable_impeller = enabled;
+        self
+    }
+
+    /// Sets the check run before enabling Impeller. When the check fails, the
+    /// engine falls back to Skia (with a warning).
+    pub fn with_impeller_requirements_check<F>(mut self, check: F) -> Self
+    where
+        F: FnOnce() -> Result<(), String> + 'static,
+    {
+        self.impeller_requirements_check = Some(Box::new(check));
+        self
+    }
+
+    /// Declares the runtime mode the app is deployed with. Building the
+    /// engine fails if the linked engine library runs Dart code in a different
+    /// mode. When unset, the mode of the engine library is used.
+    pub fn with_runtime_mode(mut self, mode: RuntimeMode) -> Self {
+        self.runtime_mode = Some(mode);
+        self
+    }
+
+    /// Sets the path of the AOT library (`libapp.so`). When unset, it's
+    /// detected from the assets path (see [`aot_library_candidates`]) when
+    /// running AOT compiled Dart code.
     pub fn with_aot_library_path(mut self, path: PathBuf) -> Self {
         self.aot_library = path;
         self
     }
 
+    /// Loads the AOT compiled Dart code from an ELF snapshot in memory (e.g.:
+    /// embedded in the executable, or downloaded) instead of the AOT library
+    /// path. As the embedder API only loads snapshots from files, it's written
+    /// to a temporary file, which is removed once the engine is dropped.
+    pub fn with_aot_data_bytes(mut self, bytes: Vec<u8>) -> Self {
+        self.aot_data = Some(bytes);
+        self
+    }
+
+    /// Sets the path of the `flutter_assets` directory. Like the ICU data path,
+    /// it's canonicalized when building the engine (see
+    /// [`Self::with_paths_relative_to_executable`] for how relative paths are
+    /// resolved), and building fails if it doesn't exist.
     pub fn with_asset_path(mut self, path: PathBuf) -> Self {
         self.assets = path;
         self
@@ -68,11 +286,27 @@ impl FlutterEngineBuilder {
         self
     }
 
+    /// Resolves relative assets and ICU data paths against the directory of
+    /// the executable, instead of the current working directory (the default),
+    /// so that bundles can be launched from anywhere.
+    pub fn with_paths_relative_to_executable(mut self, enabled: bool) -> Self {
+        self.paths_relative_to_executable = enabled;
+        self
+    }
+
     pub fn with_persistent_cache_path(mut self, path: PathBuf) -> Self {
         self.persistent_cache = path;
         self
     }
 
+    /// Sets the tag of the messages logged by the engine and the Dart code
+    /// (`flutter` by default), reported as the `tag` field of their `tracing`
+    /// events. The tag must not contain NUL bytes (or [`Self::build`] fails).
+    pub fn with_log_tag(mut self, tag: impl Into<String>) -> Self {
+        self.log_tag = Some(tag.into());
+        self
+    }
+
     pub fn with_arg(mut self, arg: String) -> Self {
         self.args.push(arg);
         self
@@ -85,7 +319,341 @@ impl FlutterEngineBuilder {
         self
     }
 
-    pub fn build(self) -> Result<FlutterEngine, CreateError> {
-        FlutterEngine::new(self)
+    pub fn build(mut self) -> Result<FlutterEngine, CreateError> {
+        let executable_dir = self
+            .paths_relative_to_executable
+            .then(executable_dir)
+            .transpose()?;
+        self.assets = resolve_path(&self.assets, executable_dir.as_deref())?;
+        self.icu_data = resolve_path(&self.icu_data, executable_dir.as_deref())?;
+
+        let runtime_mode =
+            validate_runtime_mode(self.runtime_mode, RuntimeMode::of_engine_library())?;
+        if runtime_mode == RuntimeMode::Aot
+            && self.aot_data.is_none()
+            && self.aot_library.as_os_str().is_empty()
+        {
+            self.aot_library = detect_aot_library(&self.assets)?;
+        }
+        match &self.aot_data {
+            Some(aot_data) if runtime_mode == RuntimeMode::Aot => validate_aot_data(aot_data)?,
+            _ => validate_dart_artifacts(
+                runtime_mode == RuntimeMode::Aot,
+                &self.assets,
+                &self.aot_library,
+            )?,
+        }
+
+        self.thread_affinity.validate(available_cpus())?;
+        let log_tag = self.log_tag.as_deref().map(log_tag_cstring).transpose()?;
+        self.apply_renderer_switches();
+
+        FlutterEngine::new(self, log_tag)
+    }
+
+    fn apply_renderer_switches(&mut self) {
+        if !self.enable_impeller {
+            return;
+        }
+
+        if let Some(check) = self.impeller_requirements_check.take() {
+            if let Err(reason) = check() {
+                warn!(
+                    "Impeller is not supported ({}), falling back to Skia",
+                    reason
+                );
+                return;
+            }
+        }
+
+        self.args.push(ENABLE_IMPELLER_SWITCH.into());
+    }
+}
+
+/// Makes sure the declared runtime mode (if any) matches the mode of the
+/// engine library, returning the mode to run with.
+fn validate_runtime_mode(
+    declared: Option<RuntimeMode>,
+    engine_library: RuntimeMode,
+) -> Result<RuntimeMode, CreateError> {
+    match declared {
+        Some(declared) if declared != engine_library => Err(CreateError::RuntimeModeMismatch {
+            declared,
+            engine_library,
+        }),
+        _ => Ok(engine_library),
+    }
+}
+
+fn executable_dir() -> Result<PathBuf, CreateError> {
+    let executable = std::env::current_exe().map_err(CreateError::ExecutablePathUnknown)?;
+    Ok(executable
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default())
+}
+
+/// Canonicalizes `path` (resolving symlinks), so that the engine gets the same
+/// absolute path regardless of the working directory. Relative paths are
+/// resolved against `base` when set, and the working directory otherwise.
+fn resolve_path(path: &Path, base: Option<&Path>) -> Result<PathBuf, CreateError> {
+    let path = match base {
+        Some(base) if path.is_relative() => base.join(path),
+        _ => path.to_path_buf(),
+    };
+
+    path.canonicalize()
+        .map_err(|source| CreateError::PathResolutionFailed { path, source })
+}
+
+/// Converts the log tag for the engine, which can't pass a tag containing a
+/// NUL byte.
+fn log_tag_cstring(tag: &str) -> Result<CString, CreateError> {
+    CString::new(tag).map_err(|_| CreateError::InvalidLogTag(tag.to_owned()))
+}
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const KERNEL_BLOB_FILE_NAME: &str = "kernel_blob.bin";
+const AOT_LIBRARY_FILE_NAME: &str = "libapp.so";
+
+/// Makes sure the Dart artifacts match the mode of the engine library: an AOT
+/// library for AOT (profile/release) engines, and a kernel blob in the assets
+/// for JIT (debug) engines. Mixing up both is a common packaging mistake,
+/// which otherwise results in a cryptic engine failure.
+fn validate_dart_artifacts(
+    runs_aot_compiled_dart_code: bool,
+    assets: &Path,
+    aot_library: &Path,
+) -> Result<(), CreateError> {
+    if runs_aot_compiled_dart_code {
+        return validate_aot_library(aot_library);
+    }
+
+    let kernel_blob = assets.join(KERNEL_BLOB_FILE_NAME);
+    if !kernel_blob.is_file() {
+        return Err(CreateError::KernelBlobNotFound(kernel_blob));
+    }
+
+    Ok(())
+}
+
+/// The paths the AOT library is looked up at when it isn't set, in order:
+/// within the assets directory, next to it, and in the `lib` directory of a
+/// Flutter Linux bundle (i.e.: `lib/libapp.so` next to `data/flutter_assets`).
+pub fn aot_library_candidates(assets: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![assets.join(AOT_LIBRARY_FILE_NAME)];
+    if let Some(parent) = assets.parent() {
+        candidates.push(parent.join(AOT_LIBRARY_FILE_NAME));
+        if let Some(bundle) = parent.parent() {
+            candidates.push(bundle.join("lib").join(AOT_LIBRARY_FILE_NAME));
+        }
+    }
+    candidates
+}
+
+fn detect_aot_library(assets: &Path) -> Result<PathBuf, CreateError> {
+    let candidates = aot_library_candidates(assets);
+    match candidates.iter().find(|candidate| candidate.is_file()) {
+        Some(aot_library) => Ok(aot_library.clone()),
+        None => Err(CreateError::AOTLibraryNotDetected(candidates)),
+    }
+}
+
+/// Makes sure the AOT library exists and looks like an ELF shared object, so
+/// that release-mode packaging errors are reported before the engine fails
+/// with a less descriptive error.
+fn validate_aot_library(path: &Path) -> Result<(), CreateError> {
+    if !path.is_file() {
+        return Err(CreateError::AOTLibraryNotFound(path.to_path_buf()));
+    }
+
+    let mut magic = [0u8; 4];
+    let is_elf = File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| magic == ELF_MAGIC)
+        .unwrap_or(false);
+
+    if !is_elf {
+        return Err(CreateError::InvalidAOTLibrary(path.to_path_buf()));
+    }
+
+    Ok(())
+}
+
+/// Makes sure AOT data passed as bytes looks like an ELF snapshot.
+fn validate_aot_data(aot_data: &[u8]) -> Result<(), CreateError> {
+    if !aot_data.starts_with(&ELF_MAGIC) {
+        return Err(CreateError::InvalidAOTDataBytes);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn relative_paths_are_canonicalized() {
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(resolve_path(Path::new("."), None).unwrap(), cwd);
+
+        let dir = TempDir::new("flust-engine-bundle");
+        let base = dir.path();
+        std::fs::create_dir_all(base.join("data/flutter_assets")).unwrap();
+
+        let resolved = resolve_path(Path::new("data/../data/flutter_assets"), Some(base));
+        let missing = resolve_path(Path::new("data/icudtl.dat"), Some(base));
+        let expected = base.canonicalize().unwrap().join("data/flutter_assets");
+
+        assert_eq!(resolved.unwrap(), expected);
+        assert!(matches!(
+            missing,
+            Err(CreateError::PathResolutionFailed { path, .. }) if path == base.join("data/icudtl.dat")
+        ));
+    }
+
+    #[test]
+    fn log_tags_with_nul_bytes_are_rejected() {
+        assert_eq!(log_tag_cstring("app").unwrap().as_bytes(), b"app");
+        assert!(matches!(
+            log_tag_cstring("a\0pp"),
+            Err(CreateError::InvalidLogTag(tag)) if tag == "a\0pp"
+        ));
+    }
+
+    #[test]
+    fn aot_library_is_detected_in_bundle() {
+        let dir = TempDir::new("flust-engine-aot-bundle");
+        let base = dir.path();
+        let assets = base.join("data/flutter_assets");
+        std::fs::create_dir_all(&assets).unwrap();
+        std::fs::create_dir_all(base.join("lib")).unwrap();
+
+        let missing = detect_aot_library(&assets);
+        std::fs::write(base.join("lib/libapp.so"), ELF_MAGIC).unwrap();
+        let detected = detect_aot_library(&assets);
+        std::fs::write(base.join("data/libapp.so"), ELF_MAGIC).unwrap();
+        let next_to_assets = detect_aot_library(&assets);
+
+        assert!(matches!(
+            missing,
+            Err(CreateError::AOTLibraryNotDetected(candidates)) if candidates.len() == 3
+        ));
+        assert_eq!(detected.unwrap(), base.join("lib/libapp.so"));
+        assert_eq!(next_to_assets.unwrap(), base.join("data/libapp.so"));
+    }
+
+    #[test]
+    fn missing_aot_library_is_reported() {
+        let dir = TempDir::new("flust-engine-missing-aot-library");
+        let path = dir.path().join("libapp.so");
+
+        assert!(matches!(
+            validate_aot_library(&path),
+            Err(CreateError::AOTLibraryNotFound(p)) if p == path
+        ));
+    }
+
+    #[test]
+    fn non_elf_aot_library_is_reported() {
+        let dir = TempDir::new("flust-engine-invalid-aot-library");
+        let path = dir.path().join("libapp.so");
+        std::fs::write(&path, b"not an elf file").unwrap();
+
+        assert!(matches!(
+            validate_aot_library(&path),
+            Err(CreateError::InvalidAOTLibrary(_))
+        ));
+    }
+
+    #[test]
+    fn non_elf_aot_data_is_reported() {
+        assert!(matches!(
+            validate_aot_data(b"not an elf file"),
+            Err(CreateError::InvalidAOTDataBytes)
+        ));
+        assert!(validate_aot_data(&[ELF_MAGIC.as_slice(), b"snapshot"].concat()).is_ok());
+    }
+
+    #[test]
+    fn mismatched_dart_artifacts_are_reported() {
+        let dir = TempDir::new("flust-engine-jit-assets");
+        let assets = dir.path();
+        let aot_library = assets.join("libapp.so");
+
+        // JIT engine, AOT assets (no kernel blob).
+        let jit_result = validate_dart_artifacts(false, assets, &aot_library);
+
+        // AOT engine, JIT assets (kernel blob without an AOT library).
+        std::fs::write(assets.join(KERNEL_BLOB_FILE_NAME), b"").unwrap();
+        let aot_result = validate_dart_artifacts(true, assets, &aot_library);
+        let jit_ok = validate_dart_artifacts(false, assets, &aot_library);
+
+        assert!(matches!(
+            jit_result,
+            Err(CreateError::KernelBlobNotFound(_))
+        ));
+        assert!(matches!(
+            aot_result,
+            Err(CreateError::AOTLibraryNotFound(p)) if p == aot_library
+        ));
+        assert!(jit_ok.is_ok());
+    }
+
+    #[test]
+    fn declaring_aot_against_jit_library_errors() {
+        let jit_library = RuntimeMode::from_runs_aot_compiled_dart_code(false);
+
+        assert!(matches!(
+            validate_runtime_mode(Some(RuntimeMode::Aot), jit_library),
+            Err(CreateError::RuntimeModeMismatch {
+                declared: RuntimeMode::Aot,
+                engine_library: RuntimeMode::Jit,
+            })
+        ));
+        assert!(matches!(
+            validate_runtime_mode(Some(RuntimeMode::Jit), jit_library),
+            Ok(RuntimeMode::Jit)
+        ));
+        assert!(matches!(
+            validate_runtime_mode(None, jit_library),
+            Ok(RuntimeMode::Jit)
+        ));
+    }
+
+    #[test]
+    fn impeller_switch_is_emitted() {
+        let mut builder = FlutterEngineBuilder::new().with_enable_impeller(true);
+        builder.apply_renderer_switches();
+        assert_eq!(builder.args, vec![ENABLE_IMPELLER_SWITCH.to_string()]);
+
+        let mut builder = FlutterEngineBuilder::new();
+        builder.apply_renderer_switches();
+        assert!(builder.args.is_empty());
+    }
+
+    #[test]
+    fn impeller_falls_back_when_requirements_are_not_met() {
+        let checked = Arc::new(Mutex::new(false));
+        let checked_clone = checked.clone();
+
+        let mut builder = FlutterEngineBuilder::new()
+            .with_enable_impeller(true)
+            .with_impeller_requirements_check(move || {
+                *checked_clone.lock() = true;
+                Err("OpenGL ES 3.0 is required".into())
+            });
+        builder.apply_renderer_switches();
+
+        assert!(*checked.lock());
+        assert!(builder.args.is_empty());
+
+        let mut builder = FlutterEngineBuilder::new()
+            .with_enable_impeller(true)
+            .with_impeller_requirements_check(|| Ok(()));
+        builder.apply_renderer_switches();
+        assert_eq!(builder.args, vec![ENABLE_IMPELLER_SWITCH.to_string()]);
     }
 }
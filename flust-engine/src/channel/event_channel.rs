@@ -1,116 +1,308 @@
-use std::sync::{Arc, RwLock, Weak};
+//! Streams of events sent to Dart, implementing the protocol of Flutter's
+//! `EventChannel`: Dart starts and stops listening with the `listen` and
+//! `cancel` method calls, and events are sent as success or error envelopes,
+//! until an empty message ends the stream.
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use tracing::error;
+use serde::Serialize;
+use tracing::{trace, warn};
 
-use crate::channel::method_channel::MethodCallHandler;
-use crate::{
-    channel::{ChannelImpl, EventHandler, MethodCallHandler, MethodChannel},
-    codec::{standard_codec::CODEC, MethodCall, MethodCodec, Value},
-    error::MethodCallError,
-    FlutterEngine, FlutterEngineWeakRef,
-};
+use crate::channel::platform_message::PlatformMessage;
+use crate::channel::{Channel, MethodCall, MethodCallHandler, MethodChannel, MethodError};
+use crate::codec::value::to_value;
+use crate::codec::{self, MethodCallResult, MethodCodec, Value};
+use crate::{FlutterEngine, FlutterEngineWeakRef};
 
 pub trait EventHandler {
-    fn on_listen(&mut self, args: Value, engine: FlutterEngine) -> Result<Value, MethodCallError>;
-    fn on_cancel(&mut self, engine: FlutterEngine) -> Result<Value, MethodCallError>;
+    /// Called when Dart starts listening, with the arguments of
+    /// `receiveBroadcastStream`. Events are sent through `sink` (from any
+    /// thread) until the stream is cancelled. Returning an error fails the
+    /// `listen` call on the Dart side.
+    fn on_listen(&mut self, args: Value, sink: EventSink) -> Result<(), MethodError<Value>>;
+
+    /// Called when Dart stops listening (or starts listening again, which
+    /// replaces the current stream). The sink of the stream is closed already.
+    fn on_cancel(&mut self, args: Value) -> Result<(), MethodError<Value>>;
+
+    /// Called when Dart starts or stops listening to the channel (i.e.: sets
+    /// or removes its handler, which happens before `listen` and after
+    /// `cancel`). See [`Channel::listening_changed`].
+    fn on_listening_changed(&mut self, _listening: bool) {}
 }
 
-pub struct EventChannel {
-    name: String,
-    engine: FlutterEngineWeakRef,
-    method_handler: Arc<RwLock<dyn MethodCallHandler + Send + Sync>>,
-    plugin_name: Option<&'static str>,
+/// Sends the events of a stream to Dart. It can be cloned and used from any
+/// thread, events are sent from the platform thread. Once the stream was
+/// cancelled or ended, events are dropped.
+#[derive(Clone)]
+pub struct EventSink {
+    inner: Arc<EventSinkInner>,
 }
 
-struct EventChannelMethodCallHandler {
-    event_handler: Weak<RwLock<dyn EventHandler + Send + Sync>>,
+struct EventSinkInner {
+    channel: String,
+    engine: FlutterEngineWeakRef,
+    codec: &'static dyn MethodCodec,
+    active: AtomicBool,
 }
 
-impl EventChannel {
-    pub fn new<N: AsRef<str>>(
-        name: N,
-        handler: Weak<RwLock<dyn EventHandler + Send + Sync>>,
-    ) -> Self {
+impl EventSink {
+    fn new(channel: &str, engine: FlutterEngineWeakRef, codec: &'static dyn MethodCodec) -> Self {
         Self {
-            name: name.as_ref().to_owned(),
-            engine: Default::default(),
-            method_handler: Arc::new(RwLock::new(EventChannelMethodCallHandler::new(handler))),
-            plugin_name: None,
+            inner: Arc::new(EventSinkInner {
+                channel: channel.to_owned(),
+                engine,
+                codec,
+                active: AtomicBool::new(true),
+            }),
         }
     }
 
-    /// When flutter listen to a stream of events using EventChannel.
-    /// This method send back a success event.
-    /// It can be call multiple times to simulate stream.
-    pub fn send_success_event(&self, data: &Value) {
-        let buf = self.codec().encode_success_envelope(data);
-        self.send_buffer(&buf);
+    /// Whether the stream is still listened to.
+    pub fn is_active(&self) -> bool {
+        self.inner.active.load(Ordering::Acquire)
+    }
+
+    pub fn success<T>(&self, event: T)
+    where
+        T: Serialize,
+    {
+        let event = to_value(event).expect("Failed to encode event to value");
+        self.send(self.inner.codec.encode_success_envelope(&event), false);
+    }
+
+    pub fn error<T, S1, S2>(&self, code: S1, message: S2, details: T)
+    where
+        T: Serialize,
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        let details = to_value(details).expect("Failed to encode details to value");
+        let buf = self
+            .inner
+            .codec
+            .encode_error_envelope(code.as_ref(), message.as_ref(), &details);
+        self.send(buf, false);
+    }
+
+    /// Ends the stream (i.e.: closes it on the Dart side). Events sent
+    /// afterwards are dropped.
+    pub fn end_of_stream(&self) {
+        self.send(vec![], true);
+    }
+
+    fn close(&self) {
+        self.inner.active.store(false, Ordering::Release);
+    }
+
+    fn send(&self, buf: Vec<u8>, end_of_stream: bool) {
+        let Some(engine) = self.inner.engine.upgrade() else {
+            return;
+        };
+
+        // Checked on the platform thread, which the stream is cancelled from,
+        // so that no event is sent after the `cancel` call was answered.
+        let inner = self.inner.clone();
+        engine.run_on_platform_thread(move |engine| {
+            if !inner.active.load(Ordering::Acquire) {
+                return;
+            }
+            if end_of_stream {
+                inner.active.store(false, Ordering::Release);
+            }
+
+            engine.send_platform_message(PlatformMessage {
+                channel: Cow::Borrowed(inner.channel.as_str()),
+                message: &buf,
+                response_handle: None,
+            });
+        });
+    }
+}
+
+pub struct EventChannel {
+    method_channel: MethodChannel,
+}
+
+impl EventChannel {
+    pub fn new<N, H>(name: N, event_handler: H, codec: &'static dyn MethodCodec) -> Self
+    where
+        N: AsRef<str>,
+        H: EventHandler + 'static,
+    {
+        let handler = EventChannelHandler {
+            name: name.as_ref().to_owned(),
+            codec,
+            event_handler: Box::new(event_handler),
+            sink: None,
+        };
+
+        Self {
+            method_channel: MethodChannel::new(name, handler, codec),
+        }
     }
 
-    /// When flutter listen to a stream of events using EventChannel.
-    /// This method send back a error event.
-    /// It can be call multiple times to simulate stream.
-    pub fn send_error_event(&self, code: &str, message: &str, data: &Value) {
-        let buf = self.codec().encode_error_envelope(code, message, data);
-        self.send_buffer(&buf);
+    /// Feeds `call` (i.e.: `listen` or `cancel`) to the event handler of this
+    /// channel, returning the response it sent. See
+    /// [`MethodChannel::dispatch_for_test`].
+    pub fn dispatch_for_test(&self, call: codec::MethodCall) -> Option<MethodCallResult> {
+        self.method_channel.dispatch_for_test(call)
     }
 }
 
-impl ChannelImpl for EventChannel {
+impl Channel for EventChannel {
     fn name(&self) -> &str {
-        self.name.as_str()
+        self.method_channel.name()
     }
 
     fn engine(&self) -> Option<FlutterEngine> {
-        self.engine.upgrade()
+        self.method_channel.engine()
     }
 
-    fn init(&mut self, engine: FlutterEngineWeakRef, plugin_name: &'static str) {
-        if self.engine.upgrade().is_some() {
-            error!("Channel {} was already initialized", self.name);
-        }
-        self.engine = engine;
-        self.plugin_name.replace(plugin_name);
+    fn init(&mut self, engine: FlutterEngineWeakRef) {
+        self.method_channel.init(engine);
     }
 
-    fn plugin_name(&self) -> &'static str {
-        self.plugin_name.unwrap()
+    fn handle_platform_message(&self, msg: PlatformMessage) {
+        self.method_channel.handle_platform_message(msg);
     }
-}
 
-impl MethodChannel for EventChannel {
-    fn method_handler(&self) -> Option<Arc<RwLock<dyn MethodCallHandler + Send + Sync>>> {
-        Some(Arc::clone(&self.method_handler))
+    fn listening_changed(&self, listening: bool) {
+        self.method_channel.listening_changed(listening);
     }
+}
 
-    fn codec(&self) -> &'static dyn MethodCodec {
-        &CODEC
+struct EventChannelHandler {
+    name: String,
+    codec: &'static dyn MethodCodec,
+    event_handler: Box<dyn EventHandler>,
+    sink: Option<EventSink>,
+}
+
+impl EventChannelHandler {
+    fn cancel(&mut self, args: Value) -> Option<Result<(), MethodError<Value>>> {
+        let sink = self.sink.take()?;
+        sink.close();
+        Some(self.event_handler.on_cancel(args))
     }
 }
 
-impl EventChannelMethodCallHandler {
-    pub fn new(handler: Weak<RwLock<dyn EventHandler + Send + Sync>>) -> Self {
-        Self {
-            event_handler: handler,
+impl MethodCallHandler for EventChannelHandler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        trace!("on event channel {}, got {}", self.name, call.method());
+        let args = call.raw_args().clone();
+
+        match call.method().as_str() {
+            "listen" => {
+                // Like the official embedders, listening again replaces the
+                // current stream.
+                if let Some(Err(_)) = self.cancel(Value::Null) {
+                    warn!("Failed to cancel the previous stream of {}", self.name);
+                }
+
+                let sink = EventSink::new(&self.name, call.engine(), self.codec);
+                match self.event_handler.on_listen(args, sink.clone()) {
+                    Ok(()) => {
+                        self.sink = Some(sink);
+                        call.success_empty();
+                    }
+                    Err(err) => {
+                        sink.close();
+                        call.respond::<Value, Value>(Err(err));
+                    }
+                }
+            }
+            "cancel" => match self.cancel(args) {
+                Some(result) => call.respond(result.map(|()| Value::Null)),
+                None => call.error("error", "No active stream to cancel", Value::Null),
+            },
+            _ => call.not_implemented(),
         }
     }
+
+    fn on_listening_changed(&mut self, listening: bool) {
+        self.event_handler.on_listening_changed(listening);
+    }
 }
 
-impl MethodCallHandler for EventChannelMethodCallHandler {
-    fn on_method_call(
-        &mut self,
-        call: MethodCall,
-        engine: FlutterEngine,
-    ) -> Result<Value, MethodCallError> {
-        if let Some(handler) = self.event_handler.upgrade() {
-            let mut handler = handler.write().unwrap();
-            match call.method.as_str() {
-                "listen" => handler.on_listen(call.args, engine),
-                "cancel" => handler.on_cancel(engine),
-                _ => Err(MethodCallError::NotImplemented),
-            }
-        } else {
-            Err(MethodCallError::ChannelClosed)
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::codec::STANDARD_CODEC;
+
+    #[derive(Default)]
+    struct SensorHandler {
+        sink: Arc<Mutex<Option<EventSink>>>,
+        cancellations: Arc<Mutex<usize>>,
+        listening: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl EventHandler for SensorHandler {
+        fn on_listen(&mut self, _args: Value, sink: EventSink) -> Result<(), MethodError<Value>> {
+            *self.sink.lock() = Some(sink);
+            Ok(())
         }
+
+        fn on_cancel(&mut self, _args: Value) -> Result<(), MethodError<Value>> {
+            *self.cancellations.lock() += 1;
+            Ok(())
+        }
+
+        fn on_listening_changed(&mut self, listening: bool) {
+            self.listening.lock().push(listening);
+        }
+    }
+
+    fn method_call(method: &str) -> codec::MethodCall {
+        codec::MethodCall {
+            method: method.into(),
+            args: Value::Null,
+        }
+    }
+
+    #[test]
+    fn streams_are_listened_and_cancelled() {
+        let handler = SensorHandler::default();
+        let (sink, cancellations) = (handler.sink.clone(), handler.cancellations.clone());
+        let channel = EventChannel::new("flust/sensor", handler, &STANDARD_CODEC);
+
+        assert_eq!(
+            channel.dispatch_for_test(method_call("listen")),
+            Some(MethodCallResult::Ok(Value::Null))
+        );
+        let first_sink = sink.lock().clone().unwrap();
+        assert!(first_sink.is_active());
+
+        // Listening again replaces the stream.
+        channel.dispatch_for_test(method_call("listen"));
+        assert!(!first_sink.is_active());
+        assert_eq!(*cancellations.lock(), 1);
+
+        assert_eq!(
+            channel.dispatch_for_test(method_call("cancel")),
+            Some(MethodCallResult::Ok(Value::Null))
+        );
+        assert!(!sink.lock().as_ref().unwrap().is_active());
+        assert_eq!(*cancellations.lock(), 2);
+
+        assert!(matches!(
+            channel.dispatch_for_test(method_call("cancel")),
+            Some(MethodCallResult::Err { .. })
+        ));
+        assert_eq!(*cancellations.lock(), 2);
+    }
+
+    #[test]
+    fn listening_changes_are_forwarded_to_the_event_handler() {
+        let handler = SensorHandler::default();
+        let listening = handler.listening.clone();
+        let channel = EventChannel::new("flust/sensor", handler, &STANDARD_CODEC);
+
+        channel.listening_changed(true);
+        channel.listening_changed(false);
+        assert_eq!(*listening.lock(), vec![true, false]);
     }
 }
@@ -51,6 +51,10 @@ impl Message {
 
 pub trait MessageHandler {
     fn on_message(&mut self, msg: Message);
+
+    /// Called when Dart starts or stops listening to the channel. See
+    /// [`Channel::listening_changed`].
+    fn on_listening_changed(&mut self, _listening: bool) {}
 }
 
 pub struct MessageChannel {
@@ -105,6 +109,20 @@ impl MessageChannel {
         T: Serialize,
         F: FnOnce(V) + 'static + Send,
         V: DeserializeOwned,
+    {
+        self.send_with_reply(value, move |reply| {
+            callback(reply.expect("Failed to decode message reply"))
+        });
+    }
+
+    /// Send a value on this channel, calling `callback` with the decoded
+    /// reply, or with `None` if the reply is empty (i.e.: Dart has no handler
+    /// for the channel) or can't be decoded as `V`.
+    pub fn send_with_reply<T, F, V>(&self, value: T, callback: F)
+    where
+        T: Serialize,
+        F: FnOnce(Option<V>) + 'static + Send,
+        V: DeserializeOwned,
     {
         if let Some(engine) = self.engine() {
             if !engine.is_platform_thread() {
@@ -115,9 +133,13 @@ impl MessageChannel {
             let buf = codec.encode_message(&to_value(value).unwrap());
 
             let handle = PlatformMessageResponseHandle::new(engine.clone(), move |data| {
-                let resp = codec.decode_message(data);
-                let val = resp.unwrap();
-                callback(from_value_owned(&val).unwrap());
+                // Some codecs decode empty messages too (e.g.: as an empty
+                // `U8List`), which would hide a missing handler.
+                let reply = (!data.is_empty())
+                    .then(|| codec.decode_message(data))
+                    .flatten()
+                    .and_then(|value| from_value_owned(&value).ok());
+                callback(reply);
             });
 
             engine.send_platform_message(PlatformMessage {
@@ -162,4 +184,10 @@ impl Channel for MessageChannel {
 
         self.message_handler.borrow_mut().on_message(msg);
     }
+
+    fn listening_changed(&self, listening: bool) {
+        self.message_handler
+            .borrow_mut()
+            .on_listening_changed(listening);
+    }
 }
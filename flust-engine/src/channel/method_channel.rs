@@ -6,6 +6,7 @@ use crate::{codec, codec::MethodCodec, FlutterEngine, FlutterEngineWeakRef};
 
 use crate::codec::value::{from_value, from_value_owned, to_value};
 use crate::codec::{MethodCallResult, Value};
+use crossbeam_channel::{unbounded, Sender};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -15,7 +16,15 @@ pub struct MethodCall {
     engine: FlutterEngineWeakRef,
     codec: &'static dyn MethodCodec,
     inner: codec::MethodCall,
-    response_handle: Option<PlatformMessageResponseHandle>,
+    responder: MethodCallResponder,
+}
+
+/// Where the response of a [`MethodCall`] goes.
+enum MethodCallResponder {
+    /// Sent back to the engine, if the call expects a response.
+    Engine(Option<PlatformMessageResponseHandle>),
+    /// Captured by [`MethodChannel::dispatch_for_test`].
+    Capture(Sender<MethodCallResult>),
 }
 
 pub enum MethodError<D>
@@ -47,7 +56,10 @@ impl MethodCall {
     }
 
     pub fn can_respond(&self) -> bool {
-        self.response_handle.is_some()
+        match &self.responder {
+            MethodCallResponder::Engine(handle) => handle.is_some(),
+            MethodCallResponder::Capture(_) => true,
+        }
     }
 
     pub fn respond<T, D>(self, result: Result<T, MethodError<D>>)
@@ -55,34 +67,20 @@ impl MethodCall {
         T: Serialize,
         D: Serialize + DeserializeOwned,
     {
+        let handle = match self.responder {
+            MethodCallResponder::Engine(handle) => handle,
+            MethodCallResponder::Capture(reply) => {
+                let _ = reply.send(method_call_result(result));
+                return;
+            }
+        };
+
         if let Some(engine) = self.engine.upgrade() {
-            let handle = self
-                .response_handle
-                .expect("Message can not be response handle");
-
-            let result = match result {
-                Ok(val) => {
-                    let value = to_value(val).expect("Failed to encode data to value");
-                    MethodCallResult::Ok(value)
-                }
-                Err(err) => match err {
-                    MethodError::Err {
-                        code,
-                        message,
-                        details,
-                    } => {
-                        let details = to_value(details).expect("Failed to encode details to value");
-                        MethodCallResult::Err {
-                            code,
-                            message,
-                            details,
-                        }
-                    }
-                    MethodError::NotImplemented => MethodCallResult::NotImplemented,
-                },
-            };
+            let handle = handle.expect("Message can not be response handle");
 
-            let buf = self.codec.encode_method_call_response(&result);
+            let buf = self
+                .codec
+                .encode_method_call_response(&method_call_result(result));
             engine.run_on_platform_thread(move |eng| {
                 eng.send_platform_message_response(handle, &buf);
             });
@@ -122,8 +120,40 @@ impl MethodCall {
     }
 }
 
+fn method_call_result<T, D>(result: Result<T, MethodError<D>>) -> MethodCallResult
+where
+    T: Serialize,
+    D: Serialize + DeserializeOwned,
+{
+    match result {
+        Ok(val) => {
+            let value = to_value(val).expect("Failed to encode data to value");
+            MethodCallResult::Ok(value)
+        }
+        Err(err) => match err {
+            MethodError::Err {
+                code,
+                message,
+                details,
+            } => {
+                let details = to_value(details).expect("Failed to encode details to value");
+                MethodCallResult::Err {
+                    code,
+                    message,
+                    details,
+                }
+            }
+            MethodError::NotImplemented => MethodCallResult::NotImplemented,
+        },
+    }
+}
+
 pub trait MethodCallHandler {
     fn on_method_call(&mut self, call: MethodCall);
+
+    /// Called when Dart starts or stops listening to the channel. See
+    /// [`Channel::listening_changed`].
+    fn on_listening_changed(&mut self, _listening: bool) {}
 }
 
 pub struct MethodChannel {
@@ -151,6 +181,23 @@ impl MethodChannel {
         self.codec
     }
 
+    /// Feeds `call` to the method handler of this channel, returning the
+    /// response it sent, if any. This doesn't require a running engine, so
+    /// that method handlers can be unit tested. Only responses sent before the
+    /// handler returns are captured.
+    pub fn dispatch_for_test(&self, call: codec::MethodCall) -> Option<MethodCallResult> {
+        let (reply, response) = unbounded();
+        let call = MethodCall {
+            engine: self.engine.clone(),
+            codec: self.codec,
+            inner: call,
+            responder: MethodCallResponder::Capture(reply),
+        };
+
+        self.method_handler.borrow_mut().on_method_call(call);
+        response.try_recv().ok()
+    }
+
     /// Invoke a flutter method using this channel
     pub fn invoke_method<S, T>(&self, method: S, args: T)
     where
@@ -254,9 +301,15 @@ impl Channel for MethodChannel {
             engine: self.engine.clone(),
             codec,
             inner: call,
-            response_handle: msg.response_handle,
+            responder: MethodCallResponder::Engine(msg.response_handle),
         };
 
         self.method_handler.borrow_mut().on_method_call(call);
     }
+
+    fn listening_changed(&self, listening: bool) {
+        self.method_handler
+            .borrow_mut()
+            .on_listening_changed(listening);
+    }
 }
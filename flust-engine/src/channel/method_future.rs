@@ -0,0 +1,195 @@
+//! `Future` adapters for method calls invoked on Dart and for messages sent to
+//! Dart, for plugins using an async runtime (e.g.: `tokio` or `async-std`).
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_channel::oneshot;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::channel::{MessageChannel, MethodChannel, MethodError};
+
+/// Future resolving with the reply of a method invoked using
+/// [`MethodChannel::invoke_method_async`].
+///
+/// The reply is received on the platform thread, which wakes the task
+/// awaiting the future on whichever executor it is polled by. If the engine
+/// shuts down (or was never running) before replying, the future resolves
+/// with [`MethodError::NotImplemented`], the same as if Dart had no handler
+/// for the method.
+#[must_use = "futures do nothing unless polled"]
+pub struct MethodResultFuture<V, D>
+where
+    D: DeserializeOwned + Serialize,
+{
+    receiver: oneshot::Receiver<Result<V, MethodError<D>>>,
+}
+
+impl<V, D> MethodResultFuture<V, D>
+where
+    D: DeserializeOwned + Serialize,
+{
+    fn channel() -> (oneshot::Sender<Result<V, MethodError<D>>>, Self) {
+        let (sender, receiver) = oneshot::channel();
+        (sender, Self { receiver })
+    }
+}
+
+impl<V, D> Future for MethodResultFuture<V, D>
+where
+    D: DeserializeOwned + Serialize,
+{
+    type Output = Result<V, MethodError<D>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(MethodError::NotImplemented)))
+    }
+}
+
+impl MethodChannel {
+    /// Invoke a flutter method using this channel, returning a future that
+    /// resolves with its result. See [`MethodChannel::invoke_method_with_result`].
+    pub fn invoke_method_async<T, V, D>(&self, method: String, args: T) -> MethodResultFuture<V, D>
+    where
+        T: Serialize,
+        V: DeserializeOwned + Send + 'static,
+        D: DeserializeOwned + Serialize + Send + 'static,
+    {
+        let (sender, future) = MethodResultFuture::channel();
+        self.invoke_method_with_result(method, args, move |result| {
+            // The receiver is gone if the future was dropped before the reply.
+            let _ = sender.send(result);
+        });
+        future
+    }
+}
+
+/// Future resolving with the reply to a message sent using
+/// [`MessageChannel::send_async`]: `None` if the reply can't be decoded (see
+/// [`MessageChannel::send_with_reply`]), or if the engine shuts down (or was
+/// never running) before replying.
+#[must_use = "futures do nothing unless polled"]
+pub struct MessageReplyFuture<V> {
+    receiver: oneshot::Receiver<Option<V>>,
+}
+
+impl<V> MessageReplyFuture<V> {
+    fn channel() -> (oneshot::Sender<Option<V>>, Self) {
+        let (sender, receiver) = oneshot::channel();
+        (sender, Self { receiver })
+    }
+}
+
+impl<V> Future for MessageReplyFuture<V> {
+    type Output = Option<V>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|reply| reply.ok().flatten())
+    }
+}
+
+impl MessageChannel {
+    /// Send a value on this channel, returning a future that resolves with
+    /// the reply. See [`MessageChannel::send_with_reply`].
+    pub fn send_async<T, V>(&self, value: T) -> MessageReplyFuture<V>
+    where
+        T: Serialize,
+        V: DeserializeOwned + Send + 'static,
+    {
+        let (sender, future) = MessageReplyFuture::channel();
+        self.send_with_reply(value, move |reply| {
+            // The receiver is gone if the future was dropped before the reply.
+            let _ = sender.send(reply);
+        });
+        future
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::{Message, MessageHandler};
+    use crate::codec::{MessageCodec, Value, BINARY_CODEC, STANDARD_CODEC};
+    use crate::tests::{reply_to_sent_messages, uninitialized_engine};
+
+    struct NoopMessageHandler;
+
+    impl MessageHandler for NoopMessageHandler {
+        fn on_message(&mut self, _msg: Message) {}
+    }
+
+    #[test]
+    fn awaiting_echo_reply_resolves_with_value() {
+        let (sender, future) = MethodResultFuture::<String, Value>::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(Ok("echo".to_owned()));
+        });
+
+        let result = futures_lite::future::block_on(future);
+        assert!(matches!(result, Ok(reply) if reply == "echo"));
+    }
+
+    #[test]
+    fn dropped_reply_resolves_as_not_implemented() {
+        let (sender, future) = MethodResultFuture::<String, Value>::channel();
+        drop(sender);
+
+        let result = futures_lite::future::block_on(future);
+        assert!(matches!(result, Err(MethodError::NotImplemented)));
+    }
+
+    #[test]
+    fn dropped_message_reply_resolves_as_none() {
+        let (sender, future) = MessageReplyFuture::<Vec<u8>>::channel();
+        drop(sender);
+
+        assert_eq!(futures_lite::future::block_on(future), None);
+    }
+
+    #[test]
+    fn message_replies_resolve_with_the_decoded_value() {
+        let engine = uninitialized_engine();
+        let channel = engine
+            .register_channel(MessageChannel::new(
+                "flust/echo",
+                NoopMessageHandler,
+                &STANDARD_CODEC,
+            ))
+            .upgrade()
+            .unwrap();
+
+        let future = channel.send_async::<_, String>("ping");
+        reply_to_sent_messages(&STANDARD_CODEC.encode_message(&Value::String("pong".into())));
+        assert_eq!(
+            futures_lite::future::block_on(future).as_deref(),
+            Some("pong")
+        );
+    }
+
+    #[test]
+    fn empty_message_replies_resolve_as_none() {
+        let engine = uninitialized_engine();
+        let channel = engine
+            .register_channel(MessageChannel::new(
+                "flust/bytes",
+                NoopMessageHandler,
+                &BINARY_CODEC,
+            ))
+            .upgrade()
+            .unwrap();
+
+        let future = channel.send_async::<_, Vec<u8>>(vec![1u8, 2, 3]);
+        reply_to_sent_messages(&[]);
+        assert_eq!(futures_lite::future::block_on(future), None);
+
+        let future = channel.send_async::<_, Vec<u8>>(vec![1u8, 2, 3]);
+        reply_to_sent_messages(&[4, 5]);
+        assert_eq!(futures_lite::future::block_on(future), Some(vec![4, 5]));
+    }
+}
@@ -7,17 +7,21 @@ use tracing::error;
 use crate::{FlutterEngine, FlutterEngineWeakRef};
 
 pub use self::{
+    event_channel::{EventChannel, EventHandler, EventSink},
     message_channel::{Message, MessageChannel, MessageHandler},
-    // event_channel::EventChannel,
     method_channel::{MethodCall, MethodCallHandler, MethodChannel, MethodError},
     registry::ChannelRegistry,
 };
+
+#[cfg(feature = "async")]
+pub use self::method_future::{MessageReplyFuture, MethodResultFuture};
 use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
 
+mod event_channel;
 mod message_channel;
-// TODO: Reimplement event channel support
-// mod event_channel;
 mod method_channel;
+#[cfg(feature = "async")]
+mod method_future;
 pub mod platform_message;
 mod registry;
 
@@ -27,12 +31,23 @@ pub trait Channel {
     fn init(&mut self, engine: FlutterEngineWeakRef);
     fn handle_platform_message(&self, msg: PlatformMessage);
 
+    /// Called on the platform thread when Dart starts or stops listening to
+    /// the messages sent on this channel (including when the channel is
+    /// registered while Dart is listening already), so that expensive
+    /// producers (e.g.: sensors) only run while their messages are handled.
+    fn listening_changed(&self, _listening: bool) {}
+
     /// When flutter call a method using MethodChannel,
     /// it can wait for rust response using await syntax.
     /// This method send a response to flutter. This is a low level method.
+    /// It can be called from any thread, the response is sent from the
+    /// platform thread.
     fn send_response(&self, response_handle: PlatformMessageResponseHandle, buf: &[u8]) {
         if let Some(engine) = self.engine() {
-            engine.send_platform_message_response(response_handle, buf);
+            let buf = buf.to_vec();
+            engine.run_on_platform_thread(move |engine| {
+                engine.send_platform_message_response(response_handle, &buf);
+            });
         } else {
             error!("Channel {} was not initialized", self.name());
         }
@@ -1,13 +1,30 @@
-use crate::FlutterEngine;
+use crate::{FlutterEngine, FlutterEngineWeakRef};
 use flust_engine_sys::{FlutterPlatformMessage, FlutterPlatformMessageResponseHandle};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ffi::{c_void, CStr, CString};
-use std::{mem, ptr};
-use tracing::{error, trace};
+use std::{fmt, mem, ptr};
+use tracing::{error, trace, warn};
+
+// Replies are sent by the fake in tests, as if the engine sent them.
+#[cfg(test)]
+use crate::tests::FlutterPlatformMessageCreateResponseHandle;
+#[cfg(not(test))]
+use flust_engine_sys::FlutterPlatformMessageCreateResponseHandle;
 
-#[derive(Debug)]
 pub struct PlatformMessageResponseHandle {
     handle: *const FlutterPlatformMessageResponseHandle,
+    /// The engine responded through when the handle is dropped without a
+    /// response (only set for the messages received from the engine).
+    engine: Option<FlutterEngineWeakRef>,
+}
+
+impl fmt::Debug for PlatformMessageResponseHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PlatformMessageResponseHandle")
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
 }
 
 unsafe impl Send for PlatformMessageResponseHandle {}
@@ -22,16 +39,54 @@ impl PlatformMessageResponseHandle {
         unsafe {
             let callback = Box::new(callback);
             let mut handle: *mut FlutterPlatformMessageResponseHandle = ptr::null_mut();
-            flust_engine_sys::FlutterPlatformMessageCreateResponseHandle(
+            FlutterPlatformMessageCreateResponseHandle(
                 engine.engine_ptr(),
                 Some(response_handle_callback),
                 Box::into_raw(Box::new(callback)) as _,
                 &mut handle,
             );
 
-            Self { handle }
+            Self {
+                handle,
+                engine: None,
+            }
         }
     }
+
+    /// Makes dropping the handle without a response send an empty response
+    /// through `engine` (i.e.: as if the message wasn't handled), so that the
+    /// Dart future completes and the engine frees the handle.
+    pub(crate) fn respond_on_drop(&mut self, engine: FlutterEngineWeakRef) {
+        self.engine = Some(engine);
+    }
+}
+
+/// The response handles of the platform messages received from the engine
+/// which are still waiting for a response, along with the channel each message
+/// was received on. Handlers may defer their responses, so several messages
+/// can be waiting at once, and each of them must be responded to exactly once.
+#[derive(Default)]
+pub(crate) struct PendingResponses {
+    channels: HashMap<usize, String>,
+}
+
+impl PendingResponses {
+    pub(crate) fn track(&mut self, handle: &PlatformMessageResponseHandle, channel: &str) {
+        self.channels
+            .insert(handle.handle as usize, channel.to_owned());
+    }
+
+    /// Stops tracking `handle` before responding to it, returning the channel
+    /// of its message, or `None` if it isn't waiting for a response (e.g.: it
+    /// was responded to already).
+    pub(crate) fn complete(&mut self, handle: &PlatformMessageResponseHandle) -> Option<String> {
+        self.channels.remove(&(handle.handle as usize))
+    }
+
+    /// The channels of the messages still waiting for a response.
+    pub(crate) fn pending_channels(&self) -> impl Iterator<Item = &str> {
+        self.channels.values().map(String::as_str)
+    }
 }
 
 type ResponseType = Box<dyn FnOnce(&[u8]) + Send>;
@@ -51,7 +106,10 @@ unsafe extern "C" fn response_handle_callback(
 
 impl From<*const FlutterPlatformMessageResponseHandle> for PlatformMessageResponseHandle {
     fn from(val: *const FlutterPlatformMessageResponseHandle) -> Self {
-        PlatformMessageResponseHandle { handle: val }
+        PlatformMessageResponseHandle {
+            handle: val,
+            engine: None,
+        }
     }
 }
 
@@ -65,8 +123,22 @@ impl From<PlatformMessageResponseHandle> for *const FlutterPlatformMessageRespon
 
 impl Drop for PlatformMessageResponseHandle {
     fn drop(&mut self) {
-        if !self.handle.is_null() {
-            error!("A message response handle has been dropped without sending a response! This WILL lead to leaking memory.");
+        if self.handle.is_null() {
+            return;
+        }
+
+        match self.engine.take().and_then(|engine| engine.upgrade()) {
+            Some(engine) => {
+                warn!("A message response handle has been dropped without sending a response, sending an empty response");
+                let handle = Self {
+                    handle: mem::replace(&mut self.handle, ptr::null()),
+                    engine: None,
+                };
+                engine.run_on_platform_thread(move |engine| {
+                    engine.send_platform_message_response(handle, &[]);
+                });
+            }
+            None => error!("A message response handle has been dropped without sending a response! This WILL lead to leaking memory."),
         }
     }
 }
@@ -113,3 +185,41 @@ impl<'a, 'b> From<FlutterPlatformMessage> for PlatformMessage<'a, 'b> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_handle(address: usize) -> PlatformMessageResponseHandle {
+        (address as *const FlutterPlatformMessageResponseHandle).into()
+    }
+
+    #[test]
+    fn deferred_responses_are_matched_to_their_message() {
+        let mut pending = PendingResponses::default();
+        let (first, second) = (response_handle(0x10), response_handle(0x20));
+        pending.track(&first, "flutter/platform");
+        pending.track(&second, "flutter/textinput");
+
+        // Responses may come in any order.
+        assert_eq!(
+            pending.complete(&second).as_deref(),
+            Some("flutter/textinput")
+        );
+        assert_eq!(
+            pending.pending_channels().collect::<Vec<_>>(),
+            ["flutter/platform"]
+        );
+        assert_eq!(
+            pending.complete(&first).as_deref(),
+            Some("flutter/platform")
+        );
+
+        // A second response to the same message is caught.
+        assert_eq!(pending.complete(&first), None);
+
+        for handle in [first, second] {
+            let _: *const FlutterPlatformMessageResponseHandle = handle.into();
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ops::Deref,
     sync::{Arc, Weak},
 };
@@ -14,6 +14,8 @@ use crate::channel::platform_message::PlatformMessage;
 #[derive(Default)]
 pub struct ChannelRegistry {
     channels: HashMap<String, Arc<dyn Channel>>,
+    /// The channels Dart listens to, registered or not.
+    listening: HashSet<String>,
     engine: FlutterEngineWeakRef,
 }
 
@@ -42,6 +44,30 @@ impl ChannelRegistry {
         self.channels.remove(channel_name)
     }
 
+    /// Whether Dart listens to the messages sent on `channel_name`.
+    pub fn is_listening(&self, channel_name: &str) -> bool {
+        self.listening.contains(channel_name)
+    }
+
+    /// Records whether Dart listens to `channel_name`, returning the channel
+    /// to notify (if registered and the state changed). The channel isn't
+    /// notified right away, so that it can use the registry when notified.
+    pub(crate) fn update_listening(
+        &mut self,
+        channel_name: &str,
+        listening: bool,
+    ) -> Option<Arc<dyn Channel>> {
+        let changed = if listening {
+            self.listening.insert(channel_name.to_owned())
+        } else {
+            self.listening.remove(channel_name)
+        };
+
+        changed
+            .then(|| self.channels.get(channel_name).cloned())
+            .flatten()
+    }
+
     pub fn with_channel<F>(&self, channel_name: &str, f: F)
     where
         F: FnOnce(&dyn Channel),
@@ -52,6 +78,10 @@ impl ChannelRegistry {
     }
 
     pub fn handle(&self, mut message: PlatformMessage) {
+        if let Some(handle) = &mut message.response_handle {
+            handle.respond_on_drop(self.engine.clone());
+        }
+
         if let Some(channel) = self.channels.get(message.channel.deref()) {
             trace!("Processing message from channel: {}", message.channel);
             channel.handle_platform_message(message);
@@ -69,3 +99,52 @@ impl ChannelRegistry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::FlutterEngine;
+
+    #[derive(Default)]
+    struct RecordingChannel {
+        updates: Mutex<Vec<bool>>,
+    }
+
+    impl Channel for RecordingChannel {
+        fn name(&self) -> &str {
+            "flust/sensor"
+        }
+
+        fn engine(&self) -> Option<FlutterEngine> {
+            None
+        }
+
+        fn init(&mut self, _engine: FlutterEngineWeakRef) {}
+
+        fn handle_platform_message(&self, _msg: PlatformMessage) {}
+
+        fn listening_changed(&self, listening: bool) {
+            self.updates.lock().push(listening);
+        }
+    }
+
+    #[test]
+    fn listening_changes_are_reported_once() {
+        let mut registry = ChannelRegistry::new();
+        assert!(registry.update_listening("flust/sensor", true).is_none());
+        assert!(registry.is_listening("flust/sensor"));
+
+        let channel = registry
+            .register_channel(RecordingChannel::default())
+            .upgrade()
+            .unwrap();
+        assert!(registry.update_listening("flust/sensor", true).is_none());
+
+        let notified = registry.update_listening("flust/sensor", false).unwrap();
+        notified.listening_changed(false);
+        assert!(!registry.is_listening("flust/sensor"));
+        assert_eq!(*channel.updates.lock(), vec![false]);
+    }
+}
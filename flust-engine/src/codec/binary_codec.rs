@@ -0,0 +1,61 @@
+use tracing::error;
+
+use super::{MessageCodec, Value};
+
+/// Passes messages through as raw bytes, like Flutter's `BinaryCodec`. Bytes
+/// are decoded as a [`Value::U8List`], and can be encoded from a list of
+/// integers in the `u8` range too (e.g.: a serialized `Vec<u8>`).
+pub struct BinaryCodec;
+
+pub const BINARY_CODEC: BinaryCodec = BinaryCodec {};
+
+impl MessageCodec for BinaryCodec {
+    fn encode_message(&self, v: &Value) -> Vec<u8> {
+        match v {
+            Value::U8List(bytes) => bytes.clone(),
+            Value::Null => Vec::new(),
+            Value::List(values) => match values.iter().map(byte).collect() {
+                Some(bytes) => bytes,
+                None => {
+                    error!("Invalid value: {:?}, can only encode bytes or null", v);
+                    Vec::new()
+                }
+            },
+            v => {
+                error!("Invalid value: {:?}, can only encode bytes or null", v);
+                Vec::new()
+            }
+        }
+    }
+
+    fn decode_message(&self, buf: &[u8]) -> Option<Value> {
+        Some(Value::U8List(buf.to_vec()))
+    }
+}
+
+fn byte(value: &Value) -> Option<u8> {
+    match *value {
+        Value::I32(i) => u8::try_from(i).ok(),
+        Value::I64(i) => u8::try_from(i).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::value::{from_value, to_value};
+
+    #[test]
+    fn bytes_roundtrip() {
+        let bytes = vec![0u8, 1, 127, 255];
+        let buf = BINARY_CODEC.encode_message(&to_value(&bytes).unwrap());
+        assert_eq!(buf, bytes);
+
+        let decoded: Vec<u8> = from_value(&BINARY_CODEC.decode_message(&buf).unwrap()).unwrap();
+        assert_eq!(decoded, bytes);
+        assert!(BINARY_CODEC
+            .encode_message(&Value::List(vec![Value::I64(256)]))
+            .is_empty());
+    }
+}
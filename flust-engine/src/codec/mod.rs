@@ -2,12 +2,14 @@ use serde::{Deserialize, Serialize};
 
 pub use self::value::Value;
 
+mod binary_codec;
 mod json_codec;
 mod standard_codec;
 mod string_codec;
 #[macro_use]
 pub mod value;
 
+pub use binary_codec::BINARY_CODEC;
 pub use json_codec::JSON_CODEC;
 pub use standard_codec::STANDARD_CODEC;
 pub use string_codec::STRING_CODEC;
@@ -18,6 +20,7 @@ pub struct MethodCall {
     pub args: Value,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum MethodCallResult {
     Ok(Value),
     Err {
@@ -19,6 +19,7 @@ const VALUE_INT64LIST: u8 = 10;
 const VALUE_FLOAT64LIST: u8 = 11;
 const VALUE_LIST: u8 = 12;
 const VALUE_MAP: u8 = 13;
+const VALUE_FLOAT32LIST: u8 = 14;
 
 #[derive(Debug)]
 enum DecodeError {
@@ -43,7 +44,14 @@ impl StandardMethodCodec {
             VALUE_TRUE => Value::Boolean(true),
             VALUE_INT32 => Value::I32(reader.read_i32()),
             VALUE_INT64 => Value::I64(reader.read_i64()),
-            VALUE_LARGEINT => panic!("Not implemented"),
+            // Written by older versions of Dart for integers over 64 bits, as
+            // a hexadecimal string. Only those fitting in 64 bits are decoded.
+            VALUE_LARGEINT => {
+                let len = reader.read_size();
+                let hex = reader.read_string(len);
+                let n = i64::from_str_radix(&hex, 16).map_err(|_| DecodeError::Invalid)?;
+                Value::I64(n)
+            }
             VALUE_FLOAT64 => {
                 reader.align_to(8);
                 Value::F64(reader.read_f64())
@@ -68,6 +76,12 @@ impl StandardMethodCodec {
                 let len = reader.read_size();
                 Value::F64List(reader.read_f64_list(len))
             }
+            // There's no `Value` for float32 lists, they're widened losslessly
+            // (and hence encoded back as float64 lists).
+            VALUE_FLOAT32LIST => {
+                let len = reader.read_size();
+                Value::F64List(reader.read_f32_list(len))
+            }
             VALUE_LIST => {
                 let len = reader.read_size();
                 let mut list = Vec::new();
@@ -99,7 +113,10 @@ impl StandardMethodCodec {
                 }
                 Value::Map(map)
             }
-            _ => Value::Null,
+            t => {
+                error!("Invalid value type: {}", t);
+                return Err(DecodeError::Invalid);
+            }
         })
     }
     fn write_string(writer: &mut Writer, s: &str) {
@@ -119,10 +136,18 @@ impl StandardMethodCodec {
                 writer.write_u8(VALUE_INT32);
                 writer.write_i32(*n);
             }
-            Value::I64(n) => {
-                writer.write_u8(VALUE_INT64);
-                writer.write_i64(*n);
-            }
+            // Like in Flutter's standard codec, integers fitting in 32 bits are
+            // always written as int32.
+            Value::I64(n) => match i32::try_from(*n) {
+                Ok(n) => {
+                    writer.write_u8(VALUE_INT32);
+                    writer.write_i32(n);
+                }
+                Err(_) => {
+                    writer.write_u8(VALUE_INT64);
+                    writer.write_i64(*n);
+                }
+            },
             Value::F64(n) => {
                 writer.write_u8(VALUE_FLOAT64);
                 writer.align_to(8);
@@ -354,6 +379,15 @@ impl<'a> Reader<'a> {
         }
         v
     }
+    fn read_f32_list(&mut self, len: usize) -> Vec<f64> {
+        let mut v = Vec::with_capacity(len);
+        self.align_to(4);
+        for _ in 0..len {
+            let n = self.read_u32();
+            v.push(f64::from(f32::from_bits(n)));
+        }
+        v
+    }
     fn ended(&self) -> bool {
         self.pos >= self.buf.len()
     }
@@ -430,3 +464,93 @@ where
     <A as AsMut<[T]>>::as_mut(&mut a).clone_from_slice(slice);
     a
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_use_the_smallest_fitting_width() {
+        let boundaries = [
+            (i64::from(i32::MIN) - 1, VALUE_INT64),
+            (i64::from(i32::MIN), VALUE_INT32),
+            (0, VALUE_INT32),
+            (i64::from(i32::MAX), VALUE_INT32),
+            (i64::from(i32::MAX) + 1, VALUE_INT64),
+        ];
+
+        for (n, value_type) in boundaries {
+            let encoded = STANDARD_CODEC.encode_message(&Value::I64(n));
+            assert_eq!(encoded[0], value_type, "type of {}", n);
+
+            let decoded = STANDARD_CODEC.decode_message(&encoded).unwrap();
+            assert_eq!(decoded.as_i64(), Some(n));
+        }
+    }
+
+    // Byte sequences from the engine's `standard_message_codec_unittests.cc`,
+    // which are written in the host byte order.
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn values_match_the_engine_encoding() {
+        let cases = [
+            (Value::Null, vec![0x00]),
+            (Value::Boolean(true), vec![0x01]),
+            (Value::Boolean(false), vec![0x02]),
+            (Value::I32(-7), vec![0x03, 0xf9, 0xff, 0xff, 0xff]),
+            (
+                Value::I64(0x1234567890abcdef),
+                vec![0x04, 0xef, 0xcd, 0xab, 0x90, 0x78, 0x56, 0x34, 0x12],
+            ),
+            (
+                Value::F64(std::f64::consts::PI),
+                vec![
+                    0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x2d, 0x44, 0x54, 0xfb,
+                    0x21, 0x09, 0x40,
+                ],
+            ),
+            (
+                Value::String("h\u{263A}w".into()),
+                vec![0x07, 0x05, 0x68, 0xe2, 0x98, 0xba, 0x77],
+            ),
+            (
+                Value::U8List(vec![0xba, 0x5e, 0xba, 0x11]),
+                vec![0x08, 0x04, 0xba, 0x5e, 0xba, 0x11],
+            ),
+            (
+                Value::I32List(vec![0x3bafeb13, 0x0000ffff]),
+                vec![
+                    0x09, 0x02, 0x00, 0x00, 0x13, 0xeb, 0xaf, 0x3b, 0xff, 0xff, 0x00, 0x00,
+                ],
+            ),
+            (
+                Value::List(vec![Value::String("hello".into()), Value::I32(-7)]),
+                vec![
+                    0x0c, 0x02, 0x07, 0x05, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x03, 0xf9, 0xff, 0xff,
+                    0xff,
+                ],
+            ),
+            (
+                Value::Map([("a".to_owned(), Value::Null)].into()),
+                vec![0x0d, 0x01, 0x07, 0x01, 0x61, 0x00],
+            ),
+        ];
+
+        for (value, bytes) in cases {
+            assert_eq!(STANDARD_CODEC.encode_message(&value), bytes, "{:?}", value);
+            assert_eq!(STANDARD_CODEC.decode_message(&bytes), Some(value));
+        }
+
+        let float32_list = [
+            0x0e, 0x02, 0x00, 0x00, 0xd8, 0x0f, 0x49, 0x40, 0x00, 0x00, 0x7a, 0x44,
+        ];
+        assert_eq!(
+            STANDARD_CODEC.decode_message(&float32_list),
+            Some(Value::F64List(vec![
+                f64::from(f32::from_bits(0x40490fd8)),
+                1000.0
+            ]))
+        );
+        assert_eq!(STANDARD_CODEC.decode_message(&[0x0f]), None);
+    }
+}
@@ -39,6 +39,19 @@ pub enum Value {
     Map(HashMap<String, Value>),
 }
 
+impl Value {
+    /// The value of an integer, regardless of its width. The standard codec
+    /// decodes integers as [`Value::I32`] or [`Value::I64`] depending on how
+    /// they were encoded (i.e.: on whether they fit in 32 bits).
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::I32(n) => Some(i64::from(*n)),
+            Value::I64(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
 impl Serialize for Value {
     #[inline]
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
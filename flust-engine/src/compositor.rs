@@ -1,6 +1,13 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
 use thiserror::Error;
+use tracing::{error, warn};
 
-use crate::ffi::{FlutterBackingStore, FlutterBackingStoreConfig, FlutterPresentViewInfo};
+use crate::{
+    diagnostics::{EngineErrorHandler, EngineFailure},
+    ffi::{FlutterBackingStore, FlutterBackingStoreConfig, FlutterPresentViewInfo},
+};
 
 pub trait FlutterCompositorHandler {
     fn present_view(&self, info: FlutterPresentViewInfo) -> Result<(), CompositorPresentError>;
@@ -33,3 +40,160 @@ pub enum CompositorCollectBackingStoreError {
     #[error("Failed to collect backing store: {0}")]
     CollectFailed(String),
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompositorState {
+    /// No frame was presented yet.
+    Starting,
+    /// A frame was presented through the compositor.
+    Running,
+    /// Rendering straight into the window framebuffers instead.
+    FallenBack,
+    /// The compositor failed before presenting a frame, without a fallback.
+    Failed,
+}
+
+/// Tracks whether the compositor works until it presents its first frame,
+/// deciding whether to fall back to rendering without it (see
+/// [`crate::builder::FlutterEngineBuilder::with_compositor_fallback`]).
+pub(crate) struct CompositorStartupMonitor {
+    fallback_enabled: bool,
+    state: Mutex<CompositorState>,
+}
+
+impl CompositorStartupMonitor {
+    pub(crate) fn new(fallback_enabled: bool) -> Self {
+        Self {
+            fallback_enabled,
+            state: Mutex::new(CompositorState::Starting),
+        }
+    }
+
+    /// Whether backing stores are created for the window framebuffers.
+    pub(crate) fn is_fallen_back(&self) -> bool {
+        *self.state.lock() == CompositorState::FallenBack
+    }
+
+    /// Records a backing store creation failure, returning whether to fall
+    /// back to the window framebuffer. Failures happening before the first
+    /// frame are reported once, unless falling back.
+    pub(crate) fn backing_store_failed(
+        &self,
+        reason: &str,
+        handler: Option<&Arc<Mutex<dyn EngineErrorHandler + Send>>>,
+    ) -> bool {
+        let mut state = self.state.lock();
+        match *state {
+            CompositorState::Starting if self.fallback_enabled => {
+                warn!(
+                    "The compositor is unavailable ({}), falling back to rendering without it",
+                    reason
+                );
+                *state = CompositorState::FallenBack;
+                true
+            }
+            CompositorState::Starting => {
+                *state = CompositorState::Failed;
+                drop(state);
+                report(reason, handler);
+                false
+            }
+            CompositorState::FallenBack => true,
+            CompositorState::Running | CompositorState::Failed => false,
+        }
+    }
+
+    /// Records the outcome of presenting a frame through the compositor,
+    /// reporting a failure to present the first frame.
+    pub(crate) fn record_present(
+        &self,
+        presented: bool,
+        handler: Option<&Arc<Mutex<dyn EngineErrorHandler + Send>>>,
+    ) {
+        let mut state = self.state.lock();
+        if *state != CompositorState::Starting {
+            return;
+        }
+
+        if presented {
+            *state = CompositorState::Running;
+        } else {
+            *state = CompositorState::Failed;
+            drop(state);
+            report("presenting the first frame failed", handler);
+        }
+    }
+}
+
+fn report(reason: &str, handler: Option<&Arc<Mutex<dyn EngineErrorHandler + Send>>>) {
+    let failure = EngineFailure::CompositorUnavailable {
+        reason: reason.to_owned(),
+    };
+    error!("{}", failure);
+
+    if let Some(handler) = handler {
+        handler.lock().on_engine_error(failure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingErrorHandler {
+        failures: Vec<EngineFailure>,
+    }
+
+    impl EngineErrorHandler for RecordingErrorHandler {
+        fn on_engine_error(&mut self, failure: EngineFailure) {
+            self.failures.push(failure);
+        }
+    }
+
+    fn recording_handler() -> (
+        Arc<Mutex<RecordingErrorHandler>>,
+        Arc<Mutex<dyn EngineErrorHandler + Send>>,
+    ) {
+        let recording = Arc::new(Mutex::new(RecordingErrorHandler::default()));
+        (recording.clone(), recording)
+    }
+
+    #[test]
+    fn backing_store_failure_triggers_configured_fallback() {
+        let (recording, handler) = recording_handler();
+        let monitor = CompositorStartupMonitor::new(true);
+
+        assert!(monitor.backing_store_failed("GL_OUT_OF_MEMORY", Some(&handler)));
+        assert!(monitor.is_fallen_back());
+        assert!(recording.lock().failures.is_empty());
+    }
+
+    #[test]
+    fn backing_store_failure_without_fallback_is_reported_once() {
+        let (recording, handler) = recording_handler();
+        let monitor = CompositorStartupMonitor::new(false);
+
+        assert!(!monitor.backing_store_failed("GL_OUT_OF_MEMORY", Some(&handler)));
+        assert!(!monitor.backing_store_failed("GL_OUT_OF_MEMORY", Some(&handler)));
+        assert!(!monitor.is_fallen_back());
+        assert_eq!(
+            recording.lock().failures,
+            vec![EngineFailure::CompositorUnavailable {
+                reason: "GL_OUT_OF_MEMORY".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn failures_after_the_first_frame_never_fall_back() {
+        let (recording, handler) = recording_handler();
+        let monitor = CompositorStartupMonitor::new(true);
+
+        monitor.record_present(true, Some(&handler));
+        assert!(!monitor.backing_store_failed("GL_OUT_OF_MEMORY", Some(&handler)));
+        monitor.record_present(false, Some(&handler));
+        assert!(!monitor.is_fallen_back());
+        assert!(recording.lock().failures.is_empty());
+    }
+}
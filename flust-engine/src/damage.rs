@@ -0,0 +1,168 @@
+//! Damage tracking of the window surface the implicit view is rendered into
+//! without a compositor, so that the engine only repaints the parts of the
+//! back buffers that changed since they were presented (see
+//! `FlutterOpenGLRendererConfig.populate_existing_damage`).
+use std::{collections::VecDeque, mem};
+
+use dpi::{PhysicalPosition, PhysicalSize};
+
+use crate::ffi::PhysicalRect;
+
+/// The number of presented frames whose damage is kept, which bounds the age
+/// of the back buffers that can be partially repainted (e.g.: 3 when triple
+/// buffering).
+const DAMAGE_HISTORY_LEN: usize = 4;
+
+pub(crate) struct WindowDamage {
+    frame_size: PhysicalSize<u32>,
+    /// The bounding rect of the damage of the last presented frames (most
+    /// recent first).
+    frames: VecDeque<PhysicalRect>,
+    /// Whether the last frame failed to be presented, leaving what it
+    /// rendered in the back buffer.
+    present_failed: bool,
+    /// The existing damage reported last, which the engine reads after the
+    /// callback returns.
+    reported: flust_engine_sys::FlutterRect,
+}
+
+impl Default for WindowDamage {
+    fn default() -> Self {
+        Self {
+            frame_size: PhysicalSize::default(),
+            frames: VecDeque::new(),
+            present_failed: false,
+            reported: empty_rect().into(),
+        }
+    }
+}
+
+impl WindowDamage {
+    /// Sets the size of the next frame. The damage of the frames of another
+    /// size is forgotten, as resizing the surface discards its back buffers.
+    pub(crate) fn set_frame_size(&mut self, size: PhysicalSize<u32>) {
+        if size != self.frame_size {
+            self.frame_size = size;
+            self.frames.clear();
+        }
+    }
+
+    /// The area of a back buffer of `buffer_age` (see `EGL_EXT_buffer_age`)
+    /// that changed since it was presented, i.e.: the damage of the frames
+    /// presented since. The engine only supports a single rect, so this is
+    /// their bounding rect. The whole frame is reported until a frame is
+    /// presented again after a failure.
+    pub(crate) fn existing_damage(&self, buffer_age: u32) -> PhysicalRect {
+        let whole_frame = PhysicalRect::new(PhysicalPosition::default(), self.frame_size.cast());
+        if self.present_failed {
+            return whole_frame;
+        }
+        let Some(presented_since) = (buffer_age as usize).checked_sub(1) else {
+            return whole_frame;
+        };
+        if presented_since > self.frames.len() {
+            return whole_frame;
+        }
+
+        self.frames
+            .iter()
+            .take(presented_since)
+            .copied()
+            .fold(empty_rect(), bounding_rect)
+    }
+
+    /// Fills `damage` with the existing damage of a back buffer of
+    /// `buffer_age`, see [`WindowDamage::existing_damage`].
+    pub(crate) fn report(&mut self, buffer_age: u32, damage: &mut flust_engine_sys::FlutterDamage) {
+        self.reported = self.existing_damage(buffer_age).into();
+        *damage = flust_engine_sys::FlutterDamage {
+            struct_size: mem::size_of::<flust_engine_sys::FlutterDamage>(),
+            num_rects: 1,
+            damage: &mut self.reported,
+        };
+    }
+
+    /// Records the damage of a presented frame. A frame that failed to be
+    /// presented isn't recorded, since the screen didn't change, but the back
+    /// buffer it was rendered into must be repainted entirely.
+    pub(crate) fn frame_presented(&mut self, presented: bool, damage: &[PhysicalRect]) {
+        self.present_failed = !presented;
+        if !presented {
+            return;
+        }
+
+        let damage = damage.iter().copied().fold(empty_rect(), bounding_rect);
+        self.frames.push_front(damage);
+        self.frames.truncate(DAMAGE_HISTORY_LEN);
+    }
+}
+
+fn empty_rect() -> PhysicalRect {
+    PhysicalRect::new(PhysicalPosition::default(), PhysicalSize::default())
+}
+
+/// The smallest rect containing both `a` and `b` (ignoring empty rects).
+fn bounding_rect(a: PhysicalRect, b: PhysicalRect) -> PhysicalRect {
+    match (a.is_empty(), b.is_empty()) {
+        (_, true) => a,
+        (true, false) => b,
+        (false, false) => {
+            let left = a.origin.x.min(b.origin.x);
+            let top = a.origin.y.min(b.origin.y);
+            let right = (a.origin.x + a.size.width).max(b.origin.x + b.size.width);
+            let bottom = (a.origin.y + a.size.height).max(b.origin.y + b.size.height);
+            PhysicalRect::new(
+                PhysicalPosition::new(left, top),
+                PhysicalSize::new(right - left, bottom - top),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> PhysicalRect {
+        PhysicalRect::new(
+            PhysicalPosition::new(x, y),
+            PhysicalSize::new(width, height),
+        )
+    }
+
+    #[test]
+    fn existing_damage_covers_the_frames_presented_since_the_buffer() {
+        let mut damage = WindowDamage::default();
+        damage.set_frame_size(PhysicalSize::new(800, 600));
+        let whole_frame = rect(0.0, 0.0, 800.0, 600.0);
+
+        // Nothing is known of the back buffers yet.
+        assert_eq!(damage.existing_damage(2), whole_frame);
+
+        damage.frame_presented(true, &[rect(10.0, 10.0, 20.0, 20.0)]);
+        damage.frame_presented(true, &[rect(100.0, 50.0, 10.0, 10.0)]);
+
+        // The contents of undefined buffers are unknown.
+        assert_eq!(damage.existing_damage(0), whole_frame);
+        // The buffer presented last is up to date.
+        assert!(damage.existing_damage(1).is_empty());
+        assert_eq!(damage.existing_damage(2), rect(100.0, 50.0, 10.0, 10.0));
+        assert_eq!(damage.existing_damage(3), rect(10.0, 10.0, 100.0, 50.0));
+        assert_eq!(damage.existing_damage(4), whole_frame);
+
+        // The frames failing to be presented are repainted entirely, while
+        // the damage of the presented ones is kept.
+        damage.frame_presented(false, &[rect(0.0, 0.0, 1.0, 1.0)]);
+        assert_eq!(damage.existing_damage(1), whole_frame);
+        assert_eq!(damage.existing_damage(2), whole_frame);
+        damage.frame_presented(false, &[]);
+        assert_eq!(damage.existing_damage(1), whole_frame);
+
+        damage.frame_presented(true, &[rect(200.0, 100.0, 10.0, 10.0)]);
+        assert!(damage.existing_damage(1).is_empty());
+        assert_eq!(damage.existing_damage(3), rect(100.0, 50.0, 110.0, 60.0));
+
+        damage.set_frame_size(PhysicalSize::new(400, 300));
+        assert_eq!(damage.existing_damage(2), rect(0.0, 0.0, 400.0, 300.0));
+    }
+}
@@ -0,0 +1,149 @@
+//! Reporting of engine-internal failures happening after the engine is running.
+//!
+//! The embedder API doesn't expose an error or abort hook, so these failures
+//! can only be detected indirectly, through the embedder callbacks invoked by
+//! the engine. The following failure classes are currently detectable:
+//!
+//! - Repeated present failures: the OpenGL `present` callback or the
+//!   compositor `present_view` callback failing for several consecutive frames
+//!   (e.g.: after losing the GPU context), which leaves the app not rendering.
+//! - An unusable compositor: the compositor failing to create a backing store
+//!   or to present the first frame, unless it falls back to rendering without
+//!   it (see [`crate::builder::FlutterEngineBuilder::with_compositor_fallback`]).
+//!
+//! Failures that don't go through an embedder callback (e.g.: a Dart isolate
+//! crash, or a hang of the raster thread) can't be detected by the embedder.
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+use tracing::error;
+
+/// Number of consecutive present failures after which
+/// [`EngineFailure::RepeatedPresentFailure`] is reported.
+pub const PRESENT_FAILURE_THRESHOLD: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineFailure {
+    /// Frames failed to be presented this many consecutive times.
+    RepeatedPresentFailure { consecutive_failures: u32 },
+    /// The compositor failed before presenting its first frame.
+    CompositorUnavailable { reason: String },
+}
+
+impl fmt::Display for EngineFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineFailure::RepeatedPresentFailure {
+                consecutive_failures,
+            } => write!(
+                f,
+                "failed to present {} consecutive frames, the engine stopped rendering",
+                consecutive_failures
+            ),
+            EngineFailure::CompositorUnavailable { reason } => write!(
+                f,
+                "the compositor failed before presenting the first frame: {}",
+                reason
+            ),
+        }
+    }
+}
+
+/// Handler notified of engine-internal failures, so that the app can log them
+/// or restart. It's called from the thread the failure was detected on (e.g.:
+/// the raster thread for present failures).
+pub trait EngineErrorHandler {
+    fn on_engine_error(&mut self, failure: EngineFailure);
+}
+
+/// Tracks consecutive present failures. A failure is reported once when the
+/// threshold is reached, and again only after a frame was presented
+/// successfully.
+pub(crate) struct PresentFailureMonitor {
+    threshold: u32,
+    consecutive_failures: AtomicU32,
+}
+
+impl PresentFailureMonitor {
+    pub(crate) fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            consecutive_failures: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn record(
+        &self,
+        presented: bool,
+        handler: Option<&Arc<Mutex<dyn EngineErrorHandler + Send>>>,
+    ) {
+        if presented {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        let consecutive_failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if consecutive_failures != self.threshold {
+            return;
+        }
+
+        let failure = EngineFailure::RepeatedPresentFailure {
+            consecutive_failures,
+        };
+        error!("{}", failure);
+
+        if let Some(handler) = handler {
+            handler.lock().on_engine_error(failure);
+        }
+    }
+}
+
+impl Default for PresentFailureMonitor {
+    fn default() -> Self {
+        Self::new(PRESENT_FAILURE_THRESHOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingErrorHandler {
+        failures: Vec<EngineFailure>,
+    }
+
+    impl EngineErrorHandler for RecordingErrorHandler {
+        fn on_engine_error(&mut self, failure: EngineFailure) {
+            self.failures.push(failure);
+        }
+    }
+
+    #[test]
+    fn repeated_present_failure_triggers_handler() {
+        let recording = Arc::new(Mutex::new(RecordingErrorHandler::default()));
+        let handler: Arc<Mutex<dyn EngineErrorHandler + Send>> = recording.clone();
+        let monitor = PresentFailureMonitor::new(3);
+
+        monitor.record(false, Some(&handler));
+        monitor.record(false, Some(&handler));
+        monitor.record(true, Some(&handler));
+        assert!(recording.lock().failures.is_empty());
+
+        for _ in 0..5 {
+            monitor.record(false, Some(&handler));
+        }
+        assert_eq!(
+            recording.lock().failures,
+            vec![EngineFailure::RepeatedPresentFailure {
+                consecutive_failures: 3
+            }]
+        );
+    }
+}
@@ -1,20 +1,26 @@
 use std::{
-    ffi::CString,
+    ffi::{c_char, CStr, CString},
+    fs::{self, OpenOptions},
+    io::{self, Write},
     mem,
-    path::Path,
+    path::{Path, PathBuf},
     ptr, slice,
+    sync::atomic::{AtomicU32, Ordering},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use bitflags::bitflags;
 use dpi::{PhysicalPosition, PhysicalSize};
 use flust_engine_sys::{
     FlutterBackingStoreType, FlutterEngineDisplayId, FlutterLayerContentType, FlutterSize,
 };
 
 pub use flust_engine_sys::FlutterViewId;
-use tracing::error;
+use tracing::{error, warn};
 
-use crate::{path_to_cstring, FlutterEngine, FlutterEngineError};
+use crate::{
+    path_to_cstring, view::FlutterPointerTransform, CreateError, FlutterEngine, FlutterEngineError,
+};
 
 // Warning: The implicit view ID value needs to be kept in sync with the
 // `kFlutterImplicitViewId` constant on the engine side:
@@ -30,6 +36,9 @@ pub enum FlutterPointerPhase {
     Add,
     Remove,
     Hover,
+    PanZoomStart,
+    PanZoomUpdate,
+    PanZoomEnd,
 }
 
 impl From<FlutterPointerPhase> for flust_engine_sys::FlutterPointerPhase {
@@ -42,6 +51,13 @@ impl From<FlutterPointerPhase> for flust_engine_sys::FlutterPointerPhase {
             FlutterPointerPhase::Add => flust_engine_sys::FlutterPointerPhase::kAdd,
             FlutterPointerPhase::Remove => flust_engine_sys::FlutterPointerPhase::kRemove,
             FlutterPointerPhase::Hover => flust_engine_sys::FlutterPointerPhase::kHover,
+            FlutterPointerPhase::PanZoomStart => {
+                flust_engine_sys::FlutterPointerPhase::kPanZoomStart
+            }
+            FlutterPointerPhase::PanZoomUpdate => {
+                flust_engine_sys::FlutterPointerPhase::kPanZoomUpdate
+            }
+            FlutterPointerPhase::PanZoomEnd => flust_engine_sys::FlutterPointerPhase::kPanZoomEnd,
         }
     }
 }
@@ -50,6 +66,7 @@ impl From<FlutterPointerPhase> for flust_engine_sys::FlutterPointerPhase {
 pub enum FlutterPointerDeviceKind {
     Mouse,
     Touch,
+    Trackpad,
 }
 
 impl From<FlutterPointerDeviceKind> for flust_engine_sys::FlutterPointerDeviceKind {
@@ -61,6 +78,9 @@ impl From<FlutterPointerDeviceKind> for flust_engine_sys::FlutterPointerDeviceKi
             FlutterPointerDeviceKind::Touch => {
                 flust_engine_sys::FlutterPointerDeviceKind::kFlutterPointerDeviceKindTouch
             }
+            FlutterPointerDeviceKind::Trackpad => {
+                flust_engine_sys::FlutterPointerDeviceKind::kFlutterPointerDeviceKindTrackpad
+            }
         }
     }
 }
@@ -84,22 +104,65 @@ impl From<FlutterPointerSignalKind> for flust_engine_sys::FlutterPointerSignalKi
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub enum FlutterPointerMouseButtons {
-    None = 0,
-    Primary = 1,
-    Secondary = 2,
-    Middle = 4,
-    Back = 8,
-    Forward = 16,
+bitflags! {
+    /// The mouse buttons held during a pointer event. Since several buttons
+    /// can be held at once, backends report all of them rather than the button
+    /// the event is about.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct FlutterPointerMouseButtons: i64 {
+        const PRIMARY = 1;
+        const SECONDARY = 2;
+        const MIDDLE = 4;
+        const BACK = 8;
+        const FORWARD = 16;
+    }
 }
 
 impl From<FlutterPointerMouseButtons> for i64 {
     fn from(btn: FlutterPointerMouseButtons) -> Self {
-        btn as i64
+        btn.bits()
+    }
+}
+
+impl FlutterPointerPhase {
+    /// The phase of a mouse button press or release, which changed the held
+    /// buttons from `before` to `after`. Like the other Flutter desktop
+    /// embedders, only the first press and the last release are sent as
+    /// `Down` and `Up`: pressing or releasing other buttons in between moves
+    /// the pointer instead, carrying the updated buttons.
+    pub fn from_buttons_change(
+        before: FlutterPointerMouseButtons,
+        after: FlutterPointerMouseButtons,
+    ) -> Self {
+        match (before.is_empty(), after.is_empty()) {
+            (true, false) => FlutterPointerPhase::Down,
+            (false, true) => FlutterPointerPhase::Up,
+            (false, false) => FlutterPointerPhase::Move,
+            (true, true) => FlutterPointerPhase::Hover,
+        }
     }
 }
 
+/// A pointer event sent to the engine.
+///
+/// The embedder API doesn't carry keyboard modifiers along with pointer
+/// events. Instead, the framework combines pointer events with the modifier
+/// state it tracks from key events, so modified scrolls (e.g.: Ctrl+scroll to
+/// zoom) are handled in Dart by checking `HardwareKeyboard.instance` (e.g.:
+/// `isControlPressed`) when receiving a `PointerScrollEvent`. Backends must
+/// therefore keep the engine's key state up to date, including modifiers held
+/// before the window gained keyboard focus.
+///
+/// `device` identifies the pointer device to the engine, and must stay the same
+/// from `Add` to `Remove`. It isn't the pointer identifier seen by the
+/// framework: the engine assigns a fresh one to every `Down`-`Up` sequence, so
+/// a device id may be reused (e.g.: for a later touch) once the previous
+/// pointer was removed or lifted.
+///
+/// Trackpad gestures are sent as `PanZoomStart`, `PanZoomUpdate` and
+/// `PanZoomEnd` events, whose pan offset, scale and rotation (see
+/// [`FlutterPointerEvent::with_pan_zoom`]) are relative to the start of the
+/// gesture.
 #[derive(Copy, Clone, Debug)]
 pub struct FlutterPointerEvent {
     timestamp: Duration,
@@ -112,6 +175,9 @@ pub struct FlutterPointerEvent {
     scroll_delta_y: f64,
     device_kind: FlutterPointerDeviceKind,
     buttons: FlutterPointerMouseButtons,
+    pan: (f64, f64),
+    scale: f64,
+    rotation: f64,
     view_id: FlutterViewId,
 }
 
@@ -140,9 +206,40 @@ impl FlutterPointerEvent {
             scroll_delta_y,
             device_kind,
             buttons,
+            pan: (0.0, 0.0),
+            scale: 1.0,
+            rotation: 0.0,
             view_id,
         }
     }
+
+    /// Sets the pan offset (in physical pixels), the scale factor and the
+    /// rotation (in radians) of a pan-zoom event, accumulated since
+    /// `PanZoomStart`.
+    pub fn with_pan_zoom(mut self, pan: (f64, f64), scale: f64, rotation: f64) -> Self {
+        self.pan = pan;
+        self.scale = scale;
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn phase(&self) -> FlutterPointerPhase {
+        self.phase
+    }
+
+    pub fn view_id(&self) -> FlutterViewId {
+        self.view_id
+    }
+
+    /// Maps the coordinates of the event from the surface of its view to the
+    /// view, see [`FlutterPointerTransform`].
+    pub(crate) fn transformed(mut self, transform: &FlutterPointerTransform) -> Self {
+        (self.x, self.y) = transform.apply_to_position((self.x, self.y));
+        (self.scroll_delta_x, self.scroll_delta_y) =
+            transform.apply_to_delta((self.scroll_delta_x, self.scroll_delta_y));
+        self.pan = transform.apply_to_delta(self.pan);
+        self
+    }
 }
 
 impl From<FlutterPointerEvent> for flust_engine_sys::FlutterPointerEvent {
@@ -159,10 +256,10 @@ impl From<FlutterPointerEvent> for flust_engine_sys::FlutterPointerEvent {
             scroll_delta_y: event.scroll_delta_y,
             device_kind: event.device_kind.into(),
             buttons: event.buttons.into(),
-            pan_x: 0.0,
-            pan_y: 0.0,
-            scale: 1.0,
-            rotation: 0.0,
+            pan_x: event.pan.0,
+            pan_y: event.pan.1,
+            scale: event.scale,
+            rotation: event.rotation,
             view_id: event.view_id,
             #[cfg(all(target_arch = "arm", target_os = "android"))]
             __bindgen_padding_0: 0,
@@ -334,6 +431,45 @@ impl FlutterKeyEvent {
         }
     }
 
+    /// Creates an empty event (`physical` 0 and `logical` 0). See
+    /// [`FlutterKeyEvent`] for when such an event is required.
+    pub fn empty(timestamp: Duration, device_type: FlutterKeyEventDeviceType) -> Self {
+        Self::new(
+            timestamp,
+            FlutterKeyEventType::Down,
+            FlutterPhysicalKey::new(0),
+            FlutterLogicalKey::new(0),
+            None,
+            false,
+            device_type,
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.physical.0 == 0 && self.logical.0 == 0
+    }
+
+    pub fn kind(&self) -> FlutterKeyEventType {
+        self.kind
+    }
+
+    pub fn physical(&self) -> &FlutterPhysicalKey {
+        &self.physical
+    }
+
+    /// Replaces a down event for a key that is already pressed (according to
+    /// `is_pressed`) with an empty event, as required by the regularity rules
+    /// documented on [`FlutterKeyEvent`]. All other events are returned as is.
+    pub fn deduplicate_down<F>(self, is_pressed: F) -> Self
+    where
+        F: FnOnce(&FlutterPhysicalKey) -> bool,
+    {
+        if self.kind == FlutterKeyEventType::Down && is_pressed(&self.physical) {
+            return Self::empty(self.timestamp, self.device_type);
+        }
+        self
+    }
+
     // Note: The `From` trait can *not* be used for this conversion because the
     // character's `CString` needs to outlive the conversion.
     pub fn as_ptr(&self) -> flust_engine_sys::FlutterKeyEvent {
@@ -354,10 +490,37 @@ impl FlutterKeyEvent {
     }
 }
 
+/// An update to whether Dart listens to the messages sent on a channel (i.e.:
+/// has a handler set for it).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlutterChannelUpdate {
+    pub channel: String,
+    pub listening: bool,
+}
+
+impl From<&flust_engine_sys::FlutterChannelUpdate> for FlutterChannelUpdate {
+    fn from(update: &flust_engine_sys::FlutterChannelUpdate) -> Self {
+        Self {
+            channel: cstr_to_string(update.channel),
+            listening: update.listening,
+        }
+    }
+}
+
 pub struct FlutterFrameInfo {
     pub size: PhysicalSize<u32>,
 }
 
+/// The metrics of a view, as sent to the engine by
+/// [`FlutterEngine::set_viewport_metrics`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlutterWindowMetrics {
+    /// The size of the view, in physical pixels.
+    pub size: PhysicalSize<usize>,
+    pub pixel_ratio: f64,
+    pub display_id: FlutterEngineDisplayId,
+}
+
 impl From<flust_engine_sys::FlutterFrameInfo> for FlutterFrameInfo {
     fn from(frame_info: flust_engine_sys::FlutterFrameInfo) -> Self {
         Self {
@@ -366,6 +529,27 @@ impl From<flust_engine_sys::FlutterFrameInfo> for FlutterFrameInfo {
     }
 }
 
+/// The information passed when presenting the window surface (i.e.: without
+/// a compositor).
+pub struct FlutterPresentInfo {
+    /// The area that changed in the presented frame, in physical pixels.
+    pub frame_damage: Vec<PhysicalRect>,
+}
+
+impl From<flust_engine_sys::FlutterPresentInfo> for FlutterPresentInfo {
+    fn from(present_info: flust_engine_sys::FlutterPresentInfo) -> Self {
+        let damage = present_info.frame_damage;
+        let rects = match damage.damage.is_null() {
+            true => &[],
+            false => unsafe { slice::from_raw_parts(damage.damage, damage.num_rects) },
+        };
+
+        Self {
+            frame_damage: rects.iter().copied().map(PhysicalRect::from).collect(),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct FlutterBackingStoreConfig {
     /// The size of the render target the engine expects to render into.
@@ -389,6 +573,10 @@ pub struct FlutterBackingStoreUserData {
     // The `view_id` field is used for being able to determine the targeted view
     // in the `collect_backing_store_callback`.
     pub view_id: FlutterViewId,
+    // Whether this is a compositor fallback backing store (see
+    // `FlutterBackingStore::window_framebuffer`), which isn't handled by the
+    // compositor handler of the view.
+    pub(crate) window_framebuffer: bool,
 }
 
 impl FlutterBackingStoreUserData {
@@ -421,7 +609,10 @@ impl FlutterBackingStore {
     pub fn new(description: FlutterBackingStoreDescription, view_id: FlutterViewId) -> Self {
         Self {
             description,
-            user_data: FlutterBackingStoreUserData { view_id },
+            user_data: FlutterBackingStoreUserData {
+                view_id,
+                window_framebuffer: false,
+            },
             // Note: `raw_user_data` is initialized as `nullptr` but it will
             // point to an actual `user_data` value after a roundtrip through
             // the embedder API.
@@ -434,6 +625,45 @@ impl FlutterBackingStore {
         }
     }
 
+    /// A backing store rendering straight into the window framebuffer
+    /// `framebuffer_id` (of the internal `format`), used when falling back to
+    /// rendering without the compositor.
+    pub(crate) fn window_framebuffer(
+        framebuffer_id: u32,
+        format: u32,
+        view_id: FlutterViewId,
+    ) -> Self {
+        let framebuffer = FlutterOpenGLFramebuffer::new(
+            format,
+            FlutterOpenGLBackingStoreFramebuffer {
+                framebuffer_id,
+                texture_id: 0,
+            },
+        );
+        let description = FlutterBackingStoreDescription::OpenGL(
+            FlutterOpenGLBackingStore::Framebuffer(framebuffer),
+        );
+
+        let mut backing_store = Self::new(description, view_id);
+        backing_store.user_data.window_framebuffer = true;
+        backing_store
+    }
+
+    pub(crate) fn is_window_framebuffer(&self) -> bool {
+        self.user_data.window_framebuffer
+    }
+
+    /// Releases a backing store created by [`Self::window_framebuffer`].
+    pub(crate) fn collect_window_framebuffer(mut self) {
+        if let FlutterBackingStoreDescription::OpenGL(FlutterOpenGLBackingStore::Framebuffer(
+            mut framebuffer,
+        )) = self.description
+        {
+            framebuffer.drop_raw_user_data();
+        }
+        self.drop_raw_user_data();
+    }
+
     pub(crate) fn into_ffi(self, target: &mut flust_engine_sys::FlutterBackingStore) {
         self.user_data.into_ffi(target);
         self.description.into_ffi(target);
@@ -807,17 +1037,23 @@ impl From<flust_engine_sys::FlutterLayer> for FlutterLayer {
                     FlutterLayerContent::BackingStore(backing_store)
                 }
                 FlutterLayerContentType::kFlutterLayerContentTypePlatformView => {
-                    FlutterLayerContent::PlatformView
+                    let platform_view = unsafe { &*layer.__bindgen_anon_1.platform_view };
+                    FlutterLayerContent::PlatformView(platform_view.into())
                 }
             },
             offset: PhysicalPosition::new(layer.offset.x, layer.offset.y),
             size: PhysicalSize::new(layer.size.width, layer.size.height),
-            backing_store_present_info: unsafe { (*layer.backing_store_present_info).into() },
+            // Only set for backing store layers.
+            backing_store_present_info: match unsafe { layer.backing_store_present_info.as_ref() } {
+                Some(present_info) => (*present_info).into(),
+                None => FlutterBackingStorePresentInfo {
+                    paint_region: FlutterRegion { rects: Vec::new() },
+                },
+            },
         }
     }
 }
 
-// TODO: Add support for platform view layer content
 pub enum FlutterLayerContent {
     /// Indicates that the contents of this layer are rendered by Flutter into a
     /// backing store.
@@ -825,7 +1061,73 @@ pub enum FlutterLayerContent {
 
     /// Indicates that the contents of this layer are determined by the
     /// embedder.
-    PlatformView,
+    PlatformView(FlutterPlatformView),
+}
+
+/// A platform view to composite, at the offset and size of its layer.
+#[derive(Clone, Debug)]
+pub struct FlutterPlatformView {
+    /// The identifier of the platform view, as specified by the app when
+    /// creating it (i.e.: the `id` of the `create` call of the
+    /// `flutter/platform_views` channel).
+    pub identifier: i64,
+    /// The mutations to apply to the platform view, in order. The
+    /// coordinates of the clips are in the space of the transformations
+    /// preceding them, starting with the root surface transformation (e.g.:
+    /// the device pixel ratio), which is the first mutation when present.
+    pub mutations: Vec<FlutterPlatformViewMutation>,
+}
+
+impl From<&flust_engine_sys::FlutterPlatformView> for FlutterPlatformView {
+    fn from(platform_view: &flust_engine_sys::FlutterPlatformView) -> Self {
+        let mutations = if platform_view.mutations.is_null() {
+            vec![]
+        } else {
+            unsafe { slice::from_raw_parts(platform_view.mutations, platform_view.mutations_count) }
+                .iter()
+                .map(|mutation| unsafe { &**mutation }.into())
+                .collect()
+        };
+
+        Self {
+            identifier: platform_view.identifier,
+            mutations,
+        }
+    }
+}
+
+/// A mutation the app applied to a platform view (e.g.: by wrapping it in an
+/// `Opacity` or a `ClipRect` widget).
+#[derive(Copy, Clone, Debug)]
+pub enum FlutterPlatformViewMutation {
+    /// The opacity of the view, between 0 (transparent) and 1 (opaque).
+    Opacity(f64),
+    ClipRect(flust_engine_sys::FlutterRect),
+    ClipRoundedRect(flust_engine_sys::FlutterRoundedRect),
+    Transformation(flust_engine_sys::FlutterTransformation),
+}
+
+impl From<&flust_engine_sys::FlutterPlatformViewMutation> for FlutterPlatformViewMutation {
+    fn from(mutation: &flust_engine_sys::FlutterPlatformViewMutation) -> Self {
+        use flust_engine_sys::FlutterPlatformViewMutationType as Type;
+
+        unsafe {
+            match mutation.type_ {
+                Type::kFlutterPlatformViewMutationTypeOpacity => {
+                    Self::Opacity(mutation.__bindgen_anon_1.opacity)
+                }
+                Type::kFlutterPlatformViewMutationTypeClipRect => {
+                    Self::ClipRect(mutation.__bindgen_anon_1.clip_rect)
+                }
+                Type::kFlutterPlatformViewMutationTypeClipRoundedRect => {
+                    Self::ClipRoundedRect(mutation.__bindgen_anon_1.clip_rounded_rect)
+                }
+                Type::kFlutterPlatformViewMutationTypeTransformation => {
+                    Self::Transformation(mutation.__bindgen_anon_1.transformation)
+                }
+            }
+        }
+    }
 }
 
 impl FlutterLayerContent {
@@ -843,6 +1145,24 @@ impl FlutterLayerContent {
 
         Some(framebuffer.name)
     }
+
+    /// The name of the texture attached to the framebuffer of an OpenGL
+    /// backing store, which (unlike the framebuffer) can be used from the
+    /// contexts sharing objects with the one it was created in.
+    pub fn get_opengl_backing_store_texture_name(&self) -> Option<u32> {
+        let FlutterLayerContent::BackingStore(backing_store) = self else {
+            return None;
+        };
+
+        let FlutterBackingStoreDescription::OpenGL(FlutterOpenGLBackingStore::Framebuffer(
+            framebuffer,
+        )) = backing_store.description
+        else {
+            return None;
+        };
+
+        Some(framebuffer.user_data.texture_id)
+    }
 }
 
 /// Contains additional information about the backing store provided during
@@ -854,6 +1174,15 @@ pub struct FlutterBackingStorePresentInfo {
     pub paint_region: FlutterRegion,
 }
 
+impl FlutterBackingStorePresentInfo {
+    /// The rectangles of the paint region, in physical pixels relative to the
+    /// top left corner of the backing store (i.e.: the same coordinate space
+    /// as `wl_surface.damage_buffer`).
+    pub fn paint_rects(&self) -> Vec<PhysicalRect> {
+        self.paint_region.physical_rects()
+    }
+}
+
 impl From<flust_engine_sys::FlutterBackingStorePresentInfo> for FlutterBackingStorePresentInfo {
     fn from(present_info: flust_engine_sys::FlutterBackingStorePresentInfo) -> Self {
         Self {
@@ -877,6 +1206,620 @@ impl From<flust_engine_sys::FlutterRegion> for FlutterRegion {
     }
 }
 
+impl FlutterRegion {
+    pub fn physical_rects(&self) -> Vec<PhysicalRect> {
+        self.rects.iter().copied().map(PhysicalRect::from).collect()
+    }
+}
+
+/// An axis-aligned rectangle in physical pixels. The origin is the top left
+/// corner of the rectangle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PhysicalRect {
+    pub origin: PhysicalPosition<f64>,
+    pub size: PhysicalSize<f64>,
+}
+
+impl PhysicalRect {
+    pub fn new(origin: PhysicalPosition<f64>, size: PhysicalSize<f64>) -> Self {
+        Self { origin, size }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.width <= 0.0 || self.size.height <= 0.0
+    }
+}
+
+impl From<flust_engine_sys::FlutterRect> for PhysicalRect {
+    fn from(rect: flust_engine_sys::FlutterRect) -> Self {
+        Self {
+            origin: PhysicalPosition::new(rect.left, rect.top),
+            size: PhysicalSize::new(rect.right - rect.left, rect.bottom - rect.top),
+        }
+    }
+}
+
+impl From<PhysicalRect> for flust_engine_sys::FlutterRect {
+    fn from(rect: PhysicalRect) -> Self {
+        Self {
+            left: rect.origin.x,
+            top: rect.origin.y,
+            right: rect.origin.x + rect.size.width,
+            bottom: rect.origin.y + rect.size.height,
+        }
+    }
+}
+
+/// The set of possible actions that can be conveyed to a semantics node.
+///
+/// Must match the `SemanticsAction` enum in semantics.dart.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum FlutterSemanticsAction {
+    Tap = 1 << 0,
+    LongPress = 1 << 1,
+    ScrollLeft = 1 << 2,
+    ScrollRight = 1 << 3,
+    ScrollUp = 1 << 4,
+    ScrollDown = 1 << 5,
+    Increase = 1 << 6,
+    Decrease = 1 << 7,
+    ShowOnScreen = 1 << 8,
+    MoveCursorForwardByCharacter = 1 << 9,
+    MoveCursorBackwardByCharacter = 1 << 10,
+    SetSelection = 1 << 11,
+    Copy = 1 << 12,
+    Cut = 1 << 13,
+    Paste = 1 << 14,
+    DidGainAccessibilityFocus = 1 << 15,
+    DidLoseAccessibilityFocus = 1 << 16,
+    CustomAction = 1 << 17,
+    Dismiss = 1 << 18,
+    MoveCursorForwardByWord = 1 << 19,
+    MoveCursorBackwardByWord = 1 << 20,
+    SetText = 1 << 21,
+    Focus = 1 << 22,
+}
+
+impl FlutterSemanticsAction {
+    pub(crate) fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            x if x == Self::Tap as u32 => Some(Self::Tap),
+            x if x == Self::LongPress as u32 => Some(Self::LongPress),
+            x if x == Self::ScrollLeft as u32 => Some(Self::ScrollLeft),
+            x if x == Self::ScrollRight as u32 => Some(Self::ScrollRight),
+            x if x == Self::ScrollUp as u32 => Some(Self::ScrollUp),
+            x if x == Self::ScrollDown as u32 => Some(Self::ScrollDown),
+            x if x == Self::Increase as u32 => Some(Self::Increase),
+            x if x == Self::Decrease as u32 => Some(Self::Decrease),
+            x if x == Self::ShowOnScreen as u32 => Some(Self::ShowOnScreen),
+            x if x == Self::MoveCursorForwardByCharacter as u32 => {
+                Some(Self::MoveCursorForwardByCharacter)
+            }
+            x if x == Self::MoveCursorBackwardByCharacter as u32 => {
+                Some(Self::MoveCursorBackwardByCharacter)
+            }
+            x if x == Self::SetSelection as u32 => Some(Self::SetSelection),
+            x if x == Self::Copy as u32 => Some(Self::Copy),
+            x if x == Self::Cut as u32 => Some(Self::Cut),
+            x if x == Self::Paste as u32 => Some(Self::Paste),
+            x if x == Self::DidGainAccessibilityFocus as u32 => {
+                Some(Self::DidGainAccessibilityFocus)
+            }
+            x if x == Self::DidLoseAccessibilityFocus as u32 => {
+                Some(Self::DidLoseAccessibilityFocus)
+            }
+            x if x == Self::CustomAction as u32 => Some(Self::CustomAction),
+            x if x == Self::Dismiss as u32 => Some(Self::Dismiss),
+            x if x == Self::MoveCursorForwardByWord as u32 => Some(Self::MoveCursorForwardByWord),
+            x if x == Self::MoveCursorBackwardByWord as u32 => Some(Self::MoveCursorBackwardByWord),
+            x if x == Self::SetText as u32 => Some(Self::SetText),
+            x if x == Self::Focus as u32 => Some(Self::Focus),
+            _ => None,
+        }
+    }
+}
+
+impl FlutterSemanticsAction {
+    /// Decodes the set of actions of a semantics node into its actions, in
+    /// ascending bit order. Unknown bits are ignored.
+    pub(crate) fn from_bitmask(bits: u32) -> Vec<Self> {
+        (0..u32::BITS)
+            .filter_map(|bit| Self::from_bits(bits & (1 << bit)))
+            .collect()
+    }
+}
+
+impl From<FlutterSemanticsAction> for flust_engine_sys::FlutterSemanticsAction {
+    fn from(action: FlutterSemanticsAction) -> Self {
+        match action {
+            FlutterSemanticsAction::Tap => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionTap
+            }
+            FlutterSemanticsAction::LongPress => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionLongPress
+            }
+            FlutterSemanticsAction::ScrollLeft => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionScrollLeft
+            }
+            FlutterSemanticsAction::ScrollRight => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionScrollRight
+            }
+            FlutterSemanticsAction::ScrollUp => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionScrollUp
+            }
+            FlutterSemanticsAction::ScrollDown => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionScrollDown
+            }
+            FlutterSemanticsAction::Increase => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionIncrease
+            }
+            FlutterSemanticsAction::Decrease => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionDecrease
+            }
+            FlutterSemanticsAction::ShowOnScreen => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionShowOnScreen
+            }
+            FlutterSemanticsAction::MoveCursorForwardByCharacter => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionMoveCursorForwardByCharacter
+            }
+            FlutterSemanticsAction::MoveCursorBackwardByCharacter => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionMoveCursorBackwardByCharacter
+            }
+            FlutterSemanticsAction::SetSelection => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionSetSelection
+            }
+            FlutterSemanticsAction::Copy => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionCopy
+            }
+            FlutterSemanticsAction::Cut => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionCut
+            }
+            FlutterSemanticsAction::Paste => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionPaste
+            }
+            FlutterSemanticsAction::DidGainAccessibilityFocus => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionDidGainAccessibilityFocus
+            }
+            FlutterSemanticsAction::DidLoseAccessibilityFocus => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionDidLoseAccessibilityFocus
+            }
+            FlutterSemanticsAction::CustomAction => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionCustomAction
+            }
+            FlutterSemanticsAction::Dismiss => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionDismiss
+            }
+            FlutterSemanticsAction::MoveCursorForwardByWord => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionMoveCursorForwardByWord
+            }
+            FlutterSemanticsAction::MoveCursorBackwardByWord => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionMoveCursorBackwardByWord
+            }
+            FlutterSemanticsAction::SetText => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionSetText
+            }
+            FlutterSemanticsAction::Focus => {
+                flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionFocus
+            }
+        }
+    }
+}
+
+bitflags! {
+    /// The set of flags of a semantics node.
+    ///
+    /// Must match the `SemanticsFlag` enum in semantics.dart.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct FlutterSemanticsFlags: u32 {
+        const HAS_CHECKED_STATE = 1 << 0;
+        const IS_CHECKED = 1 << 1;
+        const IS_SELECTED = 1 << 2;
+        const IS_BUTTON = 1 << 3;
+        const IS_TEXT_FIELD = 1 << 4;
+        const IS_FOCUSED = 1 << 5;
+        const HAS_ENABLED_STATE = 1 << 6;
+        const IS_ENABLED = 1 << 7;
+        const IS_IN_MUTUALLY_EXCLUSIVE_GROUP = 1 << 8;
+        const IS_HEADER = 1 << 9;
+        const IS_OBSCURED = 1 << 10;
+        const SCOPES_ROUTE = 1 << 11;
+        const NAMES_ROUTE = 1 << 12;
+        const IS_HIDDEN = 1 << 13;
+        const IS_IMAGE = 1 << 14;
+        const IS_LIVE_REGION = 1 << 15;
+        const HAS_TOGGLED_STATE = 1 << 16;
+        const IS_TOGGLED = 1 << 17;
+        const HAS_IMPLICIT_SCROLLING = 1 << 18;
+        const IS_MULTILINE = 1 << 19;
+        const IS_READ_ONLY = 1 << 20;
+        const IS_FOCUSABLE = 1 << 21;
+        const IS_LINK = 1 << 22;
+        const IS_SLIDER = 1 << 23;
+        const IS_KEYBOARD_KEY = 1 << 24;
+        const IS_CHECK_STATE_MIXED = 1 << 25;
+        const HAS_EXPANDED_STATE = 1 << 26;
+        const IS_EXPANDED = 1 << 27;
+    }
+}
+
+/// The reading direction of the text of a semantics node.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FlutterTextDirection {
+    #[default]
+    Unknown,
+    RightToLeft,
+    LeftToRight,
+}
+
+impl From<flust_engine_sys::FlutterTextDirection> for FlutterTextDirection {
+    fn from(direction: flust_engine_sys::FlutterTextDirection) -> Self {
+        match direction {
+            flust_engine_sys::FlutterTextDirection::kFlutterTextDirectionUnknown => {
+                FlutterTextDirection::Unknown
+            }
+            flust_engine_sys::FlutterTextDirection::kFlutterTextDirectionRTL => {
+                FlutterTextDirection::RightToLeft
+            }
+            flust_engine_sys::FlutterTextDirection::kFlutterTextDirectionLTR => {
+                FlutterTextDirection::LeftToRight
+            }
+        }
+    }
+}
+
+/// A node of the semantics tree. Updates only carry the nodes which changed,
+/// which replace the nodes with the same `id`.
+///
+/// The string attributes of the node texts (e.g.: spell-out or locale hints)
+/// are not decoded.
+#[derive(Clone, Debug)]
+pub struct FlutterSemanticsNode {
+    /// The unique identifier of this node.
+    pub id: i32,
+    pub flags: FlutterSemanticsFlags,
+    /// The actions applicable to this node.
+    pub actions: Vec<FlutterSemanticsAction>,
+    pub text_selection_base: i32,
+    pub text_selection_extent: i32,
+    /// The number of scrollable children that contribute to semantics.
+    pub scroll_child_count: i32,
+    /// The index of the first visible semantic child of a scroll node.
+    pub scroll_index: i32,
+    /// The scrolling position (and its range), in logical pixels.
+    pub scroll_position: f64,
+    pub scroll_extent_max: f64,
+    pub scroll_extent_min: f64,
+    pub elevation: f64,
+    pub thickness: f64,
+    pub label: String,
+    pub hint: String,
+    pub value: String,
+    /// The value after a [`FlutterSemanticsAction::Increase`] action.
+    pub increased_value: String,
+    /// The value after a [`FlutterSemanticsAction::Decrease`] action.
+    pub decreased_value: String,
+    pub tooltip: String,
+    pub text_direction: FlutterTextDirection,
+    /// The bounding box of this node, in its own coordinate system.
+    pub rect: flust_engine_sys::FlutterRect,
+    /// The transform from the coordinate system of this node to the one of
+    /// its parent.
+    pub transform: flust_engine_sys::FlutterTransformation,
+    pub children_in_traversal_order: Vec<i32>,
+    pub children_in_hit_test_order: Vec<i32>,
+    /// The IDs of the [`FlutterSemanticsCustomAction`]s of this node.
+    pub custom_accessibility_actions: Vec<i32>,
+    /// The platform view this node is associated with, if any.
+    pub platform_view_id: Option<i64>,
+}
+
+impl From<&flust_engine_sys::FlutterSemanticsNode2> for FlutterSemanticsNode {
+    fn from(node: &flust_engine_sys::FlutterSemanticsNode2) -> Self {
+        Self {
+            id: node.id,
+            flags: FlutterSemanticsFlags::from_bits_retain(node.flags.0),
+            actions: FlutterSemanticsAction::from_bitmask(node.actions.0),
+            text_selection_base: node.text_selection_base,
+            text_selection_extent: node.text_selection_extent,
+            scroll_child_count: node.scroll_child_count,
+            scroll_index: node.scroll_index,
+            scroll_position: node.scroll_position,
+            scroll_extent_max: node.scroll_extent_max,
+            scroll_extent_min: node.scroll_extent_min,
+            elevation: node.elevation,
+            thickness: node.thickness,
+            label: cstr_to_string(node.label),
+            hint: cstr_to_string(node.hint),
+            value: cstr_to_string(node.value),
+            increased_value: cstr_to_string(node.increased_value),
+            decreased_value: cstr_to_string(node.decreased_value),
+            tooltip: cstr_to_string(node.tooltip),
+            text_direction: node.text_direction.into(),
+            rect: node.rect,
+            transform: node.transform,
+            children_in_traversal_order: slice_to_vec(
+                node.children_in_traversal_order,
+                node.child_count,
+            ),
+            children_in_hit_test_order: slice_to_vec(
+                node.children_in_hit_test_order,
+                node.child_count,
+            ),
+            custom_accessibility_actions: slice_to_vec(
+                node.custom_accessibility_actions,
+                node.custom_accessibility_actions_count,
+            ),
+            platform_view_id: (node.platform_view_id >= 0).then_some(node.platform_view_id),
+        }
+    }
+}
+
+/// A custom semantics action, or an action override.
+///
+/// Action overrides are custom actions that the application developer requests
+/// to be used in place of the standard actions in the `FlutterSemanticsAction`
+/// enum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlutterSemanticsCustomAction {
+    /// The unique custom action or action override ID.
+    pub id: i32,
+
+    /// For overridden standard actions, corresponds to the
+    /// `FlutterSemanticsAction` to override.
+    pub override_action: Option<FlutterSemanticsAction>,
+
+    /// The user-readable name of this custom semantics action.
+    pub label: String,
+
+    /// The hint description of this custom semantics action.
+    pub hint: String,
+}
+
+impl From<&flust_engine_sys::FlutterSemanticsCustomAction2> for FlutterSemanticsCustomAction {
+    fn from(action: &flust_engine_sys::FlutterSemanticsCustomAction2) -> Self {
+        Self {
+            id: action.id,
+            // The engine uses `0` for custom actions that do not override a
+            // standard action.
+            override_action: FlutterSemanticsAction::from_bits(action.override_action.0),
+            label: cstr_to_string(action.label),
+            hint: cstr_to_string(action.hint),
+        }
+    }
+}
+
+/// A batch of updates to semantics nodes and custom actions.
+#[derive(Clone, Debug, Default)]
+pub struct FlutterSemanticsUpdate {
+    pub nodes: Vec<FlutterSemanticsNode>,
+    pub custom_actions: Vec<FlutterSemanticsCustomAction>,
+}
+
+impl From<&flust_engine_sys::FlutterSemanticsUpdate2> for FlutterSemanticsUpdate {
+    fn from(update: &flust_engine_sys::FlutterSemanticsUpdate2) -> Self {
+        let nodes = if update.nodes.is_null() {
+            vec![]
+        } else {
+            unsafe { slice::from_raw_parts(update.nodes, update.node_count) }
+                .iter()
+                .map(|node| unsafe { &**node }.into())
+                .collect()
+        };
+
+        let custom_actions = if update.custom_actions.is_null() {
+            vec![]
+        } else {
+            unsafe { slice::from_raw_parts(update.custom_actions, update.custom_action_count) }
+                .iter()
+                .map(|action| unsafe { &**action }.into())
+                .collect()
+        };
+
+        Self {
+            nodes,
+            custom_actions,
+        }
+    }
+}
+
+fn slice_to_vec<T: Copy>(data: *const T, len: usize) -> Vec<T> {
+    if data.is_null() || len == 0 {
+        return vec![];
+    }
+
+    unsafe { slice::from_raw_parts(data, len) }.to_vec()
+}
+
+fn cstr_to_string(value: *const c_char) -> String {
+    if value.is_null() {
+        return String::new();
+    }
+
+    unsafe { CStr::from_ptr(value) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// A locale, in the order of preference passed to
+/// [`FlutterEngine::update_locales`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlutterLocale {
+    /// The language code (e.g.: `en`).
+    pub language_code: String,
+    /// The country code (e.g.: `US`).
+    pub country_code: Option<String>,
+    pub script_code: Option<String>,
+    pub variant_code: Option<String>,
+}
+
+impl FlutterLocale {
+    pub fn new(language_code: impl Into<String>, country_code: Option<String>) -> Self {
+        Self {
+            language_code: language_code.into(),
+            country_code,
+            script_code: None,
+            variant_code: None,
+        }
+    }
+}
+
+impl From<&flust_engine_sys::FlutterLocale> for FlutterLocale {
+    fn from(locale: &flust_engine_sys::FlutterLocale) -> Self {
+        let optional = |code: *const c_char| (!code.is_null()).then(|| cstr_to_string(code));
+
+        Self {
+            language_code: cstr_to_string(locale.language_code),
+            country_code: optional(locale.country_code),
+            script_code: optional(locale.script_code),
+            variant_code: optional(locale.variant_code),
+        }
+    }
+}
+
+/// The strings of a [`FlutterLocale`], kept alive while the engine reads the
+/// `FlutterLocale` pointing to them.
+pub(crate) struct FlutterLocaleStrings {
+    language_code: CString,
+    country_code: Option<CString>,
+    script_code: Option<CString>,
+    variant_code: Option<CString>,
+}
+
+impl FlutterLocaleStrings {
+    /// Returns `None` if any of the codes contains a null byte.
+    pub(crate) fn new(locale: &FlutterLocale) -> Option<Self> {
+        let optional = |code: &Option<String>| match code {
+            Some(code) => CString::new(code.as_str()).ok().map(Some),
+            None => Some(None),
+        };
+
+        Some(Self {
+            language_code: CString::new(locale.language_code.as_str()).ok()?,
+            country_code: optional(&locale.country_code)?,
+            script_code: optional(&locale.script_code)?,
+            variant_code: optional(&locale.variant_code)?,
+        })
+    }
+
+    pub(crate) fn to_ffi(&self) -> flust_engine_sys::FlutterLocale {
+        let optional = |code: &Option<CString>| code.as_ref().map_or(ptr::null(), |c| c.as_ptr());
+
+        flust_engine_sys::FlutterLocale {
+            struct_size: mem::size_of::<flust_engine_sys::FlutterLocale>(),
+            language_code: self.language_code.as_ptr(),
+            country_code: optional(&self.country_code),
+            script_code: optional(&self.script_code),
+            variant_code: optional(&self.variant_code),
+        }
+    }
+}
+
+/// The id of a Dart `SendPort` (i.e.: its `nativePort`), which objects are
+/// posted to with [`FlutterEngine::post_dart_object`].
+pub type FlutterEngineDartPort = flust_engine_sys::FlutterEngineDartPort;
+
+/// An object posted to a Dart `SendPort`, received by the isolate listening on
+/// the corresponding `ReceivePort` as the equivalent Dart object. The embedder
+/// API can only post these kinds of objects: in particular, `SendPort`s can't
+/// be posted, only their id (as an `Int`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DartObject {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    /// Received as a `Uint8List`.
+    Buffer(Vec<u8>),
+}
+
+/// The data of a [`DartObject`], kept alive while the engine reads the
+/// `FlutterEngineDartObject` pointing to it.
+pub(crate) struct DartObjectData<'a> {
+    object: &'a DartObject,
+    string: Option<CString>,
+    buffer: Option<flust_engine_sys::FlutterEngineDartBuffer>,
+}
+
+impl<'a> DartObjectData<'a> {
+    /// Returns `None` if the object is a string containing a null byte.
+    pub(crate) fn new(object: &'a DartObject) -> Option<Self> {
+        let string = match object {
+            DartObject::String(string) => Some(CString::new(string.as_str()).ok()?),
+            _ => None,
+        };
+
+        // Without a collect callback, the VM copies the buffer while posting
+        // it, so it's never written to.
+        let buffer = match object {
+            DartObject::Buffer(buffer) => Some(flust_engine_sys::FlutterEngineDartBuffer {
+                struct_size: mem::size_of::<flust_engine_sys::FlutterEngineDartBuffer>(),
+                user_data: ptr::null_mut(),
+                buffer_collect_callback: None,
+                buffer: buffer.as_ptr() as *mut u8,
+                buffer_size: buffer.len(),
+            }),
+            _ => None,
+        };
+
+        Some(Self {
+            object,
+            string,
+            buffer,
+        })
+    }
+
+    pub(crate) fn to_ffi(&self) -> flust_engine_sys::FlutterEngineDartObject {
+        use flust_engine_sys::{
+            FlutterEngineDartObjectType as Type, FlutterEngineDartObject__bindgen_ty_1 as Value,
+        };
+
+        let (type_, value) = match (self.object, &self.string, &self.buffer) {
+            (DartObject::Null, _, _) => (
+                Type::kFlutterEngineDartObjectTypeNull,
+                Value { int64_value: 0 },
+            ),
+            (DartObject::Bool(value), _, _) => (
+                Type::kFlutterEngineDartObjectTypeBool,
+                Value { bool_value: *value },
+            ),
+            (DartObject::Int(value), _, _) => (
+                Type::kFlutterEngineDartObjectTypeInt64,
+                Value {
+                    int64_value: *value,
+                },
+            ),
+            (DartObject::Double(value), _, _) => (
+                Type::kFlutterEngineDartObjectTypeDouble,
+                Value {
+                    double_value: *value,
+                },
+            ),
+            (DartObject::String(_), Some(string), _) => (
+                Type::kFlutterEngineDartObjectTypeString,
+                Value {
+                    string_value: string.as_ptr(),
+                },
+            ),
+            (DartObject::Buffer(_), _, Some(buffer)) => (
+                Type::kFlutterEngineDartObjectTypeBuffer,
+                Value {
+                    buffer_value: buffer,
+                },
+            ),
+            (DartObject::String(_) | DartObject::Buffer(_), _, _) => {
+                unreachable!("The data of strings and buffers is created along with them")
+            }
+        };
+
+        flust_engine_sys::FlutterEngineDartObject {
+            type_,
+            __bindgen_anon_1: value,
+        }
+    }
+}
+
 /// The update type parameter that is passed to `FlutterEngineNotifyDisplayUpdate`.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub enum FlutterEngineDisplaysUpdateType {
@@ -959,17 +1902,39 @@ impl FlutterEngineResultExt for FlutterEngineResult {
 
 pub(crate) struct FlutterEngineAOTData {
     pub(crate) data: flust_engine_sys::FlutterEngineAOTData,
+    /// The file the AOT data was loaded from when it was passed as bytes,
+    /// kept until the data is collected.
+    pub(crate) elf_file: Option<TemporaryElfFile>,
 }
 
 impl FlutterEngineAOTData {
-    pub(crate) fn new(aot_library_path: &Path) -> Result<Self, FlutterEngineError> {
+    /// Loads the AOT data from `aot_data` if set (see
+    /// [`crate::builder::FlutterEngineBuilder::with_aot_data_bytes`]), and
+    /// from the ELF library at `aot_library_path` otherwise.
+    pub(crate) fn new(
+        aot_library_path: &Path,
+        aot_data: Option<&[u8]>,
+    ) -> Result<Self, CreateError> {
         let data: flust_engine_sys::FlutterEngineAOTData = ptr::null_mut();
 
-        if FlutterEngine::runs_aot_compiled_dart_code() {
-            Self::create_aot_data(aot_library_path, &data)?;
+        if !FlutterEngine::runs_aot_compiled_dart_code() {
+            return Ok(Self {
+                data,
+                elf_file: None,
+            });
         }
 
-        Ok(Self { data })
+        // The embedder API only loads ELF snapshots from a path.
+        let elf_file = aot_data
+            .map(TemporaryElfFile::write)
+            .transpose()
+            .map_err(CreateError::AOTDataWriteFailed)?;
+        let elf_path = elf_file
+            .as_ref()
+            .map_or(aot_library_path, |file| file.path.as_path());
+        Self::create_aot_data(elf_path, &data)?;
+
+        Ok(Self { data, elf_file })
     }
 
     fn create_aot_data(
@@ -1011,3 +1976,261 @@ impl Drop for FlutterEngineAOTData {
         }
     }
 }
+
+/// An ELF snapshot written to a temporary file, which is removed on drop.
+pub(crate) struct TemporaryElfFile {
+    path: PathBuf,
+}
+
+impl TemporaryElfFile {
+    fn write(bytes: &[u8]) -> io::Result<Self> {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "flust-aot-data-{}-{}.so",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(&path)?;
+        let elf_file = Self { path };
+        file.write_all(bytes)?;
+        Ok(elf_file)
+    }
+}
+
+impl Drop for TemporaryElfFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove AOT data file {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aot_data_file_is_removed_on_drop() {
+        let bytes = b"\x7fELF snapshot";
+        let elf_file = TemporaryElfFile::write(bytes).unwrap();
+        let path = elf_file.path.clone();
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+
+        drop(elf_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn locale_roundtrips_through_ffi() {
+        let locale = FlutterLocale {
+            script_code: Some("Hant".into()),
+            ..FlutterLocale::new("zh", Some("TW".into()))
+        };
+        let strings = FlutterLocaleStrings::new(&locale).unwrap();
+
+        assert_eq!(FlutterLocale::from(&strings.to_ffi()), locale);
+    }
+
+    #[test]
+    fn dart_objects_are_encoded_for_ffi() {
+        use flust_engine_sys::FlutterEngineDartObjectType as Type;
+
+        let int = DartObject::Int(1 << 40);
+        let data = DartObjectData::new(&int).unwrap();
+        let object = data.to_ffi();
+        assert_eq!(object.type_, Type::kFlutterEngineDartObjectTypeInt64);
+        assert_eq!(unsafe { object.__bindgen_anon_1.int64_value }, 1 << 40);
+
+        let buffer = DartObject::Buffer(vec![0xca, 0xfe]);
+        let data = DartObjectData::new(&buffer).unwrap();
+        let object = data.to_ffi();
+        assert_eq!(object.type_, Type::kFlutterEngineDartObjectTypeBuffer);
+        let buffer = unsafe { &*object.__bindgen_anon_1.buffer_value };
+        assert!(buffer.buffer_collect_callback.is_none());
+        assert_eq!(
+            unsafe { slice::from_raw_parts(buffer.buffer, buffer.buffer_size) },
+            [0xca, 0xfe]
+        );
+
+        let string = DartObject::String("nul\0".into());
+        assert!(DartObjectData::new(&string).is_none());
+    }
+
+    #[test]
+    fn decode_platform_view_mutations() {
+        let mut opacity: flust_engine_sys::FlutterPlatformViewMutation = unsafe { mem::zeroed() };
+        opacity.type_ = flust_engine_sys::FlutterPlatformViewMutationType::kFlutterPlatformViewMutationTypeOpacity;
+        opacity.__bindgen_anon_1.opacity = 0.5;
+        let mut clip: flust_engine_sys::FlutterPlatformViewMutation = unsafe { mem::zeroed() };
+        clip.type_ = flust_engine_sys::FlutterPlatformViewMutationType::kFlutterPlatformViewMutationTypeClipRect;
+        clip.__bindgen_anon_1.clip_rect = flust_engine_sys::FlutterRect {
+            left: 0.0,
+            top: 10.0,
+            right: 20.0,
+            bottom: 30.0,
+        };
+        let mutations = [&opacity as *const _, &clip as *const _];
+
+        let mut platform_view: flust_engine_sys::FlutterPlatformView = unsafe { mem::zeroed() };
+        platform_view.struct_size = mem::size_of::<flust_engine_sys::FlutterPlatformView>();
+        platform_view.identifier = 3;
+        platform_view.mutations_count = mutations.len();
+        platform_view.mutations = mutations.as_ptr() as *mut _;
+
+        let platform_view = FlutterPlatformView::from(&platform_view);
+
+        assert_eq!(platform_view.identifier, 3);
+        let [FlutterPlatformViewMutation::Opacity(opacity), FlutterPlatformViewMutation::ClipRect(clip)] =
+            platform_view.mutations[..]
+        else {
+            panic!("unexpected mutations: {:?}", platform_view.mutations);
+        };
+        assert_eq!(opacity, 0.5);
+        assert_eq!(
+            PhysicalRect::from(clip),
+            PhysicalRect::new(
+                PhysicalPosition::new(0.0, 10.0),
+                PhysicalSize::new(20.0, 20.0)
+            )
+        );
+    }
+
+    #[test]
+    fn decode_semantics_node() {
+        let label = CString::new("Send").unwrap();
+        let children = [3, 4];
+
+        let mut node: flust_engine_sys::FlutterSemanticsNode2 = unsafe { mem::zeroed() };
+        node.flags = flust_engine_sys::FlutterSemanticsFlag::kFlutterSemanticsFlagIsButton
+            | flust_engine_sys::FlutterSemanticsFlag::kFlutterSemanticsFlagIsFocused;
+        node.actions = flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionTap
+            | flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionLongPress;
+        node.struct_size = mem::size_of::<flust_engine_sys::FlutterSemanticsNode2>();
+        node.id = 2;
+        node.label = label.as_ptr();
+        node.text_direction = flust_engine_sys::FlutterTextDirection::kFlutterTextDirectionLTR;
+        node.child_count = children.len();
+        node.children_in_traversal_order = children.as_ptr();
+        node.children_in_hit_test_order = children.as_ptr();
+        node.platform_view_id = -1;
+
+        let node = FlutterSemanticsNode::from(&node);
+
+        assert_eq!(node.id, 2);
+        assert_eq!(
+            node.flags,
+            FlutterSemanticsFlags::IS_BUTTON | FlutterSemanticsFlags::IS_FOCUSED
+        );
+        assert_eq!(
+            node.actions,
+            vec![
+                FlutterSemanticsAction::Tap,
+                FlutterSemanticsAction::LongPress
+            ]
+        );
+        assert_eq!(node.label, "Send");
+        assert_eq!(node.hint, "");
+        assert_eq!(node.text_direction, FlutterTextDirection::LeftToRight);
+        assert_eq!(node.children_in_traversal_order, vec![3, 4]);
+        assert!(node.custom_accessibility_actions.is_empty());
+        assert_eq!(node.platform_view_id, None);
+
+        assert_eq!(
+            FlutterSemanticsAction::from_bitmask((1 << 1) | (1 << 0) | (1 << 31)),
+            vec![
+                FlutterSemanticsAction::Tap,
+                FlutterSemanticsAction::LongPress
+            ]
+        );
+    }
+
+    #[test]
+    fn convert_multi_rect_paint_region() {
+        let rects = [
+            flust_engine_sys::FlutterRect {
+                left: 0.0,
+                top: 0.0,
+                right: 100.0,
+                bottom: 20.0,
+            },
+            flust_engine_sys::FlutterRect {
+                left: 10.0,
+                top: 40.0,
+                right: 30.0,
+                bottom: 90.0,
+            },
+        ];
+        let mut region = flust_engine_sys::FlutterRegion {
+            struct_size: mem::size_of::<flust_engine_sys::FlutterRegion>(),
+            rects_count: rects.len(),
+            rects: rects.as_ptr() as *mut _,
+        };
+        let present_info = flust_engine_sys::FlutterBackingStorePresentInfo {
+            struct_size: mem::size_of::<flust_engine_sys::FlutterBackingStorePresentInfo>(),
+            paint_region: &mut region,
+        };
+
+        let present_info = FlutterBackingStorePresentInfo::from(present_info);
+
+        assert_eq!(
+            present_info.paint_rects(),
+            vec![
+                PhysicalRect::new(
+                    PhysicalPosition::new(0.0, 0.0),
+                    PhysicalSize::new(100.0, 20.0)
+                ),
+                PhysicalRect::new(
+                    PhysicalPosition::new(10.0, 40.0),
+                    PhysicalSize::new(20.0, 50.0)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_key_down_produces_empty_event() {
+        let pressed = [FlutterPhysicalKey::new(0x00070004)];
+        let is_pressed = |physical: &FlutterPhysicalKey| pressed.contains(physical);
+        let event = |kind| {
+            FlutterKeyEvent::new(
+                Duration::from_millis(10),
+                kind,
+                FlutterPhysicalKey::new(0x00070004),
+                FlutterLogicalKey::new(0x00000061),
+                CString::new("a").ok(),
+                false,
+                FlutterKeyEventDeviceType::Keyboard,
+            )
+        };
+
+        let down = event(FlutterKeyEventType::Down).deduplicate_down(is_pressed);
+        assert!(down.is_empty());
+        assert_eq!(down.kind(), FlutterKeyEventType::Down);
+        assert!(down.as_ptr().character.is_null());
+
+        let repeat = event(FlutterKeyEventType::Repeat).deduplicate_down(is_pressed);
+        assert!(!repeat.is_empty());
+
+        let other = FlutterKeyEvent::new(
+            Duration::from_millis(10),
+            FlutterKeyEventType::Down,
+            FlutterPhysicalKey::new(0x00070005),
+            FlutterLogicalKey::new(0x00000062),
+            None,
+            false,
+            FlutterKeyEventDeviceType::Keyboard,
+        )
+        .deduplicate_down(is_pressed);
+        assert!(!other.is_empty());
+    }
+}
@@ -1,19 +1,78 @@
+use crate::affinity::{set_current_thread_affinity, EngineThread, THREAD_AFFINITY};
 use crate::ffi::{
-    FlutterBackingStore, FlutterBackingStoreConfig, FlutterFrameInfo, FlutterLayer,
-    FlutterPresentViewInfo,
+    FlutterBackingStore, FlutterBackingStoreConfig, FlutterChannelUpdate, FlutterFrameInfo,
+    FlutterLayer, FlutterLocale, FlutterPresentInfo, FlutterPresentViewInfo,
+    FlutterSemanticsUpdate,
 };
 use crate::tasks::{TaskRunner, TaskRunnerInner};
-use crate::FlutterEngineInner;
+use crate::{
+    FlutterEngineInner, NextFrameCallbackUserData, ViewOperationUserData,
+    PLATFORM_RESOLVED_LOCALE_HANDLER,
+};
 use core::slice;
 use parking_lot::Mutex;
 use std::ffi::{c_char, c_uint, c_void, CStr};
-use tracing::trace;
+use std::ptr;
+use tracing::{error, trace, trace_span, Span};
+
+pub extern "C" fn present_with_info(
+    user_data: *mut c_void,
+    present_info: *const flust_engine_sys::FlutterPresentInfo,
+) -> bool {
+    trace!("present_with_info");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let present_info = FlutterPresentInfo::from(*present_info);
+        let damage: Vec<_> = present_info
+            .frame_damage
+            .iter()
+            .map(|rect| (rect.origin, rect.size))
+            .collect();
+        let presented = engine
+            .implicit_view_opengl_handler()
+            .unwrap()
+            .present_with_damage(&damage);
+        engine
+            .window_damage
+            .lock()
+            .frame_presented(presented, &present_info.frame_damage);
+        engine.record_present_result(presented)
+    }
+}
+
+pub extern "C" fn populate_existing_damage(
+    user_data: *mut c_void,
+    _fbo_id: isize,
+    existing_damage: *mut flust_engine_sys::FlutterDamage,
+) {
+    trace!("populate_existing_damage");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let buffer_age = engine.implicit_view_opengl_handler().unwrap().buffer_age();
+        engine
+            .window_damage
+            .lock()
+            .report(buffer_age, &mut *existing_damage);
+    }
+}
 
-pub extern "C" fn present(user_data: *mut c_void) -> bool {
-    trace!("present");
+pub extern "C" fn software_surface_present(
+    user_data: *mut c_void,
+    allocation: *const c_void,
+    row_bytes: usize,
+    height: usize,
+) -> bool {
+    trace!("software_surface_present");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine.implicit_view_opengl_handler().unwrap().present()
+        let Some(handler) = engine.implicit_view_software_handler() else {
+            error!("No software handler registered for the implicit view");
+            return engine.record_present_result(false);
+        };
+
+        let allocation = slice::from_raw_parts(allocation as *const u8, row_bytes * height);
+        let presented = handler.present(allocation, row_bytes, height);
+        engine.record_present_result(presented)
     }
 }
 
@@ -47,6 +106,7 @@ pub extern "C" fn fbo_with_frame_info_callback(
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
         let frame_info = FlutterFrameInfo::from(*frame_info);
+        engine.window_damage.lock().set_frame_size(frame_info.size);
         engine
             .implicit_view_opengl_handler()
             .unwrap()
@@ -81,6 +141,8 @@ pub extern "C" fn vsync_callback(user_data: *mut c_void, baton: isize) {
     trace!("vsync_callback");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
+        engine.frame_request_tracker.frame_requested(baton);
+
         // `vsync_callback` will only be called when `vsync_handler` is not empty,
         // so using `unwrap()` should be safe in here.
         engine
@@ -92,6 +154,26 @@ pub extern "C" fn vsync_callback(user_data: *mut c_void, baton: isize) {
     }
 }
 
+pub extern "C" fn update_semantics_callback2(
+    update: *const flust_engine_sys::FlutterSemanticsUpdate2,
+    user_data: *mut c_void,
+) {
+    trace!("update_semantics_callback2");
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let update = FlutterSemanticsUpdate::from(&*update);
+        // `update_semantics_callback2` will only be called when
+        // `semantics_handler` is not empty, so using `unwrap()` should be safe
+        // in here.
+        engine
+            .semantics_handler
+            .as_ref()
+            .unwrap()
+            .lock()
+            .update_semantics(update);
+    }
+}
+
 pub extern "C" fn compositor_backing_store_create_callback(
     config: *const flust_engine_sys::FlutterBackingStoreConfig,
     backing_store_out: *mut flust_engine_sys::FlutterBackingStore,
@@ -101,15 +183,13 @@ pub extern "C" fn compositor_backing_store_create_callback(
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
         let config = FlutterBackingStoreConfig::from(*config);
-        if let Ok(backing_store) = engine
-            .compositor_handler_for_view(config.view_id)
-            .unwrap()
-            .create_backing_store(config)
-        {
-            backing_store.into_ffi(&mut *backing_store_out);
-            return true;
+        let _span = create_backing_store_span(&config).entered();
+        let Some(backing_store) = engine.create_backing_store(config) else {
+            return false;
         };
-        false
+
+        backing_store.into_ffi(&mut *backing_store_out);
+        true
     }
 }
 
@@ -121,11 +201,8 @@ pub extern "C" fn compositor_backing_store_collect_callback(
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
         let backing_store = FlutterBackingStore::from(*backing_store);
-        engine
-            .compositor_handler_for_view(backing_store.user_data.view_id)
-            .unwrap()
-            .collect_backing_store(backing_store)
-            .is_ok()
+        let _span = collect_backing_store_span(&backing_store).entered();
+        engine.collect_backing_store(backing_store)
     }
 }
 
@@ -143,12 +220,80 @@ pub extern "C" fn compositor_present_view_callback(
             .collect();
 
         let info = FlutterPresentViewInfo::new(info.view_id, layers);
+        let _span = present_view_span(&info).entered();
+        engine.present_view(info)
+    }
+}
 
-        engine
-            .compositor_handler_for_view(info.view_id)
-            .unwrap()
-            .present_view(info)
-            .is_ok()
+// Trace level spans around the compositor callbacks, so that compositing can be
+// traced per frame and view. Their fields are only evaluated while the spans
+// are enabled.
+
+fn create_backing_store_span(config: &FlutterBackingStoreConfig) -> Span {
+    trace_span!(
+        "create_backing_store",
+        view_id = config.view_id,
+        width = config.size.width,
+        height = config.size.height,
+    )
+}
+
+fn collect_backing_store_span(backing_store: &FlutterBackingStore) -> Span {
+    trace_span!(
+        "collect_backing_store",
+        view_id = backing_store.user_data.view_id,
+    )
+}
+
+fn present_view_span(info: &FlutterPresentViewInfo) -> Span {
+    trace_span!(
+        "present_view",
+        view_id = info.view_id,
+        layer_count = info.layers.len(),
+    )
+}
+
+pub extern "C" fn add_view_callback(result: *const flust_engine_sys::FlutterAddViewResult) {
+    trace!("add_view_callback");
+    unsafe {
+        let result = &*result;
+        let user_data = ViewOperationUserData::from_raw(result.user_data);
+        if !result.added {
+            error!("Failed to add view {} to the engine", user_data.view_id);
+            if let Some(engine) = user_data.engine.upgrade() {
+                engine.view_registry.write().remove_view(user_data.view_id);
+            }
+        }
+
+        (user_data.on_result)(result.added);
+    }
+}
+
+pub extern "C" fn remove_view_callback(result: *const flust_engine_sys::FlutterRemoveViewResult) {
+    trace!("remove_view_callback");
+    unsafe {
+        let result = &*result;
+        let user_data = ViewOperationUserData::from_raw(result.user_data);
+        if result.removed {
+            if let Some(engine) = user_data.engine.upgrade() {
+                engine.view_registry.write().remove_view(user_data.view_id);
+            }
+        } else {
+            error!(
+                "Failed to remove view {} from the engine",
+                user_data.view_id
+            );
+        }
+
+        (user_data.on_result)(result.removed);
+    }
+}
+
+pub extern "C" fn next_frame_callback(user_data: *mut c_void) {
+    trace!("next_frame_callback");
+    unsafe {
+        let user_data = NextFrameCallbackUserData::from_raw(user_data);
+        (user_data.callback)();
     }
 }
 
@@ -159,10 +304,7 @@ pub extern "C" fn platform_message_callback(
     trace!("platform_message_callback");
     unsafe {
         let engine = &*(user_data as *const FlutterEngineInner);
-        engine
-            .channel_registry
-            .read()
-            .handle((*platform_message).into());
+        engine.handle_platform_message((*platform_message).into());
     }
 }
 
@@ -177,6 +319,104 @@ pub extern "C" fn root_isolate_create_callback(_user_data: *mut c_void) {
     // }
 }
 
+pub extern "C" fn on_pre_engine_restart_callback(user_data: *mut c_void) {
+    trace!("on_pre_engine_restart_callback");
+    // This callback is executed on the platform thread
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        engine.pre_engine_restart();
+    }
+}
+
+pub extern "C" fn compute_platform_resolved_locale_callback(
+    supported_locales: *mut *const flust_engine_sys::FlutterLocale,
+    number_of_locales: usize,
+) -> *const flust_engine_sys::FlutterLocale {
+    trace!("compute_platform_resolved_locale_callback");
+    if supported_locales.is_null() || number_of_locales == 0 {
+        return ptr::null();
+    }
+
+    let supported_locales = unsafe { slice::from_raw_parts(supported_locales, number_of_locales) };
+    resolve_locale(supported_locales, |locales| {
+        PLATFORM_RESOLVED_LOCALE_HANDLER
+            .read()
+            .as_ref()
+            .and_then(|handler| handler(locales))
+    })
+}
+
+pub extern "C" fn thread_priority_setter(priority: flust_engine_sys::FlutterThreadPriority) {
+    trace!("thread_priority_setter: {:?}", priority);
+    if let Some(thread) = EngineThread::from_priority(priority) {
+        THREAD_AFFINITY
+            .read()
+            .apply(thread, set_current_thread_affinity);
+    }
+}
+
+/// Returns the locale of `supported_locales` picked by `resolve`, or null if
+/// none was picked (or if a locale is null).
+fn resolve_locale(
+    supported_locales: &[*const flust_engine_sys::FlutterLocale],
+    resolve: impl FnOnce(&[FlutterLocale]) -> Option<usize>,
+) -> *const flust_engine_sys::FlutterLocale {
+    if supported_locales.iter().any(|locale| locale.is_null()) {
+        error!("The engine passed a null supported locale");
+        return ptr::null();
+    }
+
+    let locales: Vec<_> = supported_locales
+        .iter()
+        .map(|&locale| FlutterLocale::from(unsafe { &*locale }))
+        .collect();
+
+    match resolve(&locales) {
+        Some(index) if index < supported_locales.len() => supported_locales[index],
+        Some(index) => {
+            error!(
+                "Resolved locale index {} is out of bounds ({} supported locales)",
+                index,
+                supported_locales.len()
+            );
+            ptr::null()
+        }
+        None => ptr::null(),
+    }
+}
+
+pub extern "C" fn channel_update_callback(
+    update: *const flust_engine_sys::FlutterChannelUpdate,
+    user_data: *mut c_void,
+) {
+    trace!("channel_update_callback");
+    if update.is_null() {
+        return;
+    }
+
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        engine.channel_update(FlutterChannelUpdate::from(&*update));
+    }
+}
+
+pub extern "C" fn log_message_callback(
+    tag: *const c_char,
+    message: *const c_char,
+    user_data: *mut c_void,
+) {
+    if tag.is_null() || message.is_null() {
+        return;
+    }
+
+    unsafe {
+        let engine = &*(user_data as *const FlutterEngineInner);
+        let tag = CStr::from_ptr(tag).to_string_lossy();
+        let message = CStr::from_ptr(message).to_string_lossy();
+        engine.log_message(&tag, &message);
+    }
+}
+
 pub extern "C" fn runs_task_on_current_thread(user_data: *mut c_void) -> bool {
     trace!("runs_task_on_current_thread");
     unsafe {
@@ -218,3 +458,117 @@ pub extern "C" fn gl_external_texture_frame(
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::Arc;
+
+    use flust_engine_sys::FlutterSize;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use super::*;
+
+    /// The name and fields of a span.
+    type RecordedSpan = (&'static str, Vec<(&'static str, String)>);
+
+    /// Records every created span.
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    }
+
+    struct FieldRecorder(Vec<(&'static str, String)>);
+
+    impl Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push((field.name(), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = FieldRecorder(Vec::new());
+            span.record(&mut fields);
+
+            let mut spans = self.spans.lock();
+            spans.push((span.metadata().name(), fields.0));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn compositor_spans_carry_frame_fields() {
+        let recorder = SpanRecorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let config = FlutterBackingStoreConfig {
+                size: FlutterSize {
+                    width: 800.0,
+                    height: 600.0,
+                },
+                view_id: 1,
+            };
+            let _span = create_backing_store_span(&config).entered();
+            let _span = present_view_span(&FlutterPresentViewInfo::new(2, vec![])).entered();
+        });
+
+        assert_eq!(
+            *recorder.spans.lock(),
+            vec![
+                (
+                    "create_backing_store",
+                    vec![
+                        ("view_id", "1".to_string()),
+                        ("width", "800.0".to_string()),
+                        ("height", "600.0".to_string()),
+                    ]
+                ),
+                (
+                    "present_view",
+                    vec![
+                        ("view_id", "2".to_string()),
+                        ("layer_count", "0".to_string())
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolved_locale_points_to_a_supported_locale() {
+        let strings: Vec<_> = [
+            FlutterLocale::new("en", None),
+            FlutterLocale::new("fr", None),
+        ]
+        .iter()
+        .map(|locale| crate::ffi::FlutterLocaleStrings::new(locale).unwrap())
+        .collect();
+        let locales: Vec<_> = strings.iter().map(|strings| strings.to_ffi()).collect();
+        let supported: Vec<_> = locales.iter().map(|locale| locale as *const _).collect();
+
+        let french = resolve_locale(&supported, |locales| {
+            locales
+                .iter()
+                .position(|locale| locale.language_code == "fr")
+        });
+        assert_eq!(french, supported[1]);
+        assert!(resolve_locale(&supported, |_| Some(2)).is_null());
+        assert!(resolve_locale(&supported, |_| None).is_null());
+    }
+}
@@ -1,80 +1,317 @@
+pub mod affinity;
 pub mod builder;
 pub mod channel;
 pub mod codec;
 pub mod compositor;
+mod damage;
+pub mod diagnostics;
 pub mod error;
 pub mod ffi;
 mod flutter_callbacks;
 pub mod plugins;
+pub mod semantics;
 pub mod tasks;
 pub mod view;
+pub mod vm_service;
+mod vsync;
 
 pub mod texture_registry;
 
-use crate::builder::FlutterEngineBuilder;
+#[cfg(test)]
+mod temp_dir;
+
+use crate::affinity::{EngineThread, THREAD_AFFINITY};
+use crate::builder::{FlutterEngineBuilder, RuntimeMode};
 use crate::channel::{Channel, ChannelRegistry};
 
-use crate::channel::platform_message::{PlatformMessage, PlatformMessageResponseHandle};
-use crate::tasks::TaskRunner;
+use crate::channel::platform_message::{
+    PendingResponses, PlatformMessage, PlatformMessageResponseHandle,
+};
+use crate::tasks::{
+    RenderThread, TaskBudget, TaskRunner, PLATFORM_TASK_RUNNER_IDENTIFIER,
+    RENDER_TASK_RUNNER_IDENTIFIER,
+};
 use crate::texture_registry::{Texture, TextureRegistry};
-use compositor::FlutterCompositorHandler;
+use compositor::{CompositorStartupMonitor, FlutterCompositorHandler};
 use crossbeam_channel::{unbounded, Receiver, Sender};
+use damage::WindowDamage;
+use diagnostics::{EngineErrorHandler, PresentFailureMonitor};
+use dpi::PhysicalSize;
 use ffi::{
-    FlutterEngineAOTData, FlutterEngineDisplay, FlutterEngineDisplaysUpdateType,
-    FlutterEngineResult, FlutterEngineResultExt, FlutterKeyEvent, FlutterPointerEvent,
-    FlutterViewId,
+    DartObject, DartObjectData, FlutterBackingStore, FlutterBackingStoreConfig,
+    FlutterChannelUpdate, FlutterEngineAOTData, FlutterEngineDartPort, FlutterEngineDisplay,
+    FlutterEngineDisplaysUpdateType, FlutterEngineResult, FlutterEngineResultExt, FlutterKeyEvent,
+    FlutterLocale, FlutterLocaleStrings, FlutterPointerEvent, FlutterPresentViewInfo,
+    FlutterSemanticsAction, FlutterViewId, FlutterWindowMetrics, IMPLICIT_VIEW_ID,
 };
-use flust_engine_api::FlutterOpenGLHandler;
+use flust_engine_api::{FlutterOpenGLHandler, FlutterSoftwareHandler};
 use flust_engine_sys::{
     FlutterCompositor, FlutterEngineDisplayId, FlutterEngineGetCurrentTime,
-    FlutterEngineRunsAOTCompiledDartCode, FlutterTask, VsyncCallback,
+    FlutterEngineRunsAOTCompiledDartCode, FlutterPlatformMessageResponseHandle, FlutterTask,
+    VsyncCallback,
 };
 use parking_lot::{Mutex, RwLock};
+use semantics::FlutterSemanticsHandler;
+use std::collections::HashSet;
 use std::ffi::{c_void, CString};
 use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use thiserror::Error;
-use tracing::trace;
-use view::{FlutterView, ViewRegistry};
+use tracing::{debug, error, info, trace, warn};
+use view::{FlutterPointerTransform, FlutterView, ViewRegistry};
+use vsync::FrameRequestTracker;
+
+// These embedder API calls go to fakes recording them in tests, which check
+// the arguments reaching the embedder API.
+#[cfg(not(test))]
+use flust_engine_sys::{
+    FlutterEngineDispatchSemanticsAction, FlutterEngineInitialize, FlutterEngineSendPointerEvent,
+};
+#[cfg(test)]
+use tests::{
+    FlutterEngineDispatchSemanticsAction, FlutterEngineInitialize, FlutterEngineSendPointerEvent,
+};
+
+/// Picks the locale the app should use among the ones it supports, returning
+/// its index (see [`builder::FlutterEngineBuilder::with_platform_resolved_locale_handler`]).
+pub type PlatformResolvedLocaleHandler = dyn Fn(&[FlutterLocale]) -> Option<usize> + Send + Sync;
+
+/// The handler of the last engine created with one. The engine doesn't pass
+/// a user data to `compute_platform_resolved_locale_callback`, so it can't be
+/// looked up from the engine.
+pub(crate) static PLATFORM_RESOLVED_LOCALE_HANDLER: RwLock<
+    Option<Box<PlatformResolvedLocaleHandler>>,
+> = RwLock::new(None);
 
 pub(crate) type MainThreadEngineFn = Box<dyn FnOnce(&FlutterEngine) + Send>;
 pub(crate) type MainThreadRenderThreadFn = Box<dyn FnOnce(&FlutterEngine) + Send>;
 
 pub(crate) enum MainThreadCallback {
     Engine(MainThreadEngineFn),
-    RenderThread(MainThreadRenderThreadFn),
 }
 
 struct FlutterEngineInner {
     view_registry: RwLock<ViewRegistry>,
     vsync_handler: Option<Arc<Mutex<dyn FlutterVsyncHandler + Send>>>,
+    frame_request_tracker: FrameRequestTracker,
+    semantics_handler: Option<Arc<Mutex<dyn FlutterSemanticsHandler + Send>>>,
+    engine_error_handler: Option<Arc<Mutex<dyn EngineErrorHandler + Send>>>,
+    pre_engine_restart_handler: Option<Box<dyn Fn() + Send>>,
+    present_failure_monitor: PresentFailureMonitor,
+    window_damage: Mutex<WindowDamage>,
+    compositor_startup_monitor: CompositorStartupMonitor,
     engine_ptr: flust_engine_sys::FlutterEngine,
+    shut_down: AtomicBool,
     channel_registry: RwLock<ChannelRegistry>,
+    pending_responses: Mutex<PendingResponses>,
     platform_runner: TaskRunner,
+    platform_task_budget: Option<Duration>,
     platform_receiver: Receiver<MainThreadCallback>,
     platform_sender: Sender<MainThreadCallback>,
+    render_thread: RenderThread,
     texture_registry: TextureRegistry,
     aot_data: FlutterEngineAOTData,
     assets: PathBuf,
     icu_data: PathBuf,
     persistent_cache: PathBuf,
     arguments: Vec<String>,
+    log_tag: Option<CString>,
+    vm_service_uri: RwLock<Option<String>>,
 }
 
 impl FlutterEngineInner {
+    /// Handles a message logged by the engine or the Dart code (through
+    /// `print`), forwarding it to `tracing` (with the `flutter` target and the
+    /// log tag as the `tag` field), and recording the VM service URI when it's
+    /// announced.
+    fn log_message(&self, tag: &str, message: &str) {
+        if let Some(uri) = vm_service_uri_from_log(message) {
+            debug!("Dart VM service URI: {}", uri);
+            *self.vm_service_uri.write() = Some(uri.to_owned());
+
+            // Setting a log message callback disables the engine's own
+            // printing, which tooling (e.g.: `flutter attach`) relies on for
+            // discovering the VM service.
+            println!("{}: {}", tag, message);
+        }
+
+        info!(target: "flutter", tag, "{}", message);
+    }
+
+    /// Handles the engine being about to restart (e.g.: on hot restart),
+    /// before the new root isolate is launched.
+    fn pre_engine_restart(&self) {
+        debug!("The engine is about to restart");
+        if let Some(handler) = &self.pre_engine_restart_handler {
+            handler();
+        }
+    }
+
+    /// Dispatches a platform message received from the engine to its
+    /// channel, tracking its response handle (see [`PendingResponses`]).
+    fn handle_platform_message(&self, message: PlatformMessage) {
+        if let Some(handle) = &message.response_handle {
+            self.pending_responses
+                .lock()
+                .track(handle, &message.channel);
+        }
+        self.channel_registry.read().handle(message);
+    }
+
+    /// Records whether Dart listens to a channel, notifying the channel if
+    /// it's registered.
+    fn channel_update(&self, update: FlutterChannelUpdate) {
+        trace!(
+            "Dart {} listening to channel {}",
+            if update.listening {
+                "started"
+            } else {
+                "stopped"
+            },
+            update.channel
+        );
+        let channel = self
+            .channel_registry
+            .write()
+            .update_listening(&update.channel, update.listening);
+
+        if let Some(channel) = channel {
+            channel.listening_changed(update.listening);
+        }
+    }
+
     fn implicit_view_opengl_handler(&self) -> Option<Arc<dyn FlutterOpenGLHandler>> {
         self.view_registry.read().implicit_view_opengl_handler()
     }
 
+    fn implicit_view_software_handler(&self) -> Option<Arc<dyn FlutterSoftwareHandler>> {
+        self.view_registry.read().implicit_view_software_handler()
+    }
+
+    /// The compositor handler `operation` is dispatched to, or `None` (after
+    /// logging why) if the operation must be rejected (e.g.: for a view that
+    /// was never added or was removed already).
     fn compositor_handler_for_view(
         &self,
         view_id: FlutterViewId,
+        operation: &str,
     ) -> Option<Arc<dyn FlutterCompositorHandler>> {
         self.view_registry
             .read()
             .compositor_handler_for_view(view_id)
+            .inspect_err(|err| error!("Rejecting {}: {}", operation, err))
+            .ok()
+    }
+
+    /// Records the outcome of presenting a frame, reporting repeated failures
+    /// to the engine error handler.
+    fn record_present_result(&self, presented: bool) -> bool {
+        self.present_failure_monitor
+            .record(presented, self.engine_error_handler.as_ref());
+        presented
+    }
+
+    /// Creates a backing store through the compositor handler of the view,
+    /// or for its window framebuffer once the compositor fell back (see
+    /// [`FlutterEngineBuilder::with_compositor_fallback`]).
+    fn create_backing_store(
+        &self,
+        config: FlutterBackingStoreConfig,
+    ) -> Option<FlutterBackingStore> {
+        if !self.compositor_startup_monitor.is_fallen_back() {
+            let handler =
+                self.compositor_handler_for_view(config.view_id, "backing store creation")?;
+            let err = match handler.create_backing_store(config) {
+                Ok(backing_store) => return Some(backing_store),
+                Err(err) => err,
+            };
+
+            let fallen_back = self
+                .compositor_startup_monitor
+                .backing_store_failed(&err.to_string(), self.engine_error_handler.as_ref());
+            if !fallen_back {
+                return None;
+            }
+        }
+
+        let handler = self.opengl_handler_for_view(config.view_id, "backing store creation")?;
+        let framebuffer_id = handler.fbo_with_frame_info_callback(PhysicalSize::new(
+            config.size.width.round() as u32,
+            config.size.height.round() as u32,
+        ));
+        Some(FlutterBackingStore::window_framebuffer(
+            framebuffer_id,
+            handler.framebuffer_format(),
+            config.view_id,
+        ))
+    }
+
+    fn collect_backing_store(&self, backing_store: FlutterBackingStore) -> bool {
+        if backing_store.is_window_framebuffer() {
+            backing_store.collect_window_framebuffer();
+            return true;
+        }
+
+        let Some(handler) = self.compositor_handler_for_view(
+            backing_store.user_data.view_id,
+            "backing store collection",
+        ) else {
+            return false;
+        };
+
+        handler.collect_backing_store(backing_store).is_ok()
+    }
+
+    fn present_view(&self, info: FlutterPresentViewInfo) -> bool {
+        let presented = if self.compositor_startup_monitor.is_fallen_back() {
+            self.present_window_framebuffer(info)
+        } else {
+            let presented = self
+                .compositor_handler_for_view(info.view_id, "present")
+                .is_some_and(|handler| handler.present_view(info).is_ok());
+            self.compositor_startup_monitor
+                .record_present(presented, self.engine_error_handler.as_ref());
+            presented
+        };
+        self.record_present_result(presented)
+    }
+
+    /// Presents a frame rendered into the window framebuffer of the view, as
+    /// if the compositor was disabled.
+    fn present_window_framebuffer(&self, info: FlutterPresentViewInfo) -> bool {
+        let Some(handler) = self.opengl_handler_for_view(info.view_id, "present") else {
+            return false;
+        };
+
+        // The frame size is otherwise only reported when creating backing
+        // stores, which the engine caches across frames.
+        if let Some(layer) = info.layers.first() {
+            handler.fbo_with_frame_info_callback(PhysicalSize::new(
+                layer.size.width.round() as u32,
+                layer.size.height.round() as u32,
+            ));
+        }
+
+        handler.present()
+    }
+
+    fn opengl_handler_for_view(
+        &self,
+        view_id: FlutterViewId,
+        operation: &str,
+    ) -> Option<Arc<dyn FlutterOpenGLHandler>> {
+        let handler = self.view_registry.read().opengl_handler_for_view(view_id);
+        if handler.is_none() {
+            error!(
+                "Rejecting {}: {}",
+                operation,
+                FlutterEngineError::ViewNotFound(view_id)
+            );
+        }
+        handler
     }
 }
 
@@ -135,19 +372,25 @@ pub trait FlutterVsyncHandler {
 }
 
 impl FlutterEngine {
-    pub(crate) fn new(builder: FlutterEngineBuilder) -> Result<Self, CreateError> {
+    pub(crate) fn new(
+        builder: FlutterEngineBuilder,
+        log_tag: Option<CString>,
+    ) -> Result<Self, CreateError> {
         // Convert arguments into flutter compatible
         //
         // FlutterProjectArgs expects a full argv, so when processing it for flags
         // the first item is treated as the executable and ignored. Add a dummy value
         // so that all switches are used.
         let dummy_args: Vec<String> = vec!["flust".into()];
-        let args = [
-            dummy_args,
-            FlutterEngine::args_from_env_vars(),
-            builder.args.clone(),
-        ]
-        .concat();
+        let args = dedup_switches(
+            [
+                dummy_args,
+                FlutterEngine::args_from_env_vars(),
+                builder.args.clone(),
+            ]
+            .concat(),
+        );
+        debug!("Engine command line: {:?}", args);
 
         let mut args: Vec<_> = args
             .iter()
@@ -161,51 +404,86 @@ impl FlutterEngine {
             inner: Arc::new(FlutterEngineInner {
                 view_registry: RwLock::new(ViewRegistry::default()),
                 vsync_handler: builder.vsync_handler,
+                frame_request_tracker: FrameRequestTracker::default(),
+                semantics_handler: builder.semantics_handler,
+                engine_error_handler: builder.engine_error_handler,
+                pre_engine_restart_handler: builder.pre_engine_restart_handler,
+                present_failure_monitor: PresentFailureMonitor::default(),
+                window_damage: Mutex::new(WindowDamage::default()),
+                compositor_startup_monitor: CompositorStartupMonitor::new(
+                    builder.compositor_fallback,
+                ),
                 engine_ptr: ptr::null_mut(),
+                shut_down: AtomicBool::new(false),
                 channel_registry: RwLock::new(ChannelRegistry::new()),
+                pending_responses: Default::default(),
                 platform_runner: TaskRunner::new(
                     builder.platform_handler.expect("No platform runner set"),
                 ),
+                platform_task_budget: builder.platform_task_budget,
                 platform_receiver: main_rx,
                 platform_sender: main_tx,
+                render_thread: RenderThread::spawn(builder.thread_affinity.clone())
+                    .map_err(CreateError::RenderThreadSpawnFailed)?,
                 texture_registry: TextureRegistry::new(),
-                aot_data: FlutterEngineAOTData::new(&builder.aot_library)?,
+                aot_data: FlutterEngineAOTData::new(
+                    &builder.aot_library,
+                    builder.aot_data.as_deref(),
+                )?,
                 assets: builder.assets,
                 icu_data: builder.icu_data,
                 persistent_cache: builder.persistent_cache,
                 arguments: builder.args,
+                log_tag,
+                vm_service_uri: RwLock::new(None),
             }),
         };
 
         let inner = &engine.inner;
         inner.channel_registry.write().init(engine.downgrade());
         inner.platform_runner.init(engine.downgrade());
+        inner.render_thread.runner.init(engine.downgrade());
 
         // Configure renderer
-        let renderer_config = flust_engine_sys::FlutterRendererConfig {
-            type_: flust_engine_sys::FlutterRendererType::kOpenGL,
-            __bindgen_anon_1: flust_engine_sys::FlutterRendererConfig__bindgen_ty_1 {
-                open_gl: flust_engine_sys::FlutterOpenGLRendererConfig {
-                    struct_size: std::mem::size_of::<flust_engine_sys::FlutterOpenGLRendererConfig>(
-                    ),
-                    make_current: Some(flutter_callbacks::make_current),
-                    clear_current: Some(flutter_callbacks::clear_current),
-                    present: Some(flutter_callbacks::present),
-                    fbo_callback: None,
-                    make_resource_current: Some(flutter_callbacks::make_resource_current),
-                    fbo_reset_after_present: false,
-                    surface_transformation: None,
-                    gl_proc_resolver: Some(flutter_callbacks::gl_proc_resolver),
-                    gl_external_texture_frame_callback: Some(
-                        flutter_callbacks::gl_external_texture_frame,
-                    ),
-                    fbo_with_frame_info_callback: Some(
-                        flutter_callbacks::fbo_with_frame_info_callback,
-                    ),
-                    present_with_info: None,
-                    populate_existing_damage: None,
+        let renderer_config = if builder.software_rendering {
+            flust_engine_sys::FlutterRendererConfig {
+                type_: flust_engine_sys::FlutterRendererType::kSoftware,
+                __bindgen_anon_1: flust_engine_sys::FlutterRendererConfig__bindgen_ty_1 {
+                    software: flust_engine_sys::FlutterSoftwareRendererConfig {
+                        struct_size: std::mem::size_of::<
+                            flust_engine_sys::FlutterSoftwareRendererConfig,
+                        >(),
+                        surface_present_callback: Some(flutter_callbacks::software_surface_present),
+                    },
                 },
-            },
+            }
+        } else {
+            flust_engine_sys::FlutterRendererConfig {
+                type_: flust_engine_sys::FlutterRendererType::kOpenGL,
+                __bindgen_anon_1: flust_engine_sys::FlutterRendererConfig__bindgen_ty_1 {
+                    open_gl: flust_engine_sys::FlutterOpenGLRendererConfig {
+                        struct_size: std::mem::size_of::<
+                            flust_engine_sys::FlutterOpenGLRendererConfig,
+                        >(),
+                        make_current: Some(flutter_callbacks::make_current),
+                        clear_current: Some(flutter_callbacks::clear_current),
+                        present: None,
+                        fbo_callback: None,
+                        make_resource_current: Some(flutter_callbacks::make_resource_current),
+                        fbo_reset_after_present: false,
+                        surface_transformation: None,
+                        gl_proc_resolver: Some(flutter_callbacks::gl_proc_resolver),
+                        gl_external_texture_frame_callback: Some(
+                            flutter_callbacks::gl_external_texture_frame,
+                        ),
+                        fbo_with_frame_info_callback: Some(
+                            flutter_callbacks::fbo_with_frame_info_callback,
+                        ),
+                        present_with_info: Some(flutter_callbacks::present_with_info),
+                        populate_existing_damage: Some(flutter_callbacks::populate_existing_damage),
+                    },
+                },
+            }
         };
 
         // Configure engine threads
@@ -221,14 +499,34 @@ impl FlutterEngine {
                 flutter_callbacks::runs_task_on_current_thread,
             ),
             post_task_callback: Some(flutter_callbacks::post_task),
-            identifier: 0,
+            identifier: PLATFORM_TASK_RUNNER_IDENTIFIER,
+        };
+        let render_runner_ptr = {
+            let arc = inner.render_thread.runner.clone().inner;
+            Weak::into_raw(Arc::downgrade(&arc)) as *mut std::ffi::c_void
+        };
+        let render_task_runner = flust_engine_sys::FlutterTaskRunnerDescription {
+            struct_size: std::mem::size_of::<flust_engine_sys::FlutterTaskRunnerDescription>(),
+            user_data: render_runner_ptr,
+            runs_task_on_current_thread_callback: Some(
+                flutter_callbacks::runs_task_on_current_thread,
+            ),
+            post_task_callback: Some(flutter_callbacks::post_task),
+            identifier: RENDER_TASK_RUNNER_IDENTIFIER,
         };
         let custom_task_runners = flust_engine_sys::FlutterCustomTaskRunners {
             struct_size: std::mem::size_of::<flust_engine_sys::FlutterCustomTaskRunners>(),
             platform_task_runner: &platform_task_runner
                 as *const flust_engine_sys::FlutterTaskRunnerDescription,
-            render_task_runner: std::ptr::null(),
-            thread_priority_setter: None,
+            render_task_runner: &render_task_runner
+                as *const flust_engine_sys::FlutterTaskRunnerDescription,
+            thread_priority_setter: match builder.thread_affinity.engine_threads() {
+                None => None,
+                Some(affinity) => {
+                    *THREAD_AFFINITY.write() = affinity;
+                    Some(flutter_callbacks::thread_priority_setter)
+                }
+            },
         };
 
         let vsync_callback: VsyncCallback = match inner.vsync_handler {
@@ -236,7 +534,28 @@ impl FlutterEngine {
             None => None,
         };
 
-        let compositor: *const FlutterCompositor = match builder.compositor_enabled {
+        let compute_platform_resolved_locale_callback: flust_engine_sys::FlutterComputePlatformResolvedLocaleCallback =
+            match builder.platform_resolved_locale_handler {
+                Some(handler) => {
+                    *PLATFORM_RESOLVED_LOCALE_HANDLER.write() = Some(handler);
+                    Some(flutter_callbacks::compute_platform_resolved_locale_callback)
+                }
+                None => None,
+            };
+
+        let update_semantics_callback2: flust_engine_sys::FlutterUpdateSemanticsCallback2 =
+            match inner.semantics_handler {
+                Some(_) => Some(flutter_callbacks::update_semantics_callback2),
+                None => None,
+            };
+
+        // The compositor only creates OpenGL backing stores.
+        let compositor_enabled = builder.compositor_enabled && !builder.software_rendering;
+        if builder.compositor_enabled && builder.software_rendering {
+            warn!("The compositor is not supported with software rendering, disabling it");
+        }
+
+        let compositor: *const FlutterCompositor = match compositor_enabled {
             false => std::ptr::null(),
             true => &FlutterCompositor {
                 struct_size: std::mem::size_of::<FlutterCompositor>(),
@@ -284,22 +603,25 @@ impl FlutterEngine {
             compositor,
             dart_old_gen_heap_size: -1,
             aot_data: inner.aot_data.data,
-            compute_platform_resolved_locale_callback: None,
+            compute_platform_resolved_locale_callback,
             dart_entrypoint_argc: 0,
             dart_entrypoint_argv: std::ptr::null(),
-            log_message_callback: None,
-            log_tag: std::ptr::null(),
-            on_pre_engine_restart_callback: None,
+            log_message_callback: Some(flutter_callbacks::log_message_callback),
+            log_tag: inner
+                .log_tag
+                .as_ref()
+                .map_or(std::ptr::null(), |tag| tag.as_ptr()),
+            on_pre_engine_restart_callback: Some(flutter_callbacks::on_pre_engine_restart_callback),
             update_semantics_callback: None,
-            update_semantics_callback2: None,
-            channel_update_callback: None,
+            update_semantics_callback2,
+            channel_update_callback: Some(flutter_callbacks::channel_update_callback),
         };
 
         // Initialise engine
         unsafe {
             let inner_ptr = Weak::into_raw(Arc::downgrade(inner)) as *mut std::ffi::c_void;
 
-            if flust_engine_sys::FlutterEngineInitialize(
+            if FlutterEngineInitialize(
                 1,
                 &renderer_config,
                 &project_args,
@@ -325,9 +647,32 @@ impl FlutterEngine {
         unsafe { FlutterEngineRunsAOTCompiledDartCode() }
     }
 
+    /// The engine handle, which is null once the engine was shut down: the
+    /// embedder API then rejects calls (with `kInvalidArguments`) instead of
+    /// accessing the freed engine.
     #[inline]
     pub fn engine_ptr(&self) -> flust_engine_sys::FlutterEngine {
-        self.inner.engine_ptr
+        if self.is_shut_down() {
+            ptr::null_mut()
+        } else {
+            self.inner.engine_ptr
+        }
+    }
+
+    /// Whether [`FlutterEngine::shutdown`] was called. Calls into the engine
+    /// are ignored from then on, or fail with [`FlutterEngineError::ShutDown`].
+    pub fn is_shut_down(&self) -> bool {
+        self.inner.shut_down.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if `operation` must be skipped because the engine was
+    /// shut down.
+    fn skip_after_shutdown(&self, operation: &str) -> bool {
+        let shut_down = self.is_shut_down();
+        if shut_down {
+            debug!("Ignoring {} after the engine was shut down", operation);
+        }
+        shut_down
     }
 
     fn args_from_env_vars() -> Vec<String> {
@@ -348,10 +693,26 @@ impl FlutterEngine {
         C: Channel + 'static,
     {
         trace!("register channel: {}", channel.name());
+        let (channel, listening) = {
+            let mut registry = self.inner.channel_registry.write();
+            let listening = registry.is_listening(channel.name());
+            (registry.register_channel(channel), listening)
+        };
+
+        if let Some(channel) = channel.upgrade().filter(|_| listening) {
+            channel.listening_changed(true);
+        }
+        channel
+    }
+
+    /// Whether Dart listens to the messages sent on `channel_name` (i.e.: has
+    /// a handler set for it). See [`Channel::listening_changed`] for being
+    /// notified of changes.
+    pub fn is_channel_listening(&self, channel_name: &str) -> bool {
         self.inner
             .channel_registry
-            .write()
-            .register_channel(channel)
+            .read()
+            .is_listening(channel_name)
     }
 
     pub fn remove_channel(&self, channel_name: &str) -> Option<Arc<dyn Channel>> {
@@ -391,10 +752,55 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        if self.skip_after_shutdown("run") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
         let result = unsafe { flust_engine_sys::FlutterEngineRunInitialized(self.engine_ptr()) };
         FlutterEngineResult::from_ffi(result)
     }
 
+    /// Schedules `callback` to be called once, after the next frame is drawn
+    /// (e.g.: for removing a native splash screen once the first frame is
+    /// rendered). The callback is re-threaded to the platform thread.
+    ///
+    /// This requires a running engine.
+    pub fn set_next_frame_callback<F>(&self, callback: F) -> Result<(), FlutterEngineError>
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        trace!("set_next_frame_callback");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("set_next_frame_callback") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let user_data =
+            NextFrameCallbackUserData::on_platform_thread(self.downgrade(), callback).into_raw();
+
+        let result = unsafe {
+            flust_engine_sys::FlutterEngineSetNextFrameCallback(
+                self.engine_ptr(),
+                Some(flutter_callbacks::next_frame_callback),
+                user_data,
+            )
+        };
+        if let Err(err) = FlutterEngineResult::from_ffi(result) {
+            drop(unsafe { NextFrameCallbackUserData::from_raw(user_data) });
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Registers a view along with its own OpenGL and compositor handlers.
+    ///
+    /// Compositor callbacks (backing store creation/collection and
+    /// presentation) are dispatched to the handlers of the view they target,
+    /// based on their `view_id`.
     pub fn add_view(&self, view: FlutterView) {
         self.inner.view_registry.write().add_view(view);
     }
@@ -403,6 +809,134 @@ impl FlutterEngine {
         self.inner.view_registry.write().remove_view(view_id);
     }
 
+    /// Sets how the coordinates of the pointer events of `view_id` are mapped
+    /// to the view (e.g.: when its surface is scaled or rotated by the
+    /// compositor).
+    ///
+    /// **This changes the unit of the events sent for the view**: with a
+    /// transform, their positions and deltas (scroll and pan) must be in
+    /// *logical* pixels of its surface (as reported by windowing systems like
+    /// Wayland), and the engine scales (and rotates) them. Without one (the
+    /// default), they must be in *physical* pixels of the view, and are sent
+    /// as is. Backends should set the transform along with the metrics of the
+    /// view, so that events are mapped with the scale factor the engine lays
+    /// out the view with.
+    pub fn set_view_pointer_transform(
+        &self,
+        view_id: FlutterViewId,
+        transform: Option<FlutterPointerTransform>,
+    ) -> Result<(), FlutterEngineError> {
+        self.inner
+            .view_registry
+            .write()
+            .set_pointer_transform(view_id, transform)
+    }
+
+    /// Returns the ids of the registered views (in ascending order).
+    pub fn view_ids(&self) -> Vec<FlutterViewId> {
+        self.inner.view_registry.read().view_ids()
+    }
+
+    /// Adds a secondary view (i.e.: any view other than the implicit view) to
+    /// the running engine, along with its initial metrics.
+    ///
+    /// The view is registered before the engine is notified, so that its
+    /// handlers are available as soon as the engine starts rendering into it.
+    /// Adding the view is completed asynchronously by the engine, at which
+    /// point `on_added` is called with whether it succeeded: if it failed, the
+    /// view is unregistered again. Note that `on_added` is called on an engine
+    /// managed thread.
+    pub fn add_secondary_view<F>(
+        &self,
+        view: FlutterView,
+        width: usize,
+        height: usize,
+        pixel_ratio: f64,
+        display_id: FlutterEngineDisplayId,
+        on_added: F,
+    ) -> Result<(), FlutterEngineError>
+    where
+        F: FnOnce(bool) + Send + 'static,
+    {
+        trace!("add_secondary_view");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("add_secondary_view") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let view_id = view.id();
+        if view_id == IMPLICIT_VIEW_ID {
+            return Err(FlutterEngineError::InvalidArguments);
+        }
+
+        self.add_view(view);
+
+        let metrics = window_metrics_event(view_id, width, height, pixel_ratio, display_id);
+        let info = flust_engine_sys::FlutterAddViewInfo {
+            struct_size: std::mem::size_of::<flust_engine_sys::FlutterAddViewInfo>(),
+            view_id,
+            view_metrics: &metrics,
+            user_data: ViewOperationUserData::new(&self.inner, view_id, Box::new(on_added))
+                .into_raw(),
+            add_view_callback: Some(flutter_callbacks::add_view_callback),
+        };
+
+        let result = unsafe { flust_engine_sys::FlutterEngineAddView(self.engine_ptr(), &info) };
+        if let Err(err) = FlutterEngineResult::from_ffi(result) {
+            drop(unsafe { ViewOperationUserData::from_raw(info.user_data) });
+            self.remove_view(view_id);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Removes a secondary view from the running engine.
+    ///
+    /// The view stays registered (and its handlers alive) until the engine
+    /// confirms the removal, at which point `on_removed` is called. Note that
+    /// `on_removed` is called on an engine managed thread.
+    pub fn remove_secondary_view<F>(
+        &self,
+        view_id: FlutterViewId,
+        on_removed: F,
+    ) -> Result<(), FlutterEngineError>
+    where
+        F: FnOnce(bool) + Send + 'static,
+    {
+        trace!("remove_secondary_view");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("remove_secondary_view") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        if view_id == IMPLICIT_VIEW_ID {
+            return Err(FlutterEngineError::InvalidArguments);
+        }
+
+        let info = flust_engine_sys::FlutterRemoveViewInfo {
+            struct_size: std::mem::size_of::<flust_engine_sys::FlutterRemoveViewInfo>(),
+            view_id,
+            user_data: ViewOperationUserData::new(&self.inner, view_id, Box::new(on_removed))
+                .into_raw(),
+            remove_view_callback: Some(flutter_callbacks::remove_view_callback),
+        };
+
+        let result = unsafe { flust_engine_sys::FlutterEngineRemoveView(self.engine_ptr(), &info) };
+        if let Err(err) = FlutterEngineResult::from_ffi(result) {
+            drop(unsafe { ViewOperationUserData::from_raw(info.user_data) });
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn post_platform_callback(&self, callback: MainThreadCallback) {
         trace!("post_platform_callback");
         self.inner.platform_sender.send(callback).unwrap();
@@ -414,29 +948,94 @@ impl FlutterEngine {
         self.inner.platform_runner.runs_task_on_current_thread()
     }
 
+    /// Runs `f` on the platform thread: right away when called from the
+    /// platform thread, otherwise from [`FlutterEngine::execute_platform_tasks`].
+    ///
+    /// Callbacks run in the order they were requested in, from any thread: on
+    /// the platform thread, `f` is queued as well if callbacks posted from
+    /// other threads are still pending. Platform messages sent from such
+    /// callbacks are therefore delivered in the same order.
     pub fn run_on_platform_thread<F>(&self, f: F)
     where
         F: FnOnce(&FlutterEngine) + 'static + Send,
     {
         trace!("run_on_platform_thread");
-        if self.is_platform_thread() {
+        if self.is_platform_thread() && self.inner.platform_receiver.is_empty() {
             f(self);
         } else {
             self.post_platform_callback(MainThreadCallback::Engine(Box::new(f)));
         }
     }
 
+    #[inline]
+    pub fn is_render_thread(&self) -> bool {
+        self.inner.render_thread.runs_task_on_current_thread()
+    }
+
+    /// Runs `f` on the render thread (which the OpenGL callbacks are invoked
+    /// from): right away when called from the render thread, otherwise once
+    /// the render thread is done with its current task.
     pub fn run_on_render_thread<F>(&self, f: F)
     where
         F: FnOnce(&FlutterEngine) + 'static + Send,
     {
         trace!("run_on_render_thread");
-        // TODO: Reimplement render thread
-        // if self.is_platform_thread() {
-        //     f(self);
-        // } else {
-        self.post_platform_callback(MainThreadCallback::RenderThread(Box::new(f)));
-        // }
+        if self.is_render_thread() {
+            f(self);
+        } else {
+            self.inner.render_thread.post_callback(Box::new(f));
+        }
+    }
+
+    /// The URI of the Dart VM service (e.g.: for attaching DevTools), once it
+    /// was announced by the engine. The VM service only runs in debug and
+    /// profile mode, so this is always `None` in release mode.
+    pub fn vm_service_uri(&self) -> Option<String> {
+        self.inner.vm_service_uri.read().clone()
+    }
+
+    /// Hot reloads the app from the `kernel` file (e.g.: the `kernel_blob.bin`
+    /// of a rebuilt asset bundle) and reassembles it, in JIT (debug) mode
+    /// only. Reloading takes up to a few seconds, so it runs on a background
+    /// thread, and `callback` is called with its outcome on the platform
+    /// thread (unless the engine is dropped meanwhile). Errors preventing the
+    /// reload from starting are returned instead.
+    pub fn reload_sources<F>(
+        &self,
+        kernel: PathBuf,
+        callback: F,
+    ) -> Result<(), vm_service::HotReloadError>
+    where
+        F: FnOnce(Result<vm_service::ReloadReport, vm_service::HotReloadError>) + 'static + Send,
+    {
+        if Self::runs_aot_compiled_dart_code() {
+            return Err(vm_service::HotReloadError::NotSupported);
+        }
+
+        let uri = self
+            .vm_service_uri()
+            .ok_or(vm_service::HotReloadError::VmServiceUnavailable)?;
+        let engine = self.downgrade();
+        std::thread::Builder::new()
+            .name("flust-hot-reload".into())
+            .spawn(move || {
+                let result = vm_service::reload_sources(&uri, &kernel);
+                if let Some(engine) = engine.upgrade() {
+                    engine.run_on_platform_thread(move |_| callback(result));
+                }
+            })?;
+        Ok(())
+    }
+
+    /// Whether the engine has scheduled a frame, i.e.: it requested a vsync
+    /// (through [`FlutterVsyncHandler::request_frame_callback`]) whose baton
+    /// wasn't passed back to [`FlutterEngine::on_vsync`] yet.
+    ///
+    /// Backends can use this to avoid requesting compositor frame callbacks
+    /// (and waking up the GPU) while the UI is idle. It is always `false` if
+    /// no vsync handler is set, as the engine then schedules frames on its own.
+    pub fn has_pending_frame(&self) -> bool {
+        self.inner.frame_request_tracker.has_pending_frame()
     }
 
     pub fn on_vsync(
@@ -450,6 +1049,17 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        if self.skip_after_shutdown("on_vsync") {
+            return;
+        }
+
+        if !self.inner.frame_request_tracker.baton_returned(baton) {
+            warn!("Ignoring vsync for baton {}: it is not pending", baton);
+            return;
+        }
+
+        self.inner.texture_registry.on_vsync();
+
         unsafe {
             flust_engine_sys::FlutterEngineOnVsync(
                 self.engine_ptr(),
@@ -460,6 +1070,9 @@ impl FlutterEngine {
         }
     }
 
+    /// Sends the metrics of a view. Metrics with a zero width or height are
+    /// never valid (the engine rejects them), so they are dropped with a
+    /// warning.
     pub fn send_window_metrics_event(
         &self,
         view_id: FlutterViewId,
@@ -473,50 +1086,153 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
-        let event = flust_engine_sys::FlutterWindowMetricsEvent {
-            struct_size: std::mem::size_of::<flust_engine_sys::FlutterWindowMetricsEvent>(),
-            width,
-            height,
-            pixel_ratio,
-            left: 0,
-            top: 0,
-            physical_view_inset_top: 0.0,
-            physical_view_inset_right: 0.0,
-            physical_view_inset_bottom: 0.0,
-            physical_view_inset_left: 0.0,
-            display_id,
-            view_id,
-            #[cfg(all(target_arch = "arm", target_os = "android"))]
-            __bindgen_padding_0: 0,
-        };
+        if self.skip_after_shutdown("send_window_metrics_event") {
+            return;
+        }
+
+        if let Err(err) = validate_metrics_size(width, height) {
+            warn!("Ignoring metrics of view {}: {}", view_id, err);
+            return;
+        }
+
+        let event = window_metrics_event(view_id, width, height, pixel_ratio, display_id);
         unsafe {
             flust_engine_sys::FlutterEngineSendWindowMetricsEvent(self.engine_ptr(), &event);
         }
     }
 
+    /// Same as `send_window_metrics_event`, but rejects the metrics if the
+    /// view is not registered (e.g.: it has already been removed) or if they
+    /// have a zero size, instead of having the engine report an error.
+    pub fn send_view_metrics_event(
+        &self,
+        view_id: FlutterViewId,
+        width: usize,
+        height: usize,
+        pixel_ratio: f64,
+        display_id: FlutterEngineDisplayId,
+    ) -> Result<(), FlutterEngineError> {
+        if self.skip_after_shutdown("send_view_metrics_event") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        validate_metrics_size(width, height)?;
+
+        self.inner
+            .view_registry
+            .read()
+            .ensure_view_exists(view_id)?;
+
+        self.send_window_metrics_event(view_id, width, height, pixel_ratio, display_id);
+        Ok(())
+    }
+
+    /// Sets the metrics of a view directly, without going through the window
+    /// of a backend (e.g.: to lay out a view rendered offscreen at a fixed
+    /// size in tests). See [`FlutterEngine::send_view_metrics_event`].
+    pub fn set_viewport_metrics(
+        &self,
+        view_id: FlutterViewId,
+        metrics: FlutterWindowMetrics,
+    ) -> Result<(), FlutterEngineError> {
+        self.send_view_metrics_event(
+            view_id,
+            metrics.size.width,
+            metrics.size.height,
+            metrics.pixel_ratio,
+            metrics.display_id,
+        )
+    }
+
+    /// Sends a pointer event. See [`FlutterPointerEvent`] for how modifier
+    /// keys apply to pointer events.
     pub fn send_pointer_event(&self, event: FlutterPointerEvent) {
         if !self.is_platform_thread() {
             panic!("Not on platform thread");
         }
 
+        if self.skip_after_shutdown("send_pointer_event") {
+            return;
+        }
+
+        let transform = self
+            .inner
+            .view_registry
+            .read()
+            .pointer_transform(event.view_id());
+        let event = match transform {
+            Some(transform) => event.transformed(&transform),
+            None => event,
+        };
+
         unsafe {
-            flust_engine_sys::FlutterEngineSendPointerEvent(self.engine_ptr(), &event.into(), 1);
+            FlutterEngineSendPointerEvent(self.engine_ptr(), &event.into(), 1);
         }
     }
 
-    // TODO: Add support for key event callbacks
-    pub fn send_key_event(&self, event: FlutterKeyEvent) {
+    /// Sends a key event to the engine. `callback` is invoked on the platform
+    /// thread with whether the framework handled the event, or with `false` if
+    /// the engine rejected it.
+    pub fn send_key_event(
+        &self,
+        event: FlutterKeyEvent,
+        callback: Option<Box<dyn FnOnce(bool) + Send>>,
+    ) {
         if !self.is_platform_thread() {
             panic!("Not on platform thread");
         }
 
+        if self.skip_after_shutdown("send_key_event") {
+            if let Some(callback) = callback {
+                callback(false);
+            }
+            return;
+        }
+
+        let Some(cbk) = callback else {
+            unsafe {
+                flust_engine_sys::FlutterEngineSendKeyEvent(
+                    self.engine_ptr(),
+                    &event.as_ptr(),
+                    None,
+                    ptr::null_mut(),
+                );
+            }
+            return;
+        };
+
         unsafe {
-            flust_engine_sys::FlutterEngineSendKeyEvent(
+            let cbk = CallbackBox {
+                engine: self.downgrade(),
+                cbk,
+            };
+            let ptr = Box::into_raw(Box::new(cbk));
+            let result = flust_engine_sys::FlutterEngineSendKeyEvent(
                 self.engine_ptr(),
                 &event.as_ptr(),
-                None,
-                ptr::null_mut(),
+                Some(key_event_callback),
+                ptr as *mut c_void,
             );
+
+            // The engine only invokes the callback for accepted events.
+            if let Err(err) = FlutterEngineResult::from_ffi(result) {
+                error!("Failed to send key event: {}", err);
+                (Box::from_raw(ptr).cbk)(false);
+            }
+        }
+
+        struct CallbackBox {
+            engine: FlutterEngineWeakRef,
+            cbk: Box<dyn FnOnce(bool) + Send>,
+        }
+
+        unsafe extern "C" fn key_event_callback(handled: bool, user_data: *mut c_void) {
+            let ptr = user_data as *mut CallbackBox;
+            let b = Box::from_raw(ptr);
+            if let Some(engine) = b.engine.upgrade() {
+                let cbk = b.cbk;
+                engine.run_on_platform_thread(move |_| cbk(handled));
+            }
         }
     }
 
@@ -530,6 +1246,10 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        if self.skip_after_shutdown("notify_display_update") {
+            return;
+        }
+
         let displays: Vec<flust_engine_sys::FlutterEngineDisplay> =
             displays.iter().map(|display| (*display).into()).collect();
 
@@ -543,12 +1263,163 @@ impl FlutterEngine {
         }
     }
 
+    /// Dispatches a semantics action (e.g., coming from an assistive
+    /// technology client) to the semantics node with the given ID.
+    pub fn dispatch_semantics_action(
+        &self,
+        node_id: u64,
+        action: FlutterSemanticsAction,
+        data: &[u8],
+    ) -> Result<(), FlutterEngineError> {
+        trace!("dispatch_semantics_action");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("dispatch_semantics_action") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let result = unsafe {
+            FlutterEngineDispatchSemanticsAction(
+                self.engine_ptr(),
+                node_id,
+                action.into(),
+                data.as_ptr(),
+                data.len(),
+            )
+        };
+        FlutterEngineResult::from_ffi(result)
+    }
+
+    /// Enables or disables the semantics tree. While enabled, the framework
+    /// builds the tree and sends its updates to the semantics handler (see
+    /// [`FlutterEngineBuilder::with_semantics_handler`]), which has a runtime
+    /// cost: it's meant to be enabled once an assistive technology is active.
+    pub fn update_semantics_enabled(&self, enabled: bool) -> Result<(), FlutterEngineError> {
+        trace!("update_semantics_enabled");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("update_semantics_enabled") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let result = unsafe {
+            flust_engine_sys::FlutterEngineUpdateSemanticsEnabled(self.engine_ptr(), enabled)
+        };
+        FlutterEngineResult::from_ffi(result)
+    }
+
+    /// Notifies the engine of the locales of the user, in the order of
+    /// preference (the first one being the preferred locale). This is
+    /// expected to be called once the engine is running, and again whenever
+    /// the locales change.
+    pub fn update_locales(&self, locales: &[FlutterLocale]) -> Result<(), FlutterEngineError> {
+        trace!("update_locales");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("update_locales") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let strings = locales
+            .iter()
+            .map(FlutterLocaleStrings::new)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(FlutterEngineError::InvalidArguments)?;
+        let ffi_locales: Vec<_> = strings.iter().map(FlutterLocaleStrings::to_ffi).collect();
+        let locale_ptrs: Vec<_> = ffi_locales
+            .iter()
+            .map(|locale| locale as *const flust_engine_sys::FlutterLocale)
+            .collect();
+
+        let result = unsafe {
+            flust_engine_sys::FlutterEngineUpdateLocales(
+                self.engine_ptr(),
+                locale_ptrs.as_ptr() as *mut _,
+                locale_ptrs.len(),
+            )
+        };
+        FlutterEngineResult::from_ffi(result)
+    }
+
+    /// Asks the engine to produce a new frame, even if it's otherwise idle
+    /// (e.g.: when the embedder knows the content on screen is stale, after a
+    /// window was remapped).
+    pub fn schedule_frame(&self) -> Result<(), FlutterEngineError> {
+        trace!("schedule_frame");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("schedule_frame") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let result = unsafe { flust_engine_sys::FlutterEngineScheduleFrame(self.engine_ptr()) };
+        FlutterEngineResult::from_ffi(result)
+    }
+
+    /// Tells the engine the system is low on memory, so that it purges its
+    /// caches (e.g.: the raster cache). The engine also forwards the warning
+    /// to the framework, as a `memoryPressure` message on the `flutter/system`
+    /// channel. Repeated calls are cheap and idempotent: caches that were
+    /// purged already are left as is.
+    pub fn notify_low_memory_warning(&self) -> Result<(), FlutterEngineError> {
+        trace!("notify_low_memory_warning");
+        if !self.is_platform_thread() {
+            panic!("Not on platform thread");
+        }
+
+        if self.skip_after_shutdown("notify_low_memory_warning") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let result =
+            unsafe { flust_engine_sys::FlutterEngineNotifyLowMemoryWarning(self.engine_ptr()) };
+        FlutterEngineResult::from_ffi(result)
+    }
+
+    /// Posts `object` to the Dart `SendPort` whose id is `port`, e.g.: to
+    /// deliver the results of a plugin to the background isolate it runs on.
+    /// Unlike platform messages, objects can be posted from any thread (but
+    /// not while the engine is shutting down on another thread).
+    pub fn post_dart_object(
+        &self,
+        port: FlutterEngineDartPort,
+        object: DartObject,
+    ) -> Result<(), FlutterEngineError> {
+        trace!("post_dart_object");
+        if self.skip_after_shutdown("post_dart_object") {
+            return Err(FlutterEngineError::ShutDown);
+        }
+
+        let data = DartObjectData::new(&object).ok_or(FlutterEngineError::InvalidArguments)?;
+        let object = data.to_ffi();
+        let result = unsafe {
+            flust_engine_sys::FlutterEnginePostDartObject(self.engine_ptr(), port, &object)
+        };
+        FlutterEngineResult::from_ffi(result)
+    }
+
+    /// Sends a platform message to the framework. Messages are delivered in
+    /// the order they are sent, which requires sending them from the platform
+    /// thread: other threads must go through
+    /// [`FlutterEngine::run_on_platform_thread`], which keeps their order.
     pub(crate) fn send_platform_message(&self, message: PlatformMessage) {
         trace!("Sending message on channel {}", message.channel);
         if !self.is_platform_thread() {
             panic!("Not on platform thread");
         }
 
+        if self.skip_after_shutdown("send_platform_message") {
+            return;
+        }
+
         unsafe {
             flust_engine_sys::FlutterEngineSendPlatformMessage(self.engine_ptr(), &message.into());
         }
@@ -564,6 +1435,26 @@ impl FlutterEngine {
             panic!("Not on platform thread");
         }
 
+        let Some(channel) = self
+            .inner
+            .pending_responses
+            .lock()
+            .complete(&response_handle)
+        else {
+            // Responding again would free the handle twice.
+            error!("Ignoring repeated response to a platform message");
+            let _: *const FlutterPlatformMessageResponseHandle = response_handle.into();
+            return;
+        };
+
+        // The handle was freed along with the engine.
+        if self.skip_after_shutdown("send_platform_message_response") {
+            let _: *const FlutterPlatformMessageResponseHandle = response_handle.into();
+            return;
+        }
+
+        trace!("Responding to platform message on {}", channel);
+
         unsafe {
             flust_engine_sys::FlutterEngineSendPlatformMessageResponse(
                 self.engine_ptr(),
@@ -574,39 +1465,66 @@ impl FlutterEngine {
         }
     }
 
+    /// Shuts down the engine. Calling it again has no effect, and so have most
+    /// calls into the engine afterwards (see [`FlutterEngine::is_shut_down`]).
     pub fn shutdown(&self) {
         trace!("shutdown");
         if !self.is_platform_thread() {
             panic!("Not on platform thread")
         }
 
+        if self.inner.shut_down.swap(true, Ordering::AcqRel) {
+            debug!("Engine already shut down");
+            return;
+        }
+
+        for channel in self.inner.pending_responses.lock().pending_channels() {
+            warn!("Platform message on {} was never responded to", channel);
+        }
+
         unsafe {
-            flust_engine_sys::FlutterEngineShutdown(self.engine_ptr());
+            flust_engine_sys::FlutterEngineShutdown(self.inner.engine_ptr);
         }
+        self.inner.render_thread.stop();
     }
 
+    /// Runs the expired platform tasks, returning the deadline of the next
+    /// one. Once the engine was shut down, pending tasks are dropped.
+    ///
+    /// With a budget set (see
+    /// [`builder::FlutterEngineBuilder::with_platform_task_budget`]), the work
+    /// left once it's exceeded is deferred, and the returned deadline is due
+    /// right away.
     pub fn execute_platform_tasks(&self) -> Option<Instant> {
         if !self.is_platform_thread() {
             panic!("Not on platform thread")
         }
 
-        let next_task = self.inner.platform_runner.execute_tasks();
+        if self.is_shut_down() {
+            return None;
+        }
+
+        let budget = TaskBudget::new(self.inner.platform_task_budget);
+        let next_task = self.inner.platform_runner.execute_tasks_within(&budget);
 
-        let mut render_thread_fns = Vec::new();
-        let callbacks: Vec<MainThreadCallback> = self.inner.platform_receiver.try_iter().collect();
-        for cb in callbacks {
+        // Callbacks posted by these callbacks only run on the next call.
+        let receiver = &self.inner.platform_receiver;
+        let pending_callbacks = receiver.len();
+        let mut ran_callbacks = 0;
+        for cb in receiver.try_iter().take(pending_callbacks) {
             match cb {
                 MainThreadCallback::Engine(func) => func(self),
-                MainThreadCallback::RenderThread(f) => render_thread_fns.push(f),
+            }
+
+            ran_callbacks += 1;
+            if budget.is_exceeded() {
+                break;
             }
         }
-        if !render_thread_fns.is_empty() {
-            let engine_copy = self.clone();
-            self.post_render_thread_task(move || {
-                for f in render_thread_fns {
-                    f(&engine_copy);
-                }
-            });
+
+        if ran_callbacks < pending_callbacks {
+            let now = Instant::now();
+            return Some(next_task.map_or(now, |next_task| next_task.min(now)));
         }
 
         next_task
@@ -614,40 +1532,155 @@ impl FlutterEngine {
 
     pub(crate) fn run_task(&self, task: &FlutterTask) {
         trace!("run_task");
+        // Render tasks keep running while `FlutterEngineShutdown` waits for
+        // them, so the engine handle is used even once `shut_down` is set.
+        // Platform tasks are dropped after shutdown, and render tasks are
+        // stopped once it completed (see `RenderThread::stop`).
         unsafe {
-            flust_engine_sys::FlutterEngineRunTask(self.engine_ptr(), task as *const FlutterTask);
+            flust_engine_sys::FlutterEngineRunTask(
+                self.inner.engine_ptr,
+                task as *const FlutterTask,
+            );
         }
     }
 
-    fn post_render_thread_task<F>(&self, f: F)
-    where
-        F: FnOnce() + 'static,
-    {
-        trace!("post_render_thread_task");
-        unsafe {
-            let cbk = CallbackBox { cbk: Box::new(f) };
-            let b = Box::new(cbk);
-            let ptr = Box::into_raw(b);
-            flust_engine_sys::FlutterEnginePostRenderThreadTask(
-                self.engine_ptr(),
-                Some(render_thread_task),
-                ptr as *mut c_void,
-            );
-        }
+    pub fn create_texture(&self) -> Texture {
+        self.inner.texture_registry.create_texture(self.clone())
+    }
+}
 
-        struct CallbackBox {
-            pub cbk: Box<dyn FnOnce()>,
+/// Removes repeated switches (e.g.: `--verbose-logging` set both through an
+/// environment variable and the builder), keeping the last occurrence of each.
+/// Switches are compared by name, so that `--switch=a` is overridden by a
+/// later `--switch=b`.
+fn dedup_switches(args: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut args: Vec<String> = args
+        .into_iter()
+        .rev()
+        .filter(|arg| {
+            let Some(switch) = arg.strip_prefix("--") else {
+                return true;
+            };
+
+            let name = switch.split_once('=').map_or(switch, |(name, _)| name);
+            let first = seen.insert(name.to_owned());
+            if !first {
+                debug!("Ignoring overridden engine switch: {}", arg);
+            }
+            first
+        })
+        .collect();
+
+    args.reverse();
+    args
+}
+
+/// Extracts the VM service URI from the message logged by the engine once the
+/// VM service is listening (older engines call it "Observatory").
+fn vm_service_uri_from_log(message: &str) -> Option<&str> {
+    const PREFIXES: [&str; 2] = [
+        "The Dart VM service is listening on ",
+        "Observatory listening on ",
+    ];
+
+    PREFIXES.iter().find_map(|prefix| {
+        let (_, uri) = message.split_once(prefix)?;
+        uri.split_whitespace().next()
+    })
+}
+
+fn validate_metrics_size(width: usize, height: usize) -> Result<(), FlutterEngineError> {
+    if width == 0 || height == 0 {
+        return Err(FlutterEngineError::InvalidViewSize { width, height });
+    }
+    Ok(())
+}
+
+fn window_metrics_event(
+    view_id: FlutterViewId,
+    width: usize,
+    height: usize,
+    pixel_ratio: f64,
+    display_id: FlutterEngineDisplayId,
+) -> flust_engine_sys::FlutterWindowMetricsEvent {
+    flust_engine_sys::FlutterWindowMetricsEvent {
+        struct_size: std::mem::size_of::<flust_engine_sys::FlutterWindowMetricsEvent>(),
+        width,
+        height,
+        pixel_ratio,
+        left: 0,
+        top: 0,
+        physical_view_inset_top: 0.0,
+        physical_view_inset_right: 0.0,
+        physical_view_inset_bottom: 0.0,
+        physical_view_inset_left: 0.0,
+        display_id,
+        view_id,
+        #[cfg(all(target_arch = "arm", target_os = "android"))]
+        __bindgen_padding_0: 0,
+    }
+}
+
+/// Called with whether adding or removing a view succeeded.
+type ViewOperationCallback = Box<dyn FnOnce(bool) + Send>;
+
+/// The baton passed to the engine when adding or removing a view.
+pub(crate) struct ViewOperationUserData {
+    pub(crate) engine: Weak<FlutterEngineInner>,
+    pub(crate) view_id: FlutterViewId,
+    pub(crate) on_result: ViewOperationCallback,
+}
+
+impl ViewOperationUserData {
+    fn new(
+        engine: &Arc<FlutterEngineInner>,
+        view_id: FlutterViewId,
+        on_result: ViewOperationCallback,
+    ) -> Self {
+        Self {
+            engine: Arc::downgrade(engine),
+            view_id,
+            on_result,
         }
+    }
 
-        unsafe extern "C" fn render_thread_task(user_data: *mut c_void) {
-            let ptr = user_data as *mut CallbackBox;
-            let b = Box::from_raw(ptr);
-            (b.cbk)()
+    fn into_raw(self) -> *mut c_void {
+        Box::into_raw(Box::new(self)) as *mut c_void
+    }
+
+    pub(crate) unsafe fn from_raw(user_data: *mut c_void) -> Box<Self> {
+        Box::from_raw(user_data as *mut Self)
+    }
+}
+
+/// The baton passed to the engine along with the next frame callback.
+pub(crate) struct NextFrameCallbackUserData {
+    pub(crate) callback: Box<dyn FnOnce() + Send>,
+}
+
+impl NextFrameCallbackUserData {
+    /// Wraps `callback` so that it's re-threaded to the platform thread of
+    /// `engine` (the engine calls it from the raster thread).
+    pub(crate) fn on_platform_thread<F>(engine: FlutterEngineWeakRef, callback: F) -> Self
+    where
+        F: FnOnce(&FlutterEngine) + 'static + Send,
+    {
+        Self {
+            callback: Box::new(move || {
+                if let Some(engine) = engine.upgrade() {
+                    engine.run_on_platform_thread(callback);
+                }
+            }),
         }
     }
 
-    pub fn create_texture(&self) -> Texture {
-        self.inner.texture_registry.create_texture(self.clone())
+    pub(crate) fn into_raw(self) -> *mut c_void {
+        Box::into_raw(Box::new(self)) as *mut c_void
+    }
+
+    pub(crate) unsafe fn from_raw(user_data: *mut c_void) -> Box<Self> {
+        Box::from_raw(user_data as *mut Self)
     }
 }
 
@@ -669,6 +1702,64 @@ pub enum CreateError {
 
     #[error("Engine pointer is null")]
     EnginePtrNull,
+
+    #[error(
+        "AOT library not found: {} (the engine library runs AOT compiled Dart code, which requires a profile or release build)",
+        .0.display()
+    )]
+    AOTLibraryNotFound(PathBuf),
+
+    #[error(
+        "AOT library not found in the app bundle (searched: {}), its path must be set explicitly",
+        .0.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    AOTLibraryNotDetected(Vec<PathBuf>),
+
+    #[error("AOT library is not a valid ELF file: {}", .0.display())]
+    InvalidAOTLibrary(PathBuf),
+
+    #[error("AOT data is not a valid ELF snapshot")]
+    InvalidAOTDataBytes,
+
+    #[error("Unable to write the AOT data to a temporary file")]
+    AOTDataWriteFailed(#[source] std::io::Error),
+
+    #[error(
+        "Kernel blob not found in assets: {} (the engine library runs JIT compiled Dart code, which requires a debug build)",
+        .0.display()
+    )]
+    KernelBlobNotFound(PathBuf),
+
+    #[error(
+        "The app is declared to run in {declared} mode, but the engine library runs {engine_library} compiled Dart code"
+    )]
+    RuntimeModeMismatch {
+        declared: RuntimeMode,
+        engine_library: RuntimeMode,
+    },
+
+    #[error("Unable to pin the {thread} thread to CPU {cpu}: only {available} CPUs are available")]
+    ThreadAffinityCpuUnavailable {
+        thread: EngineThread,
+        cpu: usize,
+        available: usize,
+    },
+
+    #[error("Unable to spawn the render thread")]
+    RenderThreadSpawnFailed(#[source] std::io::Error),
+
+    #[error("Unable to resolve path: {}", .path.display())]
+    PathResolutionFailed {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Unable to determine the path of the executable")]
+    ExecutablePathUnknown(#[source] std::io::Error),
+
+    #[error("Invalid log tag (it contains a NUL byte): {0:?}")]
+    InvalidLogTag(String),
 }
 
 #[derive(Error, Debug)]
@@ -681,4 +1772,638 @@ pub enum FlutterEngineError {
 
     #[error("Internal inconsistency")]
     InternalInconsistency,
+
+    #[error("View not found: {0}")]
+    ViewNotFound(FlutterViewId),
+
+    #[error("No compositor handler for view: {0}")]
+    NoCompositorHandler(FlutterViewId),
+
+    #[error("Invalid view size: {width}x{height}")]
+    InvalidViewSize { width: usize, height: usize },
+
+    #[error("Engine has been shut down")]
+    ShutDown,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use super::*;
+    use crate::ffi::FlutterKeyEventDeviceType;
+    use crate::tasks::TaskRunnerHandler;
+
+    struct NoopTaskRunnerHandler;
+
+    impl TaskRunnerHandler for NoopTaskRunnerHandler {
+        fn wake(&self) {}
+    }
+
+    struct NoopSoftwareHandler;
+
+    impl FlutterSoftwareHandler for NoopSoftwareHandler {
+        fn present(&self, _allocation: &[u8], _row_bytes: usize, _height: usize) -> bool {
+            true
+        }
+    }
+
+    thread_local! {
+        static DISPATCHED_SEMANTICS_ACTIONS: std::cell::RefCell<Vec<(u64, u32, Vec<u8>)>> =
+            Default::default();
+        static SENT_POINTER_EVENTS: std::cell::RefCell<Vec<flust_engine_sys::FlutterPointerEvent>> =
+            Default::default();
+        static PENDING_REPLIES: std::cell::RefCell<Vec<(flust_engine_sys::FlutterDataCallback, usize)>> =
+            Default::default();
+        static INSTALLED_THREAD_PRIORITY_SETTERS: std::cell::RefCell<Vec<bool>> =
+            Default::default();
+    }
+
+    /// Records whether `thread_priority_setter` is installed, and fails like
+    /// the engine does without Dart artifacts.
+    #[allow(non_snake_case)]
+    pub(super) unsafe fn FlutterEngineInitialize(
+        _version: usize,
+        _config: *const flust_engine_sys::FlutterRendererConfig,
+        args: *const flust_engine_sys::FlutterProjectArgs,
+        _user_data: *mut c_void,
+        _engine_out: *mut flust_engine_sys::FlutterEngine,
+    ) -> flust_engine_sys::FlutterEngineResult {
+        let task_runners = &*(*args).custom_task_runners;
+        INSTALLED_THREAD_PRIORITY_SETTERS.with(|setters| {
+            setters
+                .borrow_mut()
+                .push(task_runners.thread_priority_setter.is_some())
+        });
+        flust_engine_sys::FlutterEngineResult::kInvalidArguments
+    }
+
+    #[allow(non_snake_case)]
+    pub(super) unsafe fn FlutterEngineDispatchSemanticsAction(
+        _engine: flust_engine_sys::FlutterEngine,
+        node_id: u64,
+        action: flust_engine_sys::FlutterSemanticsAction,
+        data: *const u8,
+        data_length: usize,
+    ) -> flust_engine_sys::FlutterEngineResult {
+        let data = std::slice::from_raw_parts(data, data_length).to_vec();
+        DISPATCHED_SEMANTICS_ACTIONS
+            .with(|actions| actions.borrow_mut().push((node_id, action.0, data)));
+        flust_engine_sys::FlutterEngineResult::kSuccess
+    }
+
+    #[allow(non_snake_case)]
+    pub(super) unsafe fn FlutterEngineSendPointerEvent(
+        _engine: flust_engine_sys::FlutterEngine,
+        events: *const flust_engine_sys::FlutterPointerEvent,
+        events_count: usize,
+    ) -> flust_engine_sys::FlutterEngineResult {
+        let events = std::slice::from_raw_parts(events, events_count);
+        SENT_POINTER_EVENTS.with(|sent| sent.borrow_mut().extend_from_slice(events));
+        flust_engine_sys::FlutterEngineResult::kSuccess
+    }
+
+    #[allow(non_snake_case)]
+    pub(super) unsafe fn FlutterPlatformMessageCreateResponseHandle(
+        _engine: flust_engine_sys::FlutterEngine,
+        data_callback: flust_engine_sys::FlutterDataCallback,
+        user_data: *mut c_void,
+        _response_out: *mut *mut FlutterPlatformMessageResponseHandle,
+    ) -> flust_engine_sys::FlutterEngineResult {
+        PENDING_REPLIES.with(|replies| {
+            replies
+                .borrow_mut()
+                .push((data_callback, user_data as usize))
+        });
+        flust_engine_sys::FlutterEngineResult::kSuccess
+    }
+
+    /// Replies with `data` to the messages sent so far, as the engine does
+    /// once Dart replied to them.
+    pub(super) fn reply_to_sent_messages(data: &[u8]) {
+        for (callback, user_data) in PENDING_REPLIES.take() {
+            unsafe { callback.unwrap()(data.as_ptr(), data.len(), user_data as *mut c_void) };
+        }
+    }
+
+    /// An engine whose handle was never initialized (i.e.: it's null, which
+    /// the embedder API rejects).
+    pub(super) fn uninitialized_engine() -> FlutterEngine {
+        uninitialized_engine_with(|_| {})
+    }
+
+    /// An uninitialized engine whose state is adjusted by `configure`.
+    fn uninitialized_engine_with(configure: impl FnOnce(&mut FlutterEngineInner)) -> FlutterEngine {
+        let (platform_sender, platform_receiver) = unbounded();
+        let mut inner = FlutterEngineInner {
+            view_registry: RwLock::new(ViewRegistry::default()),
+            vsync_handler: None,
+            frame_request_tracker: FrameRequestTracker::default(),
+            semantics_handler: None,
+            engine_error_handler: None,
+            pre_engine_restart_handler: None,
+            present_failure_monitor: PresentFailureMonitor::default(),
+            window_damage: Mutex::new(WindowDamage::default()),
+            compositor_startup_monitor: CompositorStartupMonitor::new(false),
+            engine_ptr: ptr::null_mut(),
+            shut_down: AtomicBool::new(false),
+            channel_registry: RwLock::new(ChannelRegistry::new()),
+            pending_responses: Default::default(),
+            platform_runner: TaskRunner::new(Arc::new(NoopTaskRunnerHandler)),
+            platform_task_budget: None,
+            platform_receiver,
+            platform_sender,
+            render_thread: RenderThread::spawn(Default::default()).unwrap(),
+            texture_registry: TextureRegistry::new(),
+            aot_data: FlutterEngineAOTData {
+                data: ptr::null_mut(),
+                elf_file: None,
+            },
+            assets: Default::default(),
+            icu_data: Default::default(),
+            persistent_cache: Default::default(),
+            arguments: Default::default(),
+            log_tag: None,
+            vm_service_uri: RwLock::new(None),
+        };
+        configure(&mut inner);
+
+        let engine = FlutterEngine {
+            #[allow(clippy::arc_with_non_send_sync)]
+            inner: Arc::new(inner),
+        };
+        engine.inner.platform_runner.init(engine.downgrade());
+        engine.inner.render_thread.runner.init(engine.downgrade());
+        engine
+    }
+
+    #[test]
+    fn duplicate_switches_collapse_to_one() {
+        let args = [
+            "flust",
+            "--verbose-logging",
+            "--cache-sksl=false",
+            "--enable-impeller",
+            "--verbose-logging",
+            "--cache-sksl=true",
+        ];
+
+        assert_eq!(
+            dedup_switches(args.map(String::from).to_vec()),
+            vec![
+                "flust",
+                "--enable-impeller",
+                "--verbose-logging",
+                "--cache-sksl=true"
+            ]
+        );
+    }
+
+    #[test]
+    fn platform_thread_callbacks_run_in_order() {
+        let engine = uninitialized_engine();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let posted = engine.downgrade();
+        let first = order.clone();
+        std::thread::spawn(move || {
+            let engine = posted.upgrade().unwrap();
+            engine.run_on_platform_thread(move |_| first.lock().push("first"));
+        })
+        .join()
+        .unwrap();
+
+        let second = order.clone();
+        engine.run_on_platform_thread(move |_| second.lock().push("second"));
+        assert!(order.lock().is_empty());
+
+        engine.execute_platform_tasks();
+        assert_eq!(*order.lock(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn exceeding_task_budget_defers_callbacks() {
+        let engine = uninitialized_engine_with(|inner| {
+            inner.platform_task_budget = Some(Duration::ZERO);
+        });
+        let ran = Arc::new(Mutex::new(0));
+
+        let posted = engine.downgrade();
+        let posting = ran.clone();
+        std::thread::spawn(move || {
+            let engine = posted.upgrade().unwrap();
+            for _ in 0..3 {
+                let ran = posting.clone();
+                engine.run_on_platform_thread(move |_| *ran.lock() += 1);
+            }
+        })
+        .join()
+        .unwrap();
+
+        let deadline = engine.execute_platform_tasks();
+        assert_eq!(*ran.lock(), 1);
+        assert!(deadline.is_some_and(|deadline| deadline <= Instant::now()));
+
+        engine.execute_platform_tasks();
+        assert_eq!(engine.execute_platform_tasks(), None);
+        assert_eq!(*ran.lock(), 3);
+    }
+
+    #[test]
+    fn render_thread_callbacks_run_off_platform_thread() {
+        let engine = uninitialized_engine();
+        let (sender, receiver) = unbounded();
+
+        engine.run_on_render_thread(move |engine| {
+            sender
+                .send((engine.is_render_thread(), engine.is_platform_thread()))
+                .unwrap();
+        });
+
+        let ran_on = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(ran_on, (true, false));
+        assert!(engine.is_platform_thread() && !engine.is_render_thread());
+    }
+
+    #[test]
+    fn only_the_ui_thread_is_pinned_by_the_thread_priority_setter() {
+        let installs_setter = |thread| {
+            let builder = FlutterEngineBuilder::new()
+                .with_platform_handler(Arc::new(NoopTaskRunnerHandler))
+                .with_thread_affinity(thread, [0]);
+            assert!(matches!(
+                FlutterEngine::new(builder, None),
+                Err(CreateError::EnginePtrNull)
+            ));
+            INSTALLED_THREAD_PRIORITY_SETTERS.with(|setters| setters.borrow_mut().pop().unwrap())
+        };
+
+        assert!(installs_setter(EngineThread::Ui));
+        // The raster thread is the render thread, pinned by each engine.
+        assert!(!installs_setter(EngineThread::Raster));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn render_thread_is_pinned_to_the_raster_cpus() {
+        fn current_thread_cpus() -> Vec<usize> {
+            let mut cpu_set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            let size = std::mem::size_of::<libc::cpu_set_t>();
+            assert_eq!(unsafe { libc::sched_getaffinity(0, size, &mut cpu_set) }, 0);
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &cpu_set) })
+                .collect()
+        }
+
+        let cpu = *current_thread_cpus().last().unwrap();
+        let mut affinity = crate::affinity::ThreadAffinity::default();
+        affinity.set(EngineThread::Raster, vec![cpu]);
+        let engine = uninitialized_engine_with(|inner| {
+            inner.render_thread = RenderThread::spawn(affinity).unwrap();
+        });
+
+        let (sender, receiver) = unbounded();
+        engine.run_on_render_thread(move |_| sender.send(current_thread_cpus()).unwrap());
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            vec![cpu]
+        );
+    }
+
+    #[test]
+    fn dropped_response_handles_are_responded_to() {
+        let engine = uninitialized_engine();
+        // The response isn't sent to the (null) engine once it's shut down,
+        // but it's still completed.
+        engine.shutdown();
+
+        let mut handle = PlatformMessageResponseHandle::from(
+            0x10 as *const FlutterPlatformMessageResponseHandle,
+        );
+        engine
+            .inner
+            .pending_responses
+            .lock()
+            .track(&handle, "flutter/platform");
+        handle.respond_on_drop(engine.downgrade());
+        drop(handle);
+
+        let pending_responses = engine.inner.pending_responses.lock();
+        assert_eq!(pending_responses.pending_channels().count(), 0);
+    }
+
+    #[test]
+    fn vm_service_uri_is_captured_from_log() {
+        let engine = uninitialized_engine();
+        assert_eq!(engine.vm_service_uri(), None);
+
+        let tag = CString::new("flutter").unwrap();
+        let message = CString::new(
+            "The Dart VM service is listening on http://127.0.0.1:41919/aBcD3f-gH_i=/",
+        )
+        .unwrap();
+        let user_data = Weak::into_raw(Arc::downgrade(&engine.inner)) as *mut std::ffi::c_void;
+        flutter_callbacks::log_message_callback(tag.as_ptr(), message.as_ptr(), user_data);
+        unsafe { drop(Weak::from_raw(user_data as *const FlutterEngineInner)) };
+
+        assert_eq!(
+            engine.vm_service_uri().as_deref(),
+            Some("http://127.0.0.1:41919/aBcD3f-gH_i=/")
+        );
+    }
+
+    /// The target and fields of an event.
+    type RecordedEvent = (&'static str, Vec<(&'static str, String)>);
+
+    /// Records every event.
+    #[derive(Clone, Default)]
+    struct EventRecorder {
+        events: Arc<Mutex<Vec<RecordedEvent>>>,
+    }
+
+    struct FieldRecorder(Vec<(&'static str, String)>);
+
+    impl Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push((field.name(), format!("{:?}", value)));
+        }
+    }
+
+    impl Subscriber for EventRecorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = FieldRecorder(Vec::new());
+            event.record(&mut fields);
+            self.events
+                .lock()
+                .push((event.metadata().target(), fields.0));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn engine_logs_are_forwarded_with_the_flutter_target_and_tag() {
+        let engine = uninitialized_engine();
+        let recorder = EventRecorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let tag = CString::new("my_app").unwrap();
+            let message = CString::new("Hello from Dart").unwrap();
+            let user_data = Arc::as_ptr(&engine.inner) as *mut std::ffi::c_void;
+            flutter_callbacks::log_message_callback(tag.as_ptr(), message.as_ptr(), user_data);
+        });
+
+        assert_eq!(
+            *recorder.events.lock(),
+            vec![(
+                "flutter",
+                vec![
+                    ("message", "Hello from Dart".to_string()),
+                    ("tag", "\"my_app\"".to_string())
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn zero_size_metrics_are_rejected() {
+        let engine = uninitialized_engine();
+
+        for (width, height) in [(0, 600), (800, 0), (0, 0)] {
+            assert!(matches!(
+                engine.send_view_metrics_event(IMPLICIT_VIEW_ID, width, height, 1.0, 0),
+                Err(FlutterEngineError::InvalidViewSize { width: w, height: h })
+                    if (w, h) == (width, height)
+            ));
+        }
+    }
+
+    #[test]
+    fn viewport_metrics_require_a_registered_view() {
+        let engine = uninitialized_engine();
+        let metrics = FlutterWindowMetrics {
+            size: PhysicalSize::new(800, 600),
+            pixel_ratio: 1.0,
+            display_id: 0,
+        };
+
+        assert!(matches!(
+            engine.set_viewport_metrics(42, metrics),
+            Err(FlutterEngineError::ViewNotFound(42))
+        ));
+        assert!(matches!(
+            engine.set_viewport_metrics(
+                42,
+                FlutterWindowMetrics {
+                    size: PhysicalSize::new(800, 0),
+                    ..metrics
+                }
+            ),
+            Err(FlutterEngineError::InvalidViewSize { .. })
+        ));
+    }
+
+    #[test]
+    fn engine_calls_after_shutdown_are_ignored() {
+        let engine = uninitialized_engine();
+
+        engine.shutdown();
+        engine.shutdown();
+        assert!(engine.is_shut_down());
+        assert!(engine.engine_ptr().is_null());
+
+        engine.on_vsync(1, 0, 0);
+        assert!(!engine.has_pending_frame());
+        assert_eq!(engine.execute_platform_tasks(), None);
+        assert!(matches!(engine.run(), Err(FlutterEngineError::ShutDown)));
+        assert!(matches!(
+            engine.schedule_frame(),
+            Err(FlutterEngineError::ShutDown)
+        ));
+        assert!(matches!(
+            engine.notify_low_memory_warning(),
+            Err(FlutterEngineError::ShutDown)
+        ));
+        assert!(matches!(
+            engine.post_dart_object(42, DartObject::Int(1)),
+            Err(FlutterEngineError::ShutDown)
+        ));
+
+        // Key events are reported as unhandled, so that they can be handled
+        // elsewhere.
+        let (sender, receiver) = unbounded();
+        let event = FlutterKeyEvent::empty(Duration::ZERO, FlutterKeyEventDeviceType::Keyboard);
+        engine.send_key_event(
+            event,
+            Some(Box::new(move |handled| sender.send(handled).unwrap())),
+        );
+        assert_eq!(receiver.try_recv(), Ok(false));
+    }
+
+    #[test]
+    fn failed_view_additions_are_reported_to_the_caller() {
+        let engine = uninitialized_engine();
+        engine.add_view(FlutterView::new_software(1, NoopSoftwareHandler));
+
+        let (sender, receiver) = unbounded();
+        let on_added = Box::new(move |added| sender.send(added).unwrap());
+        let result = flust_engine_sys::FlutterAddViewResult {
+            struct_size: std::mem::size_of::<flust_engine_sys::FlutterAddViewResult>(),
+            added: false,
+            user_data: ViewOperationUserData::new(&engine.inner, 1, on_added).into_raw(),
+        };
+        flutter_callbacks::add_view_callback(&result);
+
+        assert_eq!(receiver.try_recv(), Ok(false));
+        assert!(engine.view_ids().is_empty());
+    }
+
+    #[test]
+    fn semantics_actions_are_dispatched_to_the_engine() {
+        let engine = uninitialized_engine();
+
+        engine
+            .dispatch_semantics_action(42, FlutterSemanticsAction::SetText, b"hello")
+            .unwrap();
+        engine
+            .dispatch_semantics_action(7, FlutterSemanticsAction::Tap, &[])
+            .unwrap();
+
+        let dispatched = DISPATCHED_SEMANTICS_ACTIONS.with(|actions| actions.take());
+        let set_text = flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionSetText;
+        let tap = flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionTap;
+        assert_eq!(
+            dispatched,
+            vec![(42, set_text.0, b"hello".to_vec()), (7, tap.0, vec![])]
+        );
+    }
+
+    #[test]
+    fn pointer_events_are_sent_in_the_coordinates_of_rotated_views() {
+        let engine = uninitialized_engine();
+        engine.add_view(FlutterView::new_software(1, NoopSoftwareHandler));
+        let transform = FlutterPointerTransform {
+            scale_factor: 2.0,
+            rotation: view::FlutterViewRotation::Rotated90,
+            view_size: PhysicalSize::new(200.0, 100.0),
+        };
+        engine
+            .set_view_pointer_transform(1, Some(transform))
+            .unwrap();
+
+        let click = |view_id| {
+            FlutterPointerEvent::new(
+                0,
+                ffi::FlutterPointerPhase::Down,
+                (10.0, 30.0),
+                ffi::FlutterPointerSignalKind::None,
+                (0.0, 0.0),
+                ffi::FlutterPointerDeviceKind::Mouse,
+                ffi::FlutterPointerMouseButtons::PRIMARY,
+                view_id,
+            )
+        };
+        engine.send_pointer_event(click(1));
+        engine.send_pointer_event(click(IMPLICIT_VIEW_ID));
+
+        let sent = SENT_POINTER_EVENTS.with(|sent| sent.take());
+        let positions: Vec<_> = sent
+            .iter()
+            .map(|event| (event.view_id, event.x, event.y))
+            .collect();
+        // Events of views without a transform are sent as is.
+        assert_eq!(
+            positions,
+            vec![(1, 60.0, 80.0), (IMPLICIT_VIEW_ID, 10.0, 30.0)]
+        );
+    }
+
+    #[test]
+    fn semantics_custom_actions_are_decoded_for_the_handler() {
+        #[derive(Default)]
+        struct RecordingSemanticsHandler {
+            updates: Vec<ffi::FlutterSemanticsUpdate>,
+        }
+
+        impl FlutterSemanticsHandler for RecordingSemanticsHandler {
+            fn update_semantics(&mut self, update: ffi::FlutterSemanticsUpdate) {
+                self.updates.push(update);
+            }
+        }
+
+        let handler = Arc::new(Mutex::new(RecordingSemanticsHandler::default()));
+        let engine = uninitialized_engine_with(|inner| {
+            inner.semantics_handler = Some(handler.clone());
+        });
+
+        let label = CString::new("Mark as read").unwrap();
+        let hint = CString::new("Marks the message as read").unwrap();
+        let mut action = flust_engine_sys::FlutterSemanticsCustomAction2 {
+            struct_size: std::mem::size_of::<flust_engine_sys::FlutterSemanticsCustomAction2>(),
+            id: 7,
+            override_action: flust_engine_sys::FlutterSemanticsAction::kFlutterSemanticsActionTap,
+            label: label.as_ptr(),
+            hint: hint.as_ptr(),
+        };
+        let mut actions = [&mut action as *mut _];
+        let mut update: flust_engine_sys::FlutterSemanticsUpdate2 = unsafe { std::mem::zeroed() };
+        update.struct_size = std::mem::size_of::<flust_engine_sys::FlutterSemanticsUpdate2>();
+        update.custom_action_count = actions.len();
+        update.custom_actions = actions.as_mut_ptr();
+
+        flutter_callbacks::update_semantics_callback2(
+            &update,
+            Arc::as_ptr(&engine.inner) as *mut c_void,
+        );
+
+        let handler = handler.lock();
+        assert_eq!(handler.updates.len(), 1);
+        assert!(handler.updates[0].nodes.is_empty());
+        let [action] = handler.updates[0].custom_actions.as_slice() else {
+            panic!("expected a single custom action");
+        };
+        assert_eq!(action.id, 7);
+        assert_eq!(
+            action.override_action,
+            Some(ffi::FlutterSemanticsAction::Tap)
+        );
+        assert_eq!(action.label, "Mark as read");
+        assert_eq!(action.hint, "Marks the message as read");
+    }
+
+    #[test]
+    fn next_frame_callback_runs_once_on_the_platform_thread() {
+        let engine = uninitialized_engine();
+        let calls = Arc::new(Mutex::new(vec![]));
+        let user_data = NextFrameCallbackUserData::on_platform_thread(engine.downgrade(), {
+            let calls = calls.clone();
+            move |engine| calls.lock().push(engine.is_platform_thread())
+        });
+
+        // The engine calls it from the raster thread.
+        std::thread::spawn(move || flutter_callbacks::next_frame_callback(user_data.into_raw()))
+            .join()
+            .unwrap();
+        assert!(calls.lock().is_empty());
+
+        engine.execute_platform_tasks();
+        assert_eq!(*calls.lock(), vec![true]);
+    }
 }
@@ -7,6 +7,8 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use thiserror::Error;
+
 use crate::FlutterEngine;
 
 #[derive(Default)]
@@ -32,6 +34,31 @@ impl PluginRegistrar {
         self
     }
 
+    /// Adds the plugins of `batch`, initializing each one after its
+    /// [`Plugin::dependencies`]. Dependencies are either part of the batch, or
+    /// registered already. No plugin is initialized when a dependency is
+    /// missing or in case of a dependency cycle.
+    pub fn add_plugins(
+        &mut self,
+        engine: &FlutterEngine,
+        batch: PluginBatch,
+    ) -> Result<&mut Self, PluginRegistrarError> {
+        let declared: Vec<_> = batch
+            .plugins
+            .iter()
+            .map(|plugin| (plugin.name, plugin.dependencies))
+            .collect();
+        let order = init_order(&declared, |name| self.plugins.contains_key(name))?;
+
+        let mut plugins: Vec<_> = batch.plugins.into_iter().map(Some).collect();
+        for index in order {
+            let plugin = plugins[index].take().unwrap();
+            let arc = (plugin.init)(engine);
+            self.plugins.insert(plugin.name.to_owned(), arc);
+        }
+        Ok(self)
+    }
+
     pub fn with_plugin<F, P>(&self, f: F)
     where
         F: FnOnce(&P),
@@ -59,5 +86,150 @@ impl PluginRegistrar {
 
 pub trait Plugin {
     fn plugin_name() -> &'static str;
+
+    /// The names of the plugins that must be initialized before this one,
+    /// when added through [`PluginRegistrar::add_plugins`].
+    fn dependencies() -> &'static [&'static str] {
+        &[]
+    }
+
     fn init(&mut self, engine: &FlutterEngine);
 }
+
+/// Plugins added at once with [`PluginRegistrar::add_plugins`], so that they
+/// are initialized in the order of their dependencies.
+#[derive(Default)]
+pub struct PluginBatch {
+    plugins: Vec<PendingPlugin>,
+}
+
+struct PendingPlugin {
+    name: &'static str,
+    dependencies: &'static [&'static str],
+    init: Box<dyn FnOnce(&FlutterEngine) -> Arc<RwLock<dyn Any>>>,
+}
+
+impl PluginBatch {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_plugin<P>(mut self, plugin: P) -> Self
+    where
+        P: Plugin + 'static,
+    {
+        self.plugins.push(PendingPlugin {
+            name: P::plugin_name(),
+            dependencies: P::dependencies(),
+            init: Box::new(move |engine: &FlutterEngine| -> Arc<RwLock<dyn Any>> {
+                let mut plugin = plugin;
+                plugin.init(engine);
+                Arc::new(RwLock::new(plugin))
+            }),
+        });
+        self
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PluginRegistrarError {
+    #[error("Plugin {plugin} depends on {dependency}, which isn't registered")]
+    MissingDependency { plugin: String, dependency: String },
+
+    #[error("Plugins with cyclic dependencies: {}", .0.join(", "))]
+    DependencyCycle(Vec<String>),
+}
+
+/// The order the `plugins` (names and dependencies) are initialized in, as
+/// indices into `plugins`: each step initializes the first plugin whose
+/// dependencies are either initialized or `registered` already.
+fn init_order(
+    plugins: &[(&str, &[&str])],
+    registered: impl Fn(&str) -> bool,
+) -> Result<Vec<usize>, PluginRegistrarError> {
+    let is_pending = |name: &str| plugins.iter().any(|(pending, _)| *pending == name);
+    for (plugin, dependencies) in plugins {
+        if let Some(dependency) = dependencies
+            .iter()
+            .find(|dependency| !registered(dependency) && !is_pending(dependency))
+        {
+            return Err(PluginRegistrarError::MissingDependency {
+                plugin: plugin.to_string(),
+                dependency: dependency.to_string(),
+            });
+        }
+    }
+
+    let mut initialized = vec![false; plugins.len()];
+    let mut order = Vec::with_capacity(plugins.len());
+    let is_satisfied = |dependency: &str, initialized: &[bool]| {
+        registered(dependency)
+            || plugins
+                .iter()
+                .zip(initialized)
+                .all(|((name, _), initialized)| *name != dependency || *initialized)
+    };
+
+    while let Some(index) = (0..plugins.len()).find(|&index| {
+        let (_, dependencies) = plugins[index];
+        !initialized[index]
+            && dependencies
+                .iter()
+                .all(|dependency| is_satisfied(dependency, &initialized))
+    }) {
+        initialized[index] = true;
+        order.push(index);
+    }
+
+    if order.len() < plugins.len() {
+        let cyclic = plugins
+            .iter()
+            .zip(&initialized)
+            .filter(|(_, initialized)| !**initialized)
+            .map(|((name, _), _)| name.to_string())
+            .collect();
+        return Err(PluginRegistrarError::DependencyCycle(cyclic));
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependent_plugins_are_initialized_after_their_dependencies() {
+        let plugins: [(&str, &[&str]); 4] = [
+            ("textinput", &["keyboard", "platform"]),
+            ("keyboard", &["platform"]),
+            ("mousecursor", &[]),
+            ("platform", &["isolate"]),
+        ];
+
+        assert_eq!(
+            init_order(&plugins, |name| name == "isolate"),
+            Ok(vec![2, 3, 1, 0])
+        );
+        assert_eq!(
+            init_order(&plugins, |_| false),
+            Err(PluginRegistrarError::MissingDependency {
+                plugin: "platform".into(),
+                dependency: "isolate".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn dependency_cycles_are_rejected() {
+        let plugins: [(&str, &[&str]); 3] = [("a", &["b"]), ("b", &["a"]), ("c", &[])];
+
+        assert_eq!(
+            init_order(&plugins, |_| false),
+            Err(PluginRegistrarError::DependencyCycle(vec![
+                "a".into(),
+                "b".into()
+            ]))
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use crate::ffi::FlutterSemanticsUpdate;
+
+/// The ID of the root node of the accessibility tree.
+pub const ROOT_SEMANTICS_NODE_ID: u64 = 0;
+
+/// Handler receiving the updates of the semantics tree, which are only sent
+/// while semantics are enabled (see
+/// [`crate::FlutterEngine::update_semantics_enabled`]).
+pub trait FlutterSemanticsHandler {
+    /// Called on the platform thread with the nodes and custom actions which
+    /// changed since the previous update.
+    fn update_semantics(&mut self, update: FlutterSemanticsUpdate);
+}
@@ -1,16 +1,45 @@
-use crate::FlutterEngineWeakRef;
+use crate::affinity::{set_current_thread_affinity, EngineThread, ThreadAffinity};
+use crate::{FlutterEngineWeakRef, MainThreadRenderThreadFn};
+use crossbeam_channel::{unbounded, Receiver, Sender};
 use flust_engine_sys::{FlutterEngineGetCurrentTime, FlutterTask};
-use parking_lot::{Mutex, MutexGuard};
+use parking_lot::{Mutex, MutexGuard, RwLock};
 use priority_queue::PriorityQueue;
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
+use std::io;
 use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::thread::ThreadId;
+use std::thread::{JoinHandle, Thread, ThreadId};
 use std::time::{Duration, Instant};
 use tracing::debug;
 
+/// Identifiers of the custom task runners, which tell the engine whether two
+/// task runners run on the same thread.
+pub(crate) const PLATFORM_TASK_RUNNER_IDENTIFIER: usize = 0;
+pub(crate) const RENDER_TASK_RUNNER_IDENTIFIER: usize = 1;
+
+/// Bounds the time spent running platform work in one iteration of the event
+/// loop (see [`crate::builder::FlutterEngineBuilder::with_platform_task_budget`]).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TaskBudget {
+    deadline: Option<Instant>,
+}
+
+impl TaskBudget {
+    /// Starts a budget of `budget` (unlimited if `None`) from now.
+    pub(crate) fn new(budget: Option<Duration>) -> Self {
+        Self {
+            deadline: budget.map(|budget| Instant::now() + budget),
+        }
+    }
+
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
 pub trait TaskRunnerHandler {
     fn wake(&self);
 }
@@ -60,26 +89,34 @@ impl TaskRunner {
     }
 
     pub fn execute_tasks(&self) -> Option<Instant> {
+        self.execute_tasks_within(&TaskBudget::new(None))
+    }
+
+    /// Runs the expired tasks until `budget` is exceeded (at least one task
+    /// runs), returning the time of the next task. It's in the past if expired
+    /// tasks were deferred.
+    pub(crate) fn execute_tasks_within(&self, budget: &TaskBudget) -> Option<Instant> {
         let now = Instant::now();
-        let mut expired_tasks = Vec::new();
+        // The engine is only gone when the render thread outlives it.
+        let engine = self.inner.lock().engine.upgrade()?;
 
-        let engine = {
-            let mut inner = self.inner.lock();
-            let tasks = &mut inner.tasks;
-            while let Some((_, priority)) = tasks.peek() {
-                if priority.time > now {
-                    break;
+        loop {
+            // make sure to unlock mutex before actually running the task as it may post another task
+            let task = {
+                let mut inner = self.inner.lock();
+                match inner.tasks.peek() {
+                    Some((_, priority)) if priority.time <= now => inner.tasks.pop(),
+                    _ => None,
                 }
-                let (task, _) = tasks.pop().unwrap();
-                expired_tasks.push(task);
-            }
-            // make sure to unlock mutex before actually running the tasks as they may post another task
-            inner.engine.upgrade().unwrap()
-        };
+            };
+            let Some((task, _)) = task else {
+                break;
+            };
 
-        // run tasks
-        for task in expired_tasks {
             engine.run_task(&task.task);
+            if budget.is_exceeded() {
+                break;
+            }
         }
 
         // next task time
@@ -135,6 +172,126 @@ impl TaskRunner {
     }
 }
 
+/// Wakes the render thread when a task or a callback is posted to it.
+struct RenderThreadWaker(Thread);
+
+impl TaskRunnerHandler for RenderThreadWaker {
+    fn wake(&self) {
+        self.0.unpark();
+    }
+}
+
+/// A thread owned by the engine, running its render tasks (i.e.: the
+/// `render_task_runner` of `FlutterCustomTaskRunners`) along with the
+/// callbacks of [`crate::FlutterEngine::run_on_render_thread`]. The OpenGL
+/// callbacks (e.g.: `make_current` and `present`) are invoked from it.
+pub(crate) struct RenderThread {
+    pub(crate) runner: TaskRunner,
+    callbacks: Sender<MainThreadRenderThreadFn>,
+    /// Whether render tasks may run. It's cleared once the engine was shut
+    /// down, as tasks must keep running while it shuts down.
+    running: Arc<RwLock<bool>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl RenderThread {
+    /// Spawns the render thread, pinned to the CPUs of the raster thread (if
+    /// any).
+    pub(crate) fn spawn(affinity: ThreadAffinity) -> io::Result<Self> {
+        let (callbacks, callback_receiver) = unbounded();
+        let running = Arc::new(RwLock::new(true));
+        let (runner_sender, runner_receiver) = mpsc::sync_channel(1);
+
+        let thread_running = running.clone();
+        let handle = thread::Builder::new()
+            .name("flust-render".into())
+            .spawn(move || {
+                affinity.apply(EngineThread::Raster, set_current_thread_affinity);
+
+                let waker = Arc::new(RenderThreadWaker(thread::current()));
+                let runner = TaskRunner::new(waker);
+                runner_sender.send(runner.clone()).unwrap();
+                Self::run(runner, callback_receiver, thread_running);
+            })?;
+
+        let runner = runner_receiver
+            .recv()
+            .map_err(|_| io::Error::other("render thread exited during startup"))?;
+
+        Ok(Self {
+            runner,
+            callbacks,
+            running,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    fn run(
+        runner: TaskRunner,
+        callbacks: Receiver<MainThreadRenderThreadFn>,
+        running: Arc<RwLock<bool>>,
+    ) {
+        loop {
+            let engine = runner.inner.lock().engine.upgrade();
+            let next_task = {
+                let running = running.read();
+                if !*running {
+                    break;
+                }
+
+                let next_task = runner.execute_tasks();
+                if let Some(engine) = &engine {
+                    for f in callbacks.try_iter() {
+                        f(engine);
+                    }
+                }
+                next_task
+            };
+            // Dropping the last reference to the engine stops this thread,
+            // which requires `running` to be unlocked.
+            drop(engine);
+
+            match next_task {
+                Some(time) => thread::park_timeout(time.saturating_duration_since(Instant::now())),
+                None => thread::park(),
+            }
+        }
+        debug!("render thread stopped");
+    }
+
+    pub(crate) fn runs_task_on_current_thread(&self) -> bool {
+        self.runner.runs_task_on_current_thread()
+    }
+
+    /// Queues `f` to run on the render thread. Callbacks posted after the
+    /// engine was shut down are dropped.
+    pub(crate) fn post_callback(&self, f: MainThreadRenderThreadFn) {
+        if self.callbacks.send(f).is_ok() {
+            self.runner.wake();
+        }
+    }
+
+    /// Stops running render tasks, waiting for the task being run (if any) to
+    /// complete. The engine must not be accessed by render tasks afterwards.
+    pub(crate) fn stop(&self) {
+        *self.running.write() = false;
+        self.runner.wake();
+
+        // The last reference to the engine may be dropped on the render thread
+        // itself, which can't wait for itself to exit.
+        let handle = self.handle.lock().take();
+        if let Some(handle) = handle.filter(|_| !self.runs_task_on_current_thread()) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 #[derive(Eq, PartialEq)]
 struct TaskPriority {
     order: u64,
@@ -1,15 +1,41 @@
 use crate::FlutterEngine;
 use flust_engine_sys::FlutterOpenGLTexture;
 use parking_lot::Mutex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::c_void;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use tracing::trace;
 
+/// The textures marked as having a new frame available, which the engine
+/// didn't fetch yet. Until it does (or until the next vsync), posting more
+/// frames only replaces the frame to fetch: marking the texture again would
+/// only wake up the engine with no visible effect (e.g.: for a video decoded
+/// faster than the display refresh rate).
+#[derive(Default)]
+struct PendingFrameMarks {
+    textures: HashSet<TextureId>,
+}
+
+impl PendingFrameMarks {
+    /// Returns whether the texture needs to be marked.
+    fn mark(&mut self, texture_id: TextureId) -> bool {
+        self.textures.insert(texture_id)
+    }
+
+    fn consume(&mut self, texture_id: TextureId) {
+        self.textures.remove(&texture_id);
+    }
+
+    fn clear(&mut self) {
+        self.textures.clear();
+    }
+}
+
 pub(crate) struct TextureRegistry {
     last_id: AtomicI64,
     frames: Arc<Mutex<HashMap<TextureId, TextureFrame>>>,
+    pending_marks: Arc<Mutex<PendingFrameMarks>>,
 }
 
 impl TextureRegistry {
@@ -17,9 +43,15 @@ impl TextureRegistry {
         Self {
             last_id: AtomicI64::new(1),
             frames: Arc::new(Default::default()),
+            pending_marks: Arc::new(Default::default()),
         }
     }
 
+    /// Lets textures be marked again on the next frame posted to them.
+    pub(crate) fn on_vsync(&self) {
+        self.pending_marks.lock().clear();
+    }
+
     pub fn create_texture(&self, engine: FlutterEngine) -> Texture {
         let texture_id = self.last_id.fetch_add(1, Ordering::Relaxed);
 
@@ -37,6 +69,7 @@ impl TextureRegistry {
             engine,
             texture_id,
             frames: self.frames.clone(),
+            pending_marks: self.pending_marks.clone(),
         }
     }
 
@@ -45,6 +78,7 @@ impl TextureRegistry {
         texture_id: TextureId,
         _size: (usize, usize),
     ) -> Option<TextureFrame> {
+        self.pending_marks.lock().consume(texture_id);
         self.frames.lock().remove(&texture_id)
     }
 }
@@ -55,6 +89,7 @@ pub struct Texture {
     engine: FlutterEngine,
     texture_id: TextureId,
     frames: Arc<Mutex<HashMap<TextureId, TextureFrame>>>,
+    pending_marks: Arc<Mutex<PendingFrameMarks>>,
 }
 
 impl Texture {
@@ -62,8 +97,17 @@ impl Texture {
         self.texture_id
     }
 
+    /// Posts the next frame of the texture, replacing the previous one if the
+    /// engine didn't fetch it yet. The engine is notified at most once per
+    /// vsync (see [`PendingFrameMarks`]).
     pub fn post_frame(&self, frame: TextureFrame) {
-        post_frame_internal(&self.engine, self.texture_id, &self.frames, frame);
+        post_frame_internal(
+            &self.engine,
+            self.texture_id,
+            &self.frames,
+            &self.pending_marks,
+            frame,
+        );
     }
 }
 
@@ -71,6 +115,7 @@ fn post_frame_internal(
     engine: &FlutterEngine,
     texture_id: TextureId,
     frames: &Arc<Mutex<HashMap<TextureId, TextureFrame>>>,
+    pending_marks: &Mutex<PendingFrameMarks>,
     frame: TextureFrame,
 ) {
     if let Some(old_frame) = frames.lock().insert(texture_id, frame) {
@@ -79,6 +124,11 @@ fn post_frame_internal(
         });
     }
 
+    if !pending_marks.lock().mark(texture_id) {
+        trace!("texture {}: frame available already marked", texture_id);
+        return;
+    }
+
     engine.run_on_platform_thread(move |engine| {
         trace!("texture {}: marking frame available", texture_id);
         unsafe {
@@ -93,6 +143,7 @@ fn post_frame_internal(
 impl Drop for Texture {
     fn drop(&mut self) {
         let texture_id = self.texture_id;
+        self.pending_marks.lock().consume(texture_id);
         self.engine.run_on_platform_thread(move |engine| {
             trace!("texture {}: unregister", texture_id);
             unsafe {
@@ -142,3 +193,23 @@ unsafe extern "C" fn texture_destruction_callback(user_data: *mut c_void) {
     let user_data = Box::from_raw(user_data);
     user_data();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_marks_collapse_until_vsync() {
+        let mut marks = PendingFrameMarks::default();
+
+        let marked: Vec<_> = (0..4).map(|_| marks.mark(1)).collect();
+        assert_eq!(marked, vec![true, false, false, false]);
+        assert!(marks.mark(2));
+
+        marks.clear();
+        assert!(marks.mark(1));
+
+        marks.consume(1);
+        assert!(marks.mark(1));
+    }
+}
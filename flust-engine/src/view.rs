@@ -1,16 +1,19 @@
 use std::{collections::HashMap, sync::Arc};
 
-use flust_engine_api::FlutterOpenGLHandler;
+use dpi::PhysicalSize;
+use flust_engine_api::{FlutterOpenGLHandler, FlutterSoftwareHandler};
 
 use crate::{
     compositor::FlutterCompositorHandler,
     ffi::{FlutterViewId, IMPLICIT_VIEW_ID},
+    FlutterEngineError,
 };
 
 /// The view capable of acting as a rendering target and input source for the Flutter engine.
 pub struct FlutterView {
     id: FlutterViewId,
-    opengl_handler: Arc<dyn FlutterOpenGLHandler>,
+    opengl_handler: Option<Arc<dyn FlutterOpenGLHandler>>,
+    software_handler: Option<Arc<dyn FlutterSoftwareHandler>>,
     compositor_handler: Option<Arc<dyn FlutterCompositorHandler>>,
 }
 
@@ -21,7 +24,8 @@ impl FlutterView {
     ) -> Self {
         Self {
             id,
-            opengl_handler: Arc::new(opengl_handler),
+            opengl_handler: Some(Arc::new(opengl_handler)),
+            software_handler: None,
             compositor_handler: None,
         }
     }
@@ -33,15 +37,98 @@ impl FlutterView {
     ) -> Self {
         Self {
             id,
-            opengl_handler: Arc::new(opengl_handler),
+            opengl_handler: Some(Arc::new(opengl_handler)),
+            software_handler: None,
             compositor_handler: Some(Arc::new(compositor_handler)),
         }
     }
+
+    /// Creates a view rendered on the CPU, for engines using the software
+    /// renderer (see [`crate::builder::FlutterEngineBuilder::with_software_rendering`]).
+    pub fn new_software(
+        id: FlutterViewId,
+        software_handler: impl FlutterSoftwareHandler + 'static,
+    ) -> Self {
+        Self {
+            id,
+            opengl_handler: None,
+            software_handler: Some(Arc::new(software_handler)),
+            compositor_handler: None,
+        }
+    }
+
+    pub fn id(&self) -> FlutterViewId {
+        self.id
+    }
+
+    pub fn opengl_handler(&self) -> Option<Arc<dyn FlutterOpenGLHandler>> {
+        self.opengl_handler.clone()
+    }
+
+    pub fn software_handler(&self) -> Option<Arc<dyn FlutterSoftwareHandler>> {
+        self.software_handler.clone()
+    }
+
+    pub fn compositor_handler(&self) -> Option<Arc<dyn FlutterCompositorHandler>> {
+        self.compositor_handler.clone()
+    }
+}
+
+/// The clockwise rotation of the contents of a view on its surface (e.g.: on
+/// an output mounted in portrait orientation).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum FlutterViewRotation {
+    #[default]
+    Normal,
+    Rotated90,
+    Rotated180,
+    Rotated270,
+}
+
+/// Maps the pointer coordinates reported by the windowing system, in logical
+/// pixels of the (rotated) surface of a view, to physical pixels of the view
+/// (see [`crate::FlutterEngine::set_view_pointer_transform`]).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FlutterPointerTransform {
+    /// The (possibly fractional) scale factor of the surface.
+    pub scale_factor: f64,
+    pub rotation: FlutterViewRotation,
+    /// The physical size of the view, i.e. before its rotation.
+    pub view_size: PhysicalSize<f64>,
+}
+
+impl FlutterPointerTransform {
+    /// Transforms a position on the surface to a position in the view.
+    pub fn apply_to_position(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (x, y) = (x * self.scale_factor, y * self.scale_factor);
+        let PhysicalSize { width, height } = self.view_size;
+
+        // The inverse of the rotation of the view contents.
+        match self.rotation {
+            FlutterViewRotation::Normal => (x, y),
+            FlutterViewRotation::Rotated90 => (y, height - x),
+            FlutterViewRotation::Rotated180 => (width - x, height - y),
+            FlutterViewRotation::Rotated270 => (width - y, x),
+        }
+    }
+
+    /// Transforms a distance on the surface (e.g.: a scroll delta) to a
+    /// distance in the view.
+    pub fn apply_to_delta(&self, (dx, dy): (f64, f64)) -> (f64, f64) {
+        let (dx, dy) = (dx * self.scale_factor, dy * self.scale_factor);
+        match self.rotation {
+            FlutterViewRotation::Normal => (dx, dy),
+            FlutterViewRotation::Rotated90 => (dy, -dx),
+            FlutterViewRotation::Rotated180 => (-dx, -dy),
+            FlutterViewRotation::Rotated270 => (-dy, dx),
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct ViewRegistry {
     views: HashMap<FlutterViewId, FlutterView>,
+    pointer_transforms: HashMap<FlutterViewId, FlutterPointerTransform>,
 }
 
 impl ViewRegistry {
@@ -51,24 +138,252 @@ impl ViewRegistry {
 
     pub fn remove_view(&mut self, view_id: FlutterViewId) {
         self.views.remove(&view_id);
+        self.pointer_transforms.remove(&view_id);
+    }
+
+    pub fn set_pointer_transform(
+        &mut self,
+        view_id: FlutterViewId,
+        transform: Option<FlutterPointerTransform>,
+    ) -> Result<(), FlutterEngineError> {
+        self.ensure_view_exists(view_id)?;
+        match transform {
+            Some(transform) => self.pointer_transforms.insert(view_id, transform),
+            None => self.pointer_transforms.remove(&view_id),
+        };
+        Ok(())
+    }
+
+    pub fn pointer_transform(&self, view_id: FlutterViewId) -> Option<FlutterPointerTransform> {
+        self.pointer_transforms.get(&view_id).copied()
     }
 
     pub fn implicit_view(&self) -> Option<&FlutterView> {
         self.views.get(&IMPLICIT_VIEW_ID)
     }
 
+    pub fn view(&self, view_id: FlutterViewId) -> Option<&FlutterView> {
+        self.views.get(&view_id)
+    }
+
+    /// Returns the ids of all registered views, in ascending order.
+    pub fn view_ids(&self) -> Vec<FlutterViewId> {
+        let mut view_ids: Vec<_> = self.views.keys().copied().collect();
+        view_ids.sort_unstable();
+        view_ids
+    }
+
+    pub fn ensure_view_exists(&self, view_id: FlutterViewId) -> Result<(), FlutterEngineError> {
+        if !self.views.contains_key(&view_id) {
+            return Err(FlutterEngineError::ViewNotFound(view_id));
+        }
+        Ok(())
+    }
+
+    /// The OpenGL renderer config callbacks (`make_current`, `present`, etc.)
+    /// don't carry a view id: the engine only uses them to render into the
+    /// implicit view. Secondary views are rendered through the compositor.
     pub fn implicit_view_opengl_handler(&self) -> Option<Arc<dyn FlutterOpenGLHandler>> {
+        self.opengl_handler_for_view(IMPLICIT_VIEW_ID)
+    }
+
+    pub fn opengl_handler_for_view(
+        &self,
+        view_id: FlutterViewId,
+    ) -> Option<Arc<dyn FlutterOpenGLHandler>> {
         self.views
-            .get(&IMPLICIT_VIEW_ID)
-            .map(|view| view.opengl_handler.clone())
+            .get(&view_id)
+            .and_then(FlutterView::opengl_handler)
+    }
+
+    /// Like the OpenGL renderer config callbacks, the software renderer
+    /// present callback doesn't carry a view id.
+    pub fn implicit_view_software_handler(&self) -> Option<Arc<dyn FlutterSoftwareHandler>> {
+        self.implicit_view().and_then(FlutterView::software_handler)
     }
 
+    /// There's deliberately no fallback to the implicit view's handler for
+    /// unknown views, as it would render the frames of one view into the
+    /// window of another. Compositor callbacks for such views are rejected
+    /// instead (see [`FlutterEngineError::ViewNotFound`]).
     pub fn compositor_handler_for_view(
         &self,
         view_id: FlutterViewId,
-    ) -> Option<Arc<dyn FlutterCompositorHandler>> {
+    ) -> Result<Arc<dyn FlutterCompositorHandler>, FlutterEngineError> {
         self.views
             .get(&view_id)
-            .and_then(|view| view.compositor_handler.as_ref().cloned())
+            .ok_or(FlutterEngineError::ViewNotFound(view_id))?
+            .compositor_handler()
+            .ok_or(FlutterEngineError::NoCompositorHandler(view_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{c_void, CStr};
+
+    use dpi::PhysicalSize;
+    use parking_lot::Mutex;
+
+    use crate::{
+        compositor::{
+            CompositorCollectBackingStoreError, CompositorCreateBackingStoreError,
+            CompositorPresentError,
+        },
+        ffi::{FlutterBackingStore, FlutterBackingStoreConfig, FlutterPresentViewInfo},
+    };
+
+    use super::*;
+
+    struct NoopOpenGLHandler;
+
+    impl FlutterOpenGLHandler for NoopOpenGLHandler {
+        fn present(&self) -> bool {
+            true
+        }
+
+        fn make_current(&self) -> bool {
+            true
+        }
+
+        fn clear_current(&self) -> bool {
+            true
+        }
+
+        fn fbo_with_frame_info_callback(&self, _size: PhysicalSize<u32>) -> u32 {
+            0
+        }
+
+        fn make_resource_current(&self) -> bool {
+            true
+        }
+
+        fn gl_proc_resolver(&self, _proc: &CStr) -> *mut c_void {
+            std::ptr::null_mut()
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingCompositorHandler {
+        presented: Arc<Mutex<Vec<FlutterViewId>>>,
+    }
+
+    impl FlutterCompositorHandler for RecordingCompositorHandler {
+        fn present_view(&self, info: FlutterPresentViewInfo) -> Result<(), CompositorPresentError> {
+            self.presented.lock().push(info.view_id);
+            Ok(())
+        }
+
+        fn create_backing_store(
+            &self,
+            _config: FlutterBackingStoreConfig,
+        ) -> Result<FlutterBackingStore, CompositorCreateBackingStoreError> {
+            Err(CompositorCreateBackingStoreError::CreateFailed(
+                "unsupported".into(),
+            ))
+        }
+
+        fn collect_backing_store(
+            &self,
+            _backing_store: FlutterBackingStore,
+        ) -> Result<(), CompositorCollectBackingStoreError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn present_is_routed_to_the_view_compositor_handler() {
+        let implicit = RecordingCompositorHandler::default();
+        let secondary = RecordingCompositorHandler::default();
+
+        let mut registry = ViewRegistry::default();
+        registry.add_view(FlutterView::new_with_compositor(
+            IMPLICIT_VIEW_ID,
+            NoopOpenGLHandler,
+            implicit.clone(),
+        ));
+        registry.add_view(FlutterView::new_with_compositor(
+            1,
+            NoopOpenGLHandler,
+            secondary.clone(),
+        ));
+
+        for view_id in [1, IMPLICIT_VIEW_ID, 1] {
+            registry
+                .compositor_handler_for_view(view_id)
+                .unwrap()
+                .present_view(FlutterPresentViewInfo::new(view_id, vec![]))
+                .unwrap();
+        }
+
+        assert_eq!(*implicit.presented.lock(), vec![IMPLICIT_VIEW_ID]);
+        assert_eq!(*secondary.presented.lock(), vec![1, 1]);
+        assert!(registry.opengl_handler_for_view(1).is_some());
+    }
+
+    #[test]
+    fn present_for_unregistered_view_is_rejected() {
+        let mut registry = ViewRegistry::default();
+        registry.add_view(FlutterView::new_with_compositor(
+            IMPLICIT_VIEW_ID,
+            NoopOpenGLHandler,
+            RecordingCompositorHandler::default(),
+        ));
+        registry.add_view(FlutterView::new_without_compositor(1, NoopOpenGLHandler));
+
+        assert!(matches!(
+            registry.compositor_handler_for_view(2),
+            Err(FlutterEngineError::ViewNotFound(2))
+        ));
+        assert!(matches!(
+            registry.compositor_handler_for_view(1),
+            Err(FlutterEngineError::NoCompositorHandler(1))
+        ));
+    }
+
+    #[test]
+    fn metrics_for_unknown_view_are_rejected() {
+        let mut registry = ViewRegistry::default();
+        registry.add_view(FlutterView::new_without_compositor(
+            IMPLICIT_VIEW_ID,
+            NoopOpenGLHandler,
+        ));
+        registry.add_view(FlutterView::new_without_compositor(3, NoopOpenGLHandler));
+
+        assert_eq!(registry.view_ids(), vec![IMPLICIT_VIEW_ID, 3]);
+        assert!(registry.ensure_view_exists(3).is_ok());
+
+        registry.remove_view(3);
+        assert!(matches!(
+            registry.ensure_view_exists(3),
+            Err(FlutterEngineError::ViewNotFound(3))
+        ));
+    }
+
+    #[test]
+    fn clicks_on_a_rotated_view_are_mapped_to_the_view() {
+        let mut registry = ViewRegistry::default();
+        registry.add_view(FlutterView::new_without_compositor(3, NoopOpenGLHandler));
+
+        let transform = FlutterPointerTransform {
+            scale_factor: 2.0,
+            rotation: FlutterViewRotation::Rotated90,
+            view_size: PhysicalSize::new(200.0, 100.0),
+        };
+        assert!(matches!(
+            registry.set_pointer_transform(4, Some(transform)),
+            Err(FlutterEngineError::ViewNotFound(4))
+        ));
+        assert!(registry.set_pointer_transform(3, Some(transform)).is_ok());
+
+        // The view is shown 100x200 (physical) pixels on the surface, with its
+        // top-left corner at the top-right corner of the surface.
+        let transform = registry.pointer_transform(3).unwrap();
+        assert_eq!(transform.apply_to_position((10.0, 30.0)), (60.0, 80.0));
+        assert_eq!(transform.apply_to_position((50.0, 0.0)), (0.0, 0.0));
+        assert_eq!(transform.apply_to_delta((0.0, 5.0)), (10.0, 0.0));
+
+        registry.remove_view(3);
+        assert_eq!(registry.pointer_transform(3), None);
     }
 }
@@ -0,0 +1,284 @@
+//! Hot reload through the Dart VM service, for development in JIT (debug)
+//! mode.
+//!
+//! The embedder API has no reload entry point, so reloads go through the VM
+//! service the same way the `flutter` tool does: the sources of the isolate of
+//! each Flutter view are reloaded from a kernel file (e.g.: rebuilt by
+//! `flutter build bundle` or the frontend server), then the widget tree is
+//! reassembled. Requests use the HTTP interface of the VM service, which
+//! takes the method and its parameters as the path and query of a `GET`.
+use std::{
+    fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::Path,
+    time::Duration,
+};
+
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+use tracing::debug;
+
+/// Bound on the time waited for a response, as reloading large apps takes a
+/// few seconds.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The outcome of reloading the sources of the running app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// Whether the sources of every isolate were reloaded (and the widget
+    /// tree reassembled). On failure, the app keeps running the previous
+    /// sources.
+    pub success: bool,
+    /// The messages reported by the VM, e.g.: the compilation or reload
+    /// errors explaining a failure.
+    pub notices: Vec<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum HotReloadError {
+    #[error("Hot reload is only supported in JIT (debug) mode")]
+    NotSupported,
+
+    #[error("The VM service isn't available (yet)")]
+    VmServiceUnavailable,
+
+    #[error("Invalid VM service URI: {0}")]
+    InvalidUri(String),
+
+    #[error("No Flutter view isolate to reload")]
+    NoIsolate,
+
+    #[error("VM service request {method} failed ({code}): {message}")]
+    Rpc {
+        method: String,
+        code: i64,
+        message: String,
+    },
+
+    #[error("Invalid VM service response: {0}")]
+    InvalidResponse(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Reloads the sources of the app whose VM service listens on
+/// `vm_service_uri` (see [`crate::FlutterEngine::vm_service_uri`]) from the
+/// `kernel` file, then reassembles its widget tree. This blocks until the
+/// reload completes, so it's meant to be called from a background thread (e.g.:
+/// the one watching the sources), as [`crate::FlutterEngine::reload_sources`]
+/// does.
+pub fn reload_sources(vm_service_uri: &str, kernel: &Path) -> Result<ReloadReport, HotReloadError> {
+    let client = VmServiceClient::new(vm_service_uri)?;
+    let root_lib_uri = file_uri(&fs::canonicalize(kernel)?);
+
+    let isolates = client.flutter_view_isolates()?;
+    if isolates.is_empty() {
+        return Err(HotReloadError::NoIsolate);
+    }
+
+    let mut report = ReloadReport {
+        success: true,
+        notices: Vec::new(),
+    };
+    for isolate in &isolates {
+        debug!("Reloading the sources of {}", isolate);
+        let result = client.call(
+            "reloadSources",
+            &[("isolateId", isolate), ("rootLibUri", &root_lib_uri)],
+        )?;
+
+        report.success &= result["success"].as_bool().unwrap_or(false);
+        report.notices.extend(
+            result["notices"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|notice| notice["message"].as_str())
+                .map(str::to_owned),
+        );
+    }
+
+    // Like the `flutter` tool, the widget tree is only rebuilt once every
+    // isolate was reloaded successfully.
+    if report.success {
+        for isolate in &isolates {
+            client.call("ext.flutter.reassemble", &[("isolateId", isolate)])?;
+        }
+    }
+
+    Ok(report)
+}
+
+struct VmServiceClient {
+    /// The `host:port` the VM service listens on.
+    authority: String,
+    /// The path of the VM service, including its authentication code (e.g.:
+    /// `/aBcD3f-gH_i=/`).
+    path: String,
+}
+
+impl VmServiceClient {
+    fn new(uri: &str) -> Result<Self, HotReloadError> {
+        let invalid_uri = || HotReloadError::InvalidUri(uri.to_owned());
+        let rest = uri.strip_prefix("http://").ok_or_else(invalid_uri)?;
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if authority.is_empty() {
+            return Err(invalid_uri());
+        }
+
+        let path = path.trim_end_matches('/');
+        Ok(Self {
+            authority: authority.to_owned(),
+            path: match path {
+                "" => "/".to_owned(),
+                path => format!("/{}/", path),
+            },
+        })
+    }
+
+    fn flutter_view_isolates(&self) -> Result<Vec<String>, HotReloadError> {
+        let result = self.call("_flutter.listViews", &[])?;
+        let mut isolates: Vec<String> = result["views"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|view| view["isolate"]["id"].as_str())
+            .map(str::to_owned)
+            .collect();
+        isolates.dedup();
+        Ok(isolates)
+    }
+
+    /// Calls `method`, returning its result.
+    fn call(&self, method: &str, params: &[(&str, &str)]) -> Result<JsonValue, HotReloadError> {
+        let query = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        // HTTP/1.0 so that the response isn't chunked, and ends with the
+        // connection.
+        let request = format!(
+            "GET {}{}?{} HTTP/1.0\r\nHost: {}\r\n\r\n",
+            self.path, method, query, self.authority
+        );
+        let mut stream = TcpStream::connect(&self.authority)?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        stream.write_all(request.as_bytes())?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_response(method, &response)
+    }
+}
+
+fn parse_response(method: &str, response: &[u8]) -> Result<JsonValue, HotReloadError> {
+    let invalid_response = |reason: &str| HotReloadError::InvalidResponse(reason.to_owned());
+
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| invalid_response("missing headers"))?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(HotReloadError::InvalidResponse(status.to_owned()));
+    }
+
+    let mut body: JsonValue = serde_json::from_str(body)
+        .map_err(|err| HotReloadError::InvalidResponse(err.to_string()))?;
+    if let Some(error) = body.get("error") {
+        return Err(HotReloadError::Rpc {
+            method: method.to_owned(),
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_owned(),
+        });
+    }
+
+    match body.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(invalid_response("missing result")),
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let segments: Vec<String> = path.split('/').map(percent_encode).collect();
+    format!("file://{}", segments.join("/"))
+}
+
+/// Percent-encodes everything but unreserved characters (RFC 3986).
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufRead, io::BufReader, net::TcpListener, thread};
+
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    /// Serves one response per request, returning the requested paths.
+    fn serve(responses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/aBcD3f-gH_i=/", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for body in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut lines = BufReader::new(&stream).lines().map(Result::unwrap);
+                let request_line = lines.next().unwrap();
+                requests.push(request_line.split(' ').nth(1).unwrap().to_owned());
+                lines.find(String::is_empty);
+
+                write!(stream, "HTTP/1.0 200 OK\r\n\r\n{}", body).unwrap();
+            }
+            requests
+        });
+
+        (uri, server)
+    }
+
+    #[test]
+    fn reload_returns_the_report_of_the_vm() {
+        let (uri, server) = serve(vec![
+            r#"{"result":{"type":"FlutterViewList","views":[{"isolate":{"id":"isolates/42"}}]}}"#,
+            r#"{"result":{"type":"ReloadReport","success":true,"notices":[{"message":"Reloaded 1 library"}]}}"#,
+            r#"{"result":{"type":"Success"}}"#,
+        ]);
+
+        let dir = TempDir::new("flust-reload");
+        let kernel = dir.path().join("flust reload.dill");
+        fs::write(&kernel, []).unwrap();
+        let report = reload_sources(&uri, &kernel).unwrap();
+        assert_eq!(
+            report,
+            ReloadReport {
+                success: true,
+                notices: vec!["Reloaded 1 library".to_owned()],
+            }
+        );
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0], "/aBcD3f-gH_i=/_flutter.listViews?");
+        assert!(requests[1].starts_with(
+            "/aBcD3f-gH_i=/reloadSources?isolateId=isolates%2F42&rootLibUri=file%3A%2F%2F"
+        ));
+        assert!(requests[1].ends_with("flust%2520reload.dill"));
+        assert_eq!(
+            requests[2],
+            "/aBcD3f-gH_i=/ext.flutter.reassemble?isolateId=isolates%2F42"
+        );
+    }
+}
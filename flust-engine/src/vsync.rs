@@ -0,0 +1,77 @@
+//! Tracking of the frames requested by the engine through the vsync callback.
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+/// Batons are opaque, non-null pointers on the engine side, so no request can
+/// have this baton.
+const NO_BATON: isize = 0;
+
+/// Tracks whether the engine is waiting for a vsync baton to be returned
+/// (i.e.: whether it has scheduled a frame).
+///
+/// The engine requests at most one vsync at a time: a request is pending from
+/// the moment the vsync callback is invoked until its baton is passed back
+/// through `FlutterEngineOnVsync`. An idle engine (nothing to animate or
+/// redraw) doesn't request vsyncs, so it has no pending frame.
+///
+/// The baton of the pending request is recorded, so that each baton is
+/// returned exactly once: returning a baton twice (e.g.: from racing frame
+/// callbacks) makes the engine assert.
+#[derive(Default)]
+pub(crate) struct FrameRequestTracker {
+    pending_baton: AtomicIsize,
+}
+
+impl FrameRequestTracker {
+    pub(crate) fn frame_requested(&self, baton: isize) {
+        self.pending_baton.store(baton, Ordering::Release);
+    }
+
+    /// Marks the request of `baton` as served, returning `false` if `baton`
+    /// isn't pending (i.e.: it was already returned) and must not be passed to
+    /// the engine. Must be called *before* returning the baton, as the engine
+    /// may request the next vsync right away.
+    pub(crate) fn baton_returned(&self, baton: isize) -> bool {
+        baton != NO_BATON
+            && self
+                .pending_baton
+                .compare_exchange(baton, NO_BATON, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+    }
+
+    pub(crate) fn has_pending_frame(&self) -> bool {
+        self.pending_baton.load(Ordering::Acquire) != NO_BATON
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_engine_has_no_pending_frame() {
+        let tracker = FrameRequestTracker::default();
+        assert!(!tracker.has_pending_frame());
+
+        tracker.frame_requested(1);
+        assert!(tracker.has_pending_frame());
+
+        assert!(tracker.baton_returned(1));
+        assert!(!tracker.has_pending_frame());
+    }
+
+    #[test]
+    fn baton_is_returned_once() {
+        let tracker = FrameRequestTracker::default();
+        tracker.frame_requested(7);
+
+        assert!(!tracker.baton_returned(3));
+        assert!(tracker.has_pending_frame());
+
+        assert!(tracker.baton_returned(7));
+        assert!(!tracker.baton_returned(7));
+
+        tracker.frame_requested(8);
+        assert!(!tracker.baton_returned(7));
+        assert!(tracker.baton_returned(8));
+    }
+}
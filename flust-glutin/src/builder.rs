@@ -2,7 +2,7 @@ use dpi::PhysicalSize;
 use glutin::{
     config::{Api, Config, ConfigSurfaceTypes, ConfigTemplateBuilder},
     context::{ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentGlContext},
-    display::{Display, DisplayApiPreference, GetGlDisplay},
+    display::{Display, DisplayApiPreference, GetDisplayExtensions, GetGlDisplay},
     prelude::{GlDisplay, NotCurrentGlContext},
     surface::{GlSurface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
 };
@@ -10,7 +10,9 @@ use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 use std::num::NonZeroU32;
 use thiserror::Error;
 
-use crate::context::{Context, ResourceContext};
+use crate::context::{
+    Context, GlRequirementsError, ResourceContext, ResourceContextMode, SharedContext,
+};
 
 pub type FlutterEGLContext = (Context, ResourceContext);
 
@@ -21,6 +23,8 @@ pub struct ContextBuilderAttributes {
     pub config: Option<Config>,
     pub size: Option<PhysicalSize<NonZeroU32>>,
     pub swap_interval: Option<SwapInterval>,
+    pub shared_context: Option<SharedContext>,
+    pub transparent: bool,
 }
 
 impl ContextBuilderAttributes {
@@ -40,10 +44,12 @@ impl ContextBuilder {
     }
 
     pub fn build(self) -> Result<FlutterEGLContext, ContextBuildError> {
-        let raw_window_handle = self
-            .attributes
-            .raw_window_handle
-            .ok_or(ContextBuildError::InvalidWindowHandle)?;
+        // Shared contexts must be created on the display of the context they
+        // share objects with.
+        if let Some(shared_context) = &self.attributes.shared_context {
+            let display = shared_context.display().clone();
+            return self.build_with_display(display);
+        }
 
         // Get display from `raw_display_handle` if present (`sctk`), or from `config` otherwise (`winit`).
         let display = self
@@ -62,21 +68,33 @@ impl ContextBuilder {
             )
             .ok_or(ContextBuildError::InvalidDisplayHandle)?;
 
-        let size = self.attributes.size.ok_or(ContextBuildError::InvalidSize)?;
+        self.build_with_display(display)
+    }
 
-        // Use config from attributes if present (`winit`), or build a default one otherwise (`sctk`).
-        let config = self
+    fn build_with_display(self, display: Display) -> Result<FlutterEGLContext, ContextBuildError> {
+        let raw_window_handle = self
             .attributes
-            .config
-            .map_or_else(|| new_default_config(&display, raw_window_handle), Ok)?;
+            .raw_window_handle
+            .ok_or(ContextBuildError::InvalidWindowHandle)?;
 
-        let render_attributes_gl = ContextAttributesBuilder::new()
-            .with_context_api(glutin::context::ContextApi::OpenGl(None))
-            .build(Some(raw_window_handle));
+        let size = self.attributes.size.ok_or(ContextBuildError::InvalidSize)?;
 
-        let render_attributes_gles = ContextAttributesBuilder::new()
-            .with_context_api(glutin::context::ContextApi::Gles(None))
-            .build(Some(raw_window_handle));
+        // Use config from attributes if present (`winit`), or build a default one otherwise (`sctk`).
+        let config = self.attributes.config.map_or_else(
+            || new_default_config(&display, raw_window_handle, self.attributes.transparent),
+            Ok,
+        )?;
+
+        let render_attributes = |api| {
+            let builder = ContextAttributesBuilder::new().with_context_api(api);
+            match &self.attributes.shared_context {
+                Some(shared_context) => builder.with_sharing(shared_context),
+                None => builder,
+            }
+            .build(Some(raw_window_handle))
+        };
+        let render_attributes_gl = render_attributes(glutin::context::ContextApi::OpenGl(None));
+        let render_attributes_gles = render_attributes(glutin::context::ContextApi::Gles(None));
 
         // Create a context, trying OpenGL and then OpenGL ES.
         let render_context = unsafe {
@@ -112,7 +130,9 @@ impl ContextBuilder {
         };
         let resource_context = ResourceContext::new(resource_context.treat_as_possibly_current());
 
-        let context = Context::new(display, surface, render_context.treat_as_possibly_current());
+        let mut context =
+            Context::new(display, surface, render_context.treat_as_possibly_current());
+        context.check_gl_requirements()?;
 
         Ok((context, resource_context))
     }
@@ -141,6 +161,21 @@ impl ContextBuilder {
         self.attributes.size = size;
         self
     }
+
+    /// Creates the render context sharing the objects (e.g.: the textures of
+    /// the backing stores) of `shared_context`, on its display.
+    pub fn with_shared_context(mut self, shared_context: Option<SharedContext>) -> Self {
+        self.attributes.shared_context = shared_context;
+        self
+    }
+
+    /// Picks a default config with an alpha channel, for surfaces blended
+    /// with the ones below them (e.g.: overlay subsurfaces). Ignored when a
+    /// config is set.
+    pub fn with_transparency(mut self, transparent: bool) -> Self {
+        self.attributes.transparent = transparent;
+        self
+    }
 }
 
 #[derive(Error, Debug)]
@@ -163,6 +198,9 @@ pub enum ContextBuildError {
     #[error("Unable to set swap interval")]
     SwapIntervalUpdateFailed,
 
+    #[error(transparent)]
+    GlRequirementsError(#[from] GlRequirementsError),
+
     #[error(transparent)]
     GlutinError(#[from] glutin::error::Error),
 }
@@ -170,12 +208,26 @@ pub enum ContextBuildError {
 fn new_default_config(
     display: &Display,
     raw_window_handle: RawWindowHandle,
+    transparent: bool,
 ) -> Result<Config, ContextBuildError> {
+    // The resource context needs a pbuffer surface of the same config on
+    // displays without surfaceless contexts.
+    let surface_types = match display {
+        Display::Egl(display) => {
+            ResourceContextMode::for_display_extensions(display.extensions()).config_surface_types()
+        }
+        #[allow(unreachable_patterns)]
+        _ => ConfigSurfaceTypes::WINDOW,
+    };
     let config_template = ConfigTemplateBuilder::new()
         .compatible_with_native_window(raw_window_handle)
-        .with_surface_type(ConfigSurfaceTypes::WINDOW)
-        .with_api(Api::GLES2 | Api::GLES3 | Api::OPENGL)
-        .build();
+        .with_surface_type(surface_types)
+        .with_api(Api::GLES2 | Api::GLES3 | Api::OPENGL);
+    let config_template = match transparent {
+        true => config_template.with_alpha_size(8).with_transparency(true),
+        false => config_template,
+    }
+    .build();
 
     unsafe { display.find_configs(config_template) }?
         .next()
@@ -1,15 +1,20 @@
 use dpi::PhysicalSize;
 use glutin::{
     api::egl,
-    context::PossiblyCurrentContext,
-    display::Display,
+    config::{ConfigSurfaceTypes, GetGlConfig},
+    context::{AsRawContext, PossiblyCurrentContext, RawContext},
+    display::{Display, GetDisplayExtensions, GetGlDisplay},
     prelude::{GlDisplay, NotCurrentGlContext, PossiblyCurrentGlContext},
-    surface::{GlSurface, Surface, WindowSurface},
+    surface::{GlSurface, PbufferSurface, Rect, Surface, SurfaceAttributesBuilder, WindowSurface},
 };
 use std::{
-    ffi::{c_void, CStr},
+    collections::HashSet,
+    ffi::{c_void, CStr, CString},
+    fmt,
     num::NonZeroU32,
 };
+use thiserror::Error;
+use tracing::{debug, warn};
 
 use crate::gl;
 
@@ -49,6 +54,16 @@ impl Context {
         false
     }
 
+    /// A handle for creating contexts sharing the objects (e.g.: textures) of
+    /// this one, see [`crate::builder::ContextBuilder::with_shared_context`].
+    pub fn shared_context(&self) -> Option<SharedContext> {
+        let context = self.context.as_ref()?;
+        Some(SharedContext {
+            display: self.display.clone(),
+            context: context.raw_context(),
+        })
+    }
+
     pub fn get_proc_address(&self, proc: &CStr) -> *const c_void {
         self.display.get_proc_address(proc)
     }
@@ -67,12 +82,249 @@ impl Context {
         }
     }
 
+    /// The age of the back buffer of the surface (`EGL_EXT_buffer_age`), i.e.:
+    /// the number of frames since its contents were presented. It's 0 when its
+    /// contents are undefined, e.g.: after a resize, or when the age isn't
+    /// supported.
+    ///
+    /// On Wayland, this latches the back buffer: resizing the surface
+    /// afterwards only applies after the next swap.
+    pub fn buffer_age(&self) -> u32 {
+        self.surface.buffer_age()
+    }
+
     pub fn present(&mut self) -> bool {
         match self.context.as_ref() {
             Some(ctx) => self.surface.swap_buffers(ctx).is_ok(),
             None => false,
         }
     }
+
+    /// Presents the surface while hinting the system compositor that only the
+    /// `damage` rects have changed since the previous frame. On Wayland, this
+    /// results in `wl_surface.damage_buffer` requests being issued before the
+    /// surface is committed.
+    ///
+    /// Rects use the EGL convention where the origin is the bottom left corner
+    /// of the surface. Falls back to a regular (full surface) present when the
+    /// current platform doesn't support swapping buffers with damage.
+    pub fn present_with_damage(&mut self, damage: &[Rect]) -> bool {
+        match (&self.surface, self.context.as_ref()) {
+            (Surface::Egl(surface), Some(PossiblyCurrentContext::Egl(ctx))) => {
+                surface.swap_buffers_with_damage(ctx, damage).is_ok()
+            }
+            _ => self.present(),
+        }
+    }
+}
+
+/// A context whose objects are shared with the contexts created from it. The
+/// contexts must be created on the same display.
+#[derive(Debug, Clone)]
+pub struct SharedContext {
+    display: Display,
+    context: RawContext,
+}
+
+impl SharedContext {
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+}
+
+impl AsRawContext for SharedContext {
+    fn raw_context(&self) -> RawContext {
+        self.context
+    }
+}
+
+// The raw context is only used for creating contexts sharing its objects.
+unsafe impl Send for SharedContext {}
+
+impl Context {
+    /// Checks that the OpenGL context meets the requirements of the Impeller
+    /// renderer (i.e.: an OpenGL ES 3.0+ context).
+    pub fn check_impeller_requirements(&mut self) -> Result<(), ImpellerRequirementsError> {
+        if !self.make_current() {
+            return Err(ImpellerRequirementsError::MakeCurrentFailed);
+        }
+
+        let gl = self.load_gl();
+        let version = gl_string(&gl, gl::VERSION);
+
+        self.make_not_current();
+
+        check_impeller_gl_version(&version.ok_or(ImpellerRequirementsError::UnknownVersion)?)
+    }
+
+    /// Checks that the OpenGL context supports the GL operations used for
+    /// rendering (see [`check_gl_requirements`]), returning its version.
+    pub fn check_gl_requirements(&mut self) -> Result<GlVersion, GlRequirementsError> {
+        if !self.make_current() {
+            return Err(GlRequirementsError::MakeCurrentFailed);
+        }
+
+        let gl = self.load_gl();
+        let version = gl_string(&gl, gl::VERSION);
+        let extensions = gl_string(&gl, gl::EXTENSIONS).unwrap_or_default();
+
+        self.make_not_current();
+
+        let version = version.ok_or(GlRequirementsError::UnknownVersion)?;
+        debug!("OpenGL context version: {}", version);
+        check_gl_requirements(&version, &extensions)
+    }
+
+    fn load_gl(&self) -> gl::Gl {
+        gl::Gl::load_with(|symbol| {
+            let proc = CString::new(symbol).unwrap();
+            self.get_proc_address(proc.as_c_str())
+        })
+    }
+}
+
+fn gl_string(gl: &gl::Gl, name: gl::types::GLenum) -> Option<String> {
+    unsafe {
+        let string = gl.GetString(name);
+        (!string.is_null()).then(|| CStr::from_ptr(string.cast()).to_string_lossy().into_owned())
+    }
+}
+
+/// The version of an OpenGL (ES) context, as reported by `glGetString`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlVersion {
+    pub es: bool,
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl GlVersion {
+    /// Parses a `GL_VERSION` string, which has the following format:
+    /// `OpenGL ES <major>.<minor> <vendor-specific information>` for OpenGL ES
+    /// contexts and `<major>.<minor>[.<release>] <vendor-specific information>`
+    /// for OpenGL contexts.
+    pub fn parse(version: &str) -> Option<Self> {
+        let (es, number) = match version.strip_prefix("OpenGL ES") {
+            // Skips the profile of OpenGL ES 1.x versions (e.g.: `OpenGL ES-CM 1.1`).
+            Some(version) => (
+                true,
+                version
+                    .split_whitespace()
+                    .find(|token| token.starts_with(|c: char| c.is_ascii_digit()))?,
+            ),
+            None => (false, version.split_whitespace().next()?),
+        };
+
+        let mut number = number.split('.');
+        let major = number.next()?.parse().ok()?;
+        let minor = number.next()?.parse().ok()?;
+
+        Some(Self { es, major, minor })
+    }
+}
+
+impl fmt::Display for GlVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let api = if self.es { "OpenGL ES" } else { "OpenGL" };
+        write!(f, "{} {}.{}", api, self.major, self.minor)
+    }
+}
+
+/// Extensions required by OpenGL ES 2.0 contexts, for the features which are
+/// core in OpenGL (ES) 3.0: framebuffer blits (for presenting backing stores)
+/// and `GL_RGBA8` textures (for backing stores). Each entry lists equivalent
+/// extensions, any of which is sufficient.
+const GLES2_REQUIRED_EXTENSIONS: [&[&str]; 2] = [
+    &[
+        "GL_EXT_framebuffer_blit",
+        "GL_NV_framebuffer_blit",
+        "GL_ANGLE_framebuffer_blit",
+    ],
+    &["GL_OES_rgb8_rgba8"],
+];
+
+/// Rendering requires an OpenGL 3.0 or OpenGL ES 3.0 (or newer) context, or an
+/// OpenGL ES 2.0 context with the extensions providing the same features.
+/// `extensions` is the (space separated) `GL_EXTENSIONS` string.
+pub fn check_gl_requirements(
+    version: &str,
+    extensions: &str,
+) -> Result<GlVersion, GlRequirementsError> {
+    let gl_version = GlVersion::parse(version)
+        .ok_or_else(|| GlRequirementsError::UnsupportedVersion(version.to_owned()))?;
+
+    if gl_version.major >= 3 {
+        return Ok(gl_version);
+    }
+
+    if !gl_version.es || gl_version.major < 2 {
+        return Err(GlRequirementsError::UnsupportedVersion(version.to_owned()));
+    }
+
+    let extensions: HashSet<&str> = extensions.split_whitespace().collect();
+    let missing: Vec<String> = GLES2_REQUIRED_EXTENSIONS
+        .iter()
+        .filter(|alternatives| !alternatives.iter().any(|ext| extensions.contains(ext)))
+        .map(|alternatives| alternatives.join(" or "))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(GlRequirementsError::MissingExtensions {
+            version: version.to_owned(),
+            missing,
+        });
+    }
+
+    Ok(gl_version)
+}
+
+#[derive(Error, Debug)]
+pub enum GlRequirementsError {
+    #[error("Unable to make context current")]
+    MakeCurrentFailed,
+
+    #[error("Unable to retrieve the OpenGL version")]
+    UnknownVersion,
+
+    #[error("OpenGL 3.0 or OpenGL ES 3.0 (or newer) is required (got: {0})")]
+    UnsupportedVersion(String),
+
+    #[error("The OpenGL context ({version}) lacks required extensions: {}", .missing.join(", "))]
+    MissingExtensions {
+        version: String,
+        missing: Vec<String>,
+    },
+}
+
+/// Impeller's OpenGL backend requires an OpenGL ES 3.0 (or newer) context.
+/// Version strings of OpenGL ES contexts have the following format:
+/// `OpenGL ES <major>.<minor> <vendor-specific information>`.
+pub fn check_impeller_gl_version(version: &str) -> Result<(), ImpellerRequirementsError> {
+    let unsupported = || ImpellerRequirementsError::UnsupportedVersion(version.to_owned());
+
+    let major = version
+        .strip_prefix("OpenGL ES ")
+        .and_then(|version| version.split('.').next())
+        .and_then(|major| major.parse::<u32>().ok())
+        .ok_or_else(unsupported)?;
+
+    if major < 3 {
+        return Err(unsupported());
+    }
+
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum ImpellerRequirementsError {
+    #[error("Unable to make context current")]
+    MakeCurrentFailed,
+
+    #[error("Unable to retrieve the OpenGL version")]
+    UnknownVersion,
+
+    #[error("OpenGL ES 3.0 or newer is required (got: {0})")]
+    UnsupportedVersion(String),
 }
 
 // `Context` is only `Send` as long as it's used correctly by the engine (e.g.:
@@ -83,18 +335,181 @@ impl Context {
 // TODO: Find a solution that better leverages Rust's type system
 unsafe impl Send for Context {}
 
+const SURFACELESS_CONTEXT_EXTENSION: &str = "EGL_KHR_surfaceless_context";
+
+/// How the resource context is made current.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceContextMode {
+    /// Made current without any surface (`EGL_KHR_surfaceless_context`), so
+    /// it never contends with the render context for the window surface.
+    Surfaceless,
+    /// Made current with a 1x1 pbuffer surface of its own, for displays
+    /// lacking surfaceless context support.
+    Pbuffer,
+    /// Never made current, as the pbuffer surface couldn't be created (e.g.:
+    /// the config doesn't support pbuffers). The engine then uploads textures
+    /// from the raster thread instead of the IO thread.
+    Unavailable,
+}
+
+impl ResourceContextMode {
+    pub(crate) fn for_display_extensions(extensions: &HashSet<&str>) -> Self {
+        if extensions.contains(SURFACELESS_CONTEXT_EXTENSION) {
+            Self::Surfaceless
+        } else {
+            Self::Pbuffer
+        }
+    }
+
+    /// The surface types the config of the contexts must support for the
+    /// resource context to be made current this way.
+    pub(crate) fn config_surface_types(self) -> ConfigSurfaceTypes {
+        match self {
+            Self::Pbuffer => ConfigSurfaceTypes::WINDOW | ConfigSurfaceTypes::PBUFFER,
+            Self::Surfaceless | Self::Unavailable => ConfigSurfaceTypes::WINDOW,
+        }
+    }
+
+    /// Selects the mode for a display with `extensions`, creating the pbuffer
+    /// surface with `create_pbuffer` when the display requires one.
+    fn select<S, E: fmt::Display>(
+        extensions: &HashSet<&str>,
+        create_pbuffer: impl FnOnce() -> Result<S, E>,
+    ) -> (Self, Option<S>) {
+        match Self::for_display_extensions(extensions) {
+            Self::Pbuffer => match create_pbuffer() {
+                Ok(pbuffer) => (Self::Pbuffer, Some(pbuffer)),
+                Err(err) => {
+                    warn!(
+                        "Unable to create a pbuffer surface for the resource context, textures will be uploaded from the raster thread: {}",
+                        err
+                    );
+                    (Self::Unavailable, None)
+                }
+            },
+            mode => (mode, None),
+        }
+    }
+}
+
+/// The context used by the engine for uploading textures (e.g.: decoded
+/// images) from the IO thread.
 pub struct ResourceContext {
     context: egl::context::PossiblyCurrentContext,
+    mode: ResourceContextMode,
+    pbuffer: Option<egl::surface::Surface<PbufferSurface>>,
 }
 
 impl ResourceContext {
+    /// Creates a resource context, which is surfaceless when supported by the
+    /// display and uses a pbuffer surface otherwise (see
+    /// [`ResourceContextMode`]).
     pub fn new(context: egl::context::PossiblyCurrentContext) -> Self {
-        Self { context }
+        let display = context.display();
+        let (mode, pbuffer) = ResourceContextMode::select(display.extensions(), || {
+            let attributes = SurfaceAttributesBuilder::<PbufferSurface>::new()
+                .build(NonZeroU32::MIN, NonZeroU32::MIN);
+            unsafe { display.create_pbuffer_surface(&context.config(), &attributes) }
+        });
+
+        Self {
+            context,
+            mode,
+            pbuffer,
+        }
+    }
+
+    pub fn mode(&self) -> ResourceContextMode {
+        self.mode
     }
 
     pub fn make_current(&mut self) -> bool {
-        self.context.make_current_surfaceless().is_ok()
+        match self.mode {
+            ResourceContextMode::Surfaceless => self.context.make_current_surfaceless().is_ok(),
+            ResourceContextMode::Pbuffer => self
+                .pbuffer
+                .as_ref()
+                .is_some_and(|pbuffer| self.context.make_current(pbuffer).is_ok()),
+            ResourceContextMode::Unavailable => false,
+        }
     }
 }
 
 unsafe impl Send for ResourceContext {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impeller_gl_version_requirements() {
+        assert!(check_impeller_gl_version("OpenGL ES 3.2 Mesa 24.0.5").is_ok());
+        assert!(check_impeller_gl_version("OpenGL ES 2.0 Mesa 24.0.5").is_err());
+        assert!(check_impeller_gl_version("4.6 (Core Profile) Mesa 24.0.5").is_err());
+    }
+
+    #[test]
+    fn gl_requirements_reject_too_low_versions() {
+        assert!(matches!(
+            check_gl_requirements("2.1 Mesa 24.0.5", ""),
+            Err(GlRequirementsError::UnsupportedVersion(version)) if version == "2.1 Mesa 24.0.5"
+        ));
+        assert!(matches!(
+            check_gl_requirements("OpenGL ES 2.0 Mesa 24.0.5", "GL_EXT_framebuffer_blit"),
+            Err(GlRequirementsError::MissingExtensions { missing, .. })
+                if missing == vec!["GL_OES_rgb8_rgba8"]
+        ));
+
+        assert_eq!(
+            check_gl_requirements(
+                "OpenGL ES 2.0 Mesa 24.0.5",
+                "GL_OES_rgb8_rgba8 GL_NV_framebuffer_blit"
+            )
+            .unwrap()
+            .to_string(),
+            "OpenGL ES 2.0"
+        );
+        assert_eq!(
+            check_gl_requirements("OpenGL ES 3.2 Mesa 24.0.5", "").unwrap(),
+            GlVersion {
+                es: true,
+                major: 3,
+                minor: 2
+            }
+        );
+        assert!(check_gl_requirements("4.6 (Core Profile) Mesa 24.0.5", "").is_ok());
+    }
+
+    #[test]
+    fn resource_context_is_surfaceless_when_supported() {
+        let extensions = HashSet::from(["EGL_KHR_image_base", SURFACELESS_CONTEXT_EXTENSION]);
+        let mode = ResourceContextMode::for_display_extensions(&extensions);
+        assert_eq!(mode, ResourceContextMode::Surfaceless);
+        assert_eq!(mode.config_surface_types(), ConfigSurfaceTypes::WINDOW);
+        assert_eq!(
+            ResourceContextMode::select(&extensions, || -> Result<(), &str> {
+                panic!("no pbuffer is needed")
+            }),
+            (ResourceContextMode::Surfaceless, None)
+        );
+    }
+
+    #[test]
+    fn resource_context_falls_back_to_a_pbuffer() {
+        let extensions = HashSet::from(["EGL_KHR_image_base"]);
+        let mode = ResourceContextMode::for_display_extensions(&extensions);
+        assert_eq!(mode, ResourceContextMode::Pbuffer);
+        assert!(mode
+            .config_surface_types()
+            .contains(ConfigSurfaceTypes::WINDOW | ConfigSurfaceTypes::PBUFFER));
+
+        assert_eq!(
+            ResourceContextMode::select(&extensions, || Ok::<_, &str>("pbuffer")),
+            (ResourceContextMode::Pbuffer, Some("pbuffer"))
+        );
+        assert_eq!(
+            ResourceContextMode::select(&extensions, || Err::<(), _>("EGL_BAD_MATCH")),
+            (ResourceContextMode::Unavailable, None)
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! OpenGL debug output (`GL_KHR_debug`) and error checking helpers, routing
+//! GL errors and warnings into `tracing`.
+use std::ffi::{c_void, CStr};
+
+use tracing::{debug, error, warn};
+
+use crate::gl::{self, types::GLenum};
+
+/// Upper bound of errors read by [`check_gl_errors`]. `glGetError` may keep
+/// returning errors (e.g.: `GL_CONTEXT_LOST`), so the error queue can't be
+/// drained unconditionally.
+const MAX_REPORTED_ERRORS: usize = 16;
+
+/// Enables synchronous debug output for the current context, logging the
+/// messages reported by the driver. Returns `false` if `GL_KHR_debug` isn't
+/// supported.
+pub fn enable_debug_output(gl: &gl::Gl) -> bool {
+    if !gl.DebugMessageCallback.is_loaded() {
+        warn!("GL debug output is not supported by the current context");
+        return false;
+    }
+
+    unsafe {
+        gl.Enable(gl::DEBUG_OUTPUT);
+        gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl.DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+    }
+
+    true
+}
+
+/// Logs the errors reported by `glGetError` after `operation`. Returns the
+/// number of errors reported.
+pub fn check_gl_errors(gl: &gl::Gl, operation: &str) -> usize {
+    log_gl_errors(operation, || unsafe { gl.GetError() })
+}
+
+fn log_gl_errors(operation: &str, mut get_error: impl FnMut() -> GLenum) -> usize {
+    let mut count = 0;
+    while count < MAX_REPORTED_ERRORS {
+        let err = get_error();
+        if err == gl::NO_ERROR {
+            break;
+        }
+
+        error!(
+            "GL error after {}: {} (0x{:x})",
+            operation,
+            gl_error_name(err),
+            err
+        );
+        count += 1;
+    }
+    count
+}
+
+fn gl_error_name(err: GLenum) -> &'static str {
+    match err {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        gl::CONTEXT_LOST => "GL_CONTEXT_LOST",
+        _ => "unknown error",
+    }
+}
+
+extern "system" fn debug_message_callback(
+    _source: GLenum,
+    gltype: GLenum,
+    id: u32,
+    severity: GLenum,
+    _length: i32,
+    message: *const std::ffi::c_char,
+    _user_param: *mut c_void,
+) {
+    if message.is_null() {
+        return;
+    }
+
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    match (gltype, severity) {
+        (gl::DEBUG_TYPE_ERROR, _) | (_, gl::DEBUG_SEVERITY_HIGH) => {
+            error!("GL debug message {}: {}", id, message)
+        }
+        (_, gl::DEBUG_SEVERITY_MEDIUM | gl::DEBUG_SEVERITY_LOW) => {
+            warn!("GL debug message {}: {}", id, message)
+        }
+        _ => debug!("GL debug message {}: {}", id, message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::CString,
+        fmt,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Level, Metadata, Subscriber,
+    };
+
+    use super::*;
+
+    /// The level, target and message of an event.
+    type RecordedEvent = (Level, &'static str, String);
+
+    /// Records every event.
+    #[derive(Clone, Default)]
+    struct EventRecorder {
+        events: Arc<Mutex<Vec<RecordedEvent>>>,
+    }
+
+    struct MessageRecorder(String);
+
+    impl Visit for MessageRecorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl Subscriber for EventRecorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = MessageRecorder(String::new());
+            event.record(&mut message);
+
+            let metadata = event.metadata();
+            self.events
+                .lock()
+                .unwrap()
+                .push((*metadata.level(), metadata.target(), message.0));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    fn recorded_events(f: impl FnOnce()) -> Vec<RecordedEvent> {
+        let recorder = EventRecorder::default();
+        tracing::subscriber::with_default(recorder.clone(), f);
+        let events = recorder.events.lock().unwrap();
+        events.clone()
+    }
+
+    #[test]
+    fn forced_gl_errors_are_reported() {
+        let mut errors = vec![gl::NO_ERROR, gl::OUT_OF_MEMORY, gl::INVALID_OPERATION];
+        let events = recorded_events(|| {
+            assert_eq!(log_gl_errors("blit", || errors.pop().unwrap()), 2);
+        });
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            events,
+            vec![
+                (
+                    Level::ERROR,
+                    module_path!().trim_end_matches("::tests"),
+                    "GL error after blit: GL_INVALID_OPERATION (0x502)".to_string()
+                ),
+                (
+                    Level::ERROR,
+                    module_path!().trim_end_matches("::tests"),
+                    "GL error after blit: GL_OUT_OF_MEMORY (0x505)".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn gl_debug_messages_are_logged_by_severity() {
+        let message = CString::new("framebuffer incomplete").unwrap();
+        let events = recorded_events(|| {
+            for (gltype, severity) in [
+                (gl::DEBUG_TYPE_ERROR, gl::DEBUG_SEVERITY_LOW),
+                (gl::DEBUG_TYPE_PERFORMANCE, gl::DEBUG_SEVERITY_MEDIUM),
+                (gl::DEBUG_TYPE_OTHER, gl::DEBUG_SEVERITY_NOTIFICATION),
+            ] {
+                let user_param = std::ptr::null_mut();
+                debug_message_callback(0, gltype, 7, severity, 0, message.as_ptr(), user_param);
+            }
+        });
+
+        let levels: Vec<_> = events.iter().map(|(level, _, _)| *level).collect();
+        assert_eq!(levels, [Level::ERROR, Level::WARN, Level::DEBUG]);
+        assert_eq!(events[0].2, "GL debug message 7: framebuffer incomplete");
+    }
+
+    #[test]
+    fn gl_error_reporting_is_bounded() {
+        assert_eq!(
+            log_gl_errors("present", || gl::CONTEXT_LOST),
+            MAX_REPORTED_ERRORS
+        );
+    }
+}
@@ -6,11 +6,15 @@ use std::{
 use dpi::PhysicalSize;
 use flust_engine_api::FlutterOpenGLHandler;
 
-use crate::context::{Context, ResourceContext};
+use crate::{
+    context::{Context, ResourceContext},
+    proc_cache::GlProcCache,
+};
 
 pub struct GlutinOpenGLHandler {
     context: Arc<Mutex<Context>>,
     resource_context: Arc<Mutex<ResourceContext>>,
+    proc_cache: GlProcCache,
 }
 
 impl GlutinOpenGLHandler {
@@ -21,6 +25,7 @@ impl GlutinOpenGLHandler {
         Self {
             context,
             resource_context,
+            proc_cache: GlProcCache::new(),
         }
     }
 }
@@ -42,11 +47,20 @@ impl FlutterOpenGLHandler for GlutinOpenGLHandler {
         0
     }
 
+    fn framebuffer_format(&self) -> u32 {
+        Context::get_supported_format()
+    }
+
+    fn buffer_age(&self) -> u32 {
+        self.context.lock().unwrap().buffer_age()
+    }
+
     fn make_resource_current(&self) -> bool {
         self.resource_context.lock().unwrap().make_current()
     }
 
     fn gl_proc_resolver(&self, proc: &CStr) -> *mut c_void {
-        self.context.lock().unwrap().get_proc_address(proc) as _
+        self.proc_cache
+            .resolve(proc, || self.context.lock().unwrap().get_proc_address(proc)) as _
     }
 }
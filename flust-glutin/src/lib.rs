@@ -1,6 +1,8 @@
 pub mod builder;
 pub mod context;
+pub mod debug;
 pub mod handler;
+pub mod proc_cache;
 
 pub mod gl {
     #![allow(clippy::all)]
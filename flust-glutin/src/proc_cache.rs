@@ -0,0 +1,65 @@
+//! Caching of GL procedure addresses resolved for the engine.
+use std::{
+    collections::HashMap,
+    ffi::{c_void, CStr, CString},
+    sync::RwLock,
+};
+
+/// Cache of the GL procedure addresses resolved by `gl_proc_resolver`, which
+/// the engine calls for every GL function it uses (from several threads).
+///
+/// Addresses don't change for the lifetime of a display, so each procedure is
+/// only looked up once. Failed lookups (null addresses) aren't cached.
+#[derive(Debug, Default)]
+pub struct GlProcCache {
+    procs: RwLock<HashMap<CString, usize>>,
+}
+
+impl GlProcCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the cached address of `proc`, calling `lookup` to resolve it
+    /// if it isn't cached yet.
+    pub fn resolve(&self, proc: &CStr, lookup: impl FnOnce() -> *const c_void) -> *const c_void {
+        if let Some(&address) = self.procs.read().unwrap().get(proc) {
+            return address as *const c_void;
+        }
+
+        let address = lookup();
+        if !address.is_null() {
+            self.procs
+                .write()
+                .unwrap()
+                .insert(proc.to_owned(), address as usize);
+        }
+
+        address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_lookup_returns_same_pointer_without_requerying() {
+        let cache = GlProcCache::new();
+        let mut lookups = 0;
+        let address = 0x1234 as *const c_void;
+
+        let first = cache.resolve(c"glClear", || {
+            lookups += 1;
+            address
+        });
+        let second = cache.resolve(c"glClear", || {
+            lookups += 1;
+            std::ptr::null()
+        });
+
+        assert_eq!(first, address);
+        assert_eq!(second, address);
+        assert_eq!(lookups, 1);
+    }
+}
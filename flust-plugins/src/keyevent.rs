@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use flust_engine::{
     channel::{MessageChannel, MessageHandler},
     codec::JSON_CODEC,
+    ffi::FlutterKeyEvent,
     plugins::Plugin,
     FlutterEngine,
 };
@@ -78,6 +79,51 @@ impl KeyEventPlugin {
             channel.send(action);
         });
     }
+
+    /// Sends a key event through both keyboard channels, in the order the
+    /// framework requires: first the `flutter/keydata` event (`event`, sent
+    /// with [`FlutterEngine::send_key_event`]), then the legacy
+    /// `flutter/keyevent` message (`action`). The framework uses the latter as
+    /// a "flush" for the `flutter/keydata` events received before it, so
+    /// sending them in any other order makes it mishandle the key.
+    ///
+    /// The `flutter/keyevent` messages are considered legacy but they are
+    /// still required for now [0][1].
+    ///
+    /// [0](https://github.com/flutter/flutter/pull/132533)
+    /// [1](https://github.com/flutter/flutter/issues/136419)
+    pub fn send_key_event(
+        &self,
+        engine: &FlutterEngine,
+        event: FlutterKeyEvent,
+        action: KeyAction,
+    ) {
+        send_in_order(&EngineKeySender(engine, self), event, action);
+    }
+}
+
+/// The two halves of sending a key event, see [`KeyEventPlugin::send_key_event`].
+trait KeySender {
+    fn send_key_data(&self, event: FlutterKeyEvent);
+
+    fn send_key_action(&self, action: KeyAction);
+}
+
+struct EngineKeySender<'a>(&'a FlutterEngine, &'a KeyEventPlugin);
+
+impl KeySender for EngineKeySender<'_> {
+    fn send_key_data(&self, event: FlutterKeyEvent) {
+        self.0.send_key_event(event, None);
+    }
+
+    fn send_key_action(&self, action: KeyAction) {
+        self.1.key_action(action);
+    }
+}
+
+fn send_in_order(sender: &impl KeySender, event: FlutterKeyEvent, action: KeyAction) {
+    sender.send_key_data(event);
+    sender.send_key_action(action);
 }
 
 impl MessageHandler for Handler {
@@ -85,3 +131,53 @@ impl MessageHandler for Handler {
         msg.respond(Value::Null)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, time::Duration};
+
+    use flust_engine::ffi::FlutterKeyEventDeviceType;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingKeySender {
+        sent: RefCell<Vec<&'static str>>,
+    }
+
+    impl KeySender for RecordingKeySender {
+        fn send_key_data(&self, _event: FlutterKeyEvent) {
+            self.sent.borrow_mut().push("flutter/keydata");
+        }
+
+        fn send_key_action(&self, _action: KeyAction) {
+            self.sent.borrow_mut().push("flutter/keyevent");
+        }
+    }
+
+    #[test]
+    fn key_data_is_sent_before_key_event_flush() {
+        let sender = RecordingKeySender::default();
+        let action = KeyAction {
+            toolkit: "gtk".into(),
+            key_code: 65,
+            scan_code: 38,
+            modifiers: 0,
+            specified_logical_key: 0,
+            unicode_scalar_values: 97,
+            keymap: "linux".into(),
+            _type: KeyActionType::Keydown,
+        };
+
+        send_in_order(
+            &sender,
+            FlutterKeyEvent::empty(Duration::ZERO, FlutterKeyEventDeviceType::Keyboard),
+            action,
+        );
+
+        assert_eq!(
+            *sender.sent.borrow(),
+            vec!["flutter/keydata", "flutter/keyevent"]
+        );
+    }
+}
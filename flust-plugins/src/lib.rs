@@ -6,7 +6,9 @@ pub mod localization;
 pub mod mousecursor;
 pub mod navigation;
 pub mod platform;
+pub mod platform_views;
 pub mod settings;
+pub mod spellcheck;
 pub mod system;
 pub mod textinput;
 pub mod window;
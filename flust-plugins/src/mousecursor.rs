@@ -1,6 +1,7 @@
 //! A plugin to handle mouse cursor.
 //! It handles flutter/mousecursor type message.
 use std::{
+    collections::HashMap,
     str::FromStr,
     sync::{Arc, Weak},
 };
@@ -145,8 +146,110 @@ impl std::fmt::Display for MouseCursorError {
 
 impl std::error::Error for MouseCursorError {}
 
+/// A bitmap cursor created by the app (`createCustomCursor`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomCursor {
+    /// Non-premultiplied RGBA pixels, row by row.
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// The pixel of the image pointing at the pointer position, relative to
+    /// the top-left corner of the image.
+    pub hotspot: (u32, u32),
+}
+
+impl CustomCursor {
+    pub fn new(
+        rgba: Vec<u8>,
+        width: u32,
+        height: u32,
+        hotspot: (u32, u32),
+    ) -> Result<Self, CustomCursorError> {
+        let expected = width as usize * height as usize * 4;
+        if width == 0 || height == 0 || rgba.len() != expected {
+            return Err(CustomCursorError::InvalidBuffer {
+                width,
+                height,
+                len: rgba.len(),
+            });
+        }
+
+        if hotspot.0 >= width || hotspot.1 >= height {
+            return Err(CustomCursorError::HotspotOutOfBounds {
+                hotspot,
+                width,
+                height,
+            });
+        }
+
+        Ok(Self {
+            rgba,
+            width,
+            height,
+            hotspot,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CustomCursorError {
+    InvalidArguments,
+    InvalidBuffer {
+        width: u32,
+        height: u32,
+        len: usize,
+    },
+    HotspotOutOfBounds {
+        hotspot: (u32, u32),
+        width: u32,
+        height: u32,
+    },
+}
+
+impl std::fmt::Display for CustomCursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CustomCursorError::InvalidArguments => write!(f, "Invalid custom cursor arguments"),
+            CustomCursorError::InvalidBuffer { width, height, len } => write!(
+                f,
+                "Invalid {}x{} custom cursor buffer of {} bytes",
+                width, height, len
+            ),
+            CustomCursorError::HotspotOutOfBounds {
+                hotspot,
+                width,
+                height,
+            } => write!(
+                f,
+                "Custom cursor hotspot {:?} is outside of the {}x{} image",
+                hotspot, width, height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CustomCursorError {}
+
 pub trait MouseCursorHandler {
     fn activate_system_cursor(&mut self, kind: SystemMouseCursor) -> Result<(), MouseCursorError>;
+
+    /// Registers a bitmap cursor under `name`, replacing any cursor previously
+    /// registered with the same name. Platforms without custom cursor support
+    /// can rely on the default implementation, which rejects the cursor.
+    fn create_custom_cursor(
+        &mut self,
+        _name: String,
+        _cursor: CustomCursor,
+    ) -> Result<(), MouseCursorError> {
+        Err(MouseCursorError)
+    }
+
+    /// Activates the custom cursor registered under `name`.
+    fn set_custom_cursor(&mut self, _name: &str) -> Result<(), MouseCursorError> {
+        Err(MouseCursorError)
+    }
+
+    fn delete_custom_cursor(&mut self, _name: &str) {}
 }
 
 pub struct MouseCursorPlugin {
@@ -209,7 +312,116 @@ impl MethodCallHandler for Handler {
                     Err(_) => call.error("unknown-data", "Unknown data type", Value::Null),
                 };
             }
+            "createCustomCursor" => {
+                let (name, cursor) = match parse_custom_cursor(call.raw_args()) {
+                    Ok(parsed) => parsed,
+                    Err(err) => return call.error("invalid-cursor", err.to_string(), Value::Null),
+                };
+
+                match self
+                    .handler
+                    .lock()
+                    .create_custom_cursor(name.clone(), cursor)
+                {
+                    Ok(_) => call.success(Value::String(name)),
+                    Err(err) => call.error("unsupported", err.to_string(), Value::Null),
+                };
+            }
+            "setCustomCursor" => {
+                let Some(name) = cursor_name(call.raw_args()) else {
+                    return call.error("unknown-data", "Unknown data type", Value::Null);
+                };
+
+                match self.handler.lock().set_custom_cursor(&name) {
+                    Ok(_) => call.success_empty(),
+                    Err(err) => call.error("unknown-cursor", err.to_string(), Value::Null),
+                };
+            }
+            "deleteCustomCursor" => {
+                let Some(name) = cursor_name(call.raw_args()) else {
+                    return call.error("unknown-data", "Unknown data type", Value::Null);
+                };
+
+                self.handler.lock().delete_custom_cursor(&name);
+                call.success_empty();
+            }
             _ => call.not_implemented(),
         }
     }
 }
+
+fn cursor_name(args: &Value) -> Option<String> {
+    let Value::Map(args) = args else {
+        return None;
+    };
+
+    match args.get("name") {
+        Some(Value::String(name)) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Parses the arguments of `createCustomCursor`, which follow the Windows
+/// embedder: `name`, `buffer` (RGBA pixels), `width`, `height`, `hotX` and
+/// `hotY`.
+fn parse_custom_cursor(args: &Value) -> Result<(String, CustomCursor), CustomCursorError> {
+    let Value::Map(map) = args else {
+        return Err(CustomCursorError::InvalidArguments);
+    };
+
+    let name = cursor_name(args).ok_or(CustomCursorError::InvalidArguments)?;
+    let Some(Value::U8List(rgba)) = map.get("buffer") else {
+        return Err(CustomCursorError::InvalidArguments);
+    };
+
+    let dimension = |key| dimension(map, key).ok_or(CustomCursorError::InvalidArguments);
+    let cursor = CustomCursor::new(
+        rgba.clone(),
+        dimension("width")?,
+        dimension("height")?,
+        (dimension("hotX")?, dimension("hotY")?),
+    )?;
+
+    Ok((name, cursor))
+}
+
+/// Reads a non-negative integer, which may be sent either as an int or as a
+/// double (e.g.: the hotspot of the framework's `Offset`).
+fn dimension(map: &HashMap<String, Value>, key: &str) -> Option<u32> {
+    match map.get(key)? {
+        Value::F64(value) if value.is_finite() && *value >= 0.0 => Some(*value as u32),
+        value => value.as_i64().and_then(|value| u32::try_from(value).ok()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_args(hot_x: f64, hot_y: f64) -> Value {
+        Value::Map(HashMap::from([
+            ("name".to_owned(), Value::String("crosshair".into())),
+            ("buffer".to_owned(), Value::U8List(vec![0xff; 4 * 4 * 4])),
+            ("width".to_owned(), Value::I32(4)),
+            ("height".to_owned(), Value::I32(4)),
+            ("hotX".to_owned(), Value::F64(hot_x)),
+            ("hotY".to_owned(), Value::F64(hot_y)),
+        ]))
+    }
+
+    #[test]
+    fn custom_cursor_carries_hotspot() {
+        let (name, cursor) = parse_custom_cursor(&create_args(2.0, 3.0)).unwrap();
+        assert_eq!(name, "crosshair");
+        assert_eq!(cursor.hotspot, (2, 3));
+
+        assert_eq!(
+            parse_custom_cursor(&create_args(4.0, 0.0)),
+            Err(CustomCursorError::HotspotOutOfBounds {
+                hotspot: (4, 0),
+                width: 4,
+                height: 4,
+            })
+        );
+    }
+}
@@ -12,13 +12,23 @@ use flust_engine::{
 use serde::{Deserialize, Serialize};
 
 use flust_engine::channel::MethodCall;
-use flust_engine::codec::Value;
+use flust_engine::codec::{value::from_value, Value};
 use tracing::debug;
 use parking_lot::Mutex;
 
 pub const PLUGIN_NAME: &str = module_path!();
 pub const CHANNEL_NAME: &str = "flutter/platform";
 
+pub const TEXT_PLAIN_MIME_TYPE: &str = "text/plain";
+pub const TEXT_HTML_MIME_TYPE: &str = "text/html";
+
+/// Data stored in the clipboard, in each of its representations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardContents {
+    pub text: String,
+    pub html: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct MimeError;
 
@@ -30,17 +40,82 @@ impl std::fmt::Display for MimeError {
 
 impl std::error::Error for MimeError {}
 
+/// Error of an asynchronous clipboard read (see
+/// [`PlatformHandler::read_clipboard_data`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardReadError {
+    /// The clipboard data isn't available in the requested format.
+    Unavailable,
+    /// The owner of the clipboard data (e.g.: the app it was copied from) went
+    /// away before sending it.
+    OwnerGone,
+}
+
+impl std::fmt::Display for ClipboardReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ClipboardReadError::Unavailable => write!(f, "Clipboard data is not available"),
+            ClipboardReadError::OwnerGone => {
+                write!(f, "The clipboard owner went away before sending its data")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClipboardReadError {}
+
+impl From<MimeError> for ClipboardReadError {
+    fn from(_: MimeError) -> Self {
+        ClipboardReadError::Unavailable
+    }
+}
+
+/// Callback receiving the result of an asynchronous clipboard read.
+pub type ClipboardReply = Box<dyn FnOnce(Result<String, ClipboardReadError>) + Send>;
+
 pub trait PlatformHandler {
     fn set_application_switcher_description(&mut self, description: AppSwitcherDescription);
 
     fn set_clipboard_data(&mut self, text: String);
 
+    /// Stores data having both a plain text and an HTML representation (when
+    /// `Clipboard.setData` is called with an `html` field). Platforms without
+    /// rich text support can rely on the default implementation, which only
+    /// stores the plain text.
+    fn set_rich_clipboard_data(&mut self, contents: ClipboardContents) {
+        self.set_clipboard_data(contents.text);
+    }
+
+    /// Returns the clipboard data for `mime`. Requesting `text/html` returns
+    /// the HTML representation when available, and plain text otherwise.
     fn get_clipboard_data(&mut self, mime: &str) -> Result<String, MimeError>;
+
+    /// Reads the clipboard data for `mime` like [`Self::get_clipboard_data`],
+    /// passing it to `reply`. Platforms where the data may come from another
+    /// process should override this to reply once it was received, so that a
+    /// slow clipboard owner doesn't block the platform thread. The default
+    /// implementation replies synchronously.
+    fn read_clipboard_data(&mut self, mime: &str, reply: ClipboardReply) {
+        reply(self.get_clipboard_data(mime).map_err(Into::into));
+    }
+
+    /// Plays a system sound. Platforms without sound support can rely on the
+    /// default no-op implementation.
+    fn play_system_sound(&mut self, _sound: SystemSoundType) {}
+
+    /// Provides haptic feedback. Platforms without haptic support can rely on
+    /// the default no-op implementation.
+    fn vibrate(&mut self, _feedback: HapticFeedbackType) {}
+
+    /// Called when the app requests to be dismissed (`SystemNavigator.pop`),
+    /// e.g.: to close the window. The default implementation does nothing.
+    fn pop_system_navigator(&mut self) {}
 }
 
 pub struct PlatformPlugin {
     channel: Weak<MethodChannel>,
     handler: Arc<Mutex<dyn PlatformHandler + Send>>,
+    app_switcher_description: Arc<Mutex<Option<AppSwitcherDescription>>>,
 }
 
 impl PlatformPlugin {
@@ -48,8 +123,15 @@ impl PlatformPlugin {
         Self {
             channel: Weak::new(),
             handler,
+            app_switcher_description: Default::default(),
         }
     }
+
+    /// The last description set by the app (e.g.: through the `Title`
+    /// widget), for embedders drawing their own title bar.
+    pub fn application_switcher_description(&self) -> Option<AppSwitcherDescription> {
+        self.app_switcher_description.lock().clone()
+    }
 }
 
 impl Plugin for PlatformPlugin {
@@ -62,14 +144,30 @@ impl Plugin for PlatformPlugin {
             CHANNEL_NAME,
             Handler {
                 handler: self.handler.clone(),
+                app_switcher_description: self.app_switcher_description.clone(),
             },
             &JSON_CODEC,
         ));
     }
 }
 
+/// Methods without a desktop equivalent (system UI overlays, orientation
+/// locking, mobile-only features), acknowledged without doing anything so that
+/// awaiting them in Dart completes.
+const NO_OP_METHODS: &[&str] = &[
+    "SystemChrome.setPreferredOrientations",
+    "SystemChrome.setEnabledSystemUIMode",
+    "SystemChrome.setEnabledSystemUIOverlays",
+    "SystemChrome.setSystemUIOverlayStyle",
+    "SystemChrome.restoreSystemUIOverlays",
+    "SystemChrome.setSystemUIChangeListener",
+    "SystemNavigator.setFrameworkHandlesBack",
+    "LiveText.startLiveTextInput",
+];
+
 struct Handler {
     handler: Arc<Mutex<dyn PlatformHandler + Send>>,
+    app_switcher_description: Arc<Mutex<Option<AppSwitcherDescription>>>,
 }
 
 impl MethodCallHandler for Handler {
@@ -81,17 +179,35 @@ impl MethodCallHandler for Handler {
         );
         match call.method().as_str() {
             "SystemChrome.setApplicationSwitcherDescription" => {
-                let args: AppSwitcherDescription = call.args();
-                self.handler
-                    .lock()
-                    .set_application_switcher_description(args);
-                call.success_empty()
+                match from_value::<AppSwitcherDescription>(call.raw_args()) {
+                    Ok(description) => {
+                        *self.app_switcher_description.lock() = Some(description.clone());
+                        self.handler
+                            .lock()
+                            .set_application_switcher_description(description);
+                        call.success_empty()
+                    }
+                    Err(_) => call.error(
+                        "unknown-description",
+                        "Invalid application switcher description",
+                        Value::Null,
+                    ),
+                }
             }
             "Clipboard.setData" => {
                 if let Value::Map(v) = &call.args() {
                     if let Some(Value::String(text)) = &v.get("text") {
                         let text = text.clone();
-                        self.handler.lock().set_clipboard_data(text);
+                        match v.get("html") {
+                            Some(Value::String(html)) => self
+                                .handler
+                                .lock()
+                                .set_rich_clipboard_data(ClipboardContents {
+                                    text,
+                                    html: Some(html.clone()),
+                                }),
+                            _ => self.handler.lock().set_clipboard_data(text),
+                        }
                         return call.success_empty();
                     }
                 }
@@ -99,14 +215,61 @@ impl MethodCallHandler for Handler {
             }
             "Clipboard.getData" => {
                 if let Value::String(mime) = call.raw_args() {
-                    match self.handler.lock().get_clipboard_data(mime) {
-                        Ok(text) => call.success(ClipboardData { text }),
-                        Err(_) => call.error("unknown-data", "Unknown data type", Value::Null),
-                    }
+                    let mime = mime.clone();
+                    self.handler.lock().read_clipboard_data(
+                        &mime,
+                        Box::new(move |result| match result {
+                            Ok(text) => call.success(ClipboardData { text }),
+                            Err(err @ ClipboardReadError::OwnerGone) => {
+                                call.error("clipboard-owner-gone", err.to_string(), Value::Null)
+                            }
+                            Err(ClipboardReadError::Unavailable) => {
+                                call.error("unknown-data", "Unknown data type", Value::Null)
+                            }
+                        }),
+                    );
                 } else {
                     call.error("unknown-data", "Unknown data type", Value::Null)
                 }
             }
+            "Clipboard.hasStrings" => {
+                self.handler.lock().read_clipboard_data(
+                    TEXT_PLAIN_MIME_TYPE,
+                    Box::new(move |result| {
+                        let has_strings = result.is_ok_and(|text| !text.is_empty());
+                        call.success(ClipboardStatus { value: has_strings })
+                    }),
+                );
+            }
+            "SystemSound.play" => match from_value::<SystemSoundType>(call.raw_args()) {
+                Ok(sound) => {
+                    self.handler.lock().play_system_sound(sound);
+                    call.success_empty()
+                }
+                Err(_) => call.error("unknown-sound", "Unknown system sound type", Value::Null),
+            },
+            "HapticFeedback.vibrate" => {
+                match from_value::<Option<HapticFeedbackType>>(call.raw_args()) {
+                    Ok(feedback) => {
+                        self.handler.lock().vibrate(feedback.unwrap_or_default());
+                        call.success_empty()
+                    }
+                    Err(_) => call.error(
+                        "unknown-feedback",
+                        "Unknown haptic feedback type",
+                        Value::Null,
+                    ),
+                }
+            }
+            "SystemNavigator.pop" => {
+                self.handler.lock().pop_system_navigator();
+                call.success_empty()
+            }
+            // Live Text (text recognition from the camera) is only available
+            // on iOS.
+            "LiveText.isLiveTextInputAvailable" => call.success(false),
+            method if NO_OP_METHODS.contains(&method) => call.success_empty(),
+            // Replying (even without a handler) completes the Dart future.
             _ => call.not_implemented(),
         }
     }
@@ -118,8 +281,260 @@ struct ClipboardData {
 }
 
 #[derive(Serialize, Deserialize)]
+struct ClipboardStatus {
+    value: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemSoundType {
+    #[serde(rename = "SystemSoundType.click")]
+    Click,
+    #[serde(rename = "SystemSoundType.alert")]
+    Alert,
+    #[serde(rename = "SystemSoundType.tick")]
+    Tick,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HapticFeedbackType {
+    /// Generic vibration (sent by the framework with `null` arguments).
+    #[default]
+    #[serde(rename = "HapticFeedbackType.vibrate")]
+    Vibrate,
+    #[serde(rename = "HapticFeedbackType.lightImpact")]
+    LightImpact,
+    #[serde(rename = "HapticFeedbackType.mediumImpact")]
+    MediumImpact,
+    #[serde(rename = "HapticFeedbackType.heavyImpact")]
+    HeavyImpact,
+    #[serde(rename = "HapticFeedbackType.selectionClick")]
+    SelectionClick,
+}
+
+/// How the app is represented in the task switcher
+/// (`SystemChrome.setApplicationSwitcherDescription`).
+///
+/// On Wayland, only the label is actionable: it's used as the `xdg_toplevel`
+/// title. `xdg-shell` has no way of hinting a color to server-side
+/// decorations, so the primary color is only useful to embedders drawing
+/// their own title bar (see [`PlatformPlugin::application_switcher_description`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppSwitcherDescription {
-    pub primary_color: i64,
+    /// The primary color of the app, as a `0xAARRGGBB` value, if any.
+    #[serde(default)]
+    pub primary_color: Option<u32>,
     pub label: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flust_engine::codec::{self, MethodCallResult};
+
+    #[test]
+    fn framework_platform_methods_are_answered() {
+        use serde_json::json;
+
+        let handler = Arc::new(Mutex::new(RemoteClipboardHandler {
+            owner_gone: true,
+            ..Default::default()
+        }));
+        let channel = MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler,
+                app_switcher_description: Default::default(),
+            },
+            &JSON_CODEC,
+        );
+        let framework_calls = [
+            (
+                "SystemChrome.setPreferredOrientations",
+                json!(["DeviceOrientation.portraitUp"]),
+            ),
+            (
+                "SystemChrome.setApplicationSwitcherDescription",
+                json!({ "label": "Counter", "primaryColor": 0xFF2196F3u32 }),
+            ),
+            (
+                "SystemChrome.setEnabledSystemUIMode",
+                json!("SystemUiMode.edgeToEdge"),
+            ),
+            ("SystemChrome.setEnabledSystemUIOverlays", json!([])),
+            ("SystemChrome.setSystemUIOverlayStyle", json!({})),
+            ("SystemChrome.restoreSystemUIOverlays", json!(null)),
+            ("SystemChrome.setSystemUIChangeListener", json!(null)),
+            ("SystemNavigator.pop", json!(null)),
+            ("SystemNavigator.setFrameworkHandlesBack", json!(true)),
+            ("Clipboard.setData", json!({ "text": "flust" })),
+            ("Clipboard.hasStrings", json!(TEXT_PLAIN_MIME_TYPE)),
+            ("SystemSound.play", json!("SystemSoundType.click")),
+            ("HapticFeedback.vibrate", json!(null)),
+            ("LiveText.isLiveTextInputAvailable", json!(null)),
+            ("LiveText.startLiveTextInput", json!(null)),
+        ];
+
+        for (method, args) in framework_calls {
+            let response = channel.dispatch_for_test(codec::MethodCall {
+                method: method.into(),
+                args: Value::try_from(args).unwrap(),
+            });
+            assert!(
+                matches!(response, Some(MethodCallResult::Ok(_))),
+                "{} is not answered: {:?}",
+                method,
+                response
+            );
+        }
+
+        // The clipboard owner went away, which is answered with an error.
+        let response = channel.dispatch_for_test(codec::MethodCall {
+            method: "Clipboard.getData".into(),
+            args: Value::String(TEXT_PLAIN_MIME_TYPE.into()),
+        });
+        assert!(matches!(response, Some(MethodCallResult::Err { .. })));
+    }
+
+    /// Handler whose clipboard is owned by another process, which either
+    /// went away or didn't send its data yet.
+    #[derive(Default)]
+    struct RemoteClipboardHandler {
+        owner_gone: bool,
+        pending_reads: Vec<ClipboardReply>,
+        app_switcher_description: Option<AppSwitcherDescription>,
+        sounds: Vec<SystemSoundType>,
+        feedbacks: Vec<HapticFeedbackType>,
+    }
+
+    impl PlatformHandler for RemoteClipboardHandler {
+        fn set_application_switcher_description(&mut self, description: AppSwitcherDescription) {
+            self.app_switcher_description = Some(description);
+        }
+
+        fn set_clipboard_data(&mut self, _: String) {}
+
+        fn get_clipboard_data(&mut self, _: &str) -> Result<String, MimeError> {
+            Err(MimeError)
+        }
+
+        fn read_clipboard_data(&mut self, _: &str, reply: ClipboardReply) {
+            if self.owner_gone {
+                reply(Err(ClipboardReadError::OwnerGone));
+            } else {
+                self.pending_reads.push(reply);
+            }
+        }
+
+        fn play_system_sound(&mut self, sound: SystemSoundType) {
+            self.sounds.push(sound);
+        }
+
+        fn vibrate(&mut self, feedback: HapticFeedbackType) {
+            self.feedbacks.push(feedback);
+        }
+    }
+
+    #[test]
+    fn clipboard_reads_are_answered_asynchronously() {
+        let handler = Arc::new(Mutex::new(RemoteClipboardHandler::default()));
+        let channel = MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: handler.clone(),
+                app_switcher_description: Default::default(),
+            },
+            &JSON_CODEC,
+        );
+        let get_data = || codec::MethodCall {
+            method: "Clipboard.getData".into(),
+            args: Value::String(TEXT_PLAIN_MIME_TYPE.into()),
+        };
+
+        // The call is answered once the data arrives, not while handling it.
+        assert_eq!(channel.dispatch_for_test(get_data()), None);
+        assert_eq!(handler.lock().pending_reads.len(), 1);
+
+        handler.lock().owner_gone = true;
+        assert_eq!(
+            channel.dispatch_for_test(get_data()),
+            Some(MethodCallResult::Err {
+                code: "clipboard-owner-gone".into(),
+                message: ClipboardReadError::OwnerGone.to_string(),
+                details: Value::Null,
+            })
+        );
+    }
+
+    #[test]
+    fn app_switcher_description_is_fully_parsed() {
+        let handler = Arc::new(Mutex::new(RemoteClipboardHandler::default()));
+        let app_switcher_description = Arc::new(Mutex::new(None));
+        let channel = MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: handler.clone(),
+                app_switcher_description: app_switcher_description.clone(),
+            },
+            &JSON_CODEC,
+        );
+        let args = serde_json::json!({ "label": "Counter", "primaryColor": 0xFF2196F3u32 });
+
+        channel.dispatch_for_test(codec::MethodCall {
+            method: "SystemChrome.setApplicationSwitcherDescription".into(),
+            args: Value::try_from(args).unwrap(),
+        });
+
+        let expected = AppSwitcherDescription {
+            primary_color: Some(0xFF2196F3),
+            label: "Counter".into(),
+        };
+        assert_eq!(handler.lock().app_switcher_description, Some(expected.clone()));
+        assert_eq!(*app_switcher_description.lock(), Some(expected));
+    }
+
+    #[test]
+    fn system_sounds_and_haptic_feedback_are_forwarded_to_the_handler() {
+        let handler = Arc::new(Mutex::new(RemoteClipboardHandler::default()));
+        let channel = MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: handler.clone(),
+                app_switcher_description: Default::default(),
+            },
+            &JSON_CODEC,
+        );
+        let call = |method: &str, args: Value| {
+            channel.dispatch_for_test(codec::MethodCall {
+                method: method.into(),
+                args,
+            })
+        };
+
+        let response = call(
+            "SystemSound.play",
+            Value::String("SystemSoundType.alert".into()),
+        );
+        assert!(matches!(response, Some(MethodCallResult::Ok(_))));
+        let response = call("HapticFeedback.vibrate", Value::Null);
+        assert!(matches!(response, Some(MethodCallResult::Ok(_))));
+        let response = call(
+            "HapticFeedback.vibrate",
+            Value::String("HapticFeedbackType.heavyImpact".into()),
+        );
+        assert!(matches!(response, Some(MethodCallResult::Ok(_))));
+
+        let response = call(
+            "HapticFeedback.vibrate",
+            Value::String("HapticFeedbackType.rumble".into()),
+        );
+        assert!(matches!(response, Some(MethodCallResult::Err { .. })));
+
+        let handler = handler.lock();
+        assert_eq!(handler.sounds, vec![SystemSoundType::Alert]);
+        assert_eq!(
+            handler.feedbacks,
+            vec![HapticFeedbackType::Vibrate, HapticFeedbackType::HeavyImpact]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Plugin managing the lifecycle of platform views (i.e.: native views
+//! embedded in the widget tree, such as web views or video players).
+//! It handles flutter/platform_views type message.
+use std::{
+    collections::HashSet,
+    sync::{Arc, Weak},
+};
+
+use parking_lot::Mutex;
+use serde::Deserialize;
+use tracing::debug;
+
+use flust_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{value::from_value, Value, STANDARD_CODEC},
+    plugins::Plugin,
+    FlutterEngine,
+};
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter/platform_views";
+
+/// The arguments of a platform view creation request.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlatformViewCreationArgs {
+    /// The identifier of the view, which is also the identifier of the
+    /// platform view layers it is composited in.
+    pub id: i64,
+    /// The type of the view, as registered by the app (e.g.:
+    /// `"plugins.flutter.io/webview"`).
+    pub view_type: String,
+    #[serde(default)]
+    pub direction: PlatformViewDirection,
+    /// The initial size of the view, in logical pixels (if known).
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+    /// The creation parameters of the view, encoded by the app's
+    /// `creationParamsCodec`.
+    #[serde(default)]
+    pub params: Option<Vec<u8>>,
+}
+
+/// The layout direction of a platform view, encoded as it is on Android.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(from = "i32")]
+pub enum PlatformViewDirection {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl From<i32> for PlatformViewDirection {
+    fn from(direction: i32) -> Self {
+        match direction {
+            1 => PlatformViewDirection::Rtl,
+            _ => PlatformViewDirection::Ltr,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PlatformViewError {
+    UnknownViewType(String),
+    AlreadyExists(i64),
+    UnknownView(i64),
+    Failed(String),
+}
+
+impl std::fmt::Display for PlatformViewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PlatformViewError::UnknownViewType(view_type) => {
+                write!(f, "Unknown platform view type: {}", view_type)
+            }
+            PlatformViewError::AlreadyExists(id) => {
+                write!(f, "Platform view {} already exists", id)
+            }
+            PlatformViewError::UnknownView(id) => write!(f, "Unknown platform view: {}", id),
+            PlatformViewError::Failed(reason) => write!(f, "Platform view error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PlatformViewError {}
+
+/// Creates the native views embedded by the app, and keeps them in sync with
+/// the framework. Calls are only forwarded for views created successfully,
+/// i.e. `dispose`, `resize` and `set_direction` are never called with an
+/// unknown view id.
+pub trait PlatformViewHandler {
+    fn create(&mut self, args: PlatformViewCreationArgs) -> Result<(), PlatformViewError>;
+
+    fn dispose(&mut self, id: i64) -> Result<(), PlatformViewError>;
+
+    /// Resizes the view to `width`x`height` logical pixels.
+    fn resize(&mut self, id: i64, width: f64, height: f64) -> Result<(), PlatformViewError>;
+
+    fn set_direction(
+        &mut self,
+        id: i64,
+        direction: PlatformViewDirection,
+    ) -> Result<(), PlatformViewError>;
+}
+
+/// When created without a [`PlatformViewHandler`], creating platform views
+/// fails.
+#[derive(Default)]
+pub struct PlatformViewsPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Option<Arc<Mutex<dyn PlatformViewHandler + Send>>>,
+}
+
+impl PlatformViewsPlugin {
+    pub fn new(handler: Arc<Mutex<dyn PlatformViewHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler: Some(handler),
+        }
+    }
+}
+
+impl Plugin for PlatformViewsPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, engine: &FlutterEngine) {
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+                views: HashSet::new(),
+            },
+            &STANDARD_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Option<Arc<Mutex<dyn PlatformViewHandler + Send>>>,
+    views: HashSet<i64>,
+}
+
+impl Handler {
+    fn handle(&mut self, method: &str, args: &Value) -> Result<Value, PlatformViewError> {
+        let Some(handler) = self.handler.clone() else {
+            return Err(PlatformViewError::Failed("No platform view handler".into()));
+        };
+        let mut handler = handler.lock();
+
+        match method {
+            "create" => {
+                let args: PlatformViewCreationArgs = from_value(args)
+                    .map_err(|_| PlatformViewError::Failed("Invalid creation arguments".into()))?;
+                let id = args.id;
+                if self.views.contains(&id) {
+                    return Err(PlatformViewError::AlreadyExists(id));
+                }
+
+                handler.create(args)?;
+                self.views.insert(id);
+                Ok(Value::Null)
+            }
+            "dispose" => {
+                let id = self.known_view(view_id(args))?;
+                self.views.remove(&id);
+                handler.dispose(id)?;
+                Ok(Value::Null)
+            }
+            "resize" => {
+                let args: ResizeArgs = from_value(args)
+                    .map_err(|_| PlatformViewError::Failed("Invalid resize arguments".into()))?;
+                let id = self.known_view(Some(args.id))?;
+                handler.resize(id, args.width, args.height)?;
+
+                // The framework expects the size the view was resized to.
+                let size = [
+                    ("width".to_owned(), Value::F64(args.width)),
+                    ("height".to_owned(), Value::F64(args.height)),
+                ];
+                Ok(Value::Map(size.into()))
+            }
+            "setDirection" => {
+                let args: DirectionArgs = from_value(args)
+                    .map_err(|_| PlatformViewError::Failed("Invalid direction arguments".into()))?;
+                let id = self.known_view(Some(args.id))?;
+                handler.set_direction(id, args.direction)?;
+                Ok(Value::Null)
+            }
+            method => Err(PlatformViewError::Failed(format!(
+                "Unsupported method: {}",
+                method
+            ))),
+        }
+    }
+
+    fn known_view(&self, id: Option<i64>) -> Result<i64, PlatformViewError> {
+        let id = id.ok_or_else(|| PlatformViewError::Failed("Missing view id".into()))?;
+        match self.views.contains(&id) {
+            true => Ok(id),
+            false => Err(PlatformViewError::UnknownView(id)),
+        }
+    }
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        debug!(
+            "got method call {} with args {:?}",
+            call.method(),
+            call.raw_args()
+        );
+        match call.method().as_str() {
+            method @ ("create" | "dispose" | "resize" | "setDirection") => {
+                match self.handle(method, call.raw_args()) {
+                    Ok(value) => call.success(value),
+                    Err(err) => call.error("error", err.to_string(), Value::Null),
+                }
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+/// The id of the view to dispose, which is sent as a map on Android, and as
+/// the bare id on iOS and macOS.
+fn view_id(args: &Value) -> Option<i64> {
+    match args {
+        Value::Map(map) => map.get("id").and_then(Value::as_i64),
+        args => args.as_i64(),
+    }
+}
+
+#[derive(Deserialize)]
+struct ResizeArgs {
+    id: i64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Deserialize)]
+struct DirectionArgs {
+    id: i64,
+    direction: PlatformViewDirection,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        calls: Vec<String>,
+    }
+
+    impl PlatformViewHandler for RecordingHandler {
+        fn create(&mut self, args: PlatformViewCreationArgs) -> Result<(), PlatformViewError> {
+            if args.view_type != "flust/video" {
+                return Err(PlatformViewError::UnknownViewType(args.view_type));
+            }
+            self.calls.push(format!("create {}", args.id));
+            Ok(())
+        }
+
+        fn dispose(&mut self, id: i64) -> Result<(), PlatformViewError> {
+            self.calls.push(format!("dispose {}", id));
+            Ok(())
+        }
+
+        fn resize(&mut self, id: i64, width: f64, height: f64) -> Result<(), PlatformViewError> {
+            self.calls
+                .push(format!("resize {} {}x{}", id, width, height));
+            Ok(())
+        }
+
+        fn set_direction(
+            &mut self,
+            id: i64,
+            direction: PlatformViewDirection,
+        ) -> Result<(), PlatformViewError> {
+            self.calls
+                .push(format!("set_direction {} {:?}", id, direction));
+            Ok(())
+        }
+    }
+
+    fn map(entries: &[(&str, Value)]) -> Value {
+        Value::Map(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn calls_are_only_forwarded_for_created_views() {
+        let recording = Arc::new(Mutex::new(RecordingHandler::default()));
+        let mut handler = Handler {
+            handler: Some(recording.clone()),
+            views: HashSet::new(),
+        };
+
+        let create = map(&[
+            ("id", Value::I32(7)),
+            ("viewType", Value::String("flust/video".into())),
+            ("direction", Value::I32(1)),
+            ("params", Value::U8List(vec![0x00])),
+        ]);
+        assert_eq!(handler.handle("create", &create), Ok(Value::Null));
+        assert_eq!(
+            handler.handle("create", &create),
+            Err(PlatformViewError::AlreadyExists(7))
+        );
+
+        let resize = map(&[
+            ("id", Value::I32(7)),
+            ("width", Value::F64(320.0)),
+            ("height", Value::I32(240)),
+        ]);
+        assert!(handler.handle("resize", &resize).is_ok());
+        assert_eq!(handler.handle("dispose", &Value::I32(7)), Ok(Value::Null));
+        assert_eq!(
+            handler.handle("dispose", &map(&[("id", Value::I32(7))])),
+            Err(PlatformViewError::UnknownView(7))
+        );
+
+        let unknown_type = map(&[
+            ("id", Value::I32(8)),
+            ("viewType", Value::String("flust/map".into())),
+        ]);
+        assert_eq!(
+            handler.handle("create", &unknown_type),
+            Err(PlatformViewError::UnknownViewType("flust/map".into()))
+        );
+
+        assert_eq!(
+            recording.lock().calls,
+            vec!["create 7", "resize 7 320x240", "dispose 7"]
+        );
+    }
+}
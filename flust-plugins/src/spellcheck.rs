@@ -0,0 +1,212 @@
+//! Plugin providing spell check suggestions for text fields.
+//! It handles flutter/spellcheck type message.
+use std::sync::{Arc, Weak};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use flust_engine::{
+    channel::{MethodCall, MethodCallHandler, MethodChannel},
+    codec::{value::from_value, Value, STANDARD_CODEC},
+    plugins::Plugin,
+    FlutterEngine,
+};
+
+pub const PLUGIN_NAME: &str = module_path!();
+pub const CHANNEL_NAME: &str = "flutter/spellcheck";
+
+/// A misspelled range of text along with its replacement suggestions. This
+/// matches the result shape expected by the framework's
+/// `DefaultSpellCheckService`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestionSpan {
+    /// Start of the misspelled range (in UTF-16 code units).
+    pub start_index: i64,
+    /// End (exclusive) of the misspelled range (in UTF-16 code units).
+    pub end_index: i64,
+    pub suggestions: Vec<String>,
+}
+
+pub trait SpellCheckHandler {
+    fn check_spelling(&mut self, locale: &str, text: &str) -> Vec<SuggestionSpan>;
+}
+
+/// When created without a [`SpellCheckHandler`], spell check requests are
+/// answered with empty results.
+#[derive(Default)]
+pub struct SpellCheckPlugin {
+    channel: Weak<MethodChannel>,
+    handler: Option<Arc<Mutex<dyn SpellCheckHandler + Send>>>,
+}
+
+impl SpellCheckPlugin {
+    pub fn new(handler: Arc<Mutex<dyn SpellCheckHandler + Send>>) -> Self {
+        Self {
+            channel: Weak::new(),
+            handler: Some(handler),
+        }
+    }
+}
+
+impl Plugin for SpellCheckPlugin {
+    fn plugin_name() -> &'static str {
+        PLUGIN_NAME
+    }
+
+    fn init(&mut self, engine: &FlutterEngine) {
+        self.channel = engine.register_channel(MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                handler: self.handler.clone(),
+            },
+            &STANDARD_CODEC,
+        ));
+    }
+}
+
+struct Handler {
+    handler: Option<Arc<Mutex<dyn SpellCheckHandler + Send>>>,
+}
+
+impl MethodCallHandler for Handler {
+    fn on_method_call(&mut self, call: MethodCall) {
+        debug!(
+            "got method call {} with args {:?}",
+            call.method(),
+            call.raw_args()
+        );
+        match call.method().as_str() {
+            "SpellCheck.initiateSpellCheck" => {
+                let Ok(SpellCheckArgs(locale, text)) = from_value(call.raw_args()) else {
+                    return call.error("bad-args", "Invalid spell check arguments", Value::Null);
+                };
+
+                let spans = self
+                    .handler
+                    .as_ref()
+                    .map(|handler| handler.lock().check_spelling(&locale, &text))
+                    .unwrap_or_default();
+
+                call.success(spans)
+            }
+            _ => call.not_implemented(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SpellCheckArgs(String, String);
+
+#[cfg(test)]
+mod tests {
+    use flust_engine::codec::{self, value::to_value, MethodCallResult};
+
+    use super::*;
+
+    /// Suggests "Hello" for the first word, if misspelled as "Helo".
+    struct HelloSpellCheckHandler;
+
+    impl SpellCheckHandler for HelloSpellCheckHandler {
+        fn check_spelling(&mut self, locale: &str, text: &str) -> Vec<SuggestionSpan> {
+            assert_eq!(locale, "en-US");
+            if !text.starts_with("Helo ") {
+                return vec![];
+            }
+
+            vec![SuggestionSpan {
+                start_index: 0,
+                end_index: 4,
+                suggestions: vec!["Hello".into()],
+            }]
+        }
+    }
+
+    fn spell_check_channel(
+        handler: Option<Arc<Mutex<dyn SpellCheckHandler + Send>>>,
+    ) -> MethodChannel {
+        MethodChannel::new(CHANNEL_NAME, Handler { handler }, &STANDARD_CODEC)
+    }
+
+    fn initiate_spell_check(channel: &MethodChannel, text: &str) -> Option<MethodCallResult> {
+        channel.dispatch_for_test(codec::MethodCall {
+            method: "SpellCheck.initiateSpellCheck".into(),
+            args: Value::List(vec![
+                Value::String("en-US".into()),
+                Value::String(text.into()),
+            ]),
+        })
+    }
+
+    #[test]
+    fn spell_check_requests_are_answered_with_suggestions() {
+        let channel = spell_check_channel(Some(Arc::new(Mutex::new(HelloSpellCheckHandler))));
+
+        let Some(MethodCallResult::Ok(Value::List(spans))) =
+            initiate_spell_check(&channel, "Helo world")
+        else {
+            panic!("expected a list of suggestion spans");
+        };
+        let Value::Map(span) = &spans[0] else {
+            panic!("expected a suggestion span map");
+        };
+        assert_eq!(span.get("startIndex"), Some(&Value::I64(0)));
+        assert_eq!(span.get("endIndex"), Some(&Value::I64(4)));
+
+        assert_eq!(
+            initiate_spell_check(&channel, "Hello world"),
+            Some(MethodCallResult::Ok(Value::List(vec![])))
+        );
+
+        // Without a handler, there are no suggestions.
+        assert_eq!(
+            initiate_spell_check(&spell_check_channel(None), "Helo world"),
+            Some(MethodCallResult::Ok(Value::List(vec![])))
+        );
+
+        let reply = channel.dispatch_for_test(codec::MethodCall {
+            method: "SpellCheck.initiateSpellCheck".into(),
+            args: Value::Null,
+        });
+        assert!(matches!(reply, Some(MethodCallResult::Err { code, .. }) if code == "bad-args"));
+    }
+
+    #[test]
+    fn decode_spell_check_args() {
+        let args = Value::List(vec![
+            Value::String("en-US".into()),
+            Value::String("Helo world".into()),
+        ]);
+
+        let SpellCheckArgs(locale, text) = from_value(&args).unwrap();
+        assert_eq!(locale, "en-US");
+        assert_eq!(text, "Helo world");
+    }
+
+    #[test]
+    fn encode_suggestion_spans() {
+        let spans = vec![SuggestionSpan {
+            start_index: 0,
+            end_index: 4,
+            suggestions: vec!["Hello".into(), "Help".into()],
+        }];
+
+        let Value::List(spans) = to_value(spans).unwrap() else {
+            panic!("expected a list of suggestion spans");
+        };
+        let Value::Map(span) = &spans[0] else {
+            panic!("expected a suggestion span map");
+        };
+
+        assert_eq!(span.get("startIndex"), Some(&Value::I64(0)));
+        assert_eq!(span.get("endIndex"), Some(&Value::I64(4)));
+        assert_eq!(
+            span.get("suggestions"),
+            Some(&Value::List(vec![
+                Value::String("Hello".into()),
+                Value::String("Help".into())
+            ]))
+        );
+    }
+}
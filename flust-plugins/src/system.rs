@@ -2,10 +2,8 @@
 //! It handles flutter/localization type message.
 use std::sync::Weak;
 
-use serde::{Deserialize, Serialize};
-
 use flust_engine::{
-    channel::{MessageChannel, MessageHandler},
+    channel::{Channel, MessageChannel, MessageHandler},
     codec::JSON_CODEC,
     plugins::Plugin,
     FlutterEngine,
@@ -42,21 +40,23 @@ impl Plugin for SystemPlugin {
 }
 
 impl SystemPlugin {
+    /// Notifies the engine of memory pressure (see
+    /// [`FlutterEngine::notify_low_memory_warning`]), which purges its caches
+    /// and sends the `memoryPressure` message to the framework. Can be called
+    /// from any thread, e.g.: when the app goes to the background.
     pub fn send_memory_pressure_warning(&self) {
         info!("Sending memory pressure warning");
-        if let Some(channel) = self.channel.upgrade() {
-            channel.send(SystemMsg {
-                r#type: "memoryPressure".to_string(),
-            });
-        } else {
+        let Some(engine) = self.channel.upgrade().and_then(|channel| channel.engine()) else {
             error!("Failed to upgrade channel to send memory pressure warning");
-        }
-    }
-}
+            return;
+        };
 
-#[derive(Serialize, Deserialize)]
-struct SystemMsg {
-    r#type: String,
+        engine.run_on_platform_thread(|engine| {
+            if let Err(err) = engine.notify_low_memory_warning() {
+                error!("Failed to send memory pressure warning: {}", err);
+            }
+        });
+    }
 }
 
 struct Handler;
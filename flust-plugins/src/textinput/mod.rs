@@ -15,7 +15,7 @@ use flust_engine::{
     FlutterEngine,
 };
 
-use self::text_editing_state::TextEditingState;
+pub use self::text_editing_state::TextEditingState;
 use flust_engine::channel::MethodCall;
 use flust_engine::codec::Value;
 use parking_lot::Mutex;
@@ -29,10 +29,76 @@ pub const CHANNEL_NAME: &str = "flutter/textinput";
 const MULTILINE_INPUT_TYPE: &str = "TextInputType.multiline";
 const INPUT_ACTION_NEWLINE: &str = "TextInputAction.newline";
 
+#[rustfmt::skip]
+const IDENTITY_TRANSFORM: [f64; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0,
+];
+
 pub trait TextInputHandler {
     fn show(&mut self);
 
     fn hide(&mut self);
+
+    /// Called whenever the text being edited changes, for input methods
+    /// taking the text around the caret into account. `cursor` and `anchor`
+    /// are the byte offsets of the caret and of the selection anchor in
+    /// `text`, see [`TextInputPlugin::apply_input_method_edit`].
+    fn set_surrounding_text(&mut self, _text: &str, _cursor: usize, _anchor: usize) {}
+
+    /// Called with the area of the text being composed (or of the caret when
+    /// not composing), so that input methods can position their candidate
+    /// window next to it.
+    fn set_cursor_rect(&mut self, _rect: TextInputRect) {}
+
+    /// Called when a text field gains the focus, with the kind of text it
+    /// edits.
+    fn set_content_type(&mut self, _content_type: TextInputContentType) {}
+}
+
+/// The kind of text being edited, for input methods adapting to it (e.g.:
+/// not learning the passwords typed).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TextInputContentType {
+    /// Whether the text is obscured, as for passwords.
+    pub obscured: bool,
+    /// Whether the text may span several lines.
+    pub multiline: bool,
+    pub autocorrect: bool,
+}
+
+/// A rect in logical pixels of the view.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TextInputRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// The text being composed by an input method (e.g.: the pinyin typed
+/// before picking the matching characters).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ComposingText {
+    pub text: String,
+    /// The byte offset of the caret in `text`, `None` for hiding the caret
+    /// (which is then placed after the composing text).
+    pub cursor: Option<usize>,
+}
+
+/// The edits an input method requests at once, see
+/// [`TextInputPlugin::apply_input_method_edit`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InputMethodEdit {
+    /// The new composing text, replacing the previous one. `None` (or an
+    /// empty text) ends the composition.
+    pub composing: Option<ComposingText>,
+    /// The text to insert at the caret, replacing the selection.
+    pub commit: Option<String>,
+    /// The number of bytes to delete before and after the selection.
+    pub delete_surrounding: Option<(usize, usize)>,
 }
 
 pub struct TextInputPlugin {
@@ -50,6 +116,9 @@ struct Data {
     client_id: Option<i64>,
     client_args: Option<SetClientArgsText>,
     editing_state: Option<TextEditingState>,
+    /// The transform from the coordinates of the focused editable to the
+    /// coordinates of the view (a column-major 4x4 matrix).
+    editable_transform: [f64; 16],
 }
 
 impl Plugin for TextInputPlugin {
@@ -75,6 +144,7 @@ impl TextInputPlugin {
             client_id: None,
             client_args: None,
             editing_state: None,
+            editable_transform: IDENTITY_TRANSFORM,
         }));
         Self {
             channel: Weak::new(),
@@ -83,6 +153,21 @@ impl TextInputPlugin {
         }
     }
 
+    /// Returns a handler for
+    /// [`flust_engine::builder::FlutterEngineBuilder::with_pre_engine_restart_handler`],
+    /// forgetting the current client, which doesn't survive an engine restart.
+    pub fn pre_engine_restart_handler(&self) -> impl Fn() + Send + 'static {
+        let data = self.data.clone();
+        let handler = self.handler.clone();
+        move || {
+            let mut data = data.write().unwrap();
+            data.client_id = None;
+            data.client_args = None;
+            data.editing_state = None;
+            handler.lock().hide();
+        }
+    }
+
     fn with_channel<F>(&self, f: F)
     where
         F: FnOnce(&MethodChannel),
@@ -116,12 +201,25 @@ impl TextInputPlugin {
             if let Some(channel) = self.channel.upgrade() {
                 let mut args: Vec<Value> = Vec::new();
                 args.push_as_value(client_id);
-                args.push_as_value(state);
+                args.push_as_value(&*state);
                 channel.invoke_method("TextInputClient.updateEditingState", args)
             }
+
+            let (text, cursor, anchor) = state.surrounding_text();
+            self.handler
+                .lock()
+                .set_surrounding_text(&text, cursor, anchor);
         };
     }
 
+    /// Applies the edits of an input method to the text being edited (see
+    /// [`TextEditingState::apply_input_method_edit`]), and notifies the
+    /// framework.
+    pub fn apply_input_method_edit(&mut self, edit: &InputMethodEdit) {
+        self.with_state(|state| state.apply_input_method_edit(edit));
+        self.notify_changes();
+    }
+
     // This implementation is based on the official Windows embedder implementation:
     // https://github.com/flutter/engine/blob/3.22.0/shell/platform/windows/text_input_plugin.cc#L473-L493
     pub fn enter_pressed(&mut self) {
@@ -173,8 +271,12 @@ impl MethodCallHandler for Handler {
             "TextInput.setClient" => {
                 let mut data = self.data.write().unwrap();
                 let args: SetClientArgs = call.args();
+                let content_type = args.1.content_type();
                 data.client_id = Some(args.0);
                 data.client_args = Some(args.1);
+                drop(data);
+
+                self.handler.lock().set_content_type(content_type);
                 call.success_empty()
             }
             "TextInput.clearClient" => {
@@ -186,7 +288,32 @@ impl MethodCallHandler for Handler {
             "TextInput.setEditingState" => {
                 let mut data = self.data.write().unwrap();
                 let state: TextEditingState = call.args();
+                let (text, cursor, anchor) = state.surrounding_text();
                 data.editing_state.replace(state);
+                drop(data);
+
+                self.handler
+                    .lock()
+                    .set_surrounding_text(&text, cursor, anchor);
+                call.success_empty()
+            }
+            "TextInput.setEditableSizeAndTransform" => {
+                let args: EditableSizeAndTransformArgs = call.args();
+                if let Ok(transform) = args.transform.try_into() {
+                    self.data.write().unwrap().editable_transform = transform;
+                }
+                call.success_empty()
+            }
+            // The composing text rect is sent while composing, and the caret
+            // rect otherwise.
+            "TextInput.setMarkedTextRect" | "TextInput.setCaretRect" => {
+                let rect: RectArgs = call.args();
+                if rect.width >= 0.0 && rect.height >= 0.0 {
+                    let transform = self.data.read().unwrap().editable_transform;
+                    self.handler
+                        .lock()
+                        .set_cursor_rect(transform_rect(&transform, rect));
+                }
                 call.success_empty()
             }
             "TextInput.show" => {
@@ -222,6 +349,47 @@ impl SetClientArgsText {
         self.input_type.name.as_str() == MULTILINE_INPUT_TYPE
             && self.input_action.as_str() == INPUT_ACTION_NEWLINE
     }
+
+    fn content_type(&self) -> TextInputContentType {
+        TextInputContentType {
+            obscured: self.obscure_text,
+            multiline: self.input_type.name.as_str() == MULTILINE_INPUT_TYPE,
+            autocorrect: self.autocorrect,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EditableSizeAndTransformArgs {
+    transform: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct RectArgs {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+/// Maps a rect of an editable to the view, by transforming its corners
+/// (ignoring the perspective).
+fn transform_rect(transform: &[f64; 16], rect: RectArgs) -> TextInputRect {
+    let point = |x: f64, y: f64| {
+        (
+            transform[0] * x + transform[4] * y + transform[12],
+            transform[1] * x + transform[5] * y + transform[13],
+        )
+    };
+    let (left, top) = point(rect.x, rect.y);
+    let (right, bottom) = point(rect.x + rect.width, rect.y + rect.height);
+
+    TextInputRect {
+        x: left.min(right),
+        y: top.min(bottom),
+        width: (right - left).abs(),
+        height: (bottom - top).abs(),
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -230,3 +398,81 @@ struct SetClientArgsInputType {
     name: String,
     decimal: Option<bool>,
 }
+
+#[cfg(test)]
+mod tests {
+    use flust_engine::codec::{self, MethodCallResult};
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct ContentTypeTextInputHandler(Option<TextInputContentType>);
+
+    impl TextInputHandler for ContentTypeTextInputHandler {
+        fn show(&mut self) {}
+
+        fn hide(&mut self) {}
+
+        fn set_content_type(&mut self, content_type: TextInputContentType) {
+            self.0 = Some(content_type);
+        }
+    }
+
+    #[test]
+    fn set_client_stores_client_args() {
+        let handler = Arc::new(Mutex::new(ContentTypeTextInputHandler::default()));
+        let plugin = TextInputPlugin::new(handler.clone());
+        let channel = MethodChannel::new(
+            CHANNEL_NAME,
+            Handler {
+                data: plugin.data.clone(),
+                handler: plugin.handler.clone(),
+            },
+            &JSON_CODEC,
+        );
+
+        let args = Value::try_from(json!([
+            3,
+            {
+                "autocorrect": true,
+                "inputAction": INPUT_ACTION_NEWLINE,
+                "obscureText": false,
+                "keyboardAppearance": "Brightness.light",
+                "actionLabel": null,
+                "textCapitalization": "TextCapitalization.none",
+                "inputType": { "signed": null, "name": MULTILINE_INPUT_TYPE, "decimal": null },
+            },
+        ]))
+        .expect("JSON to value deserialization failed");
+
+        let reply = channel.dispatch_for_test(codec::MethodCall {
+            method: "TextInput.setClient".into(),
+            args,
+        });
+        assert_eq!(reply, Some(MethodCallResult::Ok(Value::Null)));
+
+        let data = plugin.data.read().unwrap();
+        assert_eq!(data.client_id, Some(3));
+        assert!(data
+            .client_args
+            .as_ref()
+            .is_some_and(SetClientArgsText::is_multiline_newline_action));
+        drop(data);
+
+        assert_eq!(
+            handler.lock().0,
+            Some(TextInputContentType {
+                obscured: false,
+                multiline: true,
+                autocorrect: true,
+            })
+        );
+
+        let reply = channel.dispatch_for_test(codec::MethodCall {
+            method: "TextInput.requestAutofill".into(),
+            args: Value::Null,
+        });
+        assert_eq!(reply, Some(MethodCallResult::NotImplemented));
+    }
+}
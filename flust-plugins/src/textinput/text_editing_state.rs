@@ -2,7 +2,10 @@ use std::ops::Range;
 
 use serde::{Deserialize, Serialize};
 
-use crate::textinput::utils::{OwnedStringUtils, StringUtils};
+use crate::textinput::{
+    utils::{OwnedStringUtils, StringUtils},
+    InputMethodEdit,
+};
 use flust_engine::codec::{value::from_value, Value};
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -159,6 +162,103 @@ impl TextEditingState {
         self.select_or_move_to(p, select);
     }
 
+    fn composing_range(&self) -> Option<Range<usize>> {
+        (self.composing_base >= 0 && self.composing_extent > self.composing_base)
+            .then_some(self.composing_base as usize..self.composing_extent as usize)
+    }
+
+    fn clear_composing_range(&mut self) {
+        self.composing_base = -1;
+        self.composing_extent = -1;
+    }
+
+    /// The byte offset of the character at `char_index`, or the length of the
+    /// text past its end.
+    fn byte_offset(&self, char_index: i64) -> usize {
+        self.text
+            .byte_index_of_char(char_index.max(0) as usize)
+            .unwrap_or(self.text.len())
+    }
+
+    /// Applies the edits of an input method in the order they're meant to be
+    /// (see `zwp_text_input_v3.done`): the composing text is removed, the
+    /// surrounding text deleted, the committed text inserted and the new
+    /// composing text inserted at the caret.
+    pub fn apply_input_method_edit(&mut self, edit: &InputMethodEdit) {
+        if let Some(range) = self.composing_range() {
+            self.text.remove_chars(range.clone());
+            self.move_to(range.start);
+        }
+        self.clear_composing_range();
+
+        if let Some((before, after)) = edit.delete_surrounding {
+            self.delete_surrounding_text(before, after);
+        }
+        if let Some(text) = &edit.commit {
+            self.add_characters(text);
+        }
+        if let Some(composing) = edit.composing.as_ref().filter(|c| !c.text.is_empty()) {
+            self.delete_selected();
+            if self.selection_extent < 0 {
+                self.move_to(self.text.char_count());
+            }
+            let start = self.selection_base as usize;
+            self.add_characters(&composing.text);
+            self.composing_base = start as i64;
+            self.composing_extent = (start + composing.text.char_count()) as i64;
+
+            let caret = composing
+                .cursor
+                .and_then(|cursor| composing.text.get(..cursor))
+                .map_or(composing.text.char_count(), |text| text.char_count());
+            self.move_to(start + caret);
+        }
+    }
+
+    /// Deletes `before` bytes of text before the selection and `after` bytes
+    /// after it (rounded to whole characters).
+    fn delete_surrounding_text(&mut self, before: usize, after: usize) {
+        let selection = self.get_selection_range();
+        let (selection_start, selection_end) = (
+            self.byte_offset(selection.start as i64),
+            self.byte_offset(selection.end as i64),
+        );
+
+        let mut end = (selection_end + after).min(self.text.len());
+        while !self.text.is_char_boundary(end) {
+            end += 1;
+        }
+        let mut start = selection_start.saturating_sub(before);
+        while !self.text.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        self.text.drain(selection_end..end);
+        let removed_before = self.text[start..selection_start].char_count();
+        self.text.drain(start..selection_start);
+        self.selection_base -= removed_before as i64;
+        self.selection_extent -= removed_before as i64;
+    }
+
+    /// The text around the caret for input methods, excluding the composing
+    /// text (which is replaced by the caret), along with the byte offsets of
+    /// the caret and of the selection anchor.
+    pub fn surrounding_text(&self) -> (String, usize, usize) {
+        match self.composing_range() {
+            Some(range) => {
+                let mut text = self.text.clone();
+                text.remove_chars(range.clone());
+                let caret = self.byte_offset(range.start as i64);
+                (text, caret, caret)
+            }
+            None => (
+                self.text.clone(),
+                self.byte_offset(self.selection_extent),
+                self.byte_offset(self.selection_base),
+            ),
+        }
+    }
+
     pub fn get_selected_text(&self) -> &str {
         if let Some(range) = self.text.byte_range_of_chars(self.get_selection_range()) {
             &self.text[range]
@@ -228,3 +328,69 @@ impl TextEditingState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textinput::ComposingText;
+
+    fn state(text: &str, caret: i64) -> TextEditingState {
+        TextEditingState {
+            composing_base: -1,
+            composing_extent: -1,
+            selection_base: caret,
+            selection_extent: caret,
+            text: text.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn composing(text: &str, cursor: Option<usize>) -> InputMethodEdit {
+        InputMethodEdit {
+            composing: Some(ComposingText {
+                text: text.to_owned(),
+                cursor,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn composing_text_is_replaced_until_committed() {
+        let mut state = state("你好!", 2);
+
+        state.apply_input_method_edit(&composing("shi", Some(3)));
+        assert_eq!(state.text, "你好shi!");
+        assert_eq!((state.composing_base, state.composing_extent), (2, 5));
+        assert_eq!(state.selection_extent, 5);
+        // The composing text is left out of the surrounding text.
+        assert_eq!(state.surrounding_text(), ("你好!".to_owned(), 6, 6));
+
+        state.apply_input_method_edit(&composing("shij", None));
+        assert_eq!(state.text, "你好shij!");
+        assert_eq!((state.composing_base, state.composing_extent), (2, 6));
+
+        state.apply_input_method_edit(&InputMethodEdit {
+            commit: Some("世界".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(state.text, "你好世界!");
+        assert_eq!((state.composing_base, state.composing_extent), (-1, -1));
+        assert_eq!(state.surrounding_text(), ("你好世界!".to_owned(), 12, 12));
+    }
+
+    #[test]
+    fn surrounding_text_is_deleted_around_the_selection() {
+        let mut state = state("你好世界", 2);
+        state.select_to(3);
+
+        // Partial characters are deleted entirely.
+        state.apply_input_method_edit(&InputMethodEdit {
+            delete_surrounding: Some((2, 3)),
+            commit: Some("们".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(state.text, "你们");
+        assert_eq!(state.selection_extent, 2);
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use dpi::Size;
 pub use flust_engine::ffi::FlutterOpenGLTargetType;
@@ -24,32 +24,252 @@ impl From<BackendConfigSctk> for Backend {
 #[derive(Debug, Clone)]
 pub struct BackendConfigSctk {
     pub opengl_target_type: FlutterOpenGLTargetType,
+
+    /// When set, window metrics events triggered by consecutive `configure`
+    /// events (e.g.: during an interactive resize) are coalesced so that at
+    /// most one event is sent to the engine per interval. The latest size is
+    /// always delivered once the interval elapses.
+    pub window_metrics_debounce_interval: Option<Duration>,
+
+    /// The buffering strategy used by the window EGL surface.
+    pub buffering: SurfaceBuffering,
+
+    /// Whether data copied to the clipboard is also stored in the primary
+    /// selection (pasted with a middle click). Disabled by default: by
+    /// convention the primary selection holds the currently *selected* text,
+    /// not explicitly copied data.
+    pub store_primary_selection: bool,
+
+    /// Routes OpenGL debug messages (`GL_KHR_debug`) into `tracing` and checks
+    /// for GL errors after the compositor's GL operations. This has a runtime
+    /// cost, so it's meant for diagnosing rendering issues only.
+    pub gl_debug_output: bool,
+
+    /// The color the window is cleared to when the engine presents an empty
+    /// frame, also used for initializing the backing stores. Apps with an
+    /// opaque background should match it to avoid flashing (transparent) black
+    /// on startup or during resizes.
+    pub clear_color: ClearColor,
+
+    /// When set, cursor changes requested by the framework are applied once
+    /// all pending platform messages were handled, so that only the last of
+    /// several rapid requests (e.g.: when quickly hovering over widgets using
+    /// different cursors) is shown. Disabled by default, as requests are then
+    /// no longer applied in the order they were handled.
+    pub coalesce_cursor_changes: bool,
+
+    /// Whether presenting frames is synchronized with resizes, i.e.: frames
+    /// rendered for the previous size are rejected until a frame of the new
+    /// size is presented. Enabled by default. Disabling it presents frames
+    /// right away, which reduces resize latency (and helps debugging resize
+    /// issues), at the cost of possibly showing a frame of the wrong size
+    /// (stretched or cropped) for one frame after each resize.
+    pub synchronize_resize: bool,
+
+    /// Renders on the CPU and presents frames through `wl_shm` buffers, so
+    /// that no EGL context is created. Meant for systems without a usable GPU
+    /// (e.g.: virtual machines or headless compositors). Disabled by default,
+    /// as it's much slower, and only the implicit view is rendered: the
+    /// engine's software renderer doesn't support the compositor (and hence
+    /// neither secondary views nor platform views). `opengl_target_type`,
+    /// `buffering` and `gl_debug_output` are ignored when set.
+    pub software_rendering: bool,
+
+    /// When set, bounds the time spent running engine platform tasks per
+    /// event loop iteration, deferring the remaining ones so that input and
+    /// frames are handled under load (see
+    /// `FlutterEngineBuilder::with_platform_task_budget`). Unset by default,
+    /// which maximizes throughput.
+    pub platform_task_budget: Option<Duration>,
+
+    /// How the window surface is scaled on high DPI outputs, see
+    /// [`SurfaceScaling`].
+    pub scaling: SurfaceScaling,
+
+    /// Falls back to rendering straight into the window surface when the
+    /// compositor fails to create a backing store before presenting its first
+    /// frame (see `FlutterEngineBuilder::with_compositor_fallback`), instead
+    /// of failing to render. Enabled by default. Platform views aren't shown
+    /// once fallen back.
+    pub compositor_fallback: bool,
 }
 
 impl Default for BackendConfigSctk {
     fn default() -> Self {
         Self {
             opengl_target_type: FlutterOpenGLTargetType::Framebuffer,
+            window_metrics_debounce_interval: None,
+            buffering: SurfaceBuffering::default(),
+            store_primary_selection: false,
+            gl_debug_output: false,
+            clear_color: ClearColor::TRANSPARENT,
+            coalesce_cursor_changes: false,
+            synchronize_resize: true,
+            software_rendering: false,
+            platform_task_budget: None,
+            scaling: SurfaceScaling::default(),
+            compositor_fallback: true,
         }
     }
 }
 
 impl From<FlutterOpenGLTargetType> for BackendConfigSctk {
     fn from(opengl_target_type: FlutterOpenGLTargetType) -> Self {
-        Self { opengl_target_type }
+        Self {
+            opengl_target_type,
+            ..Default::default()
+        }
     }
 }
 
+/// A color with non-premultiplied components in the `0.0..=1.0` range.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClearColor {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+}
+
+impl ClearColor {
+    pub const TRANSPARENT: ClearColor = ClearColor::new(0.0, 0.0, 0.0, 0.0);
+
+    pub const fn new(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// Creates a color from a `0xAARRGGBB` value, the same format as Dart's
+    /// `Color`.
+    pub fn from_argb(argb: u32) -> Self {
+        let [alpha, red, green, blue] = argb.to_be_bytes().map(|c| c as f32 / 255.0);
+        Self::new(red, green, blue, alpha)
+    }
+
+    /// The color with its RGB components multiplied by its alpha, as the
+    /// window surface (and the engine) expect premultiplied alpha.
+    pub fn premultiplied(self) -> Self {
+        Self::new(
+            self.red * self.alpha,
+            self.green * self.alpha,
+            self.blue * self.alpha,
+            self.alpha,
+        )
+    }
+}
+
+/// How the window surface is scaled to the scale factor of its output.
+///
+/// Wayland compositors expose fractional scale factors (e.g.: 1.5) through
+/// `wp_fractional_scale_v1`, while surfaces could traditionally only be scaled
+/// by integer factors (`wl_surface.set_buffer_scale`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SurfaceScaling {
+    /// Renders at the integer scale preferred by the compositor, which rounds
+    /// fractional scales up (e.g.: 2 for 1.5), and lets the compositor
+    /// downscale the surface. The buffer is mapped to the logical size of the
+    /// window through `wp_viewporter` when supported (and through the buffer
+    /// scale otherwise). This works with every compositor, but renders more
+    /// pixels than displayed (78% more at 1.5), and downscaling slightly
+    /// blurs text and thin lines.
+    #[default]
+    Integer,
+
+    /// Renders at the exact fractional scale of the output, and maps the
+    /// buffer to the logical size of the window through `wp_viewporter`, so
+    /// that rendering is sharp and not more expensive than needed. This
+    /// requires both `wp_fractional_scale_v1` and `wp_viewporter`, falling back
+    /// to [`SurfaceScaling::Integer`] otherwise. As the physical size of the
+    /// window is rounded, widgets laid out on logical pixel boundaries may
+    /// not be aligned with physical pixels (e.g.: hairline seams between
+    /// adjacent boxes).
+    Fractional,
+}
+
+/// Number of buffers the window surface may cycle through.
+///
+/// EGL doesn't allow requesting an exact buffer count. Instead, the preference
+/// is applied through the surface swap interval, which determines whether the
+/// driver may allocate an additional buffer (where the driver allows it)
+/// rather than waiting for the compositor to release one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SurfaceBuffering {
+    /// Swapping buffers waits for the compositor to be ready for a new frame
+    /// (swap interval of 1), so at most one frame is queued for presentation.
+    /// This minimizes latency, but a frame that takes slightly longer than the
+    /// frame interval to render causes a missed frame (jank).
+    Double,
+
+    /// Swapping buffers never waits (swap interval of 0) and an extra buffer
+    /// is used when needed. Frame pacing is smoother since rendering doesn't
+    /// block on the compositor, at the cost of up to one additional frame of
+    /// latency.
+    #[default]
+    Triple,
+}
+
 /// Attributes used when creating an application.
 #[derive(Debug, Clone, Default)]
 pub struct ApplicationAttributes {
     pub backend: Backend,
     pub inner_size: Option<Size>,
     pub title: Option<String>,
+
+    /// The application identifier, used as the Wayland `xdg_toplevel` app id
+    /// and as the X11 `WM_CLASS`. Desktop environments use it to group windows
+    /// and to associate them with a desktop entry: in order for the window to
+    /// pick up the icon (and name) of the application, the `.desktop` file
+    /// must be named after the app id (e.g.: `com.example.MyApp.desktop` for
+    /// the `com.example.MyApp` app id).
+    ///
+    /// See [`ApplicationAttributes::app_id_or_default`] for the value used
+    /// when unset.
     pub app_id: Option<String>,
     pub args: Vec<String>,
+
+    /// Opts into the Impeller renderer (instead of Skia).
+    pub enable_impeller: bool,
     pub aot_library_path: PathBuf,
     pub assets_path: PathBuf,
     pub icu_data_path: PathBuf,
     pub persistent_cache_path: PathBuf,
 }
+
+impl ApplicationAttributes {
+    /// Returns the configured app id, or the name of the current executable
+    /// when unset.
+    pub fn app_id_or_default(&self) -> String {
+        self.app_id.clone().unwrap_or_else(default_app_id)
+    }
+}
+
+fn default_app_id() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "flust".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_id_or_default() {
+        let attributes = ApplicationAttributes {
+            app_id: Some("com.example.MyApp".into()),
+            ..Default::default()
+        };
+        assert_eq!(attributes.app_id_or_default(), "com.example.MyApp");
+
+        let attributes = ApplicationAttributes::default();
+        assert!(!attributes.app_id_or_default().is_empty());
+    }
+}
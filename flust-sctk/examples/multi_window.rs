@@ -0,0 +1,53 @@
+//! Opens two top-level windows rendering two different Flutter views.
+//!
+//! Usage: `cargo run --example multi_window -- <flutter_assets> <icudtl.dat>`
+//!
+//! The Flutter app needs to render content for every view (e.g.: using
+//! `runWidget` with a `View` per `PlatformDispatcher.views` entry).
+use std::{env, path::PathBuf, process::ExitCode};
+
+use dpi::LogicalSize;
+use flust_runner_api::{ApplicationAttributes, BackendConfigSctk};
+use flust_sctk::application::SctkApplication;
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1).map(PathBuf::from);
+    let (Some(assets_path), Some(icu_data_path)) = (args.next(), args.next()) else {
+        eprintln!("Usage: multi_window <flutter_assets> <icudtl.dat>");
+        return ExitCode::FAILURE;
+    };
+
+    let attributes = ApplicationAttributes {
+        title: Some("Flust (main window)".into()),
+        app_id: Some("rs.flust.MultiWindow".into()),
+        assets_path,
+        icu_data_path,
+        ..Default::default()
+    };
+
+    let secondary_attributes = ApplicationAttributes {
+        title: Some("Flust (secondary window)".into()),
+        inner_size: Some(LogicalSize::new(640.0, 480.0).into()),
+        ..attributes.clone()
+    };
+
+    let mut app = match SctkApplication::new(attributes, BackendConfigSctk::default()) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("Failed to create application: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(err) = app.create_window(secondary_attributes) {
+        eprintln!("Failed to create secondary window: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(err) = app.run() {
+        eprintln!("Failed to run application: {}", err);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
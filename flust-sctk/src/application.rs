@@ -1,38 +1,93 @@
-use std::{collections::HashMap, fmt::Debug, rc::Rc, sync::Arc};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt::Debug,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use calloop::futures::{Executor, Scheduler};
 use flust_engine::{
     builder::FlutterEngineBuilder,
-    ffi::{FlutterEngineDisplay, FlutterKeyEventDeviceType, FlutterKeyEventType},
+    ffi::{
+        FlutterEngineDisplay, FlutterKeyEvent, FlutterKeyEventDeviceType, FlutterKeyEventType,
+        FlutterViewId, IMPLICIT_VIEW_ID,
+    },
     plugins::{Plugin, PluginRegistrar},
     CreateError, FlutterEngine,
 };
 use flust_plugins::{
-    isolate::IsolatePlugin, keyevent::KeyEventPlugin, lifecycle::LifecyclePlugin,
-    localization::LocalizationPlugin, mousecursor::MouseCursorPlugin, navigation::NavigationPlugin,
-    platform::PlatformPlugin, system::SystemPlugin, textinput::TextInputPlugin,
+    isolate::IsolatePlugin,
+    keyevent::KeyEventPlugin,
+    lifecycle::LifecyclePlugin,
+    localization::LocalizationPlugin,
+    mousecursor::MouseCursorPlugin,
+    navigation::NavigationPlugin,
+    platform::PlatformPlugin,
+    platform_views::PlatformViewsPlugin,
+    spellcheck::SpellCheckPlugin,
+    system::SystemPlugin,
+    textinput::{InputMethodEdit, TextInputPlugin},
+};
+use flust_plugins::{
+    keyboard::KeyboardPlugin, platform::ClipboardReadError, settings::SettingsPlugin,
 };
-use flust_plugins::{keyboard::KeyboardPlugin, settings::SettingsPlugin};
 use flust_runner_api::{ApplicationAttributes, BackendConfigSctk};
 use parking_lot::{Mutex, RwLock};
 use smithay_client_toolkit::{
     compositor::{CompositorHandler, CompositorState, SurfaceData},
-    delegate_compositor, delegate_keyboard, delegate_output, delegate_pointer, delegate_registry,
-    delegate_seat, delegate_shm, delegate_xdg_shell, delegate_xdg_window,
+    data_device_manager::{
+        data_device::DataDeviceHandler,
+        data_offer::{DataOfferHandler, DragOffer},
+        data_source::DataSourceHandler,
+        DataDeviceManagerState, WritePipe,
+    },
+    delegate_compositor, delegate_data_device, delegate_keyboard, delegate_output,
+    delegate_pointer, delegate_primary_selection, delegate_registry, delegate_seat, delegate_shm,
+    delegate_subcompositor, delegate_xdg_shell, delegate_xdg_window,
     output::{OutputHandler, OutputState},
+    primary_selection::{
+        device::PrimarySelectionDeviceHandler, selection::PrimarySelectionSourceHandler,
+        PrimarySelectionManagerState,
+    },
     reexports::{
         calloop::{
             self,
+            channel::{self as calloop_channel, Channel, Event as ChannelEvent},
             timer::{TimeoutAction, Timer},
-            EventLoop, LoopHandle, LoopSignal,
+            EventLoop, LoopHandle, LoopSignal, RegistrationToken,
         },
         calloop_wayland_source::WaylandSource,
+        csd_frame::WindowState,
+        protocols::wp::{
+            fractional_scale::v1::client::{
+                wp_fractional_scale_manager_v1::{self, WpFractionalScaleManagerV1},
+                wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+            },
+            pointer_gestures::zv1::client::{
+                zwp_pointer_gesture_pinch_v1::{self, ZwpPointerGesturePinchV1},
+                zwp_pointer_gestures_v1::{self, ZwpPointerGesturesV1},
+            },
+            primary_selection::zv1::client::{
+                zwp_primary_selection_device_v1::ZwpPrimarySelectionDeviceV1,
+                zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1,
+            },
+            text_input::zv3::client::{
+                zwp_text_input_manager_v3::{self, ZwpTextInputManagerV3},
+                zwp_text_input_v3::{self, ZwpTextInputV3},
+            },
+            viewporter::client::{
+                wp_viewport::{self, WpViewport},
+                wp_viewporter::{self, WpViewporter},
+            },
+        },
     },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers},
-        pointer::{PointerEvent, PointerHandler, ThemeSpec},
+        pointer::{PointerEvent, PointerEventKind, PointerHandler, ThemeSpec},
         Capability, SeatHandler, SeatState,
     },
     shell::xdg::{
@@ -40,6 +95,7 @@ use smithay_client_toolkit::{
         XdgShell,
     },
     shm::{Shm, ShmHandler},
+    subcompositor::SubcompositorState,
 };
 use thiserror::Error;
 use tracing::{error, trace, warn};
@@ -47,24 +103,34 @@ use wayland_backend::client::ObjectId;
 use wayland_client::{
     globals::{registry_queue_init, BindError, GlobalError},
     protocol::{
+        wl_data_device::WlDataDevice,
+        wl_data_device_manager::DndAction,
+        wl_data_source::WlDataSource,
         wl_keyboard::WlKeyboard,
         wl_output::{Transform, WlOutput},
         wl_pointer::WlPointer,
         wl_seat::WlSeat,
         wl_surface::WlSurface,
     },
-    ConnectError, Connection, Proxy, QueueHandle,
+    ConnectError, Connection, Dispatch, Proxy, QueueHandle,
 };
 
 use crate::{
+    clipboard::{read_offered_data, PendingClipboardRead, SctkClipboard},
+    egl::check_impeller_requirements,
+    gesture::PinchGestureData,
     handler::{
         get_flutter_frame_time_nanos, SctkAsyncResult, SctkKeyboardHandler, SctkMouseCursorHandler,
         SctkPlatformHandler, SctkPlatformTaskHandler, SctkSettingsHandler, SctkTextInputHandler,
         SctkVsyncHandler, FRAME_INTERVAL_60_HZ_IN_NANOS,
     },
     keyboard::{SctkFlutterStringExt, SctkKeyEvent},
+    locale::{best_supported_locale, environment_locales, LocaleMonitor, SharedLocaleMonitor},
     output::SctkOutput,
-    window::{SctkFlutterWindow, SctkFlutterWindowCreateError},
+    platform_view::SctkPlatformViewSurfaces,
+    text_input::{TextInputData, TextInputEvent},
+    viewport::{fractional_scale_factor, SctkScalingGlobals},
+    window::{SctkFlutterWindow, SctkFlutterWindowCreateError, RESIZE_RECOVERY_TIMEOUT},
 };
 
 pub struct SctkApplication {
@@ -74,25 +140,41 @@ pub struct SctkApplication {
 
 pub struct SctkApplicationState {
     conn: Connection,
+    qh: QueueHandle<SctkApplicationState>,
     loop_handle: LoopHandle<'static, SctkApplicationState>,
     loop_signal: LoopSignal,
     registry_state: RegistryState,
     compositor_state: CompositorState,
+    subcompositor_state: Arc<SubcompositorState>,
+    xdg_shell_state: XdgShell,
+    config: BackendConfigSctk,
     shm_state: Shm,
     output_state: OutputState,
     seat_state: SeatState,
     engine: FlutterEngine,
     windows: HashMap<ObjectId, SctkFlutterWindow>,
-    active_state: HashMap<ObjectId, bool>,
+    next_view_id: FlutterViewId,
+    removed_views_sender: calloop_channel::Sender<FlutterViewId>,
+    active_state: HashMap<ObjectId, WindowLifecycle>,
     pointers: HashMap<ObjectId, WlPointer>,
+    pointer_gestures: Option<ZwpPointerGesturesV1>,
+    scaling_globals: SctkScalingGlobals,
+    pinch_gestures: HashMap<ObjectId, ZwpPointerGesturePinchV1>,
     keyboards: HashMap<ObjectId, WlKeyboard>,
+    text_input_manager: Option<ZwpTextInputManagerV3>,
     startup_synchronizer: ImplicitWindowStartupSynchronizer,
+    platform_task_timer: PlatformTaskTimer,
+    resize_recovery_timers: HashMap<ObjectId, RegistrationToken>,
     plugins: Rc<RwLock<PluginRegistrar>>,
     mouse_cursor_handler: Arc<Mutex<SctkMouseCursorHandler>>,
     keyboard_handler: Arc<Mutex<SctkKeyboardHandler>>,
+    text_input_handler: Arc<Mutex<SctkTextInputHandler>>,
     vsync_handler: Arc<Mutex<SctkVsyncHandler>>,
+    clipboard: Arc<SctkClipboard>,
+    locales: SharedLocaleMonitor,
     async_scheduler: Scheduler<SctkAsyncResult>,
     modifiers: Modifiers,
+    first_frame_callback: Option<Box<dyn FnOnce() + Send>>,
 }
 
 impl SctkApplication {
@@ -116,17 +198,79 @@ impl SctkApplication {
             },
         )?;
 
+        // Secondary windows are only dropped once the engine confirms that
+        // their views have been removed (which happens on an engine thread).
+        let (removed_views_sender, removed_views): (_, Channel<FlutterViewId>) =
+            calloop_channel::channel();
+        event_loop
+            .handle()
+            .insert_source(removed_views, |event, _metadata, state| {
+                if let ChannelEvent::Msg(view_id) = event {
+                    state
+                        .windows
+                        .retain(|_, window| window.view_id() != view_id);
+                }
+            })?;
+
+        // Clipboard data offered by other clients is read asynchronously, as
+        // the selection owner may be slow to send it.
+        let (clipboard_reads_sender, clipboard_reads): (_, Channel<PendingClipboardRead>) =
+            calloop_channel::channel();
+        event_loop
+            .handle()
+            .insert_source(clipboard_reads, |event, _metadata, state| {
+                if let ChannelEvent::Msg(read) = event {
+                    state.schedule_clipboard_read(read);
+                }
+            })
+            .map_err(|err| err.error)?;
+
         let registry_state = RegistryState::new(&globals);
         let output_state = OutputState::new(&globals, &qh);
         let seat_state = SeatState::new(&globals, &qh);
         let compositor_state = CompositorState::bind(&globals, &qh)?;
+        let subcompositor_state = Arc::new(SubcompositorState::bind(
+            compositor_state.wl_compositor().clone(),
+            &globals,
+            &qh,
+        )?);
         let xdg_shell_state = XdgShell::bind(&globals, &qh)?;
         let shm_state = Shm::bind(&globals, &qh)?;
+        let pointer_gestures = globals.bind(&qh, 1..=1, ()).ok();
+        let text_input_manager = globals.bind(&qh, 1..=1, ()).ok();
+        let scaling_globals = SctkScalingGlobals::bind(&globals, &qh);
+        // The clipboard is unavailable without a data device manager (e.g.:
+        // in kiosk compositors), which isn't required for rendering.
+        let data_device_manager = DataDeviceManagerState::bind(&globals, &qh)
+            .map_err(|err| warn!("Clipboard is unavailable: {}", err))
+            .ok();
+        let clipboard = Arc::new(SctkClipboard::new(
+            conn.clone(),
+            qh.clone(),
+            data_device_manager,
+            PrimarySelectionManagerState::bind(&globals, &qh).ok(),
+        ));
 
         let platform_task_handler = Arc::new(SctkPlatformTaskHandler::new(event_loop.get_signal()));
         let vsync_handler = Arc::new(Mutex::new(SctkVsyncHandler::new(qh.clone())));
+        let text_input_handler = Arc::new(Mutex::new(SctkTextInputHandler::new()));
+        let keyboard_handler = Arc::new(Mutex::new(SctkKeyboardHandler::new()));
+        let text_input_plugin = TextInputPlugin::new(text_input_handler.clone());
+        let pre_engine_restart_handler = {
+            let reset_text_input = text_input_plugin.pre_engine_restart_handler();
+            let keyboard_handler = keyboard_handler.clone();
+            move || {
+                reset_text_input();
+                keyboard_handler.lock().reset();
+            }
+        };
+
+        let locales = Arc::new(Mutex::new(LocaleMonitor::new(environment_locales(
+            |name| std::env::var(name).ok(),
+        ))));
+        let preferred_locales = locales.clone();
 
-        let engine = FlutterEngineBuilder::new()
+        let mut engine_builder = FlutterEngineBuilder::new()
             .with_platform_handler(platform_task_handler)
             .with_vsync_handler(vsync_handler.clone())
             .with_aot_library_path(attributes.aot_library_path.clone())
@@ -134,43 +278,82 @@ impl SctkApplication {
             .with_icu_data_path(attributes.icu_data_path.clone())
             .with_persistent_cache_path(attributes.persistent_cache_path.clone())
             .with_args(attributes.args.clone())
-            .with_compositor_enabled(true)
-            .build()?;
+            .with_enable_impeller(attributes.enable_impeller)
+            .with_compositor_enabled(!config.software_rendering)
+            .with_compositor_fallback(config.compositor_fallback)
+            .with_software_rendering(config.software_rendering)
+            .with_platform_task_budget(config.platform_task_budget)
+            .with_pre_engine_restart_handler(pre_engine_restart_handler)
+            .with_platform_resolved_locale_handler(move |supported| {
+                best_supported_locale(&preferred_locales.lock().locales(), supported)
+            });
+        // The windows (and their contexts) are created once the engine is, so
+        // the requirements of Impeller are checked with a probe surface.
+        if attributes.enable_impeller && !config.software_rendering {
+            let surface = compositor_state.create_surface(&qh);
+            engine_builder = engine_builder.with_impeller_requirements_check(move || {
+                let checked = check_impeller_requirements(&surface);
+                surface.destroy();
+                checked
+            });
+        }
+        let engine = engine_builder.build()?;
 
         let implicit_window = SctkFlutterWindow::new(
+            IMPLICIT_VIEW_ID,
             engine.downgrade(),
             &qh,
             &compositor_state,
+            &subcompositor_state,
             &xdg_shell_state,
+            &shm_state,
+            &scaling_globals,
             vsync_handler.clone(),
+            None,
             attributes,
-            config,
+            config.clone(),
         )?;
 
         engine.add_view(implicit_window.create_flutter_view());
+        // Pointer events can be received before the first metrics are sent.
+        let transform = implicit_window.initial_pointer_transform();
+        if let Err(err) = engine.set_view_pointer_transform(IMPLICIT_VIEW_ID, Some(transform)) {
+            error!(
+                "Failed to set the pointer transform of the implicit view: {}",
+                err
+            );
+        }
 
         vsync_handler
             .lock()
             .init(engine.downgrade(), implicit_window.wl_surface());
 
         let noop_isolate_cb = || trace!("[isolate-plugin] isolate has been created");
-        let platform_handler =
-            unsafe { SctkPlatformHandler::new(conn.display(), implicit_window.xdg_toplevel()) };
+        let platform_handler = SctkPlatformHandler::new(
+            implicit_window.xdg_toplevel(),
+            clipboard.clone(),
+            clipboard_reads_sender,
+            config.store_primary_selection,
+        );
         let platform_handler = Arc::new(Mutex::new(platform_handler));
-        let mouse_cursor_handler = Arc::new(Mutex::new(SctkMouseCursorHandler::new(conn.clone())));
-        let text_input_handler = Arc::new(Mutex::new(SctkTextInputHandler::new()));
-        let keyboard_handler = Arc::new(Mutex::new(SctkKeyboardHandler::new()));
+        let mouse_cursor_handler = Arc::new(Mutex::new(
+            SctkMouseCursorHandler::new(conn.clone())
+                .with_coalescing(config.coalesce_cursor_changes)
+                .with_shm(shm_state.wl_shm().clone()),
+        ));
 
         let mut plugins = PluginRegistrar::new();
         plugins.add_plugin(&engine, IsolatePlugin::new(noop_isolate_cb));
         plugins.add_plugin(&engine, KeyEventPlugin::new());
-        plugins.add_plugin(&engine, TextInputPlugin::new(text_input_handler.clone()));
+        plugins.add_plugin(&engine, text_input_plugin);
         plugins.add_plugin(&engine, KeyboardPlugin::new(keyboard_handler.clone()));
         plugins.add_plugin(&engine, LifecyclePlugin::default());
         plugins.add_plugin(&engine, LocalizationPlugin::default());
         plugins.add_plugin(&engine, NavigationPlugin::default());
         plugins.add_plugin(&engine, PlatformPlugin::new(platform_handler));
+        plugins.add_plugin(&engine, PlatformViewsPlugin::default());
         plugins.add_plugin(&engine, SettingsPlugin::default());
+        plugins.add_plugin(&engine, SpellCheckPlugin::default());
         plugins.add_plugin(&engine, SystemPlugin::default());
         plugins.add_plugin(
             &engine,
@@ -179,25 +362,41 @@ impl SctkApplication {
 
         let state = SctkApplicationState {
             conn,
+            qh,
             loop_handle: event_loop.handle(),
             loop_signal: event_loop.get_signal(),
             windows: HashMap::from([(implicit_window.xdg_toplevel_id(), implicit_window)]),
+            next_view_id: IMPLICIT_VIEW_ID + 1,
+            removed_views_sender,
             pointers: HashMap::new(),
+            pointer_gestures,
+            scaling_globals,
+            pinch_gestures: HashMap::new(),
             keyboards: HashMap::new(),
+            text_input_manager,
             active_state: HashMap::new(),
             compositor_state,
+            subcompositor_state,
+            xdg_shell_state,
+            config,
             shm_state,
             registry_state,
             output_state,
             seat_state,
             engine,
             startup_synchronizer: ImplicitWindowStartupSynchronizer::new(),
+            platform_task_timer: PlatformTaskTimer::default(),
+            resize_recovery_timers: HashMap::new(),
             plugins: Rc::new(RwLock::new(plugins)),
             mouse_cursor_handler,
             keyboard_handler,
+            text_input_handler,
             vsync_handler,
+            clipboard,
+            locales,
             async_scheduler,
             modifiers: Modifiers::default(),
+            first_frame_callback: None,
         };
 
         Ok(Self { event_loop, state })
@@ -215,6 +414,8 @@ impl SctkApplication {
             .insert_source(Timer::immediate(), |_event, _metadata, state| {
                 state.engine.run().expect("Failed to run engine");
 
+                state.set_first_frame_callback();
+
                 state.schedule_async_startup_tasks();
 
                 state.maybe_send_startup_pending_configure();
@@ -223,12 +424,9 @@ impl SctkApplication {
             })?;
 
         self.event_loop.run(None, &mut self.state, |state| {
-            let next_task_timer = state
-                .engine
-                .execute_platform_tasks()
-                .map(Timer::from_deadline);
-
-            insert_timer_source(&state.loop_handle, next_task_timer);
+            let next_task_deadline = state.engine.execute_platform_tasks();
+            state.schedule_platform_task_timer(next_task_deadline);
+            state.mouse_cursor_handler.lock().apply_pending_cursor();
         })?;
 
         Ok(())
@@ -240,9 +438,54 @@ impl SctkApplication {
     {
         self.state.add_plugin(plugin);
     }
+
+    /// The registry of the platform view subsurfaces of the window of
+    /// `view_id`, `None` if there's no such window. Platform views are
+    /// created by a `PlatformViewHandler` registering their surfaces in it,
+    /// installed by replacing the default [`PlatformViewsPlugin`] (which
+    /// rejects every platform view) with [`Self::add_plugin`].
+    pub fn platform_view_surfaces(
+        &self,
+        view_id: FlutterViewId,
+    ) -> Option<SctkPlatformViewSurfaces> {
+        self.state.platform_view_surfaces(view_id)
+    }
+
+    /// Sets a one-shot callback called once Flutter has rendered its first
+    /// frame (e.g.: for hiding a native splash screen). The callback is called
+    /// on the platform thread.
+    pub fn on_first_frame<F>(&mut self, callback: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.state.first_frame_callback = Some(Box::new(callback));
+    }
+
+    /// Creates an additional top-level window, rendering a new Flutter view.
+    /// Returns the id of the view.
+    ///
+    /// The view is added to the engine once the initial size of the window is
+    /// known (i.e.: after its first `configure` event). Closing the window
+    /// removes the view from the engine.
+    pub fn create_window(
+        &mut self,
+        attributes: ApplicationAttributes,
+    ) -> Result<FlutterViewId, SctkFlutterWindowCreateError> {
+        self.state.create_window(attributes)
+    }
 }
 
 impl SctkApplicationState {
+    fn set_first_frame_callback(&mut self) {
+        let Some(callback) = self.first_frame_callback.take() else {
+            return;
+        };
+
+        if let Err(err) = self.engine.set_next_frame_callback(move |_| callback()) {
+            error!("Failed to set first frame callback: {}", err);
+        }
+    }
+
     pub fn add_plugin<P>(&mut self, plugin: P)
     where
         P: Plugin + 'static,
@@ -250,6 +493,16 @@ impl SctkApplicationState {
         self.plugins.write().add_plugin(&self.engine, plugin);
     }
 
+    pub fn platform_view_surfaces(
+        &self,
+        view_id: FlutterViewId,
+    ) -> Option<SctkPlatformViewSurfaces> {
+        self.windows
+            .values()
+            .find(|window| window.view_id() == view_id)
+            .map(SctkFlutterWindow::platform_view_surfaces)
+    }
+
     pub fn with_plugin<F, P>(&self, f: F)
     where
         F: FnOnce(&P),
@@ -266,6 +519,30 @@ impl SctkApplicationState {
         self.plugins.write().with_plugin_mut(f)
     }
 
+    fn schedule_platform_task_timer(&mut self, deadline: Option<Instant>) {
+        if !self.platform_task_timer.needs_reschedule(deadline) {
+            return;
+        }
+
+        let Some(deadline) = deadline else {
+            return;
+        };
+
+        if let Some(token) = self.platform_task_timer.token.take() {
+            self.loop_handle.remove(token);
+        }
+
+        let token = self
+            .loop_handle
+            .insert_source(Timer::from_deadline(deadline), |_, _, state| {
+                let next_deadline = state.engine.execute_platform_tasks();
+                state.platform_task_timer.on_timeout(next_deadline)
+            })
+            .expect("Unable to insert timer source");
+
+        self.platform_task_timer.set_scheduled(token, deadline);
+    }
+
     fn find_window_by_surface_id_mut(
         &mut self,
         surface_id: ObjectId,
@@ -279,8 +556,66 @@ impl SctkApplicationState {
         })
     }
 
-    fn get_implicit_window_mut(&mut self) -> Option<&mut SctkFlutterWindow> {
-        self.windows.iter_mut().last().map(|(_key, window)| window)
+    fn create_window(
+        &mut self,
+        attributes: ApplicationAttributes,
+    ) -> Result<FlutterViewId, SctkFlutterWindowCreateError> {
+        let view_id = self.next_view_id;
+        let engine_context = self
+            .windows
+            .values()
+            .find(|window| window.view_id() == IMPLICIT_VIEW_ID)
+            .and_then(SctkFlutterWindow::opengl_context);
+
+        let window = SctkFlutterWindow::new(
+            view_id,
+            self.engine.downgrade(),
+            &self.qh,
+            &self.compositor_state,
+            &self.subcompositor_state,
+            &self.xdg_shell_state,
+            &self.shm_state,
+            &self.scaling_globals,
+            self.vsync_handler.clone(),
+            engine_context,
+            attributes,
+            self.config.clone(),
+        )?;
+
+        self.next_view_id += 1;
+        self.windows.insert(window.xdg_toplevel_id(), window);
+
+        Ok(view_id)
+    }
+
+    fn close_window(&mut self, xdg_toplevel_id: ObjectId) {
+        let Some(window) = self.windows.get(&xdg_toplevel_id) else {
+            return;
+        };
+
+        let view_id = window.view_id();
+        if view_id == IMPLICIT_VIEW_ID {
+            self.loop_signal.stop();
+            return;
+        }
+
+        if !self.startup_synchronizer.is_engine_running {
+            self.windows.remove(&xdg_toplevel_id);
+            return;
+        }
+
+        let sender = self.removed_views_sender.clone();
+        let result = self.engine.remove_secondary_view(view_id, move |_removed| {
+            if let Err(err) = sender.send(view_id) {
+                error!("[{}] Failed to notify view removal: {}", view_id, err);
+            }
+        });
+
+        // The view is not known to the engine (e.g.: the window was closed
+        // before being configured), so it can be dropped right away.
+        if result.is_err() {
+            self.windows.remove(&xdg_toplevel_id);
+        }
     }
 
     fn maybe_send_startup_pending_configure(&mut self) {
@@ -288,26 +623,94 @@ impl SctkApplicationState {
 
         self.notify_display_update();
 
-        let Some((configure, serial)) = self.startup_synchronizer.pending_configure.take() else {
-            return;
-        };
-
         let conn = self.conn.clone();
-        if let Some(window) = self.get_implicit_window_mut() {
-            window.configure(&conn, configure, serial);
-        };
+        let pending_configures: Vec<_> = self
+            .startup_synchronizer
+            .pending_configures
+            .drain()
+            .collect();
+
+        for (xdg_toplevel_id, (configure, serial)) in pending_configures {
+            let Some(window) = self.windows.get_mut(&xdg_toplevel_id) else {
+                continue;
+            };
+
+            if let Some(interval) = window.configure(&conn, configure, serial) {
+                self.schedule_window_metrics_flush(xdg_toplevel_id.clone(), interval);
+            }
+            self.schedule_resize_recovery(xdg_toplevel_id);
+        }
     }
 
-    fn maybe_update_lifecycle_state(&mut self, xdg_toplevel_id: ObjectId, is_active: bool) {
-        let was_active = self.active_state.iter().any(|(_, &active)| active);
+    fn schedule_window_metrics_flush(&self, xdg_toplevel_id: ObjectId, interval: Duration) {
+        let timer = Timer::from_duration(interval);
+        let result = self.loop_handle.insert_source(timer, move |_, _, state| {
+            if let Some(window) = state.windows.get_mut(&xdg_toplevel_id) {
+                window.flush_pending_window_metrics();
+            }
+            TimeoutAction::Drop
+        });
+
+        if let Err(err) = result {
+            error!("Failed to schedule window metrics flush: {}", err);
+        }
+    }
 
-        self.active_state.insert(xdg_toplevel_id, is_active);
+    /// (Re-)starts the resize recovery timer of the window, keeping a single
+    /// timer source per window however many resizes happen in a row.
+    fn schedule_resize_recovery(&mut self, xdg_toplevel_id: ObjectId) {
+        if let Some(token) = self.resize_recovery_timers.remove(&xdg_toplevel_id) {
+            self.loop_handle.remove(token);
+        }
 
-        if was_active != is_active && self.startup_synchronizer.is_engine_running {
-            self.with_plugin(|lifecycle: &LifecyclePlugin| match is_active {
-                true => lifecycle.send_app_is_resumed(),
-                false => lifecycle.send_app_is_inactive(),
-            })
+        let timer = Timer::from_duration(RESIZE_RECOVERY_TIMEOUT);
+        let id = xdg_toplevel_id.clone();
+        let result = self.loop_handle.insert_source(timer, move |_, _, state| {
+            state.resize_recovery_timers.remove(&id);
+            if let Some(window) = state.windows.get_mut(&id) {
+                window.recover_stuck_resize();
+            }
+            TimeoutAction::Drop
+        });
+
+        match result {
+            Ok(token) => {
+                self.resize_recovery_timers.insert(xdg_toplevel_id, token);
+            }
+            Err(err) => error!("Failed to schedule resize recovery: {}", err),
+        }
+    }
+
+    fn maybe_update_lifecycle_state(
+        &mut self,
+        xdg_toplevel_id: ObjectId,
+        window_lifecycle: WindowLifecycle,
+    ) {
+        let previous_state = app_lifecycle_state(self.active_state.values().copied());
+
+        self.active_state.insert(xdg_toplevel_id, window_lifecycle);
+
+        let state = app_lifecycle_state(self.active_state.values().copied());
+        if previous_state != state && self.startup_synchronizer.is_engine_running {
+            trace!(
+                "app lifecycle state: {:?} (frames enabled: {})",
+                state,
+                state.frames_enabled()
+            );
+            self.vsync_handler
+                .lock()
+                .set_frames_enabled(state.frames_enabled());
+            self.with_plugin(|lifecycle: &LifecyclePlugin| match state {
+                AppLifecycleState::Resumed => lifecycle.send_app_is_resumed(),
+                AppLifecycleState::Inactive => lifecycle.send_app_is_inactive(),
+                AppLifecycleState::Paused => lifecycle.send_app_is_paused(),
+            });
+
+            // Caches (e.g.: the raster cache) are of no use while none of the
+            // windows are visible.
+            if state == AppLifecycleState::Paused {
+                self.with_plugin(|system: &SystemPlugin| system.send_memory_pressure_warning());
+            }
         }
     }
 
@@ -319,6 +722,52 @@ impl SctkApplicationState {
                 error!("Failed to schedule engine async jobs: {}", err);
             };
         });
+
+        let locale_changes = SctkSettingsHandler::read_and_monitor_locale_changes(
+            self.engine.downgrade(),
+            self.locales.clone(),
+        );
+        if let Err(err) = self.async_scheduler.schedule(locale_changes) {
+            error!("Failed to schedule engine async jobs: {}", err);
+        }
+    }
+
+    /// Replies to `read` once the selection owner sent its data, without
+    /// blocking the event loop meanwhile.
+    fn schedule_clipboard_read(&self, read: PendingClipboardRead) {
+        let PendingClipboardRead { offer, pipe, reply } = read;
+        let pipe = match self.loop_handle.adapt_io(pipe) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                error!("Failed to read clipboard data: {}", err);
+                return reply(Err(ClipboardReadError::Unavailable));
+            }
+        };
+
+        // The read is dropped if it can't be scheduled, in which case the reply
+        // is taken back to report the failure.
+        let reply = Rc::new(Cell::new(Some(reply)));
+        let clipboard = self.clipboard.clone();
+        let read = {
+            let reply = reply.clone();
+            async move {
+                let result = read_offered_data(pipe, || clipboard.is_selection_offer(&offer)).await;
+                if let Err(err) = &result {
+                    trace!("unable to load clipboard data: {}", err);
+                }
+                if let Some(reply) = reply.take() {
+                    reply(result.map_err(Into::into));
+                }
+                Ok(())
+            }
+        };
+
+        if let Err(err) = self.async_scheduler.schedule(read) {
+            error!("Failed to schedule clipboard read: {}", err);
+            if let Some(reply) = reply.take() {
+                reply(Err(ClipboardReadError::Unavailable));
+            }
+        }
     }
 
     /// Find the maximum refresh rate from the surface current outputs.
@@ -374,20 +823,12 @@ impl SctkApplicationState {
     }
 
     fn send_key_event(&self, event: SctkKeyEvent) {
-        self.engine.send_key_event(event.clone().into());
-
-        // The `flutter/keyevent`'s are considered legacy but they are still
-        // required for now [0][1], so the current implementation is mostly
-        // using them as a "flush" event for `flutter/keydata` messages.
-        //
         // TODO: Remove `KeyEventPlugin` once it is no longer *required* for
-        // keyboard handling (planned for Q4 2024 [2]).
+        // keyboard handling (planned for Q4 2024 [0]).
         //
-        // [0](https://github.com/flutter/flutter/pull/132533)
-        // [1](https://github.com/flutter/flutter/issues/136419)
-        // [2](https://github.com/flutter/flutter/issues/136419)
+        // [0](https://github.com/flutter/flutter/issues/136419)
         self.with_plugin(|keyevent: &KeyEventPlugin| {
-            keyevent.key_action(event.into());
+            keyevent.send_key_event(&self.engine, event.clone().into(), event.into());
         });
     }
 
@@ -445,7 +886,7 @@ impl SctkApplicationState {
                     // selected text).
                 }
                 _ => {
-                    let Some(text) = event.event.utf8 else {
+                    let Some(text) = event.character() else {
                         return;
                     };
 
@@ -464,6 +905,7 @@ impl SctkApplicationState {
 }
 
 delegate_compositor!(SctkApplicationState);
+delegate_subcompositor!(SctkApplicationState);
 delegate_output!(SctkApplicationState);
 delegate_shm!(SctkApplicationState);
 
@@ -474,6 +916,10 @@ delegate_seat!(SctkApplicationState);
 delegate_pointer!(SctkApplicationState);
 delegate_keyboard!(SctkApplicationState);
 
+delegate_data_device!(SctkApplicationState);
+
+delegate_primary_selection!(SctkApplicationState);
+
 delegate_registry!(SctkApplicationState);
 
 impl ProvidesRegistryState for SctkApplicationState {
@@ -499,14 +945,19 @@ impl CompositorHandler for SctkApplicationState {
         );
 
         let Some(window) = self.find_window_by_surface_id_mut(surface.id()) else {
-            warn!(
-                "[{}] ignoring `scale_factor_changed` event for unknown flutter window",
-                surface.id()
-            );
+            if !is_subsurface(surface) {
+                warn!(
+                    "[{}] ignoring `scale_factor_changed` event for unknown flutter window",
+                    surface.id()
+                );
+            }
             return;
         };
 
         window.scale_factor_changed(conn, surface, new_scale_factor);
+
+        let xdg_toplevel_id = window.xdg_toplevel_id();
+        self.schedule_resize_recovery(xdg_toplevel_id);
     }
 
     fn transform_changed(
@@ -530,7 +981,16 @@ impl CompositorHandler for SctkApplicationState {
         surface: &WlSurface,
         time: u32,
     ) {
-        let baton = self.vsync_handler.lock().load_pending_baton();
+        // Only return a baton the engine is still waiting for.
+        if !self.engine.has_pending_frame() {
+            trace!("[{}] frame callback without pending frame", surface.id());
+            return;
+        }
+
+        let Some(baton) = self.vsync_handler.lock().take_pending_baton() else {
+            trace!("[{}] frame callback without pending baton", surface.id());
+            return;
+        };
         trace!(
             "[{} baton: {} time: {}] frame callback",
             surface.id(),
@@ -559,10 +1019,12 @@ impl CompositorHandler for SctkApplicationState {
         trace!("[{}] entered {}", surface.id(), output.id());
 
         let Some(window) = self.find_window_by_surface_id_mut(surface.id()) else {
-            warn!(
-                "[{}] ignoring `surface_enter` event for unknown flutter window",
-                surface.id()
-            );
+            if !is_subsurface(surface) {
+                warn!(
+                    "[{}] ignoring `surface_enter` event for unknown flutter window",
+                    surface.id()
+                );
+            }
             return;
         };
 
@@ -579,10 +1041,12 @@ impl CompositorHandler for SctkApplicationState {
         trace!("[{}] left {}", surface.id(), output.id());
 
         let Some(window) = self.find_window_by_surface_id_mut(surface.id()) else {
-            warn!(
-                "[{}] ignoring `surface_leave` event for unknown flutter window",
-                surface.id()
-            );
+            if !is_subsurface(surface) {
+                warn!(
+                    "[{}] ignoring `surface_leave` event for unknown flutter window",
+                    surface.id()
+                );
+            }
             return;
         };
 
@@ -590,6 +1054,14 @@ impl CompositorHandler for SctkApplicationState {
     }
 }
 
+/// Whether `surface` is a subsurface (e.g.: of an overlay), whose scale and
+/// outputs are the ones of its window.
+fn is_subsurface(surface: &WlSurface) -> bool {
+    surface
+        .data::<SurfaceData>()
+        .is_some_and(|data| data.parent_surface().is_some())
+}
+
 impl ShmHandler for SctkApplicationState {
     fn shm_state(&mut self) -> &mut Shm {
         &mut self.shm_state
@@ -605,6 +1077,10 @@ impl PointerHandler for SctkApplicationState {
         events: &[PointerEvent],
     ) {
         for event in events {
+            if let PointerEventKind::Press { serial, .. } = event.kind {
+                self.clipboard.set_serial(serial);
+            }
+
             let Some(window) = self.find_window_by_surface_id_mut(event.surface.id()) else {
                 warn!(
                     "[{}] ignoring pointer event for unknown flutter window",
@@ -618,6 +1094,167 @@ impl PointerHandler for SctkApplicationState {
     }
 }
 
+impl Dispatch<ZwpPointerGesturesV1, ()> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        _pointer_gestures: &ZwpPointerGesturesV1,
+        _event: zwp_pointer_gestures_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // `zwp_pointer_gestures_v1` has no events
+    }
+}
+
+impl Dispatch<ZwpPointerGesturePinchV1, PinchGestureData> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        _pinch: &ZwpPointerGesturePinchV1,
+        event: zwp_pointer_gesture_pinch_v1::Event,
+        data: &PinchGestureData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let Some((surface, phase, gesture)) = data.handle_event(event) else {
+            return;
+        };
+
+        let Some(window) = state.find_window_by_surface_id_mut(surface.id()) else {
+            warn!(
+                "[{}] ignoring pinch gesture for unknown flutter window",
+                surface.id()
+            );
+            return;
+        };
+
+        window.pinch_gesture_event(&data.pointer, phase, gesture);
+    }
+}
+
+impl Dispatch<ZwpTextInputManagerV3, ()> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        _manager: &ZwpTextInputManagerV3,
+        _event: zwp_text_input_manager_v3::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // `zwp_text_input_manager_v3` has no events
+    }
+}
+
+impl Dispatch<ZwpTextInputV3, TextInputData> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        text_input: &ZwpTextInputV3,
+        event: zwp_text_input_v3::Event,
+        data: &TextInputData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let Some(event) = data.handle_event(event) else {
+            return;
+        };
+
+        // The handler is called by the text input plugin, so it must not be
+        // locked while editing the text.
+        let edit = match event {
+            TextInputEvent::Enter => {
+                state.text_input_handler.lock().enter(text_input);
+                return;
+            }
+            TextInputEvent::Leave { composing } => {
+                state.text_input_handler.lock().leave(text_input);
+                if !composing {
+                    return;
+                }
+                InputMethodEdit::default()
+            }
+            TextInputEvent::Done(edit) => edit,
+        };
+
+        state.with_plugin_mut(|text_input: &mut TextInputPlugin| {
+            text_input.apply_input_method_edit(&edit);
+        });
+    }
+}
+
+impl Dispatch<WpViewporter, ()> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        _viewporter: &WpViewporter,
+        _event: wp_viewporter::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // `wp_viewporter` has no events
+    }
+}
+
+impl Dispatch<WpViewport, ()> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        _viewport: &WpViewport,
+        _event: wp_viewport::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // `wp_viewport` has no events
+    }
+}
+
+impl Dispatch<WpFractionalScaleManagerV1, ()> for SctkApplicationState {
+    fn event(
+        _state: &mut Self,
+        _manager: &WpFractionalScaleManagerV1,
+        _event: wp_fractional_scale_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // `wp_fractional_scale_manager_v1` has no events
+    }
+}
+
+impl Dispatch<WpFractionalScaleV1, WlSurface> for SctkApplicationState {
+    fn event(
+        state: &mut Self,
+        _fractional_scale: &WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        surface: &WlSurface,
+        conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let wp_fractional_scale_v1::Event::PreferredScale { scale } = event else {
+            return;
+        };
+
+        let new_scale_factor = fractional_scale_factor(scale);
+        trace!(
+            "[{}] fractional scale changed: {}",
+            surface.id(),
+            new_scale_factor
+        );
+
+        let Some(window) = state.find_window_by_surface_id_mut(surface.id()) else {
+            warn!(
+                "[{}] ignoring fractional scale change for unknown flutter window",
+                surface.id()
+            );
+            return;
+        };
+
+        window.fractional_scale_changed(conn, surface, new_scale_factor);
+
+        let xdg_toplevel_id = window.xdg_toplevel_id();
+        state.schedule_resize_recovery(xdg_toplevel_id);
+    }
+}
+
 impl KeyboardHandler for SctkApplicationState {
     fn enter(
         &mut self,
@@ -625,10 +1262,12 @@ impl KeyboardHandler for SctkApplicationState {
         _qh: &QueueHandle<Self>,
         _keyboard: &WlKeyboard,
         _surface: &WlSurface,
-        _serial: u32,
+        serial: u32,
         raw: &[u32],
         keysyms: &[Keysym],
     ) {
+        self.clipboard.set_serial(serial);
+
         let synthesized_events = self
             .keyboard_handler
             .lock()
@@ -655,7 +1294,7 @@ impl KeyboardHandler for SctkApplicationState {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _keyboard: &WlKeyboard,
-        _serial: u32,
+        serial: u32,
         event: KeyEvent,
     ) {
         trace!(
@@ -663,27 +1302,37 @@ impl KeyboardHandler for SctkApplicationState {
             event.keysym.name().unwrap_or("[unknown]"),
         );
 
-        if self
-            .keyboard_handler
-            .lock()
-            .press_key(event.clone())
-            .is_err()
-        {
-            error!(
-                "A key was pressed which was already found in internal state. Ignoring {:?}",
-                event
-            );
-            return;
-        };
+        self.clipboard.set_serial(serial);
 
-        self.press_key_or_repeat(SctkKeyEvent::new(
+        let sctk_event = SctkKeyEvent::new(
             FlutterKeyEventDeviceType::Keyboard,
-            event,
+            event.clone(),
             FlutterKeyEventType::Down,
             None,
             self.modifiers,
             false,
-        ));
+        );
+
+        let mut keyboard_handler = self.keyboard_handler.lock();
+        let key_event = FlutterKeyEvent::from(sctk_event.clone())
+            .deduplicate_down(|physical| keyboard_handler.is_pressed(physical));
+
+        if key_event.is_empty() {
+            drop(keyboard_handler);
+            warn!(
+                "A key was pressed which was already found in internal state. Sending an empty event instead of {:?}",
+                event
+            );
+            self.engine.send_key_event(key_event, None);
+            return;
+        }
+
+        if keyboard_handler.press_key(event).is_err() {
+            error!("Unable to record key press in internal state");
+        }
+        drop(keyboard_handler);
+
+        self.press_key_or_repeat(sctk_event);
     }
 
     fn release_key(
@@ -768,6 +1417,15 @@ impl SeatHandler for SctkApplicationState {
                 .map(|themed_pointer| themed_pointer.pointer().clone());
 
             if let Some(pointer) = pointer {
+                if let Some(pointer_gestures) = &self.pointer_gestures {
+                    let pinch = pointer_gestures.get_pinch_gesture(
+                        &pointer,
+                        qh,
+                        PinchGestureData::new(pointer.clone()),
+                    );
+                    self.pinch_gestures.insert(seat.id(), pinch);
+                }
+
                 self.pointers.insert(seat.id(), pointer);
             } else {
                 error!("Failed to create themed wayland pointer");
@@ -780,6 +1438,8 @@ impl SeatHandler for SctkApplicationState {
         }
 
         if capability == Capability::Keyboard {
+            self.clipboard.ensure_data_device(&seat);
+
             if let Ok(keyboard) = self.seat_state.get_keyboard_with_repeat(
                 qh,
                 &seat,
@@ -806,6 +1466,14 @@ impl SeatHandler for SctkApplicationState {
                 error!("Failed to get keyboard");
                 self.keyboards.remove(&seat.id());
             }
+
+            if let Some(text_input_manager) = &self.text_input_manager {
+                let text_input =
+                    text_input_manager.get_text_input(&seat, qh, TextInputData::default());
+                self.text_input_handler
+                    .lock()
+                    .set_text_input_for_seat(seat.id(), text_input);
+            }
         }
     }
 
@@ -819,6 +1487,10 @@ impl SeatHandler for SctkApplicationState {
         if capability == Capability::Pointer {
             self.pointers.remove(&seat.id());
 
+            if let Some(pinch) = self.pinch_gestures.remove(&seat.id()) {
+                pinch.destroy();
+            }
+
             self.mouse_cursor_handler
                 .lock()
                 .remove_themed_pointer_for_seat(seat.id());
@@ -826,10 +1498,157 @@ impl SeatHandler for SctkApplicationState {
 
         if capability == Capability::Keyboard {
             self.keyboards.remove(&seat.id());
+
+            self.text_input_handler
+                .lock()
+                .remove_text_input_for_seat(seat.id());
         }
     }
 }
 
+// The clipboard selection is read on demand (see `SctkClipboard::load`), and
+// drag and drop is not supported, so most data device events are ignored.
+impl DataDeviceHandler for SctkApplicationState {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _data_device: &WlDataDevice,
+        _x: f64,
+        _y: f64,
+        _wl_surface: &WlSurface,
+    ) {
+    }
+
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: &WlDataDevice) {}
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _data_device: &WlDataDevice,
+        _x: f64,
+        _y: f64,
+    ) {
+    }
+
+    fn selection(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _data_device: &WlDataDevice,
+    ) {
+    }
+
+    fn drop_performed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _data_device: &WlDataDevice,
+    ) {
+    }
+}
+
+impl DataOfferHandler for SctkApplicationState {
+    fn source_actions(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        _actions: DndAction,
+    ) {
+    }
+
+    fn selected_action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        _actions: DndAction,
+    ) {
+    }
+}
+
+impl DataSourceHandler for SctkApplicationState {
+    fn accept_mime(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _mime: Option<String>,
+    ) {
+    }
+
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &WlDataSource,
+        mime: String,
+        fd: WritePipe,
+    ) {
+        self.clipboard.send_request(source, &mime, fd);
+    }
+
+    fn cancelled(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        self.clipboard.cancelled(source);
+    }
+
+    fn dnd_dropped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _source: &WlDataSource) {
+    }
+
+    fn dnd_finished(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+    ) {
+    }
+
+    fn action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _action: DndAction,
+    ) {
+    }
+}
+
+// The primary selection is only stored (see `SctkClipboard::store_primary`).
+impl PrimarySelectionDeviceHandler for SctkApplicationState {
+    fn selection(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _primary_selection_device: &ZwpPrimarySelectionDeviceV1,
+    ) {
+    }
+}
+
+impl PrimarySelectionSourceHandler for SctkApplicationState {
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &ZwpPrimarySelectionSourceV1,
+        mime: String,
+        write_pipe: WritePipe,
+    ) {
+        self.clipboard
+            .send_primary_request(source, &mime, write_pipe);
+    }
+
+    fn cancelled(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &ZwpPrimarySelectionSourceV1,
+    ) {
+        self.clipboard.primary_cancelled(source);
+    }
+}
+
 impl OutputHandler for SctkApplicationState {
     fn output_state(&mut self) -> &mut OutputState {
         &mut self.output_state
@@ -855,8 +1674,8 @@ impl OutputHandler for SctkApplicationState {
 }
 
 impl WindowHandler for SctkApplicationState {
-    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &Window) {
-        self.loop_signal.stop();
+    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, window: &Window) {
+        self.close_window(window.xdg_toplevel().id());
     }
 
     fn configure(
@@ -875,7 +1694,10 @@ impl WindowHandler for SctkApplicationState {
             configure.new_size.1.map_or(0, |v| v.get()),
         );
 
-        self.maybe_update_lifecycle_state(xdg_toplevel_id.clone(), configure.is_activated());
+        self.maybe_update_lifecycle_state(
+            xdg_toplevel_id.clone(),
+            WindowLifecycle::from_configure(&configure),
+        );
 
         let Some(window) = self.windows.get_mut(&xdg_toplevel_id) else {
             warn!(
@@ -886,11 +1708,14 @@ impl WindowHandler for SctkApplicationState {
         };
 
         if self.startup_synchronizer.is_engine_running {
-            window.configure(conn, configure, serial);
+            if let Some(interval) = window.configure(conn, configure, serial) {
+                self.schedule_window_metrics_flush(xdg_toplevel_id.clone(), interval);
+            }
+            self.schedule_resize_recovery(xdg_toplevel_id);
         } else {
             trace!("Skipped sending window metrics event because engine is not running yet");
             self.startup_synchronizer
-                .set_pending_configure(configure, serial);
+                .set_pending_configure(xdg_toplevel_id, configure, serial);
         }
     }
 }
@@ -908,6 +1733,9 @@ pub enum SctkApplicationCreateError {
     #[error(transparent)]
     CalloopInsertAsyncExecutorError(#[from] calloop::InsertError<Executor<SctkAsyncResult>>),
 
+    #[error(transparent)]
+    CalloopInsertChannelError(#[from] calloop::InsertError<Channel<FlutterViewId>>),
+
     #[error(transparent)]
     ConnectError(#[from] ConnectError),
 
@@ -933,14 +1761,50 @@ pub enum SctkApplicationRunError {
     InsertError(#[from] calloop::InsertError<Timer>),
 }
 
-fn insert_timer_source<Data>(handle: &LoopHandle<'static, Data>, timer: Option<Timer>) {
-    let Some(timer) = timer else {
-        return;
-    };
+// The platform task runner reports the deadline of its next pending task after
+// every event loop iteration. Instead of inserting (and dropping) a new timer
+// source for each of these deadlines, a single timer source is kept alive and
+// re-armed from its own callback, which runs the due tasks, for the deadline of
+// the earliest task still pending after them. The source only needs to be
+// replaced when a task is due *before* the currently scheduled deadline.
+#[derive(Default)]
+struct PlatformTaskTimer {
+    token: Option<RegistrationToken>,
+    scheduled: Option<Instant>,
+}
+
+impl PlatformTaskTimer {
+    /// Returns whether the timer source needs to be (re-)inserted in order to
+    /// fire on time for `deadline`.
+    fn needs_reschedule(&self, deadline: Option<Instant>) -> bool {
+        match (self.scheduled, deadline) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(scheduled), Some(deadline)) => deadline < scheduled,
+        }
+    }
 
-    handle
-        .insert_source(timer, |_, _, _| TimeoutAction::Drop)
-        .expect("Unable to insert timer source");
+    fn set_scheduled(&mut self, token: RegistrationToken, deadline: Instant) {
+        self.token = Some(token);
+        self.scheduled = Some(deadline);
+    }
+
+    /// Re-arms the timer for `next_deadline`, the deadline of the earliest task
+    /// pending once the due ones ran. It's in the past if a task fell due
+    /// while they ran (or was deferred), firing the timer again right away.
+    fn on_timeout(&mut self, next_deadline: Option<Instant>) -> TimeoutAction {
+        match next_deadline {
+            Some(deadline) => {
+                self.scheduled = Some(deadline);
+                TimeoutAction::ToInstant(deadline)
+            }
+            None => {
+                self.token = None;
+                self.scheduled = None;
+                TimeoutAction::Drop
+            }
+        }
+    }
 }
 
 // Trying to send a `WindowMetricsEvent` before the engine is running results in
@@ -958,9 +1822,13 @@ fn insert_timer_source<Data>(handle: &LoopHandle<'static, Data>, timer: Option<T
 //
 // [0]: https://github.com/flutter/engine/blob/e76c956498841e1ab458577d3892003e553e4f3c/shell/platform/embedder/embedder.cc#L2173-L2174
 // [1]: https://github.com/flutter/flutter/issues/144806
+//
+// Windows created before the engine is running (e.g.: secondary windows created
+// prior to `SctkApplication::run`) are synchronized in the same way, which is
+// why the latest pending configure event is tracked per window.
 #[derive(Default)]
 struct ImplicitWindowStartupSynchronizer {
-    pending_configure: Option<(WindowConfigure, u32)>,
+    pending_configures: HashMap<ObjectId, (WindowConfigure, u32)>,
     is_engine_running: bool,
 }
 
@@ -969,7 +1837,144 @@ impl ImplicitWindowStartupSynchronizer {
         Default::default()
     }
 
-    fn set_pending_configure(&mut self, configure: WindowConfigure, serial: u32) {
-        self.pending_configure = Some((configure, serial));
+    fn set_pending_configure(
+        &mut self,
+        xdg_toplevel_id: ObjectId,
+        configure: WindowConfigure,
+        serial: u32,
+    ) {
+        self.pending_configures
+            .insert(xdg_toplevel_id, (configure, serial));
+    }
+}
+
+/// The state of a window which is relevant to the app lifecycle state.
+#[derive(Debug, Clone, Copy, Default)]
+struct WindowLifecycle {
+    activated: bool,
+    /// Set by the compositor while the window isn't visible (e.g.: when it's
+    /// minimized, fully occluded, or on another workspace).
+    suspended: bool,
+}
+
+impl WindowLifecycle {
+    fn from_configure(configure: &WindowConfigure) -> Self {
+        Self {
+            activated: configure.is_activated(),
+            suspended: configure.state.contains(WindowState::SUSPENDED),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AppLifecycleState {
+    Resumed,
+    Inactive,
+    /// The framework stops scheduling frames (and thus animations) while the
+    /// app is paused, which avoids rendering windows nobody can see.
+    Paused,
+}
+
+impl AppLifecycleState {
+    /// Whether the framework requests frames in this state.
+    fn frames_enabled(self) -> bool {
+        self != AppLifecycleState::Paused
+    }
+}
+
+/// The app is resumed while any of its windows is activated, and paused once
+/// all of them are suspended.
+fn app_lifecycle_state(windows: impl Iterator<Item = WindowLifecycle>) -> AppLifecycleState {
+    let (mut any, mut activated, mut visible) = (false, false, false);
+    for window in windows {
+        any = true;
+        activated |= window.activated;
+        visible |= !window.suspended;
+    }
+
+    match (any, activated, visible) {
+        (_, true, _) => AppLifecycleState::Resumed,
+        (true, false, false) => AppLifecycleState::Paused,
+        _ => AppLifecycleState::Inactive,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn platform_task_timer_only_reschedules_for_earlier_deadlines() {
+        let now = Instant::now();
+        let mut timer = PlatformTaskTimer::default();
+
+        assert!(!timer.needs_reschedule(None));
+        assert!(timer.needs_reschedule(Some(now + Duration::from_millis(10))));
+        timer.scheduled = Some(now + Duration::from_millis(10));
+
+        assert!(!timer.needs_reschedule(Some(now + Duration::from_millis(10))));
+        assert!(!timer.needs_reschedule(Some(now + Duration::from_millis(20))));
+        assert!(timer.needs_reschedule(Some(now + Duration::from_millis(5))));
+    }
+
+    #[test]
+    fn hidden_windows_pause_frames_until_shown() {
+        let shown = WindowLifecycle {
+            activated: true,
+            suspended: false,
+        };
+        let hidden = WindowLifecycle {
+            activated: false,
+            suspended: true,
+        };
+
+        let state = app_lifecycle_state([shown].into_iter());
+        assert_eq!(state, AppLifecycleState::Resumed);
+        assert!(state.frames_enabled());
+
+        let state = app_lifecycle_state([hidden].into_iter());
+        assert_eq!(state, AppLifecycleState::Paused);
+        assert!(!state.frames_enabled());
+
+        // Frames are still needed while any window is visible.
+        let state = app_lifecycle_state([hidden, WindowLifecycle::default()].into_iter());
+        assert_eq!(state, AppLifecycleState::Inactive);
+        assert!(state.frames_enabled());
+
+        let state = app_lifecycle_state([hidden, shown].into_iter());
+        assert_eq!(state, AppLifecycleState::Resumed);
+        assert!(state.frames_enabled());
+    }
+
+    #[test]
+    fn platform_task_timer_rearms_for_the_earliest_pending_deadline() {
+        let now = Instant::now();
+        let mut timer = PlatformTaskTimer {
+            scheduled: Some(now),
+            ..Default::default()
+        };
+
+        let later = now + Duration::from_millis(20);
+        assert!(matches!(
+            timer.on_timeout(Some(later)),
+            TimeoutAction::ToInstant(deadline) if deadline == later
+        ));
+        assert_eq!(timer.scheduled, Some(later));
+
+        // A task falling due while the due tasks ran fires the timer again
+        // right away, rather than waiting for a later deadline.
+        let fell_due = later + Duration::from_millis(1);
+        assert!(matches!(
+            timer.on_timeout(Some(fell_due)),
+            TimeoutAction::ToInstant(deadline) if deadline == fell_due
+        ));
+        assert!(!timer.needs_reschedule(Some(fell_due)));
+        assert!(!timer.needs_reschedule(Some(fell_due + Duration::from_millis(5))));
+
+        assert!(matches!(timer.on_timeout(None), TimeoutAction::Drop));
+        assert_eq!(timer.scheduled, None);
+        assert!(timer.needs_reschedule(Some(fell_due)));
     }
 }
@@ -0,0 +1,475 @@
+//! Clipboard support based on `wl_data_device`, offering copied data both as
+//! plain text and (when available) as HTML, along with the primary selection
+//! (`zwp_primary_selection_device_v1`), which only holds plain text.
+//!
+//! Both are optional: without the corresponding global, storing is a no-op
+//! (and reading the clipboard fails with [`ClipboardError::NoDataDevice`]).
+use std::{
+    io::{self, Read, Write},
+    os::fd::AsFd,
+};
+
+use calloop::io::Async;
+use flust_plugins::platform::{
+    ClipboardContents, ClipboardReadError, ClipboardReply, TEXT_HTML_MIME_TYPE,
+};
+use futures_lite::AsyncReadExt;
+use parking_lot::Mutex;
+use smithay_client_toolkit::{
+    data_device_manager::{
+        data_device::DataDevice, data_offer::DataOfferError, data_source::CopyPasteSource,
+        DataDeviceManagerState, ReadPipe, WritePipe,
+    },
+    primary_selection::{
+        device::PrimarySelectionDevice, selection::PrimarySelectionSource,
+        PrimarySelectionManagerState,
+    },
+    reexports::protocols::wp::primary_selection::zv1::client::zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1,
+};
+use thiserror::Error;
+use tracing::{trace, warn};
+use wayland_backend::client::WaylandError;
+use wayland_client::{
+    protocol::{wl_data_offer::WlDataOffer, wl_data_source::WlDataSource, wl_seat::WlSeat},
+    Connection, QueueHandle,
+};
+
+use crate::application::SctkApplicationState;
+
+/// Plain text MIME types, in order of preference.
+const TEXT_PLAIN_MIME_TYPES: [&str; 3] = ["text/plain;charset=utf-8", "text/plain", "UTF8_STRING"];
+
+pub(crate) struct SctkClipboard {
+    conn: Connection,
+    qh: QueueHandle<SctkApplicationState>,
+    manager: Option<DataDeviceManagerState>,
+    primary_manager: Option<PrimarySelectionManagerState>,
+    inner: Mutex<SctkClipboardInner>,
+}
+
+/// Clipboard data being received, either from this client or from another one.
+pub(crate) enum ClipboardTransfer {
+    /// The data of our own selection, which is available right away.
+    Own(String),
+    /// A pipe through which the owner of `offer` sends its data.
+    Offer { offer: WlDataOffer, pipe: ReadPipe },
+}
+
+/// A clipboard read waiting for the data sent by another client, completed on
+/// the event loop (see [`read_offered_data`]).
+pub(crate) struct PendingClipboardRead {
+    pub(crate) offer: WlDataOffer,
+    pub(crate) pipe: ReadPipe,
+    pub(crate) reply: ClipboardReply,
+}
+
+#[derive(Default)]
+struct SctkClipboardInner {
+    device: Option<DataDevice>,
+    primary_device: Option<PrimarySelectionDevice>,
+    serial: u32,
+    selection: Option<(CopyPasteSource, ClipboardContents)>,
+    primary_selection: Option<(PrimarySelectionSource, ClipboardContents)>,
+}
+
+impl SctkClipboard {
+    pub(crate) fn new(
+        conn: Connection,
+        qh: QueueHandle<SctkApplicationState>,
+        manager: Option<DataDeviceManagerState>,
+        primary_manager: Option<PrimarySelectionManagerState>,
+    ) -> Self {
+        Self {
+            conn,
+            qh,
+            manager,
+            primary_manager,
+            inner: Default::default(),
+        }
+    }
+
+    /// Creates the data devices of the clipboard and of the primary selection
+    /// for `seat`, unless they were already created.
+    pub(crate) fn ensure_data_device(&self, seat: &WlSeat) {
+        let mut inner = self.inner.lock();
+        if inner.device.is_none() {
+            inner.device = self
+                .manager
+                .as_ref()
+                .map(|manager| manager.get_data_device(&self.qh, seat));
+        }
+        if inner.primary_device.is_none() {
+            inner.primary_device = self
+                .primary_manager
+                .as_ref()
+                .map(|manager| manager.get_selection_device(&self.qh, seat));
+        }
+    }
+
+    /// Records the serial of the latest input event, which is required for
+    /// setting the selection.
+    pub(crate) fn set_serial(&self, serial: u32) {
+        self.inner.lock().serial = serial;
+    }
+
+    pub(crate) fn store(&self, contents: ClipboardContents) {
+        let mut inner = self.inner.lock();
+        let (Some(manager), Some(device)) = (&self.manager, &inner.device) else {
+            warn!("Unable to store clipboard data without a data device");
+            return;
+        };
+
+        let source = manager.create_copy_paste_source(&self.qh, offered_mime_types(&contents));
+        source.set_selection(device, inner.serial);
+        inner.selection = Some((source, contents));
+
+        if let Err(err) = self.conn.flush() {
+            warn!("Failed to flush clipboard selection: {}", err);
+        }
+    }
+
+    pub(crate) fn store_primary(&self, text: String) {
+        let mut inner = self.inner.lock();
+        let (Some(manager), Some(device)) = (&self.primary_manager, &inner.primary_device) else {
+            warn!("Unable to store the primary selection without a primary selection device");
+            return;
+        };
+
+        let contents = ClipboardContents { text, html: None };
+        let source = manager.create_selection_source(&self.qh, offered_mime_types(&contents));
+        source.set_selection(device, inner.serial);
+        inner.primary_selection = Some((source, contents));
+
+        if let Err(err) = self.conn.flush() {
+            warn!("Failed to flush primary selection: {}", err);
+        }
+    }
+
+    /// Loads the clipboard contents in the format negotiated for
+    /// `requested_mime_type` (see [`select_mime_type`]). This blocks until the
+    /// selection owner sent its data, see [`Self::receive`] for reading it
+    /// asynchronously.
+    pub(crate) fn load(&self, requested_mime_type: &str) -> Result<String, ClipboardError> {
+        match self.receive(requested_mime_type)? {
+            ClipboardTransfer::Own(text) => Ok(text),
+            ClipboardTransfer::Offer { mut pipe, .. } => {
+                let mut text = String::new();
+                pipe.read_to_string(&mut text)?;
+                Ok(text)
+            }
+        }
+    }
+
+    /// Requests the clipboard contents in the format negotiated for
+    /// `requested_mime_type`, without waiting for the selection owner to send
+    /// them.
+    pub(crate) fn receive(
+        &self,
+        requested_mime_type: &str,
+    ) -> Result<ClipboardTransfer, ClipboardError> {
+        let inner = self.inner.lock();
+
+        // Reading our own selection through the data device would deadlock, as
+        // the data is sent from this same thread.
+        if let Some((_, contents)) = &inner.selection {
+            let offered = offered_mime_types(contents);
+            let mime_type = select_mime_type(&offered, requested_mime_type)
+                .ok_or(ClipboardError::UnsupportedMimeType)?;
+            return contents_for_mime_type(contents, mime_type)
+                .map(|text| ClipboardTransfer::Own(text.to_owned()))
+                .ok_or(ClipboardError::UnsupportedMimeType);
+        }
+
+        let offer = inner
+            .device
+            .as_ref()
+            .ok_or(ClipboardError::NoDataDevice)?
+            .data()
+            .selection_offer()
+            .ok_or(ClipboardError::Empty)?;
+
+        let mime_type = offer
+            .with_mime_types(|offered| {
+                select_mime_type(offered, requested_mime_type).map(str::to_owned)
+            })
+            .ok_or(ClipboardError::UnsupportedMimeType)?;
+
+        trace!("receiving clipboard data as {}", mime_type);
+        let pipe = offer.receive(mime_type)?;
+        self.conn.flush()?;
+
+        Ok(ClipboardTransfer::Offer {
+            offer: offer.inner().clone(),
+            pipe,
+        })
+    }
+
+    /// Whether `offer` is still the current selection, i.e.: its owner
+    /// neither replaced nor withdrew it.
+    pub(crate) fn is_selection_offer(&self, offer: &WlDataOffer) -> bool {
+        let inner = self.inner.lock();
+        inner
+            .device
+            .as_ref()
+            .and_then(|device| device.data().selection_offer())
+            .is_some_and(|selection| selection.inner() == offer)
+    }
+
+    /// Sends the data of the current selection, in the requested format.
+    pub(crate) fn send_request(&self, source: &WlDataSource, mime_type: &str, pipe: WritePipe) {
+        let inner = self.inner.lock();
+        match &inner.selection {
+            Some((selection, contents)) if selection.inner() == source => {
+                send_contents(contents, mime_type, pipe)
+            }
+            _ => {}
+        }
+    }
+
+    /// Sends the data of the current primary selection, in the requested
+    /// format.
+    pub(crate) fn send_primary_request(
+        &self,
+        source: &ZwpPrimarySelectionSourceV1,
+        mime_type: &str,
+        pipe: WritePipe,
+    ) {
+        let inner = self.inner.lock();
+        match &inner.primary_selection {
+            Some((selection, contents)) if selection.inner() == source => {
+                send_contents(contents, mime_type, pipe)
+            }
+            _ => {}
+        }
+    }
+
+    /// Clears the current selection if it was replaced (e.g.: by another
+    /// client).
+    pub(crate) fn cancelled(&self, source: &WlDataSource) {
+        let mut inner = self.inner.lock();
+        if matches!(&inner.selection, Some((selection, _)) if selection.inner() == source) {
+            inner.selection = None;
+        }
+    }
+
+    /// Clears the current primary selection if it was replaced.
+    pub(crate) fn primary_cancelled(&self, source: &ZwpPrimarySelectionSourceV1) {
+        let mut inner = self.inner.lock();
+        if matches!(&inner.primary_selection, Some((selection, _)) if selection.inner() == source) {
+            inner.primary_selection = None;
+        }
+    }
+}
+
+fn send_contents(contents: &ClipboardContents, mime_type: &str, mut pipe: WritePipe) {
+    let Some(data) = contents_for_mime_type(contents, mime_type) else {
+        warn!(
+            "Clipboard data requested for unsupported type: {}",
+            mime_type
+        );
+        return;
+    };
+
+    if let Err(err) = pipe.write_all(data.as_bytes()) {
+        warn!("Failed to send clipboard data: {}", err);
+    }
+}
+
+/// Reads the data sent through `pipe` by the selection owner, without blocking
+/// the event loop. The owner went away (e.g.: because it exited) if it closed
+/// the pipe without sending anything and its offer isn't the selection
+/// anymore (according to `is_offered`).
+pub(crate) async fn read_offered_data<F: AsFd + Read>(
+    mut pipe: Async<'_, F>,
+    is_offered: impl FnOnce() -> bool,
+) -> Result<String, ClipboardError> {
+    let mut text = String::new();
+    pipe.read_to_string(&mut text).await?;
+
+    if text.is_empty() && !is_offered() {
+        return Err(ClipboardError::OwnerGone);
+    }
+    Ok(text)
+}
+
+/// The MIME types offered for `contents`, HTML first.
+fn offered_mime_types(contents: &ClipboardContents) -> Vec<String> {
+    let html = contents.html.as_ref().map(|_| TEXT_HTML_MIME_TYPE);
+
+    html.into_iter()
+        .chain(TEXT_PLAIN_MIME_TYPES)
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Selects which of the `offered` MIME types to read. HTML is preferred when
+/// requested (and offered), falling back to plain text otherwise.
+fn select_mime_type<'a>(offered: &'a [String], requested_mime_type: &str) -> Option<&'a str> {
+    let html = (requested_mime_type == TEXT_HTML_MIME_TYPE).then_some(TEXT_HTML_MIME_TYPE);
+
+    html.into_iter()
+        .chain(TEXT_PLAIN_MIME_TYPES)
+        .find_map(|mime_type| offered.iter().find(|offered| *offered == mime_type))
+        .map(String::as_str)
+}
+
+fn contents_for_mime_type<'a>(contents: &'a ClipboardContents, mime_type: &str) -> Option<&'a str> {
+    if mime_type == TEXT_HTML_MIME_TYPE {
+        contents.html.as_deref()
+    } else if TEXT_PLAIN_MIME_TYPES.contains(&mime_type) {
+        Some(&contents.text)
+    } else {
+        None
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ClipboardError {
+    #[error("No data device available")]
+    NoDataDevice,
+
+    #[error("Clipboard is empty")]
+    Empty,
+
+    #[error("Clipboard data is not available in a supported format")]
+    UnsupportedMimeType,
+
+    #[error("The clipboard owner went away before sending its data")]
+    OwnerGone,
+
+    #[error(transparent)]
+    DataOfferError(#[from] DataOfferError),
+
+    #[error(transparent)]
+    WaylandError(#[from] WaylandError),
+
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+}
+
+impl From<ClipboardError> for ClipboardReadError {
+    fn from(err: ClipboardError) -> Self {
+        match err {
+            ClipboardError::OwnerGone => ClipboardReadError::OwnerGone,
+            _ => ClipboardReadError::Unavailable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use calloop::{
+        timer::{TimeoutAction, Timer},
+        EventLoop,
+    };
+
+    use super::*;
+
+    #[test]
+    fn rich_text_is_offered_and_read_back_by_mime_type() {
+        let contents = ClipboardContents {
+            text: "bold".into(),
+            html: Some("<b>bold</b>".into()),
+        };
+        let offered = offered_mime_types(&contents);
+        assert_eq!(
+            offered.first().map(String::as_str),
+            Some(TEXT_HTML_MIME_TYPE)
+        );
+
+        let html = select_mime_type(&offered, TEXT_HTML_MIME_TYPE).unwrap();
+        assert_eq!(contents_for_mime_type(&contents, html), Some("<b>bold</b>"));
+
+        let text = select_mime_type(&offered, "text/plain").unwrap();
+        assert_eq!(text, "text/plain;charset=utf-8");
+        assert_eq!(contents_for_mime_type(&contents, text), Some("bold"));
+    }
+
+    #[test]
+    fn html_request_falls_back_to_plain_text() {
+        let offered = vec!["UTF8_STRING".to_owned(), "image/png".to_owned()];
+        assert_eq!(
+            select_mime_type(&offered, TEXT_HTML_MIME_TYPE),
+            Some("UTF8_STRING")
+        );
+        assert_eq!(
+            select_mime_type(&["image/png".to_owned()], "text/plain"),
+            None
+        );
+    }
+
+    type ReadResult = Option<Result<String, ClipboardError>>;
+
+    /// Reads the data sent through `reader` on `event_loop`, dispatching it
+    /// until the read completes.
+    fn read_on_event_loop(
+        event_loop: &mut EventLoop<'static, ReadResult>,
+        reader: io::PipeReader,
+        is_offered: bool,
+    ) -> Result<String, ClipboardError> {
+        let (executor, scheduler) = calloop::futures::executor().unwrap();
+        event_loop
+            .handle()
+            .insert_source(executor, |result, _, read| *read = Some(result))
+            .unwrap();
+
+        let pipe = event_loop.handle().adapt_io(reader).unwrap();
+        scheduler
+            .schedule(read_offered_data(pipe, move || is_offered))
+            .unwrap();
+
+        let mut read = None;
+        while read.is_none() {
+            event_loop
+                .dispatch(Duration::from_secs(1), &mut read)
+                .unwrap();
+        }
+        read.unwrap()
+    }
+
+    #[test]
+    fn clipboard_read_does_not_block_the_event_loop() {
+        let mut event_loop = EventLoop::try_new().unwrap();
+        let (reader, mut writer) = io::pipe().unwrap();
+        let (loop_ran, loop_running) = mpsc::channel();
+
+        // The owner only sends the rest of its data once the event loop ran
+        // while waiting for it.
+        let owner = thread::spawn(move || {
+            writer.write_all(b"copied ").unwrap();
+            let loop_ran = loop_running.recv_timeout(Duration::from_secs(5)).is_ok();
+            writer.write_all(b"text").unwrap();
+            loop_ran
+        });
+        event_loop
+            .handle()
+            .insert_source(
+                Timer::from_duration(Duration::from_millis(10)),
+                move |_, _, _| {
+                    let _ = loop_ran.send(());
+                    TimeoutAction::Drop
+                },
+            )
+            .unwrap();
+
+        let text = read_on_event_loop(&mut event_loop, reader, true);
+        assert!(owner.join().unwrap());
+        assert_eq!(text.unwrap(), "copied text");
+    }
+
+    #[test]
+    fn clipboard_owner_gone_is_reported() {
+        let mut event_loop = EventLoop::try_new().unwrap();
+
+        let (reader, _) = io::pipe().unwrap();
+        let text = read_on_event_loop(&mut event_loop, reader, false);
+        assert!(matches!(text, Err(ClipboardError::OwnerGone)));
+
+        // An owner still offering the selection just sent nothing.
+        let (reader, _) = io::pipe().unwrap();
+        let text = read_on_event_loop(&mut event_loop, reader, true);
+        assert_eq!(text.unwrap(), "");
+    }
+}
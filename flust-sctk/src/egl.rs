@@ -2,6 +2,8 @@ use std::{num::NonZeroU32, ptr::NonNull};
 
 use dpi::PhysicalSize;
 use flust_glutin::builder::{ContextBuildError, ContextBuilder, FlutterEGLContext};
+use flust_glutin::context::SharedContext;
+use flust_runner_api::SurfaceBuffering;
 use glutin::surface::SwapInterval;
 use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
@@ -13,6 +15,9 @@ pub(crate) trait FlutterEGLContextWaylandExt {
     fn new_wayland_context(
         surface: &wl_surface::WlSurface,
         size: PhysicalSize<u32>,
+        buffering: SurfaceBuffering,
+        shared_context: Option<SharedContext>,
+        transparent: bool,
     ) -> Result<FlutterEGLContext, CreateWaylandContextError>;
 }
 
@@ -20,6 +25,9 @@ impl FlutterEGLContextWaylandExt for FlutterEGLContext {
     fn new_wayland_context(
         surface: &wl_surface::WlSurface,
         size: PhysicalSize<u32>,
+        buffering: SurfaceBuffering,
+        shared_context: Option<SharedContext>,
+        transparent: bool,
     ) -> Result<FlutterEGLContext, CreateWaylandContextError> {
         let display = NonNull::new(
             surface
@@ -41,14 +49,41 @@ impl FlutterEGLContextWaylandExt for FlutterEGLContext {
         let (context, resource_context) = ContextBuilder::new()
             .with_raw_display_handle(raw_display_handle)
             .with_raw_window_handle(raw_window_handle)
-            .with_swap_interval(SwapInterval::DontWait)
+            .with_swap_interval(swap_interval_for_buffering(buffering))
             .with_size(size.non_zero())
+            .with_shared_context(shared_context)
+            .with_transparency(transparent)
             .build()?;
 
         Ok((context, resource_context))
     }
 }
 
+/// Checks that the contexts created for the windows meet the requirements of
+/// the Impeller renderer, with a context created for `surface` (which isn't
+/// presented).
+pub(crate) fn check_impeller_requirements(surface: &wl_surface::WlSurface) -> Result<(), String> {
+    let (mut context, _) = FlutterEGLContext::new_wayland_context(
+        surface,
+        PhysicalSize::new(1, 1),
+        SurfaceBuffering::Double,
+        None,
+        false,
+    )
+    .map_err(|err| err.to_string())?;
+
+    context
+        .check_impeller_requirements()
+        .map_err(|err| err.to_string())
+}
+
+fn swap_interval_for_buffering(buffering: SurfaceBuffering) -> SwapInterval {
+    match buffering {
+        SurfaceBuffering::Double => SwapInterval::Wait(NonZeroU32::MIN),
+        SurfaceBuffering::Triple => SwapInterval::DontWait,
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum CreateWaylandContextError {
     #[error("Connection has been closed")]
@@ -69,3 +104,20 @@ impl NonZeroU32PhysicalSize for PhysicalSize<u32> {
         Some(PhysicalSize::new(w, h))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffering_preference_maps_to_swap_interval() {
+        assert_eq!(
+            swap_interval_for_buffering(SurfaceBuffering::Double),
+            SwapInterval::Wait(NonZeroU32::new(1).unwrap())
+        );
+        assert_eq!(
+            swap_interval_for_buffering(SurfaceBuffering::Triple),
+            SwapInterval::DontWait
+        );
+    }
+}
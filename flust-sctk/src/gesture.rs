@@ -0,0 +1,131 @@
+//! Trackpad pinch gestures (`zwp_pointer_gestures_v1`), sent to the engine as
+//! pan-zoom events so that `ScaleGestureRecognizer` works natively.
+use flust_engine::ffi::FlutterPointerPhase;
+use parking_lot::Mutex;
+use smithay_client_toolkit::reexports::protocols::wp::pointer_gestures::zv1::client::zwp_pointer_gesture_pinch_v1;
+use wayland_client::protocol::{wl_pointer::WlPointer, wl_surface::WlSurface};
+
+/// Bounds of the scale of a pinch gesture. Some touchpads report implausible
+/// scale values (e.g.: when fingers are lifted), which are clamped to this
+/// range.
+const MIN_PINCH_SCALE: f64 = 0.01;
+const MAX_PINCH_SCALE: f64 = 100.0;
+
+/// The state of a pinch gesture, accumulated since it began.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PinchGesture {
+    /// The pan offset, in logical pixels.
+    pub(crate) pan: (f64, f64),
+    pub(crate) scale: f64,
+    /// The clockwise rotation, in radians.
+    pub(crate) rotation: f64,
+}
+
+impl Default for PinchGesture {
+    fn default() -> Self {
+        Self {
+            pan: (0.0, 0.0),
+            scale: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+impl PinchGesture {
+    /// Applies a `zwp_pointer_gesture_pinch_v1.update` event, whose scale is
+    /// relative to the start of the gesture, but whose pan and rotation
+    /// (in degrees) are relative to the previous event. Invalid scale values
+    /// are ignored, keeping the previous scale.
+    fn update(&mut self, (dx, dy): (f64, f64), scale: f64, rotation: f64) {
+        self.pan.0 += dx;
+        self.pan.1 += dy;
+
+        if scale.is_finite() && scale > 0.0 {
+            self.scale = scale.clamp(MIN_PINCH_SCALE, MAX_PINCH_SCALE);
+        }
+
+        if rotation.is_finite() {
+            self.rotation += rotation.to_radians();
+        }
+    }
+}
+
+/// The user data of a `zwp_pointer_gesture_pinch_v1` object, tracking the
+/// gesture of the `wl_pointer` it was created for.
+pub(crate) struct PinchGestureData {
+    pub(crate) pointer: WlPointer,
+    inner: Mutex<PinchGestureDataInner>,
+}
+
+#[derive(Default)]
+struct PinchGestureDataInner {
+    surface: Option<WlSurface>,
+    gesture: PinchGesture,
+}
+
+impl PinchGestureData {
+    pub(crate) fn new(pointer: WlPointer) -> Self {
+        Self {
+            pointer,
+            inner: Default::default(),
+        }
+    }
+
+    /// Tracks a pinch event, returning the surface the gesture happens on
+    /// along with the pan-zoom event to send to the engine.
+    pub(crate) fn handle_event(
+        &self,
+        event: zwp_pointer_gesture_pinch_v1::Event,
+    ) -> Option<(WlSurface, FlutterPointerPhase, PinchGesture)> {
+        use zwp_pointer_gesture_pinch_v1::Event;
+
+        let mut inner = self.inner.lock();
+        let phase = match event {
+            Event::Begin { surface, .. } => {
+                inner.surface = Some(surface);
+                inner.gesture = PinchGesture::default();
+                FlutterPointerPhase::PanZoomStart
+            }
+            Event::Update {
+                dx,
+                dy,
+                scale,
+                rotation,
+                ..
+            } => {
+                inner.gesture.update((dx, dy), scale, rotation);
+                FlutterPointerPhase::PanZoomUpdate
+            }
+            Event::End { .. } => FlutterPointerPhase::PanZoomEnd,
+            _ => return None,
+        };
+
+        let surface = match phase {
+            FlutterPointerPhase::PanZoomEnd => inner.surface.take(),
+            _ => inner.surface.clone(),
+        }?;
+
+        Some((surface, phase, inner.gesture))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinch_scale_updates_are_monotonic() {
+        let mut gesture = PinchGesture::default();
+        let mut scales = vec![gesture.scale];
+
+        for scale in [1.1, 1.4, f64::NAN, 0.0, 1.8, 1e9] {
+            gesture.update((0.5, 0.0), scale, 1.0);
+            scales.push(gesture.scale);
+        }
+
+        assert!(scales.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(scales, vec![1.0, 1.1, 1.4, 1.4, 1.4, 1.8, MAX_PINCH_SCALE]);
+        assert_eq!(gesture.pan, (3.0, 0.0));
+        assert!((gesture.rotation - 6f64.to_radians()).abs() < 1e-9);
+    }
+}
@@ -1,17 +1,24 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ffi::{c_void, CStr, CString},
     iter::zip,
+    mem::ManuallyDrop,
     num::NonZeroU32,
+    os::unix::fs::MetadataExt,
+    pin::pin,
     sync::{
         atomic::{AtomicBool, AtomicIsize, Ordering},
+        mpsc::SendError,
         Arc, Mutex, RwLock, Weak,
     },
     time::Duration,
 };
 
-use ashpd::desktop::settings::{ColorScheme, Settings};
-use dpi::PhysicalSize;
+use ashpd::{
+    desktop::settings::{ColorScheme, Settings},
+    zbus::{self, zvariant::OwnedObjectPath},
+};
+use dpi::{PhysicalPosition, PhysicalSize};
 use flust_engine::{
     compositor::{
         CompositorCollectBackingStoreError, CompositorCreateBackingStoreError,
@@ -19,49 +26,79 @@ use flust_engine::{
     },
     ffi::{
         FlutterBackingStore, FlutterBackingStoreConfig, FlutterBackingStoreDescription,
-        FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterLogicalKey,
-        FlutterOpenGLBackingStore, FlutterOpenGLBackingStoreFramebuffer, FlutterOpenGLFramebuffer,
-        FlutterOpenGLSurface, FlutterOpenGLTargetType, FlutterPhysicalKey, FlutterPresentViewInfo,
+        FlutterKeyEventDeviceType, FlutterKeyEventType, FlutterLayer, FlutterLayerContent,
+        FlutterLocale, FlutterLogicalKey, FlutterOpenGLBackingStore,
+        FlutterOpenGLBackingStoreFramebuffer, FlutterOpenGLFramebuffer, FlutterOpenGLSurface,
+        FlutterOpenGLTargetType, FlutterPhysicalKey, FlutterPresentViewInfo, PhysicalRect,
     },
     tasks::TaskRunnerHandler,
     FlutterEngineWeakRef, FlutterVsyncHandler,
 };
-use flust_engine_api::FlutterOpenGLHandler;
+use flust_engine_api::{FlutterOpenGLHandler, FlutterSoftwareHandler};
 use flust_engine_sys::FlutterEngineGetCurrentTime;
 use flust_glutin::{
     context::{Context, ResourceContext},
+    debug::{check_gl_errors, enable_debug_output},
     gl,
+    proc_cache::GlProcCache,
 };
 use flust_plugins::{
     keyboard::{KeyboardStateError, KeyboardStateHandler},
-    mousecursor::{MouseCursorError, MouseCursorHandler, SystemMouseCursor},
-    platform::{AppSwitcherDescription, MimeError, PlatformHandler},
+    mousecursor::{CustomCursor, MouseCursorError, MouseCursorHandler, SystemMouseCursor},
+    platform::{
+        AppSwitcherDescription, ClipboardContents, ClipboardReadError, ClipboardReply, MimeError,
+        PlatformHandler,
+    },
     settings::{PlatformBrightness, SettingsPlugin},
-    textinput::TextInputHandler,
+    textinput::{TextInputContentType, TextInputHandler, TextInputRect},
 };
-use futures_lite::StreamExt;
+use flust_runner_api::ClearColor;
+use futures_lite::{stream, StreamExt};
+use glutin::surface::Rect;
 use smithay_client_toolkit::{
-    reexports::{calloop::LoopSignal, protocols::xdg::shell::client::xdg_toplevel::XdgToplevel},
+    reexports::{
+        calloop::{channel as calloop_channel, LoopSignal},
+        protocols::{
+            wp::text_input::zv3::client::zwp_text_input_v3::ZwpTextInputV3,
+            xdg::shell::client::xdg_toplevel::XdgToplevel,
+        },
+    },
     seat::{
         keyboard::{KeyEvent, Keysym, Modifiers},
         pointer::{CursorIcon, PointerData, PointerDataExt, ThemedPointer},
     },
+    shm::{
+        slot::{ActivateSlotError, Buffer, CreateBufferError, SlotPool},
+        Shm,
+    },
 };
-use smithay_clipboard::Clipboard;
 use thiserror::Error;
 use tracing::{error, trace, warn};
-use wayland_backend::client::ObjectId;
+use wayland_backend::client::{ObjectId, WaylandError};
 use wayland_client::{
-    protocol::{wl_display::WlDisplay, wl_surface::WlSurface},
+    protocol::{
+        wl_shm::{self, WlShm},
+        wl_surface::WlSurface,
+    },
     Connection, Proxy, QueueHandle,
 };
 
 use crate::{
     application::SctkApplicationState,
+    clipboard::{ClipboardTransfer, PendingClipboardRead, SctkClipboard},
     keyboard::{SctkKeyEvent, SctkLogicalKey, SctkPhysicalKey},
+    locale::{
+        SharedLocaleMonitor, ACCOUNTS_SERVICE_NAME, ACCOUNTS_SERVICE_PATH,
+        ACCOUNTS_SERVICE_USER_INTERFACE, LANGUAGE_PROPERTY, REGION_SETTING_KEY,
+        REGION_SETTING_NAMESPACE,
+    },
+    text_input::{surrounding_text, text_input_content_type},
 };
 
-use crate::window::SctkFlutterWindowInner;
+use crate::{
+    platform_view::{subsurface_layers, SctkOverlaySurface, SctkOverlaySurfaces, SubsurfaceLayer},
+    window::{ResizeState, SctkFlutterWindowInner},
+};
 
 const WINDOW_FRAMEBUFFER_ID: u32 = 0;
 
@@ -73,6 +110,7 @@ pub(crate) struct SctkOpenGLHandler {
     context: Arc<Mutex<Context>>,
     resource_context: Arc<Mutex<ResourceContext>>,
     current_frame_size: Arc<RwLock<PhysicalSize<u32>>>,
+    proc_cache: Arc<GlProcCache>,
 }
 
 impl SctkOpenGLHandler {
@@ -86,6 +124,7 @@ impl SctkOpenGLHandler {
             context,
             resource_context,
             current_frame_size: Default::default(),
+            proc_cache: Default::default(),
         }
     }
 
@@ -97,11 +136,19 @@ impl SctkOpenGLHandler {
     fn load_current_frame_size(&self) -> PhysicalSize<u32> {
         *self.current_frame_size.read().unwrap()
     }
+
+    pub(crate) fn context(&self) -> Arc<Mutex<Context>> {
+        self.context.clone()
+    }
 }
 
 // Note: These callbacks are executed on the *render* thread.
 impl FlutterOpenGLHandler for SctkOpenGLHandler {
     fn present(&self) -> bool {
+        self.present_with_damage(&[])
+    }
+
+    fn present_with_damage(&self, damage: &[(PhysicalPosition<f64>, PhysicalSize<f64>)]) -> bool {
         let frame_size = self.load_current_frame_size();
         // Check if this frame can be presented. This resizes the surface if a
         // resize is pending and |frame_size| matches the target size.
@@ -114,7 +161,19 @@ impl FlutterOpenGLHandler for SctkOpenGLHandler {
             return false;
         }
 
-        if !self.context.lock().unwrap().present() {
+        // Only the frame damage reported by the engine is damaged on the
+        // window surface, when known.
+        let damage: Vec<_> = damage
+            .iter()
+            .map(|&(origin, size)| PhysicalRect::new(origin, size))
+            .collect();
+        let mut context = self.context.lock().unwrap();
+        let presented = match buffer_damage(&damage, frame_size.cast()) {
+            Some(damage) => context.present_with_damage(&damage),
+            None => context.present(),
+        };
+        drop(context);
+        if !presented {
             return false;
         }
 
@@ -138,32 +197,207 @@ impl FlutterOpenGLHandler for SctkOpenGLHandler {
         0
     }
 
+    fn framebuffer_format(&self) -> u32 {
+        Context::get_supported_format()
+    }
+
+    fn buffer_age(&self) -> u32 {
+        // Querying the age latches the back buffer, which would keep its size
+        // for the frame rendered for a pending resize.
+        if self.window.upgrade().unwrap().load_resize_status() != ResizeState::Done {
+            return 0;
+        }
+
+        self.context.lock().unwrap().buffer_age()
+    }
+
     fn make_resource_current(&self) -> bool {
         self.resource_context.lock().unwrap().make_current()
     }
 
     fn gl_proc_resolver(&self, proc: &CStr) -> *mut c_void {
-        self.context.lock().unwrap().get_proc_address(proc) as _
+        self.proc_cache
+            .resolve(proc, || self.context.lock().unwrap().get_proc_address(proc)) as _
     }
 }
 
+/// Presents the frames of the engine's software renderer by copying them into
+/// `wl_shm` buffers attached to the window surface, so that no EGL context is
+/// needed.
+#[derive(Clone)]
+pub(crate) struct SctkSoftwareHandler {
+    window: Weak<SctkFlutterWindowInner>,
+    surface: WlSurface,
+    /// The pool and the buffer presented last, which is reused for the next
+    /// frame of the same size once released by the compositor.
+    buffers: Arc<Mutex<(SlotPool, Option<Buffer>)>>,
+}
+
+impl SctkSoftwareHandler {
+    pub(crate) fn new(
+        window: Weak<SctkFlutterWindowInner>,
+        surface: WlSurface,
+        pool: SlotPool,
+    ) -> Self {
+        Self {
+            window,
+            surface,
+            buffers: Arc::new(Mutex::new((pool, None))),
+        }
+    }
+
+    fn attach_frame(
+        &self,
+        allocation: &[u8],
+        row_bytes: usize,
+        size: PhysicalSize<u32>,
+    ) -> Result<(), SoftwarePresentError> {
+        let (width, height) = (size.width as i32, size.height as i32);
+        let mut buffers = self.buffers.lock().unwrap();
+        let (pool, presented) = &mut *buffers;
+
+        // The last buffer can't be drawn into while the compositor still uses
+        // it, in which case a new one is created (the previous one is
+        // destroyed once released).
+        let reusable = presented.take().filter(|buffer| {
+            buffer.height() == height
+                && buffer.stride() == row_bytes as i32
+                && buffer.canvas(pool).is_some()
+        });
+        let buffer = match reusable {
+            Some(buffer) => buffer,
+            None => {
+                pool.create_buffer(width, height, row_bytes as i32, wl_shm::Format::Argb8888)?
+                    .0
+            }
+        };
+
+        let canvas = buffer
+            .canvas(pool)
+            .ok_or(SoftwarePresentError::BufferBusy)?;
+        copy_software_frame(allocation, canvas);
+
+        buffer.attach_to(&self.surface)?;
+        self.surface.damage_buffer(0, 0, width, height);
+        self.surface.commit();
+
+        *presented = Some(buffer);
+
+        // Frames are presented on the render thread, whose requests would only
+        // be sent once the event loop flushes the connection (i.e.: after the
+        // next event).
+        let backend = self
+            .surface
+            .backend()
+            .upgrade()
+            .ok_or(SoftwarePresentError::ConnectionClosed)?;
+        Connection::from_backend(backend).flush()?;
+
+        Ok(())
+    }
+}
+
+// Note: This callback is executed on the *render* thread.
+impl FlutterSoftwareHandler for SctkSoftwareHandler {
+    fn present(&self, allocation: &[u8], row_bytes: usize, height: usize) -> bool {
+        let Some(frame_size) = software_frame_size(row_bytes, height) else {
+            error!(
+                "Invalid software frame: {} rows of {} bytes",
+                height, row_bytes
+            );
+            return false;
+        };
+
+        let window = self.window.upgrade().unwrap();
+        if !window.on_frame_generated(frame_size) {
+            return false;
+        }
+
+        if let Err(err) = self.attach_frame(allocation, row_bytes, frame_size) {
+            error!("Failed to present software frame: {}", err);
+            return false;
+        }
+
+        window.on_frame_presented();
+        true
+    }
+}
+
+/// The size of a frame rendered by the software renderer, whose pixels are 4
+/// bytes each, or `None` if the frame is empty or the rows aren't made of
+/// whole pixels.
+fn software_frame_size(row_bytes: usize, height: usize) -> Option<PhysicalSize<u32>> {
+    if row_bytes == 0 || !row_bytes.is_multiple_of(4) || height == 0 {
+        return None;
+    }
+
+    Some(PhysicalSize::new(
+        u32::try_from(row_bytes / 4).ok()?,
+        u32::try_from(height).ok()?,
+    ))
+}
+
+/// The length of a `wl_shm` buffer holding a software frame of `size`, used
+/// for sizing the initial pool (which grows as needed).
+pub(crate) fn software_frame_len(size: PhysicalSize<u32>) -> usize {
+    size.width as usize * size.height as usize * 4
+}
+
+/// Copies a frame of the software renderer into a `wl_shm` canvas with the
+/// same stride. The engine renders premultiplied pixels in the native 32-bit
+/// format, which is `WL_SHM_FORMAT_ARGB8888` on little-endian systems.
+fn copy_software_frame(allocation: &[u8], canvas: &mut [u8]) {
+    let len = allocation.len().min(canvas.len());
+    canvas[..len].copy_from_slice(&allocation[..len]);
+}
+
+#[derive(Error, Debug)]
+enum SoftwarePresentError {
+    #[error("Failed to create shm buffer: {0}")]
+    CreateBuffer(#[from] CreateBufferError),
+    #[error("Failed to attach shm buffer: {0}")]
+    Attach(#[from] ActivateSlotError),
+    #[error("The shm buffer is still in use by the compositor")]
+    BufferBusy,
+    #[error("The Wayland connection is closed")]
+    ConnectionClosed,
+    #[error("Failed to flush the Wayland connection: {0}")]
+    Flush(#[from] WaylandError),
+}
+
 #[derive(Clone)]
 pub struct SctkCompositorHandler {
     window: Weak<SctkFlutterWindowInner>,
     opengl_compositor: SctkOpenGLCompositor,
+    overlays: Arc<Mutex<SctkOverlaySurfaces>>,
 }
 
 impl SctkCompositorHandler {
-    pub fn new(
+    /// Creates the compositor of a window presenting in `context`, while the
+    /// engine renders the backing stores in `engine_context` (which is the
+    /// same for the implicit window). The layers above the bottom one are
+    /// presented in `overlays`.
+    pub(crate) fn new(
         window: Weak<SctkFlutterWindowInner>,
         context: Arc<Mutex<Context>>,
+        engine_context: Arc<Mutex<Context>>,
+        overlays: SctkOverlaySurfaces,
         opengl_target_type: FlutterOpenGLTargetType,
+        gl_debug_output: bool,
+        clear_color: ClearColor,
     ) -> Self {
-        let opengl_compositor = SctkOpenGLCompositor::new(context, opengl_target_type);
+        let opengl_compositor = SctkOpenGLCompositor::new(
+            context,
+            engine_context,
+            opengl_target_type,
+            gl_debug_output,
+            clear_color,
+        );
 
         Self {
             window,
             opengl_compositor,
+            overlays: Arc::new(Mutex::new(overlays)),
         }
     }
 
@@ -176,11 +410,79 @@ impl SctkCompositorHandler {
             ));
         }
 
+        window.platform_views().hide_absent(&HashSet::new());
+        self.overlays.lock().unwrap().hide_from(0);
         self.opengl_compositor.clear()?;
 
         window.on_frame_presented();
         Ok(())
     }
+
+    /// Stacks the platform views and overlays of `layers` (i.e.: the layers
+    /// above the bottom one) in order above the window surface, presenting
+    /// the overlays. The subsurfaces absent from the frame are unmapped. As
+    /// subsurfaces are synchronized, this is all applied with the next commit
+    /// of the window surface, i.e. along with the frame.
+    fn present_subsurface_layers(
+        &self,
+        window: &SctkFlutterWindowInner,
+        layers: &[FlutterLayer],
+    ) -> Result<(), CompositorPresentError> {
+        let scale_factor = window.load_current_scale_factor();
+        let mut overlays = self.overlays.lock().unwrap();
+        let mut below = window.wl_surface().clone();
+        let mut platform_views = HashSet::new();
+        let mut overlay_count = 0;
+
+        // The engine renders every backing store into the window surface with
+        // surface targets.
+        let framebuffer_compositor = match &self.opengl_compositor {
+            SctkOpenGLCompositor::Framebuffer(compositor) => Some(compositor),
+            SctkOpenGLCompositor::Surface(_) => None,
+        };
+
+        for subsurface_layer in subsurface_layers(layers, framebuffer_compositor.is_some()) {
+            match subsurface_layer {
+                SubsurfaceLayer::PlatformView {
+                    id,
+                    layer,
+                    unsupported,
+                } => {
+                    platform_views.insert(id);
+                    match window
+                        .platform_views()
+                        .show(id, layer, unsupported, scale_factor, &below)
+                    {
+                        Some(surface) => below = surface,
+                        None => trace!("No surface for platform view {}", id),
+                    }
+                }
+                SubsurfaceLayer::Overlay { index, layer } => {
+                    let Some(compositor) = framebuffer_compositor else {
+                        continue;
+                    };
+
+                    let overlay = overlays
+                        .get(index, layer_buffer_size(layer).cast())
+                        .map_err(|err| {
+                            CompositorPresentError::PresentFailed(format!(
+                                "Unable to create overlay surface: {}",
+                                err
+                            ))
+                        })?;
+                    overlay_count = index + 1;
+
+                    compositor.present_overlay(layer, overlay)?;
+                    overlay.show(layer.offset, scale_factor, &below);
+                    below = overlay.wl_surface().clone();
+                }
+            }
+        }
+
+        window.platform_views().hide_absent(&platform_views);
+        overlays.hide_from(overlay_count);
+        Ok(())
+    }
 }
 
 impl FlutterCompositorHandler for SctkCompositorHandler {
@@ -189,10 +491,12 @@ impl FlutterCompositorHandler for SctkCompositorHandler {
             return self.clear();
         }
 
-        // TODO: Support compositing layers and platform views.
-        debug_assert_eq!(info.layers.len(), 1);
-        let layer = info.layers.first().unwrap();
-        debug_assert!(layer.offset.x == 0.0 && layer.offset.y == 0.0);
+        // The bottom layer is always a backing store of the size of the view.
+        let Some(layer) = info.layers.first().filter(|layer| is_backing_store(layer)) else {
+            return Err(CompositorPresentError::PresentFailed(
+                "No backing store layer to present".into(),
+            ));
+        };
 
         // TODO: Investigate if conversion to `u32` is correct
         let frame_size = PhysicalSize::<u32>::new(
@@ -208,6 +512,7 @@ impl FlutterCompositorHandler for SctkCompositorHandler {
             ));
         }
 
+        self.present_subsurface_layers(&window, &info.layers[1..])?;
         self.opengl_compositor.present_opengl_view(info)?;
 
         window.on_frame_presented();
@@ -266,75 +571,136 @@ trait SctkOpenGLCompositorHandler {
 #[derive(Clone)]
 struct SctkOpenGLCompositorHandlerFramebuffer {
     context: Arc<Mutex<Context>>,
+    engine_context: Arc<Mutex<Context>>,
+    /// The framebuffer the textures of the backing stores are attached to for
+    /// blitting them. Framebuffers aren't shared between contexts, so it's
+    /// created in the context of the window (on the first present).
+    read_framebuffer: Arc<Mutex<Option<u32>>>,
     gl: gl::Gl,
     format: u32,
+    debug_output: bool,
+    clear_color: ClearColor,
 }
 
 impl SctkOpenGLCompositorHandlerFramebuffer {
-    pub fn new(context: Arc<Mutex<Context>>) -> Self {
-        context.lock().unwrap().make_current();
-
-        let gl = gl::Gl::load_with(|symbol| {
-            let proc = CString::new(symbol).unwrap();
-            context.lock().unwrap().get_proc_address(proc.as_c_str())
-        });
-
-        context.lock().unwrap().make_not_current();
+    pub fn new(
+        context: Arc<Mutex<Context>>,
+        engine_context: Arc<Mutex<Context>>,
+        debug_output: bool,
+        clear_color: ClearColor,
+    ) -> Self {
+        let gl = load_gl(&context, debug_output);
 
         Self {
             context,
+            engine_context,
+            read_framebuffer: Default::default(),
             gl,
             format: Context::get_supported_format(),
+            debug_output,
+            clear_color,
         }
     }
-}
 
-impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
-    fn present_opengl_view(
+    fn check_gl_errors(&self, operation: &str) {
+        if self.debug_output {
+            check_gl_errors(&self.gl, operation);
+        }
+    }
+
+    /// Creates a framebuffer in the current context.
+    fn gen_framebuffer(&self) -> u32 {
+        let mut framebuffer_id = 0;
+        unsafe { self.gl.GenFramebuffers(1, &mut framebuffer_id) };
+        framebuffer_id
+    }
+
+    /// The read framebuffer of the window, which must be made current.
+    fn read_framebuffer(&self) -> u32 {
+        *self
+            .read_framebuffer
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| self.gen_framebuffer())
+    }
+
+    /// Makes the context of the window current, for presenting.
+    fn make_window_context_current(&self) -> Result<(), CompositorPresentError> {
+        match self.context.lock().unwrap().make_current() {
+            true => Ok(()),
+            false => Err(CompositorPresentError::PresentFailed(
+                "Unable to make context current".into(),
+            )),
+        }
+    }
+
+    /// Blits the whole texture of `layer` into the framebuffer of the current
+    /// context (i.e.: the one of the window or of an overlay, which is of the
+    /// size of the layer), through its `read_framebuffer`.
+    fn blit_layer(
         &self,
-        info: FlutterPresentViewInfo,
+        read_framebuffer: u32,
+        layer: &FlutterLayer,
     ) -> Result<(), CompositorPresentError> {
-        let layer = info.layers.first().unwrap();
-        let source_id = layer
+        let texture_id = layer
             .content
-            .get_opengl_backing_store_framebuffer_name()
+            .get_opengl_backing_store_texture_name()
             .ok_or(CompositorPresentError::PresentFailed(
-                "Unable to retrieve framebuffer name from layer".into(),
+                "Unable to retrieve texture name from layer".into(),
             ))?;
-
-        if !self.context.lock().unwrap().make_current() {
-            return Err(CompositorPresentError::PresentFailed(
-                "Unable to make context current".into(),
-            ));
-        }
+        let size = layer_buffer_size(layer);
 
         unsafe {
             // Disable the scissor test as it can affect blit operations.
             // Prevents regressions like: https://github.com/flutter/flutter/issues/140828
             // See OpenGL specification version 4.6, section 18.3.1.
             self.gl.Disable(gl::SCISSOR_TEST);
-
-            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, source_id);
             self.gl
                 .BindFramebuffer(gl::DRAW_FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
-
-            let width = layer.size.width.round() as i32;
-            let height = layer.size.height.round() as i32;
-
+            self.gl
+                .BindFramebuffer(gl::READ_FRAMEBUFFER, read_framebuffer);
+            self.gl.FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture_id,
+                0,
+            );
             self.gl.BlitFramebuffer(
                 0,                    // srcX0
                 0,                    // srcY0
-                width,                // srcX1
-                height,               // srcY1
+                size.width,           // srcX1
+                size.height,          // srcY1
                 0,                    // dstX0
                 0,                    // dstY0
-                width,                // dstX1
-                height,               // dstY1
+                size.width,           // dstX1
+                size.height,          // dstY1
                 gl::COLOR_BUFFER_BIT, // mask
                 gl::NEAREST,          // filter
             );
+            self.gl.FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                0,
+                0,
+            );
         }
+        self.check_gl_errors("framebuffer blit");
+
+        Ok(())
+    }
 
+    /// Blits the bottom `layer` into the window framebuffer and presents it,
+    /// with the window context current.
+    fn present_layer(&self, layer: &FlutterLayer) -> Result<(), CompositorPresentError> {
+        self.blit_layer(self.read_framebuffer(), layer)?;
+
+        // The whole window surface is damaged on every frame: the paint region
+        // of the layer isn't the area that changed since the previous frame,
+        // and the overlays of the previous frame (which may have moved or
+        // disappeared since) are damaged by the compositor with their own
+        // subsurfaces, as they're never blitted into the window surface.
         if !self.context.lock().unwrap().present() {
             return Err(CompositorPresentError::PresentFailed(
                 "Present failed".into(),
@@ -344,6 +710,119 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
         Ok(())
     }
 
+    /// Presents the overlay `layer` in the surface of `overlay`. Its buffer is
+    /// shown along with the next commit of the window surface.
+    fn present_overlay(
+        &self,
+        layer: &FlutterLayer,
+        overlay: &mut SctkOverlaySurface,
+    ) -> Result<(), CompositorPresentError> {
+        let presented = self.present_in_overlay_context(layer, overlay);
+
+        // The engine context is left current for the engine even if
+        // presenting fails (including for the implicit window, whose context
+        // is the engine context).
+        match self.engine_context.lock().unwrap().make_current() {
+            true => presented,
+            false => Err(CompositorPresentError::PresentFailed(
+                "Unable to restore the engine context".into(),
+            )),
+        }
+    }
+
+    fn present_in_overlay_context(
+        &self,
+        layer: &FlutterLayer,
+        overlay: &mut SctkOverlaySurface,
+    ) -> Result<(), CompositorPresentError> {
+        if !overlay.context_mut().make_current() {
+            return Err(CompositorPresentError::PresentFailed(
+                "Unable to make overlay context current".into(),
+            ));
+        }
+
+        // Framebuffers aren't shared between contexts, so each overlay has its
+        // own read framebuffer.
+        let read_framebuffer = overlay.read_framebuffer(|| self.gen_framebuffer());
+        self.blit_layer(read_framebuffer, layer)?;
+
+        // The overlay is blitted entirely, so its whole surface is damaged
+        // (including when it's reused for another layer of the frame).
+        if !overlay.context_mut().present() {
+            return Err(CompositorPresentError::PresentFailed(
+                "Overlay present failed".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Makes the engine context current again once presented in the context
+    /// of another window, as the engine keeps rendering in it (making it
+    /// current implicitly flushes the window context).
+    fn restore_engine_context(&self) -> Result<(), CompositorPresentError> {
+        if Arc::ptr_eq(&self.context, &self.engine_context) {
+            return Ok(());
+        }
+
+        match self.engine_context.lock().unwrap().make_current() {
+            true => Ok(()),
+            false => Err(CompositorPresentError::PresentFailed(
+                "Unable to restore the engine context".into(),
+            )),
+        }
+    }
+}
+
+/// Clears the framebuffer bound for drawing to `color` (premultiplied, like
+/// the frames rendered by the engine).
+fn clear_framebuffer(gl: &gl::Gl, color: ClearColor) {
+    let color = color.premultiplied();
+    unsafe {
+        gl.ClearColor(color.red, color.green, color.blue, color.alpha);
+        gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+    }
+}
+
+/// Loads the GL functions of `context`, enabling debug output for it when
+/// requested.
+fn load_gl(context: &Mutex<Context>, debug_output: bool) -> gl::Gl {
+    context.lock().unwrap().make_current();
+
+    let gl = gl::Gl::load_with(|symbol| {
+        let proc = CString::new(symbol).unwrap();
+        context.lock().unwrap().get_proc_address(proc.as_c_str())
+    });
+
+    if debug_output {
+        enable_debug_output(&gl);
+    }
+
+    context.lock().unwrap().make_not_current();
+
+    gl
+}
+
+impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
+    fn present_opengl_view(
+        &self,
+        info: FlutterPresentViewInfo,
+    ) -> Result<(), CompositorPresentError> {
+        // The layers above the bottom one are presented in subsurfaces.
+        let Some(layer) = info.layers.first().filter(|layer| is_backing_store(layer)) else {
+            return Err(CompositorPresentError::PresentFailed(
+                "No backing store layer to present".into(),
+            ));
+        };
+
+        // The engine context is left current for the engine even if
+        // presenting fails.
+        self.make_window_context_current()?;
+        let presented = self.present_layer(layer);
+        self.restore_engine_context()?;
+        presented
+    }
+
     fn create_opengl_backing_store(
         &self,
         config: FlutterBackingStoreConfig,
@@ -397,6 +876,10 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
             );
         };
 
+        // The texture contents are undefined until the engine renders into it.
+        clear_framebuffer(&self.gl, self.clear_color);
+        self.check_gl_errors("backing store framebuffer creation");
+
         let framebuffer = FlutterOpenGLFramebuffer::new(self.format, user_data);
 
         Ok(FlutterOpenGLBackingStore::Framebuffer(framebuffer))
@@ -418,6 +901,7 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
                 .DeleteFramebuffers(1, &framebuffer.user_data.framebuffer_id);
             self.gl.DeleteTextures(1, &framebuffer.user_data.texture_id);
         }
+        self.check_gl_errors("backing store framebuffer deletion");
 
         framebuffer.drop_raw_user_data();
 
@@ -425,19 +909,19 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
     }
 
     fn clear(&self) -> Result<(), CompositorPresentError> {
-        if !self.context.lock().unwrap().make_current() {
-            return Err(CompositorPresentError::PresentFailed(
-                "Unable to make context current".into(),
-            ));
-        }
+        self.make_window_context_current()?;
 
         unsafe {
-            self.gl.ClearColor(0.0, 0.0, 0.0, 0.0);
             self.gl
-                .Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
-        };
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, WINDOW_FRAMEBUFFER_ID);
+        }
+        clear_framebuffer(&self.gl, self.clear_color);
+        self.check_gl_errors("clear");
 
-        if !self.context.lock().unwrap().present() {
+        let presented = self.context.lock().unwrap().present();
+        self.restore_engine_context()?;
+
+        if !presented {
             return Err(CompositorPresentError::PresentFailed(
                 "Present failed".into(),
             ));
@@ -447,13 +931,83 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerFramebuffer {
     }
 }
 
+fn is_backing_store(layer: &FlutterLayer) -> bool {
+    matches!(layer.content, FlutterLayerContent::BackingStore(_))
+}
+
+/// The size of the buffer of `layer`, in physical pixels.
+fn layer_buffer_size(layer: &FlutterLayer) -> PhysicalSize<i32> {
+    PhysicalSize::new(
+        layer.size.width.round() as i32,
+        layer.size.height.round() as i32,
+    )
+}
+
+/// Converts the frame damage reported by Flutter (top left origin) to the
+/// damage rects expected by EGL (bottom left origin), clipped to the buffer
+/// bounds.
+///
+/// Returns `None` when the whole buffer should be damaged instead: either
+/// because the frame damage is empty (i.e.: unknown) or because it covers the
+/// whole buffer anyway.
+fn buffer_damage(
+    frame_damage: &[PhysicalRect],
+    buffer_size: PhysicalSize<i32>,
+) -> Option<Vec<Rect>> {
+    let damage = buffer_rects(frame_damage, buffer_size);
+
+    let covers_buffer = damage.iter().any(|rect| {
+        rect.x == 0
+            && rect.y == 0
+            && rect.width == buffer_size.width
+            && rect.height == buffer_size.height
+    });
+
+    if damage.is_empty() || covers_buffer {
+        return None;
+    }
+
+    Some(damage)
+}
+
+/// Converts rects of a frame (top left origin) to rects of the buffer (bottom
+/// left origin, as expected by EGL), clipped to its bounds.
+fn buffer_rects(rects: &[PhysicalRect], buffer_size: PhysicalSize<i32>) -> Vec<Rect> {
+    rects
+        .iter()
+        .filter_map(|rect| {
+            let left = (rect.origin.x.floor() as i32).max(0);
+            let top = (rect.origin.y.floor() as i32).max(0);
+            let right = ((rect.origin.x + rect.size.width).ceil() as i32).min(buffer_size.width);
+            let bottom = ((rect.origin.y + rect.size.height).ceil() as i32).min(buffer_size.height);
+
+            if left >= right || top >= bottom {
+                return None;
+            }
+
+            Some(Rect::new(
+                left,
+                buffer_size.height - bottom,
+                right - left,
+                bottom - top,
+            ))
+        })
+        .collect()
+}
+
 #[derive(Clone)]
 struct SctkOpenGLCompositorHandlerSurface {
     context: Arc<Mutex<Context>>,
 }
 
 impl SctkOpenGLCompositorHandlerSurface {
-    pub fn new(context: Arc<Mutex<Context>>) -> Self {
+    pub fn new(context: Arc<Mutex<Context>>, debug_output: bool) -> Self {
+        // The compositor doesn't issue GL calls for surfaces, but the debug
+        // output still reports errors from the engine's rendering.
+        if debug_output {
+            load_gl(&context, debug_output);
+        }
+
         Self { context }
     }
 }
@@ -463,6 +1017,11 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerSurface {
         &self,
         _info: FlutterPresentViewInfo,
     ) -> Result<(), CompositorPresentError> {
+        // The engine renders every backing store into the window surface, so
+        // there's nothing to composite. The paint region of the layers isn't
+        // the area that changed since the previous frame, so the whole window
+        // surface is damaged.
+
         // TODO: Investigate if wrapping the present call with
         // `make_current`/`make_not_current` is expected.
         if !self.context.lock().unwrap().make_current() {
@@ -531,25 +1090,52 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositorHandlerSurface {
     }
 }
 
+/// Upgrades the `Weak` pointer `user_data` of a surface backing store (see
+/// `SctkOpenGLCompositorHandlerSurface::create_opengl_backing_store`), which
+/// stays owned by the backing store until it's collected.
+unsafe fn upgrade_user_data<T>(user_data: *mut c_void) -> Option<Arc<T>> {
+    let weak = ManuallyDrop::new(Weak::from_raw(user_data as *const T));
+    weak.upgrade()
+}
+
+/// Reports to the engine whether the GL state changed since it last used the
+/// context. The compositor doesn't issue GL calls for surfaces, and the window
+/// surface is always made current along with the same context, so the state
+/// is always the one the engine left.
+unsafe fn report_opengl_state_unchanged(opengl_state_changed: *mut bool) {
+    if let Some(opengl_state_changed) = opengl_state_changed.as_mut() {
+        *opengl_state_changed = false;
+    }
+}
+
 pub extern "C" fn make_current_callback(
     user_data: *mut c_void,
-    _opengl_state_changed: *mut bool,
+    opengl_state_changed: *mut bool,
 ) -> bool {
     trace!("make_current_callback");
     unsafe {
-        let context = &*(user_data as *const Mutex<Context>);
-        context.lock().unwrap().make_current()
+        report_opengl_state_unchanged(opengl_state_changed);
+        let Some(context) = upgrade_user_data::<Mutex<Context>>(user_data) else {
+            warn!("Unable to make a surface current: the window context is gone");
+            return false;
+        };
+        let made_current = context.lock().unwrap().make_current();
+        made_current
     }
 }
 
 pub extern "C" fn clear_current_callback(
     user_data: *mut c_void,
-    _opengl_state_changed: *mut bool,
+    opengl_state_changed: *mut bool,
 ) -> bool {
     trace!("clear_current_callback");
     unsafe {
-        let context = &*(user_data as *const Mutex<Context>);
-        context.lock().unwrap().make_not_current()
+        report_opengl_state_unchanged(opengl_state_changed);
+        let Some(context) = upgrade_user_data::<Mutex<Context>>(user_data) else {
+            return false;
+        };
+        let cleared = context.lock().unwrap().make_not_current();
+        cleared
     }
 }
 
@@ -564,17 +1150,28 @@ enum SctkOpenGLCompositor {
 }
 
 impl SctkOpenGLCompositor {
-    pub fn new(context: Arc<Mutex<Context>>, opengl_target_type: FlutterOpenGLTargetType) -> Self {
+    pub fn new(
+        context: Arc<Mutex<Context>>,
+        engine_context: Arc<Mutex<Context>>,
+        opengl_target_type: FlutterOpenGLTargetType,
+        gl_debug_output: bool,
+        clear_color: ClearColor,
+    ) -> Self {
         match opengl_target_type {
             FlutterOpenGLTargetType::Framebuffer => {
-                Self::Framebuffer(SctkOpenGLCompositorHandlerFramebuffer::new(context))
+                Self::Framebuffer(SctkOpenGLCompositorHandlerFramebuffer::new(
+                    context,
+                    engine_context,
+                    gl_debug_output,
+                    clear_color,
+                ))
             }
             FlutterOpenGLTargetType::Texture => unimplemented!(
                 "`FlutterOpenGLTargetType::Texture` is not currently implemented for SCTK backend"
             ),
-            FlutterOpenGLTargetType::Surface => {
-                Self::Surface(SctkOpenGLCompositorHandlerSurface::new(context))
-            }
+            FlutterOpenGLTargetType::Surface => Self::Surface(
+                SctkOpenGLCompositorHandlerSurface::new(context, gl_debug_output),
+            ),
         }
     }
 }
@@ -624,6 +1221,71 @@ impl SctkOpenGLCompositorHandler for SctkOpenGLCompositor {
     }
 }
 
+/// What is done with a frame request of the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameRequest {
+    /// The baton is kept until frames are enabled again (see
+    /// [`FrameScheduler::set_frames_enabled`]).
+    Deferred,
+    /// The baton is returned to the engine right away, as frame callbacks do
+    /// not fire for unmapped surfaces on Wayland.
+    Immediate,
+    /// The baton is returned from the next frame callback of the surface.
+    FrameCallback,
+}
+
+/// Tracks the frame request (baton) of the engine, and how it's answered.
+#[derive(Debug)]
+struct FrameScheduler {
+    pending_baton: AtomicIsize,
+    can_schedule_frames: AtomicBool,
+    frames_enabled: AtomicBool,
+}
+
+impl Default for FrameScheduler {
+    fn default() -> Self {
+        Self {
+            pending_baton: Default::default(),
+            can_schedule_frames: Default::default(),
+            frames_enabled: AtomicBool::new(true),
+        }
+    }
+}
+
+impl FrameScheduler {
+    fn request(&self, baton: isize) -> FrameRequest {
+        let request = if !self.frames_enabled.load(Ordering::Acquire) {
+            FrameRequest::Deferred
+        } else if !self.can_schedule_frames.load(Ordering::Relaxed) {
+            FrameRequest::Immediate
+        } else {
+            FrameRequest::FrameCallback
+        };
+
+        if request != FrameRequest::Immediate {
+            self.pending_baton.store(baton, Ordering::Release);
+        }
+        request
+    }
+
+    fn take_pending_baton(&self) -> Option<isize> {
+        match self.pending_baton.swap(0, Ordering::AcqRel) {
+            0 => None,
+            baton => Some(baton),
+        }
+    }
+
+    /// Returns the pending baton when frames are enabled again, which is to
+    /// be returned to the engine right away.
+    fn set_frames_enabled(&self, enabled: bool) -> Option<isize> {
+        let was_enabled = self.frames_enabled.swap(enabled, Ordering::AcqRel);
+        match enabled && !was_enabled {
+            true => self.take_pending_baton(),
+            false => None,
+        }
+    }
+}
+
 // TODO(multi-view): Add support for multi-view vsync once it is supported
 // upstream:
 // https://github.com/flutter/flutter/issues/142845#issuecomment-1955345110
@@ -631,8 +1293,7 @@ pub struct SctkVsyncHandler {
     qh: QueueHandle<SctkApplicationState>,
     engine: FlutterEngineWeakRef,
     implicit_window_surface: Option<WlSurface>,
-    pending_baton: AtomicIsize,
-    can_schedule_frames: AtomicBool,
+    scheduler: FrameScheduler,
 }
 
 impl SctkVsyncHandler {
@@ -641,8 +1302,7 @@ impl SctkVsyncHandler {
             qh,
             engine: Default::default(),
             implicit_window_surface: Default::default(),
-            pending_baton: Default::default(),
-            can_schedule_frames: Default::default(),
+            scheduler: Default::default(),
         }
     }
 
@@ -658,12 +1318,39 @@ impl SctkVsyncHandler {
         self.implicit_window_surface = Some(surface)
     }
 
-    pub(crate) fn load_pending_baton(&mut self) -> isize {
-        self.pending_baton.load(Ordering::Relaxed)
+    /// Takes the baton of the pending frame request, so that a baton is
+    /// returned by at most one frame callback.
+    pub(crate) fn take_pending_baton(&mut self) -> Option<isize> {
+        self.scheduler.take_pending_baton()
     }
 
     pub(crate) fn notify_present(&self) {
-        self.can_schedule_frames.store(true, Ordering::Relaxed);
+        self.scheduler
+            .can_schedule_frames
+            .store(true, Ordering::Relaxed);
+    }
+
+    /// Enables or disables frames (following the app lifecycle state). While
+    /// disabled, frame requests of the engine are deferred, so that no frames
+    /// are rendered for windows nobody can see. The deferred request is
+    /// answered once frames are enabled again.
+    pub(crate) fn set_frames_enabled(&self, enabled: bool) {
+        let Some(baton) = self.scheduler.set_frames_enabled(enabled) else {
+            return;
+        };
+
+        let Some(engine) = self.engine.upgrade() else {
+            error!("Engine upgrade failed while resuming frames");
+            return;
+        };
+
+        trace!(
+            "[baton: {}] frames enabled: returning baton to engine",
+            baton
+        );
+        let (frame_start_time_nanos, frame_target_time_nanos) =
+            get_flutter_frame_time_nanos(FRAME_INTERVAL_60_HZ_IN_NANOS);
+        engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
     }
 }
 
@@ -672,45 +1359,53 @@ impl FlutterVsyncHandler for SctkVsyncHandler {
     fn request_frame_callback(&self, baton: isize) {
         trace!("[baton: {}] requesting frame callback", baton);
 
-        self.pending_baton.store(baton, Ordering::Relaxed);
-
         let Some(engine) = self.engine.upgrade() else {
             error!("Engine upgrade failed while requesting frame callback");
             return;
         };
 
-        // Note: Frame callbacks do not fire for unmapped surfaces on Wayland.
-        // Therefore, pass back the `baton` to `FlutterEngineOnVsync` directly
-        // until the surface is mapped (e.g.: until the first `present()`).
-        let can_schedule_frames = self.can_schedule_frames.load(Ordering::Relaxed);
-        if !can_schedule_frames {
-            engine.run_on_platform_thread(move |engine| {
-                trace!(
-                    "[baton: {}] frames can not be scheduled yet: returning baton to engine",
-                    baton
-                );
-
-                // Once the surface is mapped, the `wl_output`'s refresh rate
-                // will be used for determining the frame interval. But until
-                // then, 60hz seems like a reasonable default.
-                let (frame_start_time_nanos, frame_target_time_nanos) =
-                    get_flutter_frame_time_nanos(FRAME_INTERVAL_60_HZ_IN_NANOS);
-                engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
-            });
-            return;
+        match self.scheduler.request(baton) {
+            FrameRequest::Deferred => {
+                trace!("[baton: {}] frames are disabled: deferring frame", baton);
+            }
+            // Note: Frame callbacks do not fire for unmapped surfaces on
+            // Wayland. Therefore, pass back the `baton` to `FlutterEngineOnVsync`
+            // directly until the surface is mapped (e.g.: until the first
+            // `present()`).
+            FrameRequest::Immediate => {
+                engine.run_on_platform_thread(move |engine| {
+                    trace!(
+                        "[baton: {}] frames can not be scheduled yet: returning baton to engine",
+                        baton
+                    );
+
+                    // Once the surface is mapped, the `wl_output`'s refresh
+                    // rate will be used for determining the frame interval.
+                    // But until then, 60hz seems like a reasonable default.
+                    let (frame_start_time_nanos, frame_target_time_nanos) =
+                        get_flutter_frame_time_nanos(FRAME_INTERVAL_60_HZ_IN_NANOS);
+                    engine.on_vsync(baton, frame_start_time_nanos, frame_target_time_nanos);
+                });
+            }
+            FrameRequest::FrameCallback => {
+                let Some(surface) = self.implicit_window_surface.clone() else {
+                    error!("Missing window surface while requesting frame callback");
+                    return;
+                };
+
+                let qh = self.qh.clone();
+
+                engine.run_on_platform_thread(move |engine| {
+                    // Avoid a needless wakeup if the baton was already returned.
+                    if !engine.has_pending_frame() {
+                        return;
+                    }
+
+                    surface.frame(&qh, surface.clone());
+                    surface.commit();
+                });
+            }
         }
-
-        let Some(surface) = self.implicit_window_surface.clone() else {
-            error!("Missing window surface while requesting frame callback");
-            return;
-        };
-
-        let qh = self.qh.clone();
-
-        engine.run_on_platform_thread(move |_engine| {
-            surface.frame(&qh, surface.clone());
-            surface.commit();
-        });
     }
 }
 
@@ -734,41 +1429,108 @@ impl TaskRunnerHandler for SctkPlatformTaskHandler {
 // plugin supports it.
 pub struct SctkPlatformHandler {
     implicit_xdg_toplevel: XdgToplevel,
-    clipboard: Clipboard,
+    clipboard: Arc<SctkClipboard>,
+    clipboard_reads: calloop_channel::Sender<PendingClipboardRead>,
+    store_primary_selection: bool,
 }
 
 impl SctkPlatformHandler {
-    /// # Safety
-    ///
-    /// `display` must be a valid `*mut wl_display` pointer, and it must remain
-    /// valid for as long as `Clipboard` object is alive.
-    pub unsafe fn new(display: WlDisplay, xdg_toplevel: XdgToplevel) -> Self {
+    pub(crate) fn new(
+        xdg_toplevel: XdgToplevel,
+        clipboard: Arc<SctkClipboard>,
+        clipboard_reads: calloop_channel::Sender<PendingClipboardRead>,
+        store_primary_selection: bool,
+    ) -> Self {
         Self {
             implicit_xdg_toplevel: xdg_toplevel,
-            clipboard: Clipboard::new(display.id().as_ptr() as *mut _),
+            clipboard,
+            clipboard_reads,
+            store_primary_selection,
         }
     }
 }
 
+trait ClipboardStore {
+    fn store(&mut self, contents: ClipboardContents);
+    fn store_primary(&mut self, text: String);
+}
+
+impl ClipboardStore for &SctkClipboard {
+    fn store(&mut self, contents: ClipboardContents) {
+        SctkClipboard::store(self, contents);
+    }
+
+    fn store_primary(&mut self, text: String) {
+        SctkClipboard::store_primary(self, text);
+    }
+}
+
+fn store_clipboard_contents(
+    clipboard: &mut impl ClipboardStore,
+    contents: ClipboardContents,
+    store_primary_selection: bool,
+) {
+    if store_primary_selection {
+        clipboard.store_primary(contents.text.clone());
+    }
+    clipboard.store(contents);
+}
+
 impl PlatformHandler for SctkPlatformHandler {
     fn set_application_switcher_description(&mut self, description: AppSwitcherDescription) {
+        // The primary color can't be applied to server-side decorations, it's
+        // only exposed through `PlatformPlugin::application_switcher_description`.
         self.implicit_xdg_toplevel.set_title(description.label);
     }
 
     fn set_clipboard_data(&mut self, text: String) {
-        // TODO: Is updating *both* clipboards a reasonable thing to do here?
-        self.clipboard.store(text.clone());
-        self.clipboard.store_primary(text);
+        self.set_rich_clipboard_data(ClipboardContents { text, html: None });
+    }
+
+    fn set_rich_clipboard_data(&mut self, contents: ClipboardContents) {
+        store_clipboard_contents(
+            &mut self.clipboard.as_ref(),
+            contents,
+            self.store_primary_selection,
+        );
     }
 
-    fn get_clipboard_data(&mut self, _mime: &str) -> Result<String, MimeError> {
-        self.clipboard.load().map_err(|_| MimeError {})
+    fn get_clipboard_data(&mut self, mime: &str) -> Result<String, MimeError> {
+        self.clipboard.load(mime).map_err(|err| {
+            trace!("unable to load clipboard data: {}", err);
+            MimeError {}
+        })
+    }
+
+    fn read_clipboard_data(&mut self, mime: &str, reply: ClipboardReply) {
+        let (offer, pipe) = match self.clipboard.receive(mime) {
+            Ok(ClipboardTransfer::Own(text)) => return reply(Ok(text)),
+            Ok(ClipboardTransfer::Offer { offer, pipe }) => (offer, pipe),
+            Err(err) => {
+                trace!("unable to load clipboard data: {}", err);
+                return reply(Err(err.into()));
+            }
+        };
+
+        // The data is read by the event loop (see
+        // `SctkApplicationState::schedule_clipboard_read`).
+        let read = PendingClipboardRead { offer, pipe, reply };
+        if let Err(SendError(read)) = self.clipboard_reads.send(read) {
+            warn!("Unable to read clipboard data after the event loop exited");
+            (read.reply)(Err(ClipboardReadError::Unavailable));
+        }
     }
 }
 
 pub struct SctkMouseCursorHandler {
     conn: Connection,
     themed_pointer: Option<ThemedPointer>,
+    pending_cursor: PendingCursor,
+    shm: Option<Shm>,
+    custom_cursors: HashMap<String, CustomCursor>,
+    /// The buffer of the active custom cursor, which must be kept alive for as
+    /// long as it's attached to the cursor surface.
+    custom_cursor_buffer: Option<(SlotPool, Buffer)>,
 }
 
 impl SctkMouseCursorHandler {
@@ -776,9 +1538,98 @@ impl SctkMouseCursorHandler {
         Self {
             conn,
             themed_pointer: None,
+            pending_cursor: PendingCursor::default(),
+            shm: None,
+            custom_cursors: HashMap::new(),
+            custom_cursor_buffer: None,
+        }
+    }
+
+    /// Enables custom cursors, whose images are shared with the compositor
+    /// through `shm`.
+    pub(crate) fn with_shm(mut self, shm: WlShm) -> Self {
+        self.shm = Some(Shm::from(shm));
+        self
+    }
+
+    /// Enables coalescing of cursor changes (see
+    /// [`flust_runner_api::BackendConfigSctk::coalesce_cursor_changes`]).
+    /// Coalesced changes are applied by [`Self::apply_pending_cursor`].
+    pub(crate) fn with_coalescing(mut self, coalesce: bool) -> Self {
+        self.pending_cursor.coalesce = coalesce;
+        self
+    }
+
+    /// Applies the last cursor requested since the previous call, if any.
+    pub(crate) fn apply_pending_cursor(&mut self) {
+        let Some(kind) = self.pending_cursor.take() else {
+            return;
+        };
+
+        if self.set_cursor(kind).is_err() {
+            warn!("[plugin: mousecursor] Failed to apply coalesced cursor change");
+        }
+    }
+
+    fn set_cursor(&self, kind: SystemMouseCursor) -> Result<(), MouseCursorError> {
+        let Some(themed_pointer) = self.themed_pointer.as_ref() else {
+            warn!("[plugin: mousecursor] Unable to update cursor: themed pointer is empty");
+            return Err(MouseCursorError);
+        };
+
+        let cursor: SctkMouseCursor = kind.into();
+
+        match cursor.icon {
+            Some(icon) => themed_pointer
+                .set_cursor(&self.conn, icon)
+                .or(Err(MouseCursorError)),
+            None => themed_pointer.hide_cursor().or(Err(MouseCursorError)),
         }
     }
 
+    fn set_custom_cursor_image(&mut self, name: &str) -> Result<(), MouseCursorError> {
+        let Some(cursor) = self.custom_cursors.get(name) else {
+            warn!("[plugin: mousecursor] Unknown custom cursor: {}", name);
+            return Err(MouseCursorError);
+        };
+
+        let (Some(themed_pointer), Some(shm)) = (self.themed_pointer.as_ref(), self.shm.as_ref())
+        else {
+            warn!("[plugin: mousecursor] Unable to update cursor: themed pointer is empty");
+            return Err(MouseCursorError);
+        };
+
+        let serial = themed_pointer
+            .pointer()
+            .data::<PointerData>()
+            .and_then(|data| data.pointer_data().latest_enter_serial())
+            .ok_or(MouseCursorError)?;
+
+        let (width, height) = (cursor.width as i32, cursor.height as i32);
+        let mut pool = SlotPool::new(cursor.rgba.len(), shm).or(Err(MouseCursorError))?;
+        let (buffer, canvas) = pool
+            .create_buffer(width, height, width * 4, wl_shm::Format::Argb8888)
+            .or(Err(MouseCursorError))?;
+        write_argb8888(&cursor.rgba, canvas);
+
+        let surface = themed_pointer.surface();
+        surface.set_buffer_scale(1);
+        buffer.attach_to(surface).or(Err(MouseCursorError))?;
+        surface.damage_buffer(0, 0, width, height);
+        surface.commit();
+
+        let (hotspot_x, hotspot_y) = cursor.hotspot;
+        themed_pointer.pointer().set_cursor(
+            serial,
+            Some(surface),
+            hotspot_x as i32,
+            hotspot_y as i32,
+        );
+
+        self.custom_cursor_buffer = Some((pool, buffer));
+        self.conn.flush().or(Err(MouseCursorError))
+    }
+
     pub(crate) fn set_themed_pointer(&mut self, themed_pointer: Option<ThemedPointer>) {
         self.themed_pointer = themed_pointer;
     }
@@ -803,19 +1654,72 @@ impl SctkMouseCursorHandler {
 
 impl MouseCursorHandler for SctkMouseCursorHandler {
     fn activate_system_cursor(&mut self, kind: SystemMouseCursor) -> Result<(), MouseCursorError> {
-        let Some(themed_pointer) = self.themed_pointer.as_ref() else {
-            warn!("[plugin: mousecursor] Unable to update cursor: themed pointer is empty");
+        match self.pending_cursor.request(kind) {
+            Some(kind) => self.set_cursor(kind),
+            None => Ok(()),
+        }
+    }
+
+    fn create_custom_cursor(
+        &mut self,
+        name: String,
+        cursor: CustomCursor,
+    ) -> Result<(), MouseCursorError> {
+        if self.shm.is_none() {
             return Err(MouseCursorError);
-        };
+        }
 
-        let cursor: SctkMouseCursor = kind.into();
+        self.custom_cursors.insert(name, cursor);
+        Ok(())
+    }
 
-        match cursor.icon {
-            Some(icon) => themed_pointer
-                .set_cursor(&self.conn, icon)
-                .or(Err(MouseCursorError)),
-            None => themed_pointer.hide_cursor().or(Err(MouseCursorError)),
+    fn set_custom_cursor(&mut self, name: &str) -> Result<(), MouseCursorError> {
+        // A coalesced system cursor must not replace this (more recent) one.
+        self.pending_cursor.take();
+        self.set_custom_cursor_image(name)
+    }
+
+    fn delete_custom_cursor(&mut self, name: &str) {
+        self.custom_cursors.remove(name);
+    }
+}
+
+/// Converts non-premultiplied RGBA pixels into premultiplied
+/// `WL_SHM_FORMAT_ARGB8888` ones (stored as little-endian BGRA).
+fn write_argb8888(rgba: &[u8], canvas: &mut [u8]) {
+    for (src, dst) in rgba.chunks_exact(4).zip(canvas.chunks_exact_mut(4)) {
+        let alpha = u16::from(src[3]);
+        let premultiply = |channel: u8| ((u16::from(channel) * alpha + 127) / 255) as u8;
+        dst.copy_from_slice(&[
+            premultiply(src[2]),
+            premultiply(src[1]),
+            premultiply(src[0]),
+            src[3],
+        ]);
+    }
+}
+
+/// The cursor requested last while coalescing cursor changes.
+#[derive(Default)]
+struct PendingCursor {
+    coalesce: bool,
+    pending: Option<SystemMouseCursor>,
+}
+
+impl PendingCursor {
+    /// Returns the cursor to apply right away, which is `None` when coalescing
+    /// (the cursor is then applied once taken).
+    fn request(&mut self, kind: SystemMouseCursor) -> Option<SystemMouseCursor> {
+        if !self.coalesce {
+            return Some(kind);
         }
+
+        self.pending = Some(kind);
+        None
+    }
+
+    fn take(&mut self) -> Option<SystemMouseCursor> {
+        self.pending.take()
     }
 }
 
@@ -868,19 +1772,144 @@ impl From<SystemMouseCursor> for SctkMouseCursor {
     }
 }
 
+/// Forwards the text being edited to the input method of the seats (see
+/// [`crate::text_input`]), which is only enabled while the text input is shown
+/// and one of the app surfaces has the keyboard focus.
 #[derive(Default)]
-pub struct SctkTextInputHandler {}
+pub struct SctkTextInputHandler {
+    text_inputs: HashMap<ObjectId, ZwpTextInputV3>,
+    /// The text inputs that entered a surface of the app.
+    focused: HashSet<ObjectId>,
+    shown: bool,
+    surrounding_text: Option<(String, usize, usize)>,
+    cursor_rect: Option<TextInputRect>,
+    content_type: Option<TextInputContentType>,
+}
 
 impl SctkTextInputHandler {
     pub fn new() -> Self {
         Default::default()
     }
+
+    pub(crate) fn set_text_input_for_seat(
+        &mut self,
+        seat_id: ObjectId,
+        text_input: ZwpTextInputV3,
+    ) {
+        if let Some(text_input) = self.text_inputs.insert(seat_id, text_input) {
+            self.focused.remove(&text_input.id());
+            text_input.destroy();
+        }
+    }
+
+    pub(crate) fn remove_text_input_for_seat(&mut self, seat_id: ObjectId) {
+        if let Some(text_input) = self.text_inputs.remove(&seat_id) {
+            self.focused.remove(&text_input.id());
+            text_input.destroy();
+        }
+    }
+
+    pub(crate) fn enter(&mut self, text_input: &ZwpTextInputV3) {
+        self.focused.insert(text_input.id());
+        if self.shown {
+            self.enable(text_input);
+        }
+    }
+
+    /// The input method is disabled by the compositor when leaving, so no
+    /// requests are needed.
+    pub(crate) fn leave(&mut self, text_input: &ZwpTextInputV3) {
+        self.focused.remove(&text_input.id());
+    }
+
+    fn enable(&self, text_input: &ZwpTextInputV3) {
+        text_input.enable();
+        // The state is reset on enable, so it's sent again.
+        if let Some((text, cursor, anchor)) = &self.surrounding_text {
+            send_surrounding_text(text_input, text, *cursor, *anchor);
+        }
+        if let Some(rect) = self.cursor_rect {
+            send_cursor_rect(text_input, rect);
+        }
+        if let Some(content_type) = self.content_type {
+            send_content_type(text_input, content_type);
+        }
+        text_input.commit();
+    }
+
+    fn active_text_inputs(&self) -> impl Iterator<Item = &ZwpTextInputV3> {
+        self.text_inputs
+            .values()
+            .filter(|text_input| self.shown && self.focused.contains(&text_input.id()))
+    }
 }
 
 impl TextInputHandler for SctkTextInputHandler {
-    fn show(&mut self) {}
+    fn show(&mut self) {
+        if self.shown {
+            return;
+        }
+        self.shown = true;
+        for text_input in self.active_text_inputs() {
+            self.enable(text_input);
+        }
+    }
 
-    fn hide(&mut self) {}
+    fn hide(&mut self) {
+        for text_input in self.active_text_inputs() {
+            text_input.disable();
+            text_input.commit();
+        }
+        self.shown = false;
+        self.surrounding_text = None;
+        self.cursor_rect = None;
+    }
+
+    fn set_surrounding_text(&mut self, text: &str, cursor: usize, anchor: usize) {
+        for text_input in self.active_text_inputs() {
+            send_surrounding_text(text_input, text, cursor, anchor);
+            text_input.commit();
+        }
+        self.surrounding_text = Some((text.to_owned(), cursor, anchor));
+    }
+
+    fn set_cursor_rect(&mut self, rect: TextInputRect) {
+        if self.cursor_rect == Some(rect) {
+            return;
+        }
+        for text_input in self.active_text_inputs() {
+            send_cursor_rect(text_input, rect);
+            text_input.commit();
+        }
+        self.cursor_rect = Some(rect);
+    }
+
+    fn set_content_type(&mut self, content_type: TextInputContentType) {
+        for text_input in self.active_text_inputs() {
+            send_content_type(text_input, content_type);
+            text_input.commit();
+        }
+        self.content_type = Some(content_type);
+    }
+}
+
+fn send_surrounding_text(text_input: &ZwpTextInputV3, text: &str, cursor: usize, anchor: usize) {
+    let (text, cursor, anchor) = surrounding_text(text, cursor, anchor);
+    text_input.set_surrounding_text(text.to_owned(), cursor as i32, anchor as i32);
+}
+
+fn send_cursor_rect(text_input: &ZwpTextInputV3, rect: TextInputRect) {
+    text_input.set_cursor_rectangle(
+        rect.x.round() as i32,
+        rect.y.round() as i32,
+        rect.width.round() as i32,
+        rect.height.round() as i32,
+    );
+}
+
+fn send_content_type(text_input: &ZwpTextInputV3, content_type: TextInputContentType) {
+    let (hint, purpose) = text_input_content_type(content_type);
+    text_input.set_content_type(hint, purpose);
 }
 
 #[derive(Error, Debug)]
@@ -908,6 +1937,16 @@ impl SctkKeyboardHandler {
         }
     }
 
+    /// Forgets the pressed keys, whose state is reset by the framework when
+    /// the engine restarts.
+    pub(crate) fn reset(&mut self) {
+        self.pressed_state.clear();
+    }
+
+    pub(crate) fn is_pressed(&self, physical: &FlutterPhysicalKey) -> bool {
+        self.pressed_state.contains_key(physical)
+    }
+
     pub(crate) fn release_key(
         &mut self,
         event: &KeyEvent,
@@ -1071,4 +2110,395 @@ impl SctkSettingsHandler {
 
         Ok(())
     }
+
+    /// Sends the locales of the user to the engine, and again whenever the
+    /// region (see [`REGION_SETTING_NAMESPACE`]) or the language (see
+    /// [`LANGUAGE_PROPERTY`]) change. Without the settings portal and
+    /// AccountsService, only the locales of the environment are sent.
+    pub(crate) async fn read_and_monitor_locale_changes(
+        engine: FlutterEngineWeakRef,
+        monitor: SharedLocaleMonitor,
+    ) -> SctkAsyncResult {
+        // Subscribing before reading the current values makes sure that no
+        // change is missed in between.
+        let settings = Settings::new()
+            .await
+            .inspect_err(|err| warn!("Region changes are not monitored: {}", err))
+            .ok();
+        let region_changes = match &settings {
+            Some(settings) => settings
+                .receive_setting_changed_with_args::<String>(
+                    REGION_SETTING_NAMESPACE,
+                    REGION_SETTING_KEY,
+                )
+                .await
+                .ok(),
+            None => None,
+        };
+        if let Some(settings) = &settings {
+            let region = settings
+                .read::<String>(REGION_SETTING_NAMESPACE, REGION_SETTING_KEY)
+                .await
+                .ok();
+            monitor.lock().set_region(region.as_deref());
+        }
+
+        let user = user_accounts_proxy()
+            .await
+            .inspect_err(|err| warn!("Language changes are not monitored: {}", err))
+            .ok();
+        let language_changes = match &user {
+            Some(user) => Some(
+                user.receive_property_changed::<String>(LANGUAGE_PROPERTY)
+                    .await,
+            ),
+            None => None,
+        };
+        if let Some(user) = &user {
+            let language = user.get_property::<String>(LANGUAGE_PROPERTY).await.ok();
+            monitor.lock().set_language(language.as_deref());
+        }
+        let locales = monitor.lock().changed_locales();
+        send_locales(&engine, locales);
+
+        let region_changes = stream::iter(region_changes)
+            .flatten()
+            .map(|region| LocaleSetting::Region(region.ok()));
+        let language_changes = stream::iter(language_changes)
+            .flatten()
+            .then(|change| async move { LocaleSetting::Language(change.get().await.ok()) });
+        let mut changes = pin!(region_changes.or(language_changes));
+        while let Some(change) = changes.next().await {
+            // The lock isn't held while sending them, as the engine resolves
+            // the platform locale with them.
+            let locales = {
+                let mut monitor = monitor.lock();
+                match change {
+                    LocaleSetting::Region(region) => monitor.set_region(region.as_deref()),
+                    LocaleSetting::Language(language) => monitor.set_language(language.as_deref()),
+                }
+                monitor.changed_locales()
+            };
+            send_locales(&engine, locales);
+        }
+
+        Ok(())
+    }
+}
+
+enum LocaleSetting {
+    Region(Option<String>),
+    Language(Option<String>),
+}
+
+/// Connects to the AccountsService object of the user running the app.
+async fn user_accounts_proxy() -> zbus::Result<zbus::Proxy<'static>> {
+    let connection = zbus::Connection::system().await?;
+    let accounts = zbus::Proxy::new(
+        &connection,
+        ACCOUNTS_SERVICE_NAME,
+        ACCOUNTS_SERVICE_PATH,
+        ACCOUNTS_SERVICE_NAME,
+    )
+    .await?;
+    let uid = std::fs::metadata("/proc/self")?.uid();
+    let user: OwnedObjectPath = accounts.call("FindUserById", &(i64::from(uid))).await?;
+    zbus::Proxy::new_owned(
+        connection,
+        ACCOUNTS_SERVICE_NAME,
+        user,
+        ACCOUNTS_SERVICE_USER_INTERFACE,
+    )
+    .await
+}
+
+fn send_locales(engine: &FlutterEngineWeakRef, locales: Option<Vec<FlutterLocale>>) {
+    let (Some(engine), Some(locales)) = (engine.upgrade(), locales) else {
+        return;
+    };
+
+    trace!("sending locales: {:?}", locales);
+    if let Err(err) = engine.update_locales(&locales) {
+        error!("Failed to update locales: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        os::{fd::OwnedFd, unix::net::UnixStream},
+    };
+
+    use smithay_client_toolkit::{error::GlobalError, globals::ProvidesBoundGlobal};
+    use wayland_backend::{
+        client::{Backend, ObjectData},
+        protocol::Message,
+    };
+    use wayland_client::protocol::{
+        wl_compositor::{self, WlCompositor},
+        wl_display,
+        wl_registry::{self, WlRegistry},
+        wl_surface,
+    };
+
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> PhysicalRect {
+        PhysicalRect::new(
+            PhysicalPosition::new(x, y),
+            PhysicalSize::new(width, height),
+        )
+    }
+
+    static RECORDED_CLEAR_COLOR: std::sync::Mutex<Option<[f32; 4]>> = std::sync::Mutex::new(None);
+
+    extern "system" fn record_clear_color(red: f32, green: f32, blue: f32, alpha: f32) {
+        *RECORDED_CLEAR_COLOR.lock().unwrap() = Some([red, green, blue, alpha]);
+    }
+
+    extern "system" fn ignore_clear(_mask: u32) {}
+
+    #[test]
+    fn software_frame_is_copied_into_shm_canvas() {
+        // A 2x2 frame of opaque red, green, blue and white pixels, in
+        // little-endian BGRA.
+        let frame = [
+            0x00, 0x00, 0xff, 0xff, 0x00, 0xff, 0x00, 0xff, //
+            0xff, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ];
+
+        let size = software_frame_size(8, 2).unwrap();
+        assert_eq!(size, PhysicalSize::new(2, 2));
+
+        let mut canvas = vec![0; software_frame_len(size)];
+        copy_software_frame(&frame, &mut canvas);
+        assert_eq!(canvas, frame);
+
+        assert_eq!(software_frame_size(6, 2), None);
+        assert_eq!(software_frame_size(8, 0), None);
+    }
+
+    /// Ignores the events of the objects created by the tests.
+    struct IgnoredObjectData;
+
+    impl ObjectData for IgnoredObjectData {
+        fn event(
+            self: Arc<Self>,
+            _backend: &Backend,
+            _msg: Message<ObjectId, OwnedFd>,
+        ) -> Option<Arc<dyn ObjectData>> {
+            None
+        }
+
+        fn destroyed(&self, _object_id: ObjectId) {}
+    }
+
+    /// The `wl_shm` global, bound without a registry roundtrip.
+    struct BoundShm(WlShm);
+
+    impl ProvidesBoundGlobal<WlShm, 1> for BoundShm {
+        fn bound_global(&self) -> Result<WlShm, GlobalError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// The object ids and opcodes of the requests sent to `compositor` (the
+    /// end of the socket of the fake compositor).
+    fn sent_requests(compositor: &mut UnixStream) -> Vec<(u32, u16)> {
+        let mut bytes = vec![];
+        compositor.set_nonblocking(true).unwrap();
+        let _ = compositor.read_to_end(&mut bytes);
+
+        let mut requests = vec![];
+        let mut message = &bytes[..];
+        while message.len() >= 8 {
+            let object = u32::from_ne_bytes(message[..4].try_into().unwrap());
+            let header = u32::from_ne_bytes(message[4..8].try_into().unwrap());
+            requests.push((object, header as u16));
+            message = &message[(header >> 16) as usize..];
+        }
+        requests
+    }
+
+    #[test]
+    fn software_frame_is_committed_and_flushed() {
+        let (client, mut compositor) = UnixStream::pair().unwrap();
+        let conn = Connection::from_socket(client).unwrap();
+        let data = Arc::new(IgnoredObjectData);
+
+        let registry: WlRegistry = conn
+            .display()
+            .send_constructor(wl_display::Request::GetRegistry {}, data.clone())
+            .unwrap();
+        let wl_compositor: WlCompositor = registry
+            .send_constructor(
+                wl_registry::Request::Bind {
+                    name: 1,
+                    id: (WlCompositor::interface(), 4),
+                },
+                data.clone(),
+            )
+            .unwrap();
+        let shm: WlShm = registry
+            .send_constructor(
+                wl_registry::Request::Bind {
+                    name: 2,
+                    id: (WlShm::interface(), 1),
+                },
+                data.clone(),
+            )
+            .unwrap();
+        let surface: WlSurface = wl_compositor
+            .send_constructor(wl_compositor::Request::CreateSurface {}, data)
+            .unwrap();
+
+        let size = PhysicalSize::new(2, 2);
+        let pool = SlotPool::new(software_frame_len(size), &BoundShm(shm)).unwrap();
+        let handler = SctkSoftwareHandler::new(Weak::new(), surface.clone(), pool);
+        handler.attach_frame(&[0xff; 16], 8, size).unwrap();
+
+        // The frame is sent to the compositor without waiting for the event
+        // loop to flush the connection.
+        let commit = (surface.id().protocol_id(), wl_surface::REQ_COMMIT_OPCODE);
+        assert_eq!(sent_requests(&mut compositor).last(), Some(&commit));
+    }
+
+    #[test]
+    fn framebuffer_is_cleared_to_configured_color() {
+        let gl = gl::Gl::load_with(|symbol| match symbol {
+            "glClearColor" => record_clear_color as *const c_void,
+            "glClear" => ignore_clear as *const c_void,
+            _ => std::ptr::null(),
+        });
+
+        clear_framebuffer(&gl, ClearColor::from_argb(0xff336699));
+
+        assert_eq!(
+            *RECORDED_CLEAR_COLOR.lock().unwrap(),
+            Some([0.2, 0.4, 0.6, 1.0])
+        );
+
+        // Translucent colors are premultiplied.
+        clear_framebuffer(&gl, ClearColor::from_argb(0x80336699));
+        let alpha = 128.0 / 255.0;
+        assert_eq!(
+            *RECORDED_CLEAR_COLOR.lock().unwrap(),
+            Some([0.2 * alpha, 0.4 * alpha, 0.6 * alpha, alpha])
+        );
+    }
+
+    #[test]
+    fn buffer_damage_matches_frame_damage() {
+        let buffer_size = PhysicalSize::new(200, 100);
+        let frame_damage = [rect(0.0, 0.0, 50.0, 10.0), rect(20.5, 60.0, 10.0, 50.0)];
+
+        assert_eq!(
+            buffer_damage(&frame_damage, buffer_size),
+            Some(vec![Rect::new(0, 90, 50, 10), Rect::new(20, 0, 11, 40)])
+        );
+    }
+
+    #[test]
+    fn surface_user_data_outlives_its_context() {
+        let context = Arc::new(Mutex::new(7));
+        let user_data = Arc::downgrade(&context).into_raw() as *mut c_void;
+
+        let upgraded = unsafe { upgrade_user_data::<Mutex<i32>>(user_data) };
+        assert_eq!(upgraded.map(|context| *context.lock().unwrap()), Some(7));
+
+        drop(context);
+        assert!(unsafe { upgrade_user_data::<Mutex<i32>>(user_data) }.is_none());
+        unsafe { drop(Weak::from_raw(user_data as *const Mutex<i32>)) };
+    }
+
+    #[test]
+    fn buffer_damage_falls_back_to_full_surface() {
+        let buffer_size = PhysicalSize::new(200, 100);
+
+        assert_eq!(buffer_damage(&[], buffer_size), None);
+        assert_eq!(
+            buffer_damage(&[rect(0.0, 0.0, 200.0, 100.0)], buffer_size),
+            None
+        );
+        assert_eq!(
+            buffer_damage(&[rect(300.0, 0.0, 10.0, 10.0)], buffer_size),
+            None
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordingClipboard {
+        clipboard: Option<ClipboardContents>,
+        primary: Option<String>,
+    }
+
+    impl ClipboardStore for RecordingClipboard {
+        fn store(&mut self, contents: ClipboardContents) {
+            self.clipboard = Some(contents);
+        }
+
+        fn store_primary(&mut self, text: String) {
+            self.primary = Some(text);
+        }
+    }
+
+    #[test]
+    fn frame_requests_are_deferred_while_frames_are_disabled() {
+        let scheduler = FrameScheduler::default();
+        assert_eq!(scheduler.request(1), FrameRequest::Immediate);
+        assert_eq!(scheduler.take_pending_baton(), None);
+
+        scheduler.can_schedule_frames.store(true, Ordering::Relaxed);
+        assert_eq!(scheduler.request(2), FrameRequest::FrameCallback);
+        assert_eq!(scheduler.take_pending_baton(), Some(2));
+
+        assert_eq!(scheduler.set_frames_enabled(false), None);
+        assert_eq!(scheduler.request(3), FrameRequest::Deferred);
+        assert_eq!(scheduler.set_frames_enabled(false), None);
+
+        // The deferred baton is returned once, when frames are enabled again.
+        assert_eq!(scheduler.set_frames_enabled(true), Some(3));
+        assert_eq!(scheduler.set_frames_enabled(true), None);
+        assert_eq!(scheduler.take_pending_baton(), None);
+        assert_eq!(scheduler.request(4), FrameRequest::FrameCallback);
+    }
+
+    #[test]
+    fn rapid_cursor_requests_are_coalesced() {
+        let mut pending = PendingCursor {
+            coalesce: true,
+            ..Default::default()
+        };
+
+        let mut applied: Vec<_> = [
+            SystemMouseCursor::Click,
+            SystemMouseCursor::Text,
+            SystemMouseCursor::Basic,
+        ]
+        .into_iter()
+        .filter_map(|kind| pending.request(kind))
+        .collect();
+        applied.extend(pending.take());
+        applied.extend(pending.take());
+
+        assert_eq!(applied, vec![SystemMouseCursor::Basic]);
+    }
+
+    #[test]
+    fn primary_selection_is_only_stored_when_enabled() {
+        let text = |text: &str| ClipboardContents {
+            text: text.into(),
+            html: None,
+        };
+
+        let mut clipboard = RecordingClipboard::default();
+        store_clipboard_contents(&mut clipboard, text("copied"), false);
+        assert_eq!(clipboard.clipboard, Some(text("copied")));
+        assert_eq!(clipboard.primary, None);
+
+        store_clipboard_contents(&mut clipboard, text("both"), true);
+        assert_eq!(clipboard.clipboard, Some(text("both")));
+        assert_eq!(clipboard.primary.as_deref(), Some("both"));
+    }
 }
@@ -38,6 +38,27 @@ impl SctkKeyEvent {
             synthesized,
         }
     }
+
+    /// Returns the text produced by this key event.
+    ///
+    /// Compose sequences (e.g.: `dead_acute` + `e` -> `é`) are resolved by
+    /// smithay-client-toolkit using xkbcommon's compose state: the composed
+    /// text is reported as the `utf8` value of the key event that *completes*
+    /// the sequence. The dead keys (and the compose key) that start a sequence
+    /// must therefore not produce any text, even if xkbcommon is unable to
+    /// load a compose table for the current locale.
+    pub(crate) fn character(&self) -> Option<String> {
+        if is_compose_keysym(self.event.keysym) {
+            return None;
+        }
+
+        self.event.utf8.clone().filter(|text| !text.is_empty())
+    }
+}
+
+fn is_compose_keysym(keysym: Keysym) -> bool {
+    let dead_keys = Keysym::dead_grave.raw()..=Keysym::dead_longsolidusoverlay.raw();
+    dead_keys.contains(&keysym.raw()) || keysym == Keysym::Multi_key
 }
 
 impl From<SctkKeyEvent> for FlutterKeyEvent {
@@ -50,7 +71,7 @@ impl From<SctkKeyEvent> for FlutterKeyEvent {
         // [0]: https://wayland.app/protocols/wayland#wl_keyboard:event:key
         let timestamp = FlutterEngine::get_current_time_duration();
 
-        let character = value.event.utf8.and_then(|utf8| CString::new(utf8).ok());
+        let character = value.character().and_then(|utf8| CString::new(utf8).ok());
 
         let character = match value.kind {
             FlutterKeyEventType::Up => None,
@@ -109,7 +130,7 @@ impl From<SctkKeyEvent> for KeyAction {
         let logical: FlutterLogicalKey = SctkLogicalKey::new(event.event.keysym).into();
         let specified_logical_key: i64 = logical.raw().try_into().unwrap_or(0);
 
-        let unicode_scalar_value: Option<SctkUnicodeScalarValue> = event.event.utf8.try_into().ok();
+        let unicode_scalar_value: Option<SctkUnicodeScalarValue> = event.character().try_into().ok();
         let unicode_scalar_values = unicode_scalar_value
             .map(|value| value.0 as i64)
             .unwrap_or(0);
@@ -212,3 +233,43 @@ impl SctkFlutterStringExt for String {
         (0x00..=0x1f).contains(character) || (0x7f..=0x9f).contains(character)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_event(keysym: Keysym, utf8: Option<&str>) -> SctkKeyEvent {
+        SctkKeyEvent::new(
+            FlutterKeyEventDeviceType::Keyboard,
+            KeyEvent {
+                time: 0,
+                raw_code: 0,
+                keysym,
+                utf8: utf8.map(str::to_owned),
+            },
+            FlutterKeyEventType::Down,
+            None,
+            Modifiers::default(),
+            false,
+        )
+    }
+
+    #[test]
+    fn compose_sequence_yields_composed_character() {
+        // A dead key starting a compose sequence does not produce any text,
+        // even when xkbcommon reports the raw dead-key symbol.
+        assert_eq!(key_event(Keysym::dead_acute, Some("´")).character(), None);
+        assert_eq!(key_event(Keysym::Multi_key, None).character(), None);
+
+        // The key completing the sequence carries the composed character.
+        assert_eq!(
+            key_event(Keysym::e, Some("é")).character(),
+            Some("é".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_utf8_yields_no_character() {
+        assert_eq!(key_event(Keysym::Shift_L, Some("")).character(), None);
+    }
+}
@@ -1,8 +1,14 @@
 pub mod application;
+mod clipboard;
 mod egl;
+mod gesture;
 mod handler;
 mod key_mapping_gen;
 mod keyboard;
+mod locale;
 mod output;
+pub mod platform_view;
 mod pointer;
+mod text_input;
+mod viewport;
 pub mod window;
@@ -0,0 +1,226 @@
+//! The locales of the user, sent to the engine once it's running and again
+//! whenever the region or the language set in the desktop settings change, so
+//! that the framework `Localizations` are rebuilt without restarting the app.
+use std::sync::Arc;
+
+use flust_engine::ffi::FlutterLocale;
+use parking_lot::Mutex;
+
+/// The settings portal namespace and key of the GNOME region (e.g.:
+/// `de_DE.UTF-8`).
+pub(crate) const REGION_SETTING_NAMESPACE: &str = "org.gnome.system.locale";
+pub(crate) const REGION_SETTING_KEY: &str = "region";
+
+/// The AccountsService user property holding the language picked in the
+/// desktop settings (e.g.: `de_DE.UTF-8`). It's only exported to the
+/// environment of the next session, so it's monitored on the system bus.
+pub(crate) const ACCOUNTS_SERVICE_NAME: &str = "org.freedesktop.Accounts";
+pub(crate) const ACCOUNTS_SERVICE_PATH: &str = "/org/freedesktop/Accounts";
+pub(crate) const ACCOUNTS_SERVICE_USER_INTERFACE: &str = "org.freedesktop.Accounts.User";
+pub(crate) const LANGUAGE_PROPERTY: &str = "Language";
+
+/// Parses a POSIX locale name (`language[_territory][.codeset][@modifier]`).
+/// Returns `None` for the `C` and `POSIX` locales, which carry no language.
+pub(crate) fn parse_posix_locale(name: &str) -> Option<FlutterLocale> {
+    let name = name.split(['.', '@']).next()?;
+    let (language, country) = match name.split_once('_') {
+        Some((language, country)) => (language, Some(country)),
+        None => (name, None),
+    };
+
+    if language.is_empty() || matches!(language, "C" | "POSIX") {
+        return None;
+    }
+
+    let country = country.filter(|country| !country.is_empty());
+    Some(FlutterLocale::new(language, country.map(str::to_owned)))
+}
+
+/// The locales of the environment, in the order of preference. Like gettext,
+/// the `LANGUAGE` list takes precedence over `LC_ALL`, `LC_MESSAGES` and
+/// `LANG`.
+pub(crate) fn environment_locales(var: impl Fn(&str) -> Option<String>) -> Vec<FlutterLocale> {
+    let mut names: Vec<String> = var("LANGUAGE")
+        .map(|list| list.split(':').map(str::to_owned).collect())
+        .unwrap_or_default();
+    names.extend(
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .find_map(|name| var(name).filter(|value| !value.is_empty())),
+    );
+
+    let mut locales = vec![];
+    for locale in names.iter().filter_map(|name| parse_posix_locale(name)) {
+        push_unique(&mut locales, locale);
+    }
+    locales
+}
+
+/// Returns the index of the supported locale best matching the `preferred`
+/// ones (see [`environment_locales`]): the first preferred locale having a
+/// match wins, matching the country too, if possible.
+pub(crate) fn best_supported_locale(
+    preferred: &[FlutterLocale],
+    supported: &[FlutterLocale],
+) -> Option<usize> {
+    preferred.iter().find_map(|preferred| {
+        let same_language =
+            |locale: &FlutterLocale| locale.language_code == preferred.language_code;
+        supported
+            .iter()
+            .position(|locale| {
+                same_language(locale) && locale.country_code == preferred.country_code
+            })
+            .or_else(|| supported.iter().position(same_language))
+    })
+}
+
+fn push_unique(locales: &mut Vec<FlutterLocale>, locale: FlutterLocale) {
+    if !locales.contains(&locale) {
+        locales.push(locale);
+    }
+}
+
+/// The locale state shared by the settings monitoring, updating it, and the
+/// platform locale resolution of the engine, reading it.
+pub(crate) type SharedLocaleMonitor = Arc<Mutex<LocaleMonitor>>;
+
+/// Tracks the locales set in the desktop settings and the ones sent to the
+/// engine, so that they are only sent again when they change.
+pub(crate) struct LocaleMonitor {
+    environment_locales: Vec<FlutterLocale>,
+    region: Option<FlutterLocale>,
+    language: Option<FlutterLocale>,
+    sent_locales: Option<Vec<FlutterLocale>>,
+}
+
+impl LocaleMonitor {
+    pub(crate) fn new(environment_locales: Vec<FlutterLocale>) -> Self {
+        Self {
+            environment_locales,
+            region: None,
+            language: None,
+            sent_locales: None,
+        }
+    }
+
+    pub(crate) fn set_region(&mut self, region: Option<&str>) {
+        self.region = region.and_then(parse_posix_locale);
+    }
+
+    pub(crate) fn set_language(&mut self, language: Option<&str>) {
+        self.language = language.and_then(parse_posix_locale);
+    }
+
+    /// The locales of the user, in the order of preference: the region goes
+    /// first, then the language of the desktop settings, and the environment
+    /// locales are kept as fallbacks.
+    pub(crate) fn locales(&self) -> Vec<FlutterLocale> {
+        let mut locales = vec![];
+        let settings = [&self.region, &self.language].into_iter().flatten();
+        for locale in settings.chain(&self.environment_locales) {
+            push_unique(&mut locales, locale.clone());
+        }
+
+        if locales.is_empty() {
+            locales.push(FlutterLocale::new("en", Some("US".into())));
+        }
+        locales
+    }
+
+    /// Returns the locales to send to the engine (see [`Self::locales`]), or
+    /// `None` if they were sent already.
+    pub(crate) fn changed_locales(&mut self) -> Option<Vec<FlutterLocale>> {
+        let locales = self.locales();
+        if self.sent_locales.as_ref() == Some(&locales) {
+            return None;
+        }
+
+        self.sent_locales = Some(locales.clone());
+        Some(locales)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locale(language: &str, country: &str) -> FlutterLocale {
+        FlutterLocale::new(language, Some(country.into()))
+    }
+
+    #[test]
+    fn settings_changes_resend_locales() {
+        let environment = environment_locales(|name| match name {
+            "LANGUAGE" => Some("fr_FR:en".into()),
+            "LANG" => Some("en_US.UTF-8".into()),
+            _ => None,
+        });
+        assert_eq!(
+            environment,
+            vec![
+                locale("fr", "FR"),
+                FlutterLocale::new("en", None),
+                locale("en", "US")
+            ]
+        );
+
+        let mut monitor = LocaleMonitor::new(environment);
+        assert_eq!(monitor.changed_locales(), Some(monitor.locales()));
+        assert_eq!(monitor.locales().first(), Some(&locale("fr", "FR")));
+        monitor.set_region(Some(""));
+        assert_eq!(monitor.changed_locales(), None);
+
+        monitor.set_region(Some("de_DE.UTF-8"));
+        assert_eq!(
+            monitor.changed_locales().unwrap(),
+            vec![
+                locale("de", "DE"),
+                locale("fr", "FR"),
+                FlutterLocale::new("en", None),
+                locale("en", "US")
+            ]
+        );
+        assert_eq!(monitor.changed_locales(), None);
+
+        // Switching the language re-sends the locales, after the region.
+        monitor.set_language(Some("it_IT.UTF-8"));
+        assert_eq!(
+            monitor.changed_locales().unwrap()[..2],
+            [locale("de", "DE"), locale("it", "IT")]
+        );
+        monitor.set_region(None);
+        monitor.set_language(Some("en_US.UTF-8"));
+        assert_eq!(
+            monitor.changed_locales().unwrap(),
+            vec![
+                locale("en", "US"),
+                locale("fr", "FR"),
+                FlutterLocale::new("en", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn best_supported_locale_prefers_country_matches() {
+        let supported = [
+            FlutterLocale::new("en", None),
+            locale("pt", "PT"),
+            locale("pt", "BR"),
+        ];
+
+        let best = |preferred: &[FlutterLocale]| best_supported_locale(preferred, &supported);
+        assert_eq!(best(&[locale("pt", "BR")]), Some(2));
+        assert_eq!(best(&[FlutterLocale::new("pt", None)]), Some(1));
+        assert_eq!(best(&[locale("de", "DE"), locale("en", "GB")]), Some(0));
+        assert_eq!(best(&[locale("de", "DE")]), None);
+    }
+
+    #[test]
+    fn c_locale_falls_back_to_en_us() {
+        let environment = environment_locales(|name| (name == "LANG").then(|| "C.UTF-8".into()));
+        let mut monitor = LocaleMonitor::new(environment);
+
+        assert_eq!(monitor.changed_locales(), Some(vec![locale("en", "US")]));
+    }
+}
@@ -2,6 +2,7 @@ use dpi::PhysicalSize;
 use flust_engine::ffi::FlutterEngineDisplay;
 use flust_engine_sys::FlutterEngineDisplayId;
 use smithay_client_toolkit::output::OutputInfo;
+use wayland_client::protocol::wl_output::Transform;
 
 #[derive(Debug, Clone)]
 pub(crate) struct SctkOutput {
@@ -22,12 +23,17 @@ impl SctkOutput {
             };
         };
 
-        let device_pixel_ratio = info.scale_factor as f64;
-
         let current_mode = info.modes.iter().find(|mode| mode.current);
 
+        let device_pixel_ratio = device_pixel_ratio(
+            current_mode.map(|mode| mode.dimensions),
+            info.logical_size,
+            info.transform,
+            info.scale_factor,
+        );
+
         let refresh_rate = current_mode
-            .map(|mode| mode.refresh_rate as f64 / 1000.0)
+            .map(|mode| refresh_rate_hz(mode.refresh_rate))
             .unwrap_or(0.0);
 
         let size = current_mode
@@ -50,6 +56,39 @@ impl SctkOutput {
     }
 }
 
+/// Converts a `wl_output` refresh rate (in mHz) into frames per second, as
+/// expected by `FlutterEngineDisplay`.
+fn refresh_rate_hz(refresh_rate_mhz: i32) -> f64 {
+    refresh_rate_mhz as f64 / 1000.0
+}
+
+/// The scale of an output. `wl_output` only advertises an integer scale, so
+/// the (possibly fractional) scale is derived from the ratio between the mode
+/// and the logical size reported by `xdg_output`, when available.
+fn device_pixel_ratio(
+    mode_dimensions: Option<(i32, i32)>,
+    logical_size: Option<(i32, i32)>,
+    transform: Transform,
+    scale_factor: i32,
+) -> f64 {
+    let fractional_scale = mode_dimensions.zip(logical_size).and_then(
+        |((mode_width, mode_height), (logical_width, logical_height))| {
+            // The logical size is reported after applying the transform.
+            let mode_width = match transform {
+                Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+                    mode_height
+                }
+                _ => mode_width,
+            };
+
+            (logical_width > 0 && logical_height > 0)
+                .then(|| mode_width as f64 / logical_width as f64)
+        },
+    );
+
+    fractional_scale.unwrap_or(scale_factor.max(1) as f64)
+}
+
 impl From<SctkOutput> for FlutterEngineDisplay {
     fn from(output: SctkOutput) -> Self {
         Self {
@@ -61,3 +100,39 @@ impl From<SctkOutput> for FlutterEngineDisplay {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractionally_scaled_output_is_converted_to_display() {
+        let output = SctkOutput {
+            display_id: 1,
+            refresh_rate: refresh_rate_hz(60000),
+            size: PhysicalSize::new(3840, 2160),
+            device_pixel_ratio: device_pixel_ratio(
+                Some((3840, 2160)),
+                Some((2560, 1440)),
+                Transform::Normal,
+                2,
+            ),
+        };
+
+        let display = FlutterEngineDisplay::from(output);
+        assert_eq!(display.refresh_rate, 60.0);
+        assert_eq!(display.device_pixel_ratio, 1.5);
+    }
+
+    #[test]
+    fn integer_scale_is_used_without_logical_size() {
+        assert_eq!(
+            device_pixel_ratio(Some((1920, 1080)), None, Transform::Normal, 2),
+            2.0
+        );
+        assert_eq!(
+            device_pixel_ratio(Some((3840, 2160)), Some((1440, 2560)), Transform::_90, 2),
+            1.5
+        );
+    }
+}
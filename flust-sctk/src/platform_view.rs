@@ -0,0 +1,811 @@
+//! Surfaces of the platform views of a window, and of the overlays painted
+//! above them, composited as subsurfaces of the window surface.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize};
+use flust_engine::ffi::{
+    FlutterLayer, FlutterLayerContent, FlutterPlatformViewMutation, PhysicalRect,
+};
+use flust_glutin::{builder::FlutterEGLContext, context::Context};
+use flust_runner_api::SurfaceBuffering;
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    reexports::protocols::wp::viewporter::client::{
+        wp_viewport::WpViewport, wp_viewporter::WpViewporter,
+    },
+    subcompositor::SubcompositorState,
+};
+use thiserror::Error;
+use tracing::warn;
+use wayland_client::{
+    protocol::{wl_subsurface::WlSubsurface, wl_surface::WlSurface},
+    QueueHandle,
+};
+
+use crate::{
+    application::SctkApplicationState,
+    egl::{CreateWaylandContextError, FlutterEGLContextWaylandExt, NonZeroU32PhysicalSize},
+};
+
+/// The subsurfaces of the platform views of a window, keyed by platform view
+/// id. They're owned by the embedder (e.g.: by the `PlatformViewHandler`
+/// creating the views, see
+/// [`crate::application::SctkApplication::platform_view_surfaces`]), and
+/// positioned, sized and stacked by the compositor according to their
+/// platform view layer on each frame.
+///
+/// The platform views absent from a frame are unmapped (i.e.: a null buffer
+/// is attached to their surface), so the embedder has to attach a buffer
+/// again once they're part of a frame. Subsurfaces can only be moved,
+/// resized and unmapped, so the mutations of the layers are applied only
+/// when they hide the view or leave it unchanged. The views are shown
+/// unclipped otherwise, which is logged once per view.
+#[derive(Clone)]
+pub struct SctkPlatformViewSurfaces {
+    parent: WlSurface,
+    subcompositor: Arc<SubcompositorState>,
+    viewporter: Option<WpViewporter>,
+    qh: QueueHandle<SctkApplicationState>,
+    surfaces: Arc<Mutex<HashMap<i64, PlatformViewSurface>>>,
+}
+
+struct PlatformViewSurface {
+    surface: WlSurface,
+    subsurface: WlSubsurface,
+    viewport: Option<WpViewport>,
+    /// The size the surface was last shown with, `None` when unmapped.
+    size: Option<LogicalSize<i32>>,
+    /// Whether the unsupported mutations of the view were logged.
+    unsupported_logged: bool,
+}
+
+impl SctkPlatformViewSurfaces {
+    pub(crate) fn new(
+        parent: WlSurface,
+        subcompositor: Arc<SubcompositorState>,
+        viewporter: Option<WpViewporter>,
+        qh: QueueHandle<SctkApplicationState>,
+    ) -> Self {
+        Self {
+            parent,
+            subcompositor,
+            viewporter,
+            qh,
+            surfaces: Default::default(),
+        }
+    }
+
+    /// Creates the surface of the platform view `id`, as a subsurface of the
+    /// window surface, and registers it. The embedder attaches the contents
+    /// of the view to it, and destroys it once removed.
+    pub fn create(&self, id: i64) -> WlSurface {
+        let (subsurface, surface) = self
+            .subcompositor
+            .create_subsurface(self.parent.clone(), &self.qh);
+        self.insert(id, surface.clone(), subsurface);
+        surface
+    }
+
+    /// Registers the `subsurface` of `surface` as the one of the platform
+    /// view `id`.
+    pub fn insert(&self, id: i64, surface: WlSurface, subsurface: WlSubsurface) {
+        let viewport = self
+            .viewporter
+            .as_ref()
+            .map(|viewporter| viewporter.get_viewport(&surface, &self.qh, ()));
+        let view = PlatformViewSurface {
+            surface,
+            subsurface,
+            viewport,
+            size: None,
+            unsupported_logged: false,
+        };
+
+        if let Some(previous) = self.surfaces.lock().unwrap().insert(id, view) {
+            previous.destroy_viewport();
+        }
+    }
+
+    pub fn remove(&self, id: i64) -> Option<WlSubsurface> {
+        let view = self.surfaces.lock().unwrap().remove(&id)?;
+        view.destroy_viewport();
+        Some(view.subsurface)
+    }
+
+    /// Shows the platform view `id` at the `offset` and with the `size` of
+    /// its layer, stacked right above `below`. The position and stacking are
+    /// applied with the next commit of the window surface, i.e. along with
+    /// the frame containing the layer. Returns the surface of the platform
+    /// view (for stacking the layers above it), `None` if the embedder has no
+    /// surface for it. The `unsupported` mutations of the layer are logged
+    /// the first time.
+    pub(crate) fn show(
+        &self,
+        id: i64,
+        layer: &FlutterLayer,
+        unsupported: Option<UnsupportedMutation>,
+        scale_factor: f64,
+        below: &WlSurface,
+    ) -> Option<WlSurface> {
+        let mut surfaces = self.surfaces.lock().unwrap();
+        let view = surfaces.get_mut(&id)?;
+
+        if let Some(unsupported) = unsupported {
+            if !view.unsupported_logged {
+                warn!(
+                    "Platform view {} is shown without {}, which subsurfaces don't support",
+                    id, unsupported
+                );
+                view.unsupported_logged = true;
+            }
+        }
+        let (offset, size) = (layer.offset, layer.size);
+
+        let position = subsurface_position(offset, scale_factor);
+        view.subsurface.set_position(position.x, position.y);
+        view.subsurface.place_above(below);
+
+        // The viewport destination is state of the platform view surface, so
+        // it's only committed when it changes.
+        let size = subsurface_size(size, scale_factor);
+        if view.size != Some(size) {
+            if let Some(viewport) = &view.viewport {
+                viewport.set_destination(size.width, size.height);
+                view.surface.commit();
+            }
+            view.size = Some(size);
+        }
+
+        Some(view.surface.clone())
+    }
+
+    /// Unmaps the surfaces of the platform views that aren't part of the
+    /// frame, i.e. whose id isn't in `shown`.
+    pub(crate) fn hide_absent(&self, shown: &HashSet<i64>) {
+        let mut surfaces = self.surfaces.lock().unwrap();
+        for (id, view) in surfaces.iter_mut() {
+            if view.size.is_some() && !shown.contains(id) {
+                view.surface.attach(None, 0, 0);
+                view.surface.commit();
+                view.size = None;
+            }
+        }
+    }
+}
+
+impl PlatformViewSurface {
+    fn destroy_viewport(self) {
+        if let Some(viewport) = self.viewport {
+            viewport.destroy();
+        }
+    }
+}
+
+/// A mutation of a platform view layer which can't be applied to its
+/// subsurface.
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UnsupportedMutation {
+    #[error("its opacity of {0}")]
+    Opacity(f64),
+    #[error("a clip cutting through it")]
+    PartialClip,
+    #[error("a rotation, skew or perspective transformation")]
+    Transformation,
+}
+
+/// Whether a platform view shown at `bounds` (in physical pixels of the
+/// window surface) is visible once its `mutations` are applied. Only the
+/// mutations hiding the view (e.g.: a clip excluding it, as when it's
+/// scrolled away) or leaving it unchanged (e.g.: a clip containing it) can
+/// be applied. The first other mutation is returned, along with whether the
+/// view would be visible if it was ignored.
+pub(crate) fn platform_view_visibility(
+    bounds: PhysicalRect,
+    mutations: &[FlutterPlatformViewMutation],
+) -> (bool, Option<UnsupportedMutation>) {
+    let mut transform = ScaleTranslation::IDENTITY;
+    let mut unsupported = None;
+
+    for mutation in mutations {
+        let visibility = match *mutation {
+            FlutterPlatformViewMutation::Opacity(opacity) if opacity <= 0.0 => Ok(false),
+            FlutterPlatformViewMutation::Opacity(opacity) if opacity >= 1.0 => Ok(true),
+            FlutterPlatformViewMutation::Opacity(opacity) => {
+                Err(UnsupportedMutation::Opacity(opacity))
+            }
+            FlutterPlatformViewMutation::ClipRect(rect) => {
+                let rect = transform.apply(rect);
+                clip_visibility(bounds, rect, rect)
+            }
+            FlutterPlatformViewMutation::ClipRoundedRect(rounded_rect) => {
+                // The corners only cut through the view if it overlaps them.
+                let rect = rounded_rect.rect;
+                let (upper_left, upper_right) = (
+                    rounded_rect.upper_left_corner_radius,
+                    rounded_rect.upper_right_corner_radius,
+                );
+                let (lower_left, lower_right) = (
+                    rounded_rect.lower_left_corner_radius,
+                    rounded_rect.lower_right_corner_radius,
+                );
+                let inner = flust_engine_sys::FlutterRect {
+                    left: rect.left + upper_left.width.max(lower_left.width),
+                    top: rect.top + upper_left.height.max(upper_right.height),
+                    right: rect.right - upper_right.width.max(lower_right.width),
+                    bottom: rect.bottom - lower_left.height.max(lower_right.height),
+                };
+                clip_visibility(bounds, transform.apply(rect), transform.apply(inner))
+            }
+            FlutterPlatformViewMutation::Transformation(transformation) => {
+                match ScaleTranslation::new(&transformation) {
+                    Some(inner) => {
+                        transform = transform.then(inner);
+                        Ok(true)
+                    }
+                    None => Err(UnsupportedMutation::Transformation),
+                }
+            }
+        };
+
+        match visibility {
+            Ok(true) => {}
+            Ok(false) => return (false, None),
+            // The clips following it can't be located.
+            Err(UnsupportedMutation::Transformation) => {
+                return (
+                    true,
+                    unsupported.or(Some(UnsupportedMutation::Transformation)),
+                )
+            }
+            Err(mutation) => unsupported = unsupported.or(Some(mutation)),
+        }
+    }
+
+    (true, unsupported)
+}
+
+/// Whether a view at `bounds` is visible once clipped by a clip bounded by
+/// `outer` and containing `inner`.
+fn clip_visibility(
+    bounds: PhysicalRect,
+    outer: PhysicalRect,
+    inner: PhysicalRect,
+) -> Result<bool, UnsupportedMutation> {
+    // Clips are usually the bounds of the view, which may differ slightly
+    // once scaled.
+    const TOLERANCE: f64 = 0.5;
+
+    let (left, top) = (bounds.origin.x, bounds.origin.y);
+    let (right, bottom) = (left + bounds.size.width, top + bounds.size.height);
+
+    let excluded = outer.origin.x >= right
+        || outer.origin.y >= bottom
+        || outer.origin.x + outer.size.width <= left
+        || outer.origin.y + outer.size.height <= top;
+    let contained = inner.origin.x <= left + TOLERANCE
+        && inner.origin.y <= top + TOLERANCE
+        && inner.origin.x + inner.size.width >= right - TOLERANCE
+        && inner.origin.y + inner.size.height >= bottom - TOLERANCE;
+
+    if excluded || outer.is_empty() {
+        return Ok(false);
+    }
+
+    match contained {
+        true => Ok(true),
+        false => Err(UnsupportedMutation::PartialClip),
+    }
+}
+
+/// A transformation only scaling (without flipping) and translating, which
+/// keeps rectangles axis-aligned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScaleTranslation {
+    scale_x: f64,
+    scale_y: f64,
+    translate_x: f64,
+    translate_y: f64,
+}
+
+impl ScaleTranslation {
+    const IDENTITY: Self = Self {
+        scale_x: 1.0,
+        scale_y: 1.0,
+        translate_x: 0.0,
+        translate_y: 0.0,
+    };
+
+    fn new(transformation: &flust_engine_sys::FlutterTransformation) -> Option<Self> {
+        let axis_aligned = transformation.skewX == 0.0
+            && transformation.skewY == 0.0
+            && transformation.pers0 == 0.0
+            && transformation.pers1 == 0.0
+            && transformation.pers2 == 1.0;
+        let flipped = transformation.scaleX < 0.0 || transformation.scaleY < 0.0;
+        (axis_aligned && !flipped).then_some(Self {
+            scale_x: transformation.scaleX,
+            scale_y: transformation.scaleY,
+            translate_x: transformation.transX,
+            translate_y: transformation.transY,
+        })
+    }
+
+    /// Applies `inner` before this transformation.
+    fn then(self, inner: Self) -> Self {
+        Self {
+            scale_x: self.scale_x * inner.scale_x,
+            scale_y: self.scale_y * inner.scale_y,
+            translate_x: self.scale_x * inner.translate_x + self.translate_x,
+            translate_y: self.scale_y * inner.translate_y + self.translate_y,
+        }
+    }
+
+    fn apply(&self, rect: flust_engine_sys::FlutterRect) -> PhysicalRect {
+        PhysicalRect::new(
+            PhysicalPosition::new(
+                rect.left * self.scale_x + self.translate_x,
+                rect.top * self.scale_y + self.translate_y,
+            ),
+            PhysicalSize::new(
+                (rect.right - rect.left) * self.scale_x,
+                (rect.bottom - rect.top) * self.scale_y,
+            ),
+        )
+    }
+}
+
+/// A layer presented in a subsurface of the window.
+pub(crate) enum SubsurfaceLayer<'a> {
+    /// A platform view which is visible, along with its mutations which
+    /// can't be applied.
+    PlatformView {
+        id: i64,
+        layer: &'a FlutterLayer,
+        unsupported: Option<UnsupportedMutation>,
+    },
+    /// The `index`th overlay of the frame.
+    Overlay {
+        index: usize,
+        layer: &'a FlutterLayer,
+    },
+}
+
+/// The subsurfaces presenting `layers` (i.e.: the layers above the bottom
+/// one), from bottom to top. The platform views hidden by their mutations
+/// are left out, and so are the backing stores without `overlays` (i.e.:
+/// when the engine renders them all into the window surface).
+pub(crate) fn subsurface_layers(layers: &[FlutterLayer], overlays: bool) -> Vec<SubsurfaceLayer> {
+    let mut overlay_count = 0;
+    layers
+        .iter()
+        .filter_map(|layer| match &layer.content {
+            FlutterLayerContent::PlatformView(platform_view) => {
+                let bounds = PhysicalRect::new(layer.offset, layer.size);
+                let (visible, unsupported) =
+                    platform_view_visibility(bounds, &platform_view.mutations);
+                visible.then_some(SubsurfaceLayer::PlatformView {
+                    id: platform_view.identifier,
+                    layer,
+                    unsupported,
+                })
+            }
+            FlutterLayerContent::BackingStore(_) if overlays => {
+                overlay_count += 1;
+                Some(SubsurfaceLayer::Overlay {
+                    index: overlay_count - 1,
+                    layer,
+                })
+            }
+            FlutterLayerContent::BackingStore(_) => None,
+        })
+        .collect()
+}
+
+/// The subsurfaces the overlays of a window are presented in, i.e. the
+/// backing store layers above the bottom one (which are stacked with the
+/// platform views). Each overlay has its own EGL context, sharing the
+/// textures of the engine context, and never receives input (which goes to
+/// the window surface or the platform views below).
+///
+/// They're created on the first frame with that many overlays, and unmapped
+/// when a frame has fewer.
+pub(crate) struct SctkOverlaySurfaces {
+    parent: WlSurface,
+    subcompositor: Arc<SubcompositorState>,
+    viewporter: Option<WpViewporter>,
+    input_region: Option<Region>,
+    qh: QueueHandle<SctkApplicationState>,
+    engine_context: Arc<Mutex<Context>>,
+    overlays: Vec<SctkOverlaySurface>,
+}
+
+impl SctkOverlaySurfaces {
+    pub(crate) fn new(
+        parent: WlSurface,
+        compositor_state: &CompositorState,
+        subcompositor: Arc<SubcompositorState>,
+        viewporter: Option<WpViewporter>,
+        qh: QueueHandle<SctkApplicationState>,
+        engine_context: Arc<Mutex<Context>>,
+    ) -> Self {
+        Self {
+            parent,
+            subcompositor,
+            viewporter,
+            input_region: Region::new(compositor_state).ok(),
+            qh,
+            engine_context,
+            overlays: vec![],
+        }
+    }
+
+    /// The `index`th overlay of the frame, resized to `size` (in physical
+    /// pixels). It's created if the previous frames had fewer overlays.
+    pub(crate) fn get(
+        &mut self,
+        index: usize,
+        size: PhysicalSize<u32>,
+    ) -> Result<&mut SctkOverlaySurface, CreateWaylandContextError> {
+        if index == self.overlays.len() {
+            let overlay = self.create(size)?;
+            self.overlays.push(overlay);
+        }
+
+        let overlay = &mut self.overlays[index];
+        overlay.resize(size);
+        Ok(overlay)
+    }
+
+    fn create(
+        &self,
+        size: PhysicalSize<u32>,
+    ) -> Result<SctkOverlaySurface, CreateWaylandContextError> {
+        let (subsurface, surface) = self
+            .subcompositor
+            .create_subsurface(self.parent.clone(), &self.qh);
+        surface.set_input_region(self.input_region.as_ref().map(Region::wl_region));
+        let viewport = self
+            .viewporter
+            .as_ref()
+            .map(|viewporter| viewporter.get_viewport(&surface, &self.qh, ()));
+
+        // Overlays don't wait for a frame callback on swap, as they're only
+        // shown along with the window surface (which waits for one), and
+        // don't get frame callbacks while unmapped.
+        let shared_context = self.engine_context.lock().unwrap().shared_context();
+        let context = match FlutterEGLContext::new_wayland_context(
+            &surface,
+            size,
+            SurfaceBuffering::Triple,
+            shared_context,
+            true,
+        ) {
+            Ok((context, _)) => context,
+            Err(err) => {
+                if let Some(viewport) = viewport {
+                    viewport.destroy();
+                }
+                subsurface.destroy();
+                surface.destroy();
+                return Err(err);
+            }
+        };
+
+        Ok(SctkOverlaySurface {
+            surface,
+            subsurface,
+            viewport,
+            context,
+            size,
+            read_framebuffer: None,
+        })
+    }
+
+    /// Unmaps the overlays from the `count`th one on, i.e. the ones unused by
+    /// the frame.
+    pub(crate) fn hide_from(&mut self, count: usize) {
+        for overlay in self.overlays.iter_mut().skip(count) {
+            overlay.hide();
+        }
+    }
+}
+
+impl Drop for SctkOverlaySurfaces {
+    fn drop(&mut self) {
+        for overlay in self.overlays.drain(..) {
+            // The EGL surface is destroyed before the Wayland one.
+            drop(overlay.context);
+            if let Some(viewport) = overlay.viewport {
+                viewport.destroy();
+            }
+            overlay.subsurface.destroy();
+            overlay.surface.destroy();
+        }
+    }
+}
+
+/// The subsurface an overlay layer is presented in.
+pub(crate) struct SctkOverlaySurface {
+    surface: WlSurface,
+    subsurface: WlSubsurface,
+    viewport: Option<WpViewport>,
+    context: Context,
+    size: PhysicalSize<u32>,
+    /// The framebuffer the texture of the layer is attached to for blitting
+    /// it, created in the context of the overlay (on its first present).
+    read_framebuffer: Option<u32>,
+}
+
+impl SctkOverlaySurface {
+    pub(crate) fn wl_surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    pub(crate) fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    /// The read framebuffer of the overlay, created with `create` (with the
+    /// overlay context current) the first time.
+    pub(crate) fn read_framebuffer(&mut self, create: impl FnOnce() -> u32) -> u32 {
+        *self.read_framebuffer.get_or_insert_with(create)
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size == self.size {
+            return;
+        }
+        if let Some(size) = size.non_zero() {
+            self.context.resize(size);
+        }
+        self.size = size;
+    }
+
+    /// Shows the overlay at the `offset` of its layer, stacked right above
+    /// `below`. Its buffer must have been swapped, as the viewport
+    /// destination is applied with the next commit of the overlay surface
+    /// (and the position and stacking with the next one of the window
+    /// surface).
+    pub(crate) fn show(&self, offset: PhysicalPosition<f64>, scale_factor: f64, below: &WlSurface) {
+        let position = subsurface_position(offset, scale_factor);
+        self.subsurface.set_position(position.x, position.y);
+        self.subsurface.place_above(below);
+
+        match &self.viewport {
+            Some(viewport) => {
+                let size = subsurface_size(self.size.cast(), scale_factor);
+                viewport.set_destination(size.width, size.height);
+            }
+            None => self.surface.set_buffer_scale(scale_factor as i32),
+        }
+        self.surface.commit();
+    }
+
+    fn hide(&self) {
+        self.surface.attach(None, 0, 0);
+        self.surface.commit();
+    }
+}
+
+/// Subsurfaces are positioned in the (logical) coordinates of the parent
+/// surface, whereas layer offsets are in physical pixels.
+fn subsurface_position(offset: PhysicalPosition<f64>, scale_factor: f64) -> LogicalPosition<i32> {
+    let position: LogicalPosition<f64> = offset.to_logical(scale_factor);
+    LogicalPosition::new(position.x.round() as i32, position.y.round() as i32)
+}
+
+/// The viewport destination of a subsurface showing a layer of `size`
+/// physical pixels. Viewport destinations must be positive.
+fn subsurface_size(size: PhysicalSize<f64>, scale_factor: f64) -> LogicalSize<i32> {
+    let size: LogicalSize<f64> = size.to_logical(scale_factor);
+    LogicalSize::new(
+        (size.width.round() as i32).max(1),
+        (size.height.round() as i32).max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_offsets_are_converted_to_surface_coordinates() {
+        assert_eq!(
+            subsurface_position(PhysicalPosition::new(300.0, 151.0), 2.0),
+            LogicalPosition::new(150, 76)
+        );
+        assert_eq!(
+            subsurface_position(PhysicalPosition::new(10.0, 0.0), 1.0),
+            LogicalPosition::new(10, 0)
+        );
+    }
+
+    #[test]
+    fn layer_sizes_are_converted_to_viewport_destinations() {
+        assert_eq!(
+            subsurface_size(PhysicalSize::new(300.0, 151.0), 2.0),
+            LogicalSize::new(150, 76)
+        );
+        assert_eq!(
+            subsurface_size(PhysicalSize::new(1.0, 0.0), 1.5),
+            LogicalSize::new(1, 1)
+        );
+    }
+
+    fn rect(left: f64, top: f64, right: f64, bottom: f64) -> flust_engine_sys::FlutterRect {
+        flust_engine_sys::FlutterRect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    fn scale(factor: f64) -> FlutterPlatformViewMutation {
+        FlutterPlatformViewMutation::Transformation(flust_engine_sys::FlutterTransformation {
+            scaleX: factor,
+            skewX: 0.0,
+            transX: 0.0,
+            skewY: 0.0,
+            scaleY: factor,
+            transY: 0.0,
+            pers0: 0.0,
+            pers1: 0.0,
+            pers2: 1.0,
+        })
+    }
+
+    fn layer(content: FlutterLayerContent, offset: (f64, f64)) -> FlutterLayer {
+        FlutterLayer {
+            content,
+            offset: PhysicalPosition::new(offset.0, offset.1),
+            size: PhysicalSize::new(200.0, 100.0),
+            backing_store_present_info: flust_engine::ffi::FlutterBackingStorePresentInfo {
+                paint_region: flust_engine::ffi::FlutterRegion { rects: vec![] },
+            },
+        }
+    }
+
+    fn platform_view_layer(
+        identifier: i64,
+        offset: (f64, f64),
+        mutations: Vec<FlutterPlatformViewMutation>,
+    ) -> FlutterLayer {
+        let platform_view = flust_engine::ffi::FlutterPlatformView {
+            identifier,
+            mutations,
+        };
+        layer(FlutterLayerContent::PlatformView(platform_view), offset)
+    }
+
+    fn backing_store_layer() -> FlutterLayer {
+        let backing_store = flust_engine::ffi::FlutterBackingStore::new(
+            flust_engine::ffi::FlutterBackingStoreDescription::Software,
+            0,
+        );
+        layer(FlutterLayerContent::BackingStore(backing_store), (0.0, 0.0))
+    }
+
+    /// The subsurfaces stacked for `layers`, from bottom to top.
+    fn stacking(layers: &[FlutterLayer], overlays: bool) -> Vec<String> {
+        subsurface_layers(layers, overlays)
+            .iter()
+            .map(|layer| match layer {
+                SubsurfaceLayer::PlatformView {
+                    id,
+                    unsupported: None,
+                    ..
+                } => format!("view {}", id),
+                SubsurfaceLayer::PlatformView {
+                    id,
+                    unsupported: Some(unsupported),
+                    ..
+                } => format!("view {} without {}", id, unsupported),
+                SubsurfaceLayer::Overlay { index, .. } => format!("overlay {}", index),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn subsurfaces_are_stacked_in_layer_order() {
+        // A 2x device pixel ratio, the views being 100x50 logical pixels.
+        let layers = [
+            platform_view_layer(3, (0.0, 0.0), vec![scale(2.0)]),
+            backing_store_layer(),
+            // Scrolled away from a list clipping it.
+            platform_view_layer(
+                4,
+                (0.0, 300.0),
+                vec![
+                    scale(2.0),
+                    FlutterPlatformViewMutation::ClipRect(rect(0.0, 0.0, 100.0, 100.0)),
+                ],
+            ),
+            // Within the list.
+            platform_view_layer(
+                5,
+                (0.0, 100.0),
+                vec![
+                    scale(2.0),
+                    FlutterPlatformViewMutation::ClipRect(rect(0.0, 0.0, 100.0, 100.0)),
+                ],
+            ),
+            // Halfway out of the list.
+            platform_view_layer(
+                6,
+                (0.0, 150.0),
+                vec![
+                    scale(2.0),
+                    FlutterPlatformViewMutation::ClipRect(rect(0.0, 0.0, 100.0, 100.0)),
+                ],
+            ),
+            backing_store_layer(),
+            platform_view_layer(
+                7,
+                (0.0, 0.0),
+                vec![FlutterPlatformViewMutation::Opacity(0.0)],
+            ),
+            platform_view_layer(
+                8,
+                (0.0, 0.0),
+                vec![FlutterPlatformViewMutation::Opacity(0.5)],
+            ),
+        ];
+
+        assert_eq!(
+            stacking(&layers, true),
+            vec![
+                "view 3",
+                "overlay 0",
+                "view 5",
+                "view 6 without a clip cutting through it",
+                "overlay 1",
+                "view 8 without its opacity of 0.5",
+            ]
+        );
+        assert_eq!(
+            stacking(&layers, false),
+            vec![
+                "view 3",
+                "view 5",
+                "view 6 without a clip cutting through it",
+                "view 8 without its opacity of 0.5",
+            ]
+        );
+    }
+
+    #[test]
+    fn rotated_platform_views_are_shown_unclipped() {
+        let bounds = PhysicalRect::new(
+            PhysicalPosition::new(0.0, 0.0),
+            PhysicalSize::new(200.0, 100.0),
+        );
+        let rotation =
+            FlutterPlatformViewMutation::Transformation(flust_engine_sys::FlutterTransformation {
+                scaleX: 0.0,
+                skewX: -1.0,
+                transX: 0.0,
+                skewY: 1.0,
+                scaleY: 0.0,
+                transY: 0.0,
+                pers0: 0.0,
+                pers1: 0.0,
+                pers2: 1.0,
+            });
+        // The clip following the rotation can't be located, even though
+        // it'd hide the view.
+        let clip = FlutterPlatformViewMutation::ClipRect(rect(0.0, 0.0, 0.0, 0.0));
+
+        assert_eq!(
+            platform_view_visibility(bounds, &[rotation, clip]),
+            (true, Some(UnsupportedMutation::Transformation))
+        );
+        assert_eq!(
+            platform_view_visibility(bounds, &[clip, rotation]),
+            (false, None)
+        );
+    }
+}
@@ -1,36 +1,122 @@
 use std::time::SystemTimeError;
 
-use dpi::LogicalPosition;
 use flust_engine::ffi::{
     FlutterPointerDeviceKind, FlutterPointerMouseButtons, FlutterPointerPhase,
     FlutterPointerSignalKind,
 };
 use flust_engine::ffi::{FlutterPointerEvent, FlutterViewId};
 use smithay_client_toolkit::seat::pointer::{
-    PointerEvent, PointerEventKind, BTN_BACK, BTN_EXTRA, BTN_FORWARD, BTN_LEFT, BTN_RIGHT, BTN_SIDE,
+    PointerEvent, PointerEventKind, BTN_BACK, BTN_EXTRA, BTN_FORWARD, BTN_LEFT, BTN_MIDDLE,
+    BTN_RIGHT, BTN_SIDE,
 };
 use thiserror::Error;
 
+/// A `wl_pointer` as seen by the engine. Its device id is the protocol id of
+/// the `wl_pointer`, which is stable for as long as the seat has a pointer, so
+/// a mouse keeps the same device id across enter/leave (see
+/// [`FlutterPointerEvent`] for how it relates to pointer identifiers).
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Pointer {
     pub(crate) device: i32,
-    pub(crate) pressed: u32,
+    /// The buttons currently held.
+    pub(crate) buttons: FlutterPointerMouseButtons,
+    /// The latest position of the pointer, in logical pixels.
+    pub(crate) position: (f64, f64),
+    added: bool,
 }
 
 impl Pointer {
     pub(crate) fn new(device: i32) -> Self {
-        Self { device, pressed: 0 }
+        Self {
+            device,
+            buttons: FlutterPointerMouseButtons::empty(),
+            position: (0.0, 0.0),
+            added: false,
+        }
+    }
+
+    /// Tracks whether the pointer has been added to the engine, so that
+    /// exactly one `Add` is sent on enter and one `Remove` on leave. Returns
+    /// `false` for redundant transitions (i.e.: an enter while already added,
+    /// or a leave while not added), which must not be forwarded to the engine.
+    pub(crate) fn track_presence(&mut self, kind: &PointerEventKind) -> bool {
+        match kind {
+            PointerEventKind::Enter { .. } => !std::mem::replace(&mut self.added, true),
+            PointerEventKind::Leave { .. } => std::mem::replace(&mut self.added, false),
+            _ => true,
+        }
+    }
+
+    /// Releases the buttons held when the pointer leaves (e.g.: when the
+    /// window is alt-tabbed away or the compositor grabs the pointer
+    /// mid-drag). Returns whether a `Cancel` must be sent before the `Remove`,
+    /// so that the gestures in progress are aborted instead of being stuck.
+    pub(crate) fn track_cancel(&mut self, kind: &PointerEventKind) -> bool {
+        if !matches!(kind, PointerEventKind::Leave { .. }) || self.buttons.is_empty() {
+            return false;
+        }
+
+        self.buttons = FlutterPointerMouseButtons::empty();
+        true
     }
 
-    pub(crate) fn increment_pressed(&mut self) {
-        self.pressed += 1;
+    /// Tracks the held buttons, for the `wl_pointer.button` events.
+    pub(crate) fn track_buttons(&mut self, kind: &PointerEventKind) {
+        match *kind {
+            PointerEventKind::Press { button, .. } => self
+                .buttons
+                .insert(pointer_mouse_buttons_from_wayland(button)),
+            PointerEventKind::Release { button, .. } => self
+                .buttons
+                .remove(pointer_mouse_buttons_from_wayland(button)),
+            _ => {}
+        }
+    }
+
+    fn move_phase(&self) -> FlutterPointerPhase {
+        if self.buttons.is_empty() {
+            FlutterPointerPhase::Hover
+        } else {
+            FlutterPointerPhase::Move
+        }
+    }
+
+    /// Tracks `event` and returns the engine events it translates to, in the
+    /// order they must be sent: a press still held when the pointer leaves is
+    /// cancelled before the pointer is removed.
+    pub(crate) fn track_event(
+        &mut self,
+        view_id: FlutterViewId,
+        event: &PointerEvent,
+    ) -> Result<Vec<FlutterPointerEvent>, PointerConversionError> {
+        self.position = event.position;
+
+        let mut events = vec![];
+        if self.track_cancel(&event.kind) {
+            events.push(cancel_event(view_id, self));
+        }
+
+        self.track_buttons(&event.kind);
+
+        events.push(FlutterPointerEvent::try_from(SctkPointerEvent::new(
+            view_id,
+            event.clone(),
+            *self,
+        ))?);
+        Ok(events)
     }
 
-    pub(crate) fn decrement_pressed(&mut self) {
-        self.pressed -= 1;
+    /// Trackpad gestures are sent from a separate device, since the engine
+    /// tracks the device kind (mouse or trackpad) per device.
+    pub(crate) fn pan_zoom_device(&self) -> i32 {
+        self.device + PAN_ZOOM_DEVICE_ID_OFFSET
     }
 }
 
+/// Offset of the device id of trackpad gestures from the id of the pointer
+/// they are performed with.
+const PAN_ZOOM_DEVICE_ID_OFFSET: i32 = 1 << 24;
+
 #[derive(Error, Debug)]
 pub enum PointerConversionError {
     #[error("Invalid pointer conversion")]
@@ -41,16 +127,11 @@ pub enum PointerConversionError {
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct SctkPointerEvent(FlutterViewId, PointerEvent, Pointer, f64);
+pub(crate) struct SctkPointerEvent(FlutterViewId, PointerEvent, Pointer);
 
 impl SctkPointerEvent {
-    pub(crate) fn new(
-        view_id: FlutterViewId,
-        event: PointerEvent,
-        pointer: Pointer,
-        scale_factor: f64,
-    ) -> Self {
-        Self(view_id, event, pointer, scale_factor)
+    pub(crate) fn new(view_id: FlutterViewId, event: PointerEvent, pointer: Pointer) -> Self {
+        Self(view_id, event, pointer)
     }
 }
 
@@ -58,14 +139,14 @@ impl TryFrom<SctkPointerEvent> for FlutterPointerEvent {
     type Error = PointerConversionError;
 
     fn try_from(
-        SctkPointerEvent(view_id, event, pointer, scale_factor): SctkPointerEvent,
+        SctkPointerEvent(view_id, event, pointer): SctkPointerEvent,
     ) -> Result<Self, Self::Error> {
         use PointerEventKind::*;
 
-        // Convert pointer coordinates from logical to physical pixels
-        let physical_position =
-            LogicalPosition::<f64>::from(event.position).to_physical::<f64>(scale_factor);
-        let (x, y) = (physical_position.x, physical_position.y);
+        // The coordinates are sent in logical pixels, the engine maps them to
+        // the view through its pointer transform (see
+        // `SctkFlutterWindowInner::send_window_metrics_event`).
+        let (x, y) = event.position;
 
         match event.kind {
             Enter { .. } => Ok(FlutterPointerEvent::new(
@@ -75,7 +156,7 @@ impl TryFrom<SctkPointerEvent> for FlutterPointerEvent {
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                FlutterPointerMouseButtons::None,
+                FlutterPointerMouseButtons::empty(),
                 view_id,
             )),
             Leave { .. } => Ok(FlutterPointerEvent::new(
@@ -85,72 +166,190 @@ impl TryFrom<SctkPointerEvent> for FlutterPointerEvent {
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                FlutterPointerMouseButtons::None,
+                FlutterPointerMouseButtons::empty(),
                 view_id,
             )),
             Motion { .. } => Ok(FlutterPointerEvent::new(
                 pointer.device,
-                if pointer.pressed > 0 {
-                    FlutterPointerPhase::Move
-                } else {
-                    FlutterPointerPhase::Hover
-                },
-                (x, y),
-                FlutterPointerSignalKind::None,
-                (0.0, 0.0),
-                FlutterPointerDeviceKind::Mouse,
-                FlutterPointerMouseButtons::None,
-                view_id,
-            )),
-            Press { button, .. } => Ok(FlutterPointerEvent::new(
-                pointer.device,
-                FlutterPointerPhase::Down,
-                (x, y),
-                FlutterPointerSignalKind::None,
-                (0.0, 0.0),
-                FlutterPointerDeviceKind::Mouse,
-                pointer_mouse_buttons_from_wayland(button),
-                view_id,
-            )),
-            Release { button, .. } => Ok(FlutterPointerEvent::new(
-                pointer.device,
-                FlutterPointerPhase::Up,
+                pointer.move_phase(),
                 (x, y),
                 FlutterPointerSignalKind::None,
                 (0.0, 0.0),
                 FlutterPointerDeviceKind::Mouse,
-                pointer_mouse_buttons_from_wayland(button),
+                pointer.buttons,
                 view_id,
             )),
+            // The pointer already tracks the buttons held after the event.
+            Press { button, .. } | Release { button, .. } => {
+                let changed = pointer_mouse_buttons_from_wayland(button);
+                let before = match event.kind {
+                    Press { .. } => pointer.buttons.difference(changed),
+                    _ => pointer.buttons.union(changed),
+                };
+
+                Ok(FlutterPointerEvent::new(
+                    pointer.device,
+                    FlutterPointerPhase::from_buttons_change(before, pointer.buttons),
+                    (x, y),
+                    FlutterPointerSignalKind::None,
+                    (0.0, 0.0),
+                    FlutterPointerDeviceKind::Mouse,
+                    pointer.buttons,
+                    view_id,
+                ))
+            }
             Axis {
                 horizontal,
                 vertical,
                 ..
             } => Ok(FlutterPointerEvent::new(
                 pointer.device,
-                if pointer.pressed > 0 {
-                    FlutterPointerPhase::Move
-                } else {
-                    FlutterPointerPhase::Hover
-                },
+                pointer.move_phase(),
                 (x, y),
                 FlutterPointerSignalKind::Scroll,
                 (horizontal.discrete as f64, vertical.discrete as f64),
                 FlutterPointerDeviceKind::Mouse,
-                // TODO: Are these values correct?
-                FlutterPointerMouseButtons::None,
+                pointer.buttons,
                 view_id,
             )),
         }
     }
 }
 
+/// The `Cancel` of the press of `pointer`, at its latest position.
+fn cancel_event(view_id: FlutterViewId, pointer: &Pointer) -> FlutterPointerEvent {
+    FlutterPointerEvent::new(
+        pointer.device,
+        FlutterPointerPhase::Cancel,
+        pointer.position,
+        FlutterPointerSignalKind::None,
+        (0.0, 0.0),
+        FlutterPointerDeviceKind::Mouse,
+        FlutterPointerMouseButtons::empty(),
+        view_id,
+    )
+}
+
 fn pointer_mouse_buttons_from_wayland(button: u32) -> FlutterPointerMouseButtons {
     match button {
-        BTN_LEFT => FlutterPointerMouseButtons::Primary,
-        BTN_RIGHT => FlutterPointerMouseButtons::Secondary,
-        BTN_BACK | BTN_SIDE => FlutterPointerMouseButtons::Back,
-        BTN_FORWARD | BTN_EXTRA => FlutterPointerMouseButtons::Forward,
-        _ => FlutterPointerMouseButtons::None,
+        BTN_LEFT => FlutterPointerMouseButtons::PRIMARY,
+        BTN_RIGHT => FlutterPointerMouseButtons::SECONDARY,
+        BTN_MIDDLE => FlutterPointerMouseButtons::MIDDLE,
+        BTN_BACK | BTN_SIDE => FlutterPointerMouseButtons::BACK,
+        BTN_FORWARD | BTN_EXTRA => FlutterPointerMouseButtons::FORWARD,
+        _ => FlutterPointerMouseButtons::empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use wayland_client::{protocol::wl_surface::WlSurface, Connection, Proxy};
+
+    use super::*;
+
+    #[test]
+    fn enter_leave_transitions_are_balanced() {
+        let mut pointer = Pointer::new(0);
+        let enter = PointerEventKind::Enter { serial: 0 };
+        let leave = PointerEventKind::Leave { serial: 0 };
+
+        let forwarded: Vec<bool> = [&leave, &enter, &enter, &leave, &leave, &enter]
+            .into_iter()
+            .map(|kind| pointer.track_presence(kind))
+            .collect();
+
+        assert_eq!(forwarded, vec![false, true, false, true, false, true]);
+        assert!(pointer.track_presence(&PointerEventKind::Motion { time: 0 }));
+    }
+
+    #[test]
+    fn simultaneously_held_buttons_are_all_reported() {
+        let mut pointer = Pointer::new(0);
+        let press = |button| PointerEventKind::Press {
+            time: 0,
+            button,
+            serial: 0,
+        };
+        let release = |button| PointerEventKind::Release {
+            time: 0,
+            button,
+            serial: 0,
+        };
+
+        pointer.track_buttons(&press(BTN_LEFT));
+        pointer.track_buttons(&press(BTN_RIGHT));
+        assert_eq!(
+            pointer.buttons,
+            FlutterPointerMouseButtons::PRIMARY | FlutterPointerMouseButtons::SECONDARY
+        );
+        assert_eq!(i64::from(pointer.buttons), 0b11);
+
+        pointer.track_buttons(&release(BTN_LEFT));
+        assert_eq!(pointer.buttons, FlutterPointerMouseButtons::SECONDARY);
+        assert_eq!(pointer.move_phase(), FlutterPointerPhase::Move);
+
+        pointer.track_buttons(&release(BTN_RIGHT));
+        assert!(pointer.buttons.is_empty());
+        assert_eq!(pointer.move_phase(), FlutterPointerPhase::Hover);
+    }
+
+    #[test]
+    fn leaving_during_a_press_cancels_it() {
+        let mut pointer = Pointer::new(0);
+        let leave = PointerEventKind::Leave { serial: 0 };
+        assert!(!pointer.track_cancel(&leave));
+
+        pointer.track_buttons(&PointerEventKind::Press {
+            time: 0,
+            button: BTN_LEFT,
+            serial: 0,
+        });
+        pointer.position = (10.0, 20.0);
+        assert!(!pointer.track_cancel(&PointerEventKind::Motion { time: 0 }));
+        assert!(pointer.track_cancel(&leave));
+        assert!(pointer.buttons.is_empty());
+
+        let cancel = cancel_event(1, &pointer);
+        assert_eq!(cancel.phase(), FlutterPointerPhase::Cancel);
+        assert_eq!(cancel.view_id(), 1);
+
+        // The press is only cancelled once.
+        assert!(!pointer.track_cancel(&leave));
+    }
+
+    #[test]
+    fn leaving_mid_drag_cancels_before_removing() {
+        let (client, _compositor) = UnixStream::pair().unwrap();
+        let conn = Connection::from_socket(client).unwrap();
+        let event = |kind| PointerEvent {
+            surface: WlSurface::inert(conn.backend().downgrade()),
+            position: (10.0, 20.0),
+            kind,
+        };
+        let phases = |pointer: &mut Pointer, kind| {
+            let events = pointer.track_event(1, &event(kind)).unwrap();
+            events.iter().map(|e| e.phase()).collect::<Vec<_>>()
+        };
+
+        let mut pointer = Pointer::new(0);
+        let press = PointerEventKind::Press {
+            time: 0,
+            button: BTN_LEFT,
+            serial: 0,
+        };
+        assert_eq!(phases(&mut pointer, press), [FlutterPointerPhase::Down]);
+        assert_eq!(
+            phases(&mut pointer, PointerEventKind::Leave { serial: 0 }),
+            [FlutterPointerPhase::Cancel, FlutterPointerPhase::Remove]
+        );
+        assert!(pointer.buttons.is_empty());
+
+        // Without a press in progress the pointer is only removed.
+        assert_eq!(
+            phases(&mut pointer, PointerEventKind::Leave { serial: 0 }),
+            [FlutterPointerPhase::Remove]
+        );
     }
 }
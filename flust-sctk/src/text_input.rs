@@ -0,0 +1,233 @@
+//! Input methods (`zwp_text_input_v3`), e.g.: for composing CJK text or
+//! typing with an on-screen keyboard.
+use flust_plugins::textinput::{ComposingText, InputMethodEdit, TextInputContentType};
+use parking_lot::Mutex;
+use smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
+    self, ContentHint, ContentPurpose,
+};
+
+/// Bound on the size of the surrounding text, as wayland messages are limited
+/// in size.
+const MAX_SURROUNDING_TEXT_LEN: usize = 4000;
+
+/// A `zwp_text_input_v3` event, once the state it updates is complete.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum TextInputEvent {
+    Enter,
+    /// The text input left the surfaces of the app. The composing text (if
+    /// any) must be removed.
+    Leave {
+        composing: bool,
+    },
+    Done(InputMethodEdit),
+}
+
+/// The user data of a `zwp_text_input_v3` object, accumulating the edits of
+/// the input method until they're applied on `done`.
+#[derive(Default)]
+pub(crate) struct TextInputData {
+    inner: Mutex<TextInputDataInner>,
+}
+
+#[derive(Default)]
+struct TextInputDataInner {
+    pending: InputMethodEdit,
+    /// Whether composing text was applied by the last `done`.
+    composing: bool,
+}
+
+impl TextInputData {
+    pub(crate) fn handle_event(&self, event: zwp_text_input_v3::Event) -> Option<TextInputEvent> {
+        use zwp_text_input_v3::Event;
+
+        let mut inner = self.inner.lock();
+        match event {
+            Event::Enter { .. } => Some(TextInputEvent::Enter),
+            Event::Leave { .. } => {
+                inner.pending = InputMethodEdit::default();
+                Some(TextInputEvent::Leave {
+                    composing: std::mem::take(&mut inner.composing),
+                })
+            }
+            Event::PreeditString {
+                text, cursor_begin, ..
+            } => {
+                inner.pending.composing = text.map(|text| ComposingText {
+                    text,
+                    cursor: usize::try_from(cursor_begin).ok(),
+                });
+                None
+            }
+            Event::CommitString { text } => {
+                inner.pending.commit = text;
+                None
+            }
+            Event::DeleteSurroundingText {
+                before_length,
+                after_length,
+            } => {
+                inner.pending.delete_surrounding =
+                    Some((before_length as usize, after_length as usize));
+                None
+            }
+            // The edits are applied even if the input method hasn't seen the
+            // latest state (i.e.: the serial isn't the number of commits).
+            Event::Done { .. } => {
+                let edit = std::mem::take(&mut inner.pending);
+                let composing = edit
+                    .composing
+                    .as_ref()
+                    .is_some_and(|composing| !composing.text.is_empty());
+
+                // Without new edits, the composing text is only removed if
+                // there was one.
+                if edit == InputMethodEdit::default() && !inner.composing {
+                    return None;
+                }
+                inner.composing = composing;
+                Some(TextInputEvent::Done(edit))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The part of `text` sent to the input method as the surrounding text, i.e.
+/// at most [`MAX_SURROUNDING_TEXT_LEN`] bytes around the `cursor` and the
+/// `anchor` (byte offsets in `text`), along with their offsets in that part.
+pub(crate) fn surrounding_text(text: &str, cursor: usize, anchor: usize) -> (&str, usize, usize) {
+    if text.len() < MAX_SURROUNDING_TEXT_LEN {
+        return (text, cursor, anchor);
+    }
+
+    // The text around the cursor is kept, along with as much of the selection
+    // as fits.
+    let half = MAX_SURROUNDING_TEXT_LEN / 2;
+    let mut anchor = anchor.clamp(cursor.saturating_sub(half), cursor + half);
+    while !text.is_char_boundary(anchor) {
+        if anchor < cursor {
+            anchor += 1;
+        } else {
+            anchor -= 1;
+        }
+    }
+    let (selection_start, selection_end) = (cursor.min(anchor), cursor.max(anchor));
+    let margin = (MAX_SURROUNDING_TEXT_LEN - 1 - (selection_end - selection_start)) / 2;
+
+    let mut start = selection_start.saturating_sub(margin);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let mut end = (selection_end + margin).min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    (&text[start..end], cursor - start, anchor - start)
+}
+
+/// The content hint and purpose of the text being edited. Obscured text is
+/// flagged as sensitive, so that input methods don't learn or suggest it.
+pub(crate) fn text_input_content_type(
+    content_type: TextInputContentType,
+) -> (ContentHint, ContentPurpose) {
+    let mut hint = ContentHint::empty();
+    if content_type.multiline {
+        hint |= ContentHint::Multiline;
+    }
+
+    if content_type.obscured {
+        hint |= ContentHint::HiddenText | ContentHint::SensitiveData;
+        return (hint, ContentPurpose::Password);
+    }
+
+    if content_type.autocorrect {
+        hint |= ContentHint::Completion | ContentHint::Spellcheck;
+    }
+    (hint, ContentPurpose::Normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_are_applied_on_done() {
+        let data = TextInputData::default();
+
+        let events = [
+            zwp_text_input_v3::Event::PreeditString {
+                text: Some("nihao".to_owned()),
+                cursor_begin: 5,
+                cursor_end: 5,
+            },
+            zwp_text_input_v3::Event::Done { serial: 1 },
+        ];
+        let applied: Vec<_> = events
+            .into_iter()
+            .filter_map(|event| data.handle_event(event))
+            .collect();
+        assert_eq!(
+            applied,
+            vec![TextInputEvent::Done(InputMethodEdit {
+                composing: Some(ComposingText {
+                    text: "nihao".to_owned(),
+                    cursor: Some(5),
+                }),
+                ..Default::default()
+            })]
+        );
+
+        // Committing the composing text clears it.
+        data.handle_event(zwp_text_input_v3::Event::CommitString {
+            text: Some("你好".to_owned()),
+        });
+        assert_eq!(
+            data.handle_event(zwp_text_input_v3::Event::Done { serial: 1 }),
+            Some(TextInputEvent::Done(InputMethodEdit {
+                commit: Some("你好".to_owned()),
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            data.handle_event(zwp_text_input_v3::Event::Done { serial: 1 }),
+            None
+        );
+    }
+
+    #[test]
+    fn surrounding_text_is_bounded_around_the_cursor() {
+        assert_eq!(surrounding_text("你好", 3, 0), ("你好", 3, 0));
+
+        let text = "字".repeat(2000);
+        let (surrounding, cursor, anchor) = surrounding_text(&text, 3000, 2997);
+        assert!(surrounding.len() < MAX_SURROUNDING_TEXT_LEN);
+        assert_eq!(&surrounding[anchor..cursor], "字");
+        assert_eq!(&text[3000 - cursor..][..surrounding.len()], surrounding);
+
+        // An anchor far from the cursor is moved to a character boundary.
+        let (surrounding, cursor, anchor) = surrounding_text(&text, 3000, 0);
+        assert!(surrounding.is_char_boundary(anchor));
+        assert_eq!(surrounding[anchor..cursor].chars().count(), 666);
+    }
+
+    #[test]
+    fn obscured_text_is_sensitive() {
+        let (hint, purpose) = text_input_content_type(TextInputContentType {
+            obscured: true,
+            multiline: false,
+            autocorrect: true,
+        });
+        assert_eq!(purpose, ContentPurpose::Password);
+        assert!(hint.contains(ContentHint::SensitiveData | ContentHint::HiddenText));
+        assert!(!hint.contains(ContentHint::Completion));
+
+        let (hint, purpose) = text_input_content_type(TextInputContentType {
+            obscured: false,
+            multiline: true,
+            autocorrect: false,
+        });
+        assert_eq!(purpose, ContentPurpose::Normal);
+        assert_eq!(hint, ContentHint::Multiline);
+    }
+}
@@ -0,0 +1,163 @@
+//! Scaling of the window surfaces to the scale factor of their output, see
+//! [`SurfaceScaling`].
+use dpi::LogicalSize;
+use flust_runner_api::SurfaceScaling;
+use smithay_client_toolkit::reexports::protocols::wp::{
+    fractional_scale::v1::client::{
+        wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        wp_fractional_scale_v1::WpFractionalScaleV1,
+    },
+    viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+};
+use tracing::warn;
+use wayland_client::{globals::GlobalList, protocol::wl_surface::WlSurface, Proxy, QueueHandle};
+
+use crate::application::SctkApplicationState;
+
+/// The denominator of the scales sent by `wp_fractional_scale_v1`.
+const FRACTIONAL_SCALE_DENOMINATOR: f64 = 120.0;
+
+/// The scaling globals supported by the compositor.
+#[derive(Debug, Default)]
+pub(crate) struct SctkScalingGlobals {
+    viewporter: Option<WpViewporter>,
+    fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+}
+
+impl SctkScalingGlobals {
+    pub(crate) fn bind(globals: &GlobalList, qh: &QueueHandle<SctkApplicationState>) -> Self {
+        Self {
+            viewporter: globals.bind(qh, 1..=1, ()).ok(),
+            fractional_scale_manager: globals.bind(qh, 1..=1, ()).ok(),
+        }
+    }
+
+    pub(crate) fn viewporter(&self) -> Option<&WpViewporter> {
+        self.viewporter.as_ref()
+    }
+}
+
+/// The viewport and fractional scale objects of a window surface.
+pub(crate) struct SctkSurfaceScaling {
+    viewport: Option<WpViewport>,
+    fractional_scale: Option<WpFractionalScaleV1>,
+}
+
+impl SctkSurfaceScaling {
+    pub(crate) fn new(
+        strategy: SurfaceScaling,
+        surface: &WlSurface,
+        globals: &SctkScalingGlobals,
+        qh: &QueueHandle<SctkApplicationState>,
+    ) -> Self {
+        let viewport = globals
+            .viewporter
+            .as_ref()
+            .map(|viewporter| viewporter.get_viewport(surface, qh, ()));
+
+        let fractional_scale = match (strategy, &viewport, &globals.fractional_scale_manager) {
+            (SurfaceScaling::Fractional, Some(_), Some(manager)) => {
+                Some(manager.get_fractional_scale(surface, qh, surface.clone()))
+            }
+            (SurfaceScaling::Fractional, _, _) => {
+                warn!(
+                    "[{}] fractional scaling isn't supported by the compositor: falling back to integer scaling",
+                    surface.id()
+                );
+                None
+            }
+            (SurfaceScaling::Integer, _, _) => None,
+        };
+
+        Self {
+            viewport,
+            fractional_scale,
+        }
+    }
+
+    /// The strategy actually used for the surface, i.e.: `Integer` when
+    /// fractional scaling was requested but isn't supported.
+    pub(crate) fn strategy(&self) -> SurfaceScaling {
+        match self.fractional_scale {
+            Some(_) => SurfaceScaling::Fractional,
+            None => SurfaceScaling::Integer,
+        }
+    }
+
+    /// Maps the buffer of the surface, rendered at `scale_factor`, to its
+    /// `logical_size`. Like the buffer, this is applied on the next commit.
+    pub(crate) fn apply(
+        &self,
+        surface: &WlSurface,
+        logical_size: LogicalSize<u32>,
+        scale_factor: f64,
+    ) {
+        match &self.viewport {
+            Some(viewport) => {
+                viewport.set_destination(logical_size.width as i32, logical_size.height as i32)
+            }
+            None => surface.set_buffer_scale(scale_factor as i32),
+        }
+    }
+}
+
+impl Drop for SctkSurfaceScaling {
+    fn drop(&mut self) {
+        if let Some(fractional_scale) = self.fractional_scale.take() {
+            fractional_scale.destroy();
+        }
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
+    }
+}
+
+/// Converts a `wp_fractional_scale_v1.preferred_scale` to a scale factor.
+pub(crate) fn fractional_scale_factor(scale: u32) -> f64 {
+    scale as f64 / FRACTIONAL_SCALE_DENOMINATOR
+}
+
+/// The scale factor the surface is rendered at (i.e.: its device pixel ratio)
+/// on an output scaled by `preferred_scale`.
+pub(crate) fn buffer_scale_factor(strategy: SurfaceScaling, preferred_scale: f64) -> f64 {
+    match strategy {
+        SurfaceScaling::Integer => preferred_scale.ceil(),
+        SurfaceScaling::Fractional => preferred_scale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dpi::PhysicalSize;
+
+    use super::*;
+
+    fn buffer_size(strategy: SurfaceScaling, width: u32, height: u32) -> PhysicalSize<u32> {
+        let scale_factor = buffer_scale_factor(strategy, fractional_scale_factor(180));
+        LogicalSize::new(width, height).to_physical(scale_factor)
+    }
+
+    #[test]
+    fn buffers_are_sized_by_the_scaling_strategy() {
+        assert_eq!(fractional_scale_factor(180), 1.5);
+
+        assert_eq!(
+            buffer_size(SurfaceScaling::Integer, 1280, 720),
+            PhysicalSize::new(2560, 1440)
+        );
+        assert_eq!(
+            buffer_size(SurfaceScaling::Fractional, 1280, 720),
+            PhysicalSize::new(1920, 1080)
+        );
+
+        // Fractional physical sizes are rounded.
+        assert_eq!(
+            buffer_size(SurfaceScaling::Integer, 641, 481),
+            PhysicalSize::new(1282, 962)
+        );
+        assert_eq!(
+            buffer_size(SurfaceScaling::Fractional, 641, 481),
+            PhysicalSize::new(962, 722)
+        );
+    }
+}
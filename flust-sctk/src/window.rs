@@ -1,22 +1,29 @@
 use std::{
     collections::HashMap,
     num::NonZeroU32,
-    sync::{Arc, Mutex, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant},
 };
 
 use dpi::{LogicalSize, PhysicalSize, Size};
 use flust_engine::{
-    ffi::{FlutterPointerEvent, FlutterViewId, IMPLICIT_VIEW_ID},
-    view::FlutterView,
+    ffi::{
+        FlutterPointerDeviceKind, FlutterPointerEvent, FlutterPointerMouseButtons,
+        FlutterPointerPhase, FlutterPointerSignalKind, FlutterViewId, IMPLICIT_VIEW_ID,
+    },
+    view::{FlutterPointerTransform, FlutterView, FlutterViewRotation},
     FlutterEngineWeakRef,
 };
 use flust_engine_sys::FlutterEngineDisplayId;
-use flust_glutin::builder::FlutterEGLContext;
-use flust_runner_api::{ApplicationAttributes, BackendConfigSctk};
+use flust_glutin::{builder::FlutterEGLContext, context::Context};
+use flust_runner_api::{ApplicationAttributes, BackendConfigSctk, SurfaceScaling};
 use smithay_client_toolkit::{
     compositor::{CompositorState, SurfaceData},
     reexports::protocols::xdg::shell::client::xdg_toplevel::XdgToplevel,
-    seat::pointer::{PointerEvent, PointerEventKind},
+    seat::pointer::PointerEvent,
     shell::{
         xdg::{
             window::{Window, WindowConfigure, WindowDecorations},
@@ -24,6 +31,8 @@ use smithay_client_toolkit::{
         },
         WaylandSurface,
     },
+    shm::{slot::SlotPool, CreatePoolError, Shm},
+    subcompositor::SubcompositorState,
 };
 use thiserror::Error;
 use tracing::{error, trace, warn};
@@ -36,8 +45,13 @@ use wayland_client::{
 use crate::{
     application::SctkApplicationState,
     egl::CreateWaylandContextError,
-    handler::{SctkCompositorHandler, SctkOpenGLHandler, SctkVsyncHandler},
-    pointer::SctkPointerEvent,
+    gesture::PinchGesture,
+    handler::{
+        software_frame_len, SctkCompositorHandler, SctkOpenGLHandler, SctkSoftwareHandler,
+        SctkVsyncHandler,
+    },
+    platform_view::{SctkOverlaySurfaces, SctkPlatformViewSurfaces},
+    viewport::{buffer_scale_factor, SctkScalingGlobals, SctkSurfaceScaling},
 };
 use crate::{
     egl::{FlutterEGLContextWaylandExt, NonZeroU32PhysicalSize},
@@ -59,6 +73,99 @@ pub(crate) enum ResizeState {
     FrameGenerated,
 }
 
+impl ResizeState {
+    /// The state a resize starts in. Without synchronization (see
+    /// [`BackendConfigSctk::synchronize_resize`]), resizes are immediately
+    /// done, so that frames are never rejected.
+    fn started(synchronized: bool) -> Self {
+        if synchronized {
+            ResizeState::ResizeStarted
+        } else {
+            ResizeState::Done
+        }
+    }
+
+    /// Whether a frame of `size` can be presented: while a resize is in
+    /// progress, only frames of the pending size are.
+    fn accepts_frame(
+        self,
+        pending_size: Option<PhysicalSize<NonZeroU32>>,
+        size: PhysicalSize<u32>,
+    ) -> bool {
+        if self != ResizeState::ResizeStarted {
+            return true;
+        }
+
+        pending_size.is_some_and(|pending_size| {
+            size.width == pending_size.width.get() && size.height == pending_size.height.get()
+        })
+    }
+}
+
+/// The progress of the latest resize of a window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ResizeProgress {
+    state: ResizeState,
+    /// The size of the frames presented once the resize completes.
+    pending_size: Option<PhysicalSize<NonZeroU32>>,
+}
+
+impl ResizeProgress {
+    /// Returns whether a generated frame of `size` can be presented, moving a
+    /// resize in progress to [`ResizeState::FrameGenerated`] once a frame of
+    /// the pending size is.
+    fn frame_generated(&mut self, size: PhysicalSize<u32>) -> bool {
+        if self.state != ResizeState::ResizeStarted {
+            return true;
+        }
+
+        let Some(pending_size) = self.pending_size else {
+            error!("[on_frame_generated] Invalid resize state: pending size not found");
+            return false;
+        };
+
+        if !self.state.accepts_frame(Some(pending_size), size) {
+            trace!(
+                "[on_frame_generated]: Frame size does not match expected size: {}x{} != {}x{}",
+                size.width,
+                size.height,
+                pending_size.width,
+                pending_size.height
+            );
+            return false;
+        }
+
+        self.state = ResizeState::FrameGenerated;
+        true
+    }
+}
+
+/// Time after which a resize which didn't complete (i.e.: no frame of the new
+/// size was presented) is considered stuck, and the window metrics are sent
+/// again to re-synchronize with the engine.
+pub(crate) const RESIZE_RECOVERY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How the frames of a window are rendered, see
+/// [`BackendConfigSctk::software_rendering`].
+pub(crate) enum SctkWindowRenderer {
+    OpenGL {
+        opengl_handler: SctkOpenGLHandler,
+        compositor_handler: SctkCompositorHandler,
+    },
+    Software(SctkSoftwareHandler),
+}
+
+impl SctkWindowRenderer {
+    // Note: This callback is executed on the *platform* thread.
+    fn resize(&self, size: PhysicalSize<NonZeroU32>) {
+        // Software frames are presented in buffers of the frame size, so
+        // there's no surface to resize.
+        if let SctkWindowRenderer::OpenGL { opengl_handler, .. } = self {
+            opengl_handler.resize(size);
+        }
+    }
+}
+
 pub(crate) struct SctkFlutterWindowInner {
     id: FlutterViewId,
     window: Window,
@@ -67,12 +174,19 @@ pub(crate) struct SctkFlutterWindowInner {
     current_scale_factor: RwLock<f64>,
     default_size: Size,
     pointers: RwLock<HashMap<ObjectId, Pointer>>,
-    opengl_handler: SctkOpenGLHandler,
-    compositor_handler: SctkCompositorHandler,
+    renderer: SctkWindowRenderer,
     vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
     resize_mutex: Mutex<()>,
-    resize_status: RwLock<ResizeState>,
-    pending_size: RwLock<Option<PhysicalSize<NonZeroU32>>>,
+    resize: RwLock<ResizeProgress>,
+    resize_watchdog: Mutex<ResizeWatchdog>,
+    synchronize_resize: bool,
+    /// Whether the view has been added to the engine. The implicit view is
+    /// always present, while secondary views are only added once their
+    /// initial metrics are known (i.e.: after the first `configure` event).
+    /// It's reset when the engine fails to add the view, so that it's added
+    /// again with the next metrics.
+    view_added: Arc<AtomicBool>,
+    platform_views: SctkPlatformViewSurfaces,
 }
 
 impl SctkFlutterWindowInner {
@@ -85,27 +199,44 @@ impl SctkFlutterWindowInner {
         *self.current_scale_factor.read().unwrap()
     }
 
+    pub(super) fn platform_views(&self) -> &SctkPlatformViewSurfaces {
+        &self.platform_views
+    }
+
+    pub(super) fn wl_surface(&self) -> &WlSurface {
+        self.window.wl_surface()
+    }
+
     pub(super) fn store_current_size(&self, new_size: Size) {
         let mut current_size = self.current_size.write().unwrap();
         *current_size = Some(new_size);
     }
 
     fn store_resize_status(&self, new_resize_status: ResizeState) {
-        let mut resize_status = self.resize_status.write().unwrap();
-        *resize_status = new_resize_status;
+        self.resize.write().unwrap().state = new_resize_status;
     }
 
     pub(super) fn load_resize_status(&self) -> ResizeState {
-        *self.resize_status.read().unwrap()
+        self.resize.read().unwrap().state
     }
 
-    pub(super) fn store_pending_size(&self, new_pending_size: Option<PhysicalSize<NonZeroU32>>) {
-        let mut pending_size = self.pending_size.write().unwrap();
-        *pending_size = new_pending_size;
+    /// Starts a resize to `size`, which completes once a frame of that size
+    /// is presented. Must be called with `resize_mutex` held.
+    fn start_resize(&self, size: PhysicalSize<NonZeroU32>) {
+        let resize_status = ResizeState::started(self.synchronize_resize);
+        self.store_resize_status(resize_status);
+        self.store_pending_size(Some(size));
+
+        if resize_status == ResizeState::ResizeStarted {
+            self.resize_watchdog
+                .lock()
+                .unwrap()
+                .resize_started(Instant::now());
+        }
     }
 
-    pub(super) fn load_pending_size(&self) -> Option<PhysicalSize<NonZeroU32>> {
-        *self.pending_size.read().unwrap()
+    pub(super) fn store_pending_size(&self, new_pending_size: Option<PhysicalSize<NonZeroU32>>) {
+        self.resize.write().unwrap().pending_size = new_pending_size;
     }
 
     pub(super) fn scale_internal_size(&self, new_scale_factor: f64) {
@@ -115,6 +246,14 @@ impl SctkFlutterWindowInner {
         *current_size = current_size.map(|size| size.to_logical::<u32>(new_scale_factor).into());
     }
 
+    pub(super) fn logical_size(&self) -> Option<LogicalSize<u32>> {
+        let scale_factor = self.current_scale_factor.read().unwrap();
+        self.current_size
+            .read()
+            .unwrap()
+            .map(|size| size.to_logical::<u32>(*scale_factor))
+    }
+
     pub(super) fn non_zero_physical_size(&self) -> Option<PhysicalSize<NonZeroU32>> {
         let scale_factor = self.current_scale_factor.read().unwrap();
         self.current_size
@@ -127,30 +266,7 @@ impl SctkFlutterWindowInner {
     pub(super) fn on_frame_generated(&self, size: PhysicalSize<u32>) -> bool {
         trace!("window frame generated: {}x{}", size.width, size.height);
         let _resize_mutex = self.resize_mutex.lock().unwrap();
-
-        let resize_status = self.load_resize_status();
-        if resize_status != ResizeState::ResizeStarted {
-            return true;
-        }
-
-        let Some(pending_size) = self.load_pending_size() else {
-            error!("[on_frame_generated] Invalid resize state: pending size not found");
-            return false;
-        };
-
-        if size.width != pending_size.width.get() || size.height != pending_size.height.get() {
-            trace!(
-                "[on_frame_generated]: Frame size does not match expected size: {}x{} != {}x{}",
-                size.width,
-                size.height,
-                pending_size.width,
-                pending_size.height
-            );
-            return false;
-        }
-
-        self.store_resize_status(ResizeState::FrameGenerated);
-        true
+        self.resize.write().unwrap().frame_generated(size)
     }
 
     // Note: This callback is executed on the *render* thread.
@@ -190,11 +306,82 @@ impl SctkFlutterWindowInner {
             ResizeState::FrameGenerated => {
                 // A frame was generated for a pending resize. Mark the resize as done.
                 self.store_resize_status(ResizeState::Done);
+                self.resize_watchdog.lock().unwrap().resize_done();
             }
             ResizeState::Done => {}
         }
     }
 
+    fn create_flutter_view(&self) -> FlutterView {
+        match &self.renderer {
+            SctkWindowRenderer::OpenGL {
+                opengl_handler,
+                compositor_handler,
+            } => FlutterView::new_with_compositor(
+                self.id,
+                opengl_handler.clone(),
+                compositor_handler.clone(),
+            ),
+            SctkWindowRenderer::Software(software_handler) => {
+                FlutterView::new_software(self.id, software_handler.clone())
+            }
+        }
+    }
+
+    fn is_view_added(&self) -> bool {
+        self.view_added.load(Ordering::Acquire)
+    }
+
+    fn send_window_metrics_event(&self, metrics: WindowMetrics) {
+        let Some(engine) = self.engine.upgrade() else {
+            error!("Unable to upgrade weak engine while sending window metrics event");
+            return;
+        };
+
+        let width = usize::try_from(metrics.size.width.get()).unwrap();
+        let height = usize::try_from(metrics.size.height.get()).unwrap();
+
+        // Secondary views are added to the engine along with their initial
+        // metrics.
+        if !self.view_added.swap(true, Ordering::AcqRel) {
+            let view = self.create_flutter_view();
+            let result = engine.add_secondary_view(
+                view,
+                width,
+                height,
+                metrics.pixel_ratio,
+                metrics.display_id,
+                reset_on_failure(self.view_added.clone()),
+            );
+
+            if let Err(err) = result {
+                error!("[{}] Failed to add view to the engine: {}", self.id, err);
+                self.view_added.store(false, Ordering::Release);
+                return;
+            }
+        } else {
+            let result = engine.send_view_metrics_event(
+                self.id,
+                width,
+                height,
+                metrics.pixel_ratio,
+                metrics.display_id,
+            );
+
+            if let Err(err) = result {
+                error!("[{}] Failed to send window metrics event: {}", self.id, err);
+                return;
+            }
+        }
+
+        // Pointer events are sent in logical pixels (as reported by Wayland),
+        // and scaled by the engine with the scale factor of these metrics.
+        let transform = pointer_transform(&metrics);
+        if let Err(err) = engine.set_view_pointer_transform(self.id, Some(transform)) {
+            error!("[{}] Failed to set the pointer transform: {}", self.id, err);
+        }
+    }
+
     /// A surface can be present on multiple outputs, but currently Flutter only
     /// supports passing a single `display_id` as part of the window metrics
     /// event. Therefore, the current implementation just picks the id of the
@@ -206,17 +393,34 @@ impl SctkFlutterWindowInner {
     }
 }
 
+/// The result callback of adding a view to the engine, which resets
+/// `view_added` when the engine failed to add it.
+fn reset_on_failure(view_added: Arc<AtomicBool>) -> impl FnOnce(bool) + Send + 'static {
+    move |added| {
+        if !added {
+            view_added.store(false, Ordering::Release);
+        }
+    }
+}
+
 pub struct SctkFlutterWindow {
     inner: Arc<SctkFlutterWindowInner>,
+    metrics_debouncer: WindowMetricsDebouncer,
+    scaling: SctkSurfaceScaling,
 }
 
 impl SctkFlutterWindow {
     pub fn new(
+        id: FlutterViewId,
         engine: FlutterEngineWeakRef,
         qh: &QueueHandle<SctkApplicationState>,
         compositor_state: &CompositorState,
+        subcompositor_state: &Arc<SubcompositorState>,
         xdg_shell_state: &XdgShell,
+        shm: &Shm,
+        scaling_globals: &SctkScalingGlobals,
         vsync_handler: Arc<parking_lot::Mutex<SctkVsyncHandler>>,
+        engine_context: Option<Arc<Mutex<Context>>>,
         attributes: ApplicationAttributes,
         config: BackendConfigSctk,
     ) -> Result<Self, SctkFlutterWindowCreateError> {
@@ -227,50 +431,111 @@ impl SctkFlutterWindow {
             window.set_title(title);
         }
 
-        if let Some(app_id) = attributes.app_id {
-            window.set_app_id(app_id);
-        }
+        window.set_app_id(attributes.app_id_or_default());
 
         window.set_min_size(Some((256, 256)));
         window.commit();
 
+        let metrics_debouncer =
+            WindowMetricsDebouncer::new(config.window_metrics_debounce_interval);
+
         let default_size = attributes
             .inner_size
             .unwrap_or(Size::Logical(LogicalSize::<f64>::new(1280.0, 720.0)));
 
-        let (context, resource_context) = FlutterEGLContext::new_wayland_context(
-            window.wl_surface(),
-            default_size.to_physical::<u32>(1.0),
-        )?;
-
-        let context = Arc::new(Mutex::new(context));
-        let resource_context = Arc::new(Mutex::new(resource_context));
+        let surface = window.wl_surface().clone();
+        let scaling = SctkSurfaceScaling::new(config.scaling, &surface, scaling_globals, qh);
+        let physical_size = default_size.to_physical::<u32>(1.0);
+
+        let platform_views = SctkPlatformViewSurfaces::new(
+            surface.clone(),
+            subcompositor_state.clone(),
+            scaling_globals.viewporter().cloned(),
+            qh.clone(),
+        );
+
+        // The EGL context is only created when rendering through OpenGL, so
+        // that software rendering works on systems without a usable EGL.
+        let create_renderer: Box<dyn FnOnce(Weak<SctkFlutterWindowInner>) -> SctkWindowRenderer> =
+            match config.software_rendering {
+                true => {
+                    let pool = SlotPool::new(software_frame_len(physical_size), shm)?;
+                    Box::new(move |inner| {
+                        SctkWindowRenderer::Software(SctkSoftwareHandler::new(inner, surface, pool))
+                    })
+                }
+                false => {
+                    // The engine renders the backing stores of every view in
+                    // its context (i.e.: the one of the implicit window), so
+                    // the contexts of the other windows share its textures.
+                    let shared_context = engine_context
+                        .as_ref()
+                        .and_then(|context| context.lock().unwrap().shared_context());
+                    let (context, resource_context) = FlutterEGLContext::new_wayland_context(
+                        &surface,
+                        physical_size,
+                        config.buffering,
+                        shared_context,
+                        false,
+                    )?;
+                    let context = Arc::new(Mutex::new(context));
+                    let resource_context = Arc::new(Mutex::new(resource_context));
+                    let engine_context = engine_context.unwrap_or_else(|| context.clone());
+                    let overlays = SctkOverlaySurfaces::new(
+                        surface,
+                        compositor_state,
+                        subcompositor_state.clone(),
+                        scaling_globals.viewporter().cloned(),
+                        qh.clone(),
+                        engine_context.clone(),
+                    );
+
+                    Box::new(move |inner| SctkWindowRenderer::OpenGL {
+                        opengl_handler: SctkOpenGLHandler::new(
+                            inner.clone(),
+                            context.clone(),
+                            resource_context,
+                        ),
+                        compositor_handler: SctkCompositorHandler::new(
+                            inner,
+                            context,
+                            engine_context,
+                            overlays,
+                            config.opengl_target_type,
+                            config.gl_debug_output,
+                            config.clear_color,
+                        ),
+                    })
+                }
+            };
 
         let inner = Arc::new_cyclic(|inner| SctkFlutterWindowInner {
-            id: IMPLICIT_VIEW_ID,
+            id,
             window,
             engine,
-            opengl_handler: SctkOpenGLHandler::new(
-                inner.clone(),
-                context.clone(),
-                resource_context,
-            ),
-            compositor_handler: SctkCompositorHandler::new(
-                inner.clone(),
-                context,
-                config.opengl_target_type,
-            ),
+            renderer: create_renderer(inner.clone()),
             vsync_handler,
             resize_mutex: Default::default(),
-            resize_status: Default::default(),
+            resize: Default::default(),
+            resize_watchdog: Default::default(),
+            synchronize_resize: config.synchronize_resize,
             pointers: Default::default(),
             current_size: Default::default(),
             current_scale_factor: RwLock::new(1.0),
-            pending_size: Default::default(),
+            view_added: Arc::new(AtomicBool::new(id == IMPLICIT_VIEW_ID)),
+            platform_views,
             default_size,
         });
 
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            metrics_debouncer,
+            scaling,
+        })
+    }
+
+    pub fn view_id(&self) -> FlutterViewId {
+        self.inner.id
     }
 
     pub fn xdg_toplevel_id(&self) -> ObjectId {
@@ -281,6 +546,20 @@ impl SctkFlutterWindow {
         self.inner.window.wl_surface().clone()
     }
 
+    /// The EGL context of the window, `None` when rendering in software.
+    pub(crate) fn opengl_context(&self) -> Option<Arc<Mutex<Context>>> {
+        match &self.inner.renderer {
+            SctkWindowRenderer::OpenGL { opengl_handler, .. } => Some(opengl_handler.context()),
+            SctkWindowRenderer::Software(_) => None,
+        }
+    }
+
+    /// The registry of the platform view subsurfaces of this window, see
+    /// [`SctkPlatformViewSurfaces`].
+    pub fn platform_view_surfaces(&self) -> SctkPlatformViewSurfaces {
+        self.inner.platform_views.clone()
+    }
+
     pub fn wl_surface_id(&self) -> ObjectId {
         self.inner.window.wl_surface().id()
     }
@@ -290,62 +569,96 @@ impl SctkFlutterWindow {
     }
 
     pub(crate) fn create_flutter_view(&self) -> FlutterView {
-        FlutterView::new_with_compositor(
-            self.inner.id,
-            self.inner.opengl_handler.clone(),
-            self.inner.compositor_handler.clone(),
-        )
+        self.inner.create_flutter_view()
+    }
+
+    /// The pointer transform of the view until its first metrics are sent
+    /// (see [`pointer_transform`]), laid out at the default size of the window.
+    pub(crate) fn initial_pointer_transform(&self) -> FlutterPointerTransform {
+        let scale_factor = self.inner.load_current_scale_factor();
+        FlutterPointerTransform {
+            scale_factor,
+            rotation: FlutterViewRotation::Normal,
+            view_size: self.inner.default_size.to_physical(scale_factor),
+        }
     }
 
+    /// Handles a change of the integer scale preferred for the surface, which
+    /// is ignored when the surface is scaled by the fractional scale instead.
     pub(crate) fn scale_factor_changed(
         &mut self,
         _conn: &Connection,
         surface: &WlSurface,
         new_scale_factor: i32,
     ) {
+        if self.scaling.strategy() == SurfaceScaling::Fractional {
+            trace!(
+                "[{}] ignoring integer scale factor: using the fractional scale",
+                self.inner.id
+            );
+            return;
+        }
+
+        self.preferred_scale_changed(surface, new_scale_factor.into());
+    }
+
+    /// Handles a change of the `wp_fractional_scale_v1` preferred scale.
+    pub(crate) fn fractional_scale_changed(
+        &mut self,
+        _conn: &Connection,
+        surface: &WlSurface,
+        new_scale_factor: f64,
+    ) {
+        self.preferred_scale_changed(surface, new_scale_factor);
+    }
+
+    fn preferred_scale_changed(&mut self, surface: &WlSurface, preferred_scale: f64) {
         let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
 
-        self.inner.scale_internal_size(new_scale_factor.into());
+        let new_scale_factor = buffer_scale_factor(self.scaling.strategy(), preferred_scale);
+        self.inner.scale_internal_size(new_scale_factor);
 
         let Some(physical_size) = self.inner.non_zero_physical_size() else {
             error!("Invalid physical size while handling `scale_factor_changed` event");
             return;
         };
 
-        self.inner.store_resize_status(ResizeState::ResizeStarted);
-        self.inner.store_pending_size(Some(physical_size));
+        self.inner.start_resize(physical_size);
 
-        // Note: Comment related to `opengl_handler.resize()` call from the
+        // Note: Comment related to `renderer.resize()` call from the
         // `SctkFlutterWindow.configure()` method also applies here.
-        self.inner.opengl_handler.resize(physical_size);
-        surface.set_buffer_scale(new_scale_factor);
+        self.inner.renderer.resize(physical_size);
+        if let Some(logical_size) = self.inner.logical_size() {
+            self.scaling.apply(surface, logical_size, new_scale_factor);
+        }
 
         let display_id = self.inner.get_display_id().unwrap_or_default();
 
-        if let Some(engine) = self.inner.engine.upgrade() {
-            trace!(
-                "[preferred_buffer_scale event] sending window metrics event: {}x{} (scale: {})",
-                physical_size.width,
-                physical_size.height,
-                new_scale_factor
-            );
-
-            engine.send_window_metrics_event(
-                self.inner.id,
-                usize::try_from(physical_size.width.get()).unwrap(),
-                usize::try_from(physical_size.height.get()).unwrap(),
-                new_scale_factor as f64,
-                display_id,
-            );
-        }
+        trace!(
+            "[preferred_buffer_scale event] sending window metrics event: {}x{} (scale: {})",
+            physical_size.width,
+            physical_size.height,
+            new_scale_factor
+        );
+
+        // Any debounced metrics are stale now that the scale factor changed.
+        self.metrics_debouncer.clear_pending();
+        self.inner.send_window_metrics_event(WindowMetrics {
+            size: physical_size,
+            pixel_ratio: new_scale_factor,
+            display_id,
+        });
     }
 
+    /// Handles a window `configure` event. When window metrics debouncing is
+    /// enabled, the returned interval indicates that
+    /// `flush_pending_window_metrics` needs to be called once it elapses.
     pub(crate) fn configure(
         &mut self,
         _conn: &Connection,
         configure: WindowConfigure,
         _serial: u32,
-    ) {
+    ) -> Option<Duration> {
         let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
 
         let new_logical_size = WindowLogicalSize::try_from(configure.new_size)
@@ -358,11 +671,10 @@ impl SctkFlutterWindow {
 
         let Some(physical_size) = new_logical_size.to_physical(scale_factor).non_zero() else {
             error!("Unable to convert window configure event to a physical size");
-            return;
+            return None;
         };
 
-        self.inner.store_resize_status(ResizeState::ResizeStarted);
-        self.inner.store_pending_size(Some(physical_size));
+        self.inner.start_resize(physical_size);
 
         // The resize logic is based on Flutter's Windows embedder
         // implementation. However, one notable difference between the two is
@@ -380,26 +692,100 @@ impl SctkFlutterWindow {
         // surface and update the implementation if needed.
         //
         // [0]: https://github.com/flutter/engine/blob/605b3f3/shell/platform/windows/flutter_windows_view.cc#L701-L711
-        self.inner.opengl_handler.resize(physical_size);
+        self.inner.renderer.resize(physical_size);
+        self.scaling.apply(
+            self.inner.window.wl_surface(),
+            new_logical_size.to_logical(scale_factor),
+            scale_factor,
+        );
 
         let display_id = self.inner.get_display_id().unwrap_or_default();
 
-        if let Some(engine) = self.inner.engine.upgrade() {
-            trace!(
-                "[configure event] sending window metrics event: {}x{} (scale: {})",
-                physical_size.width,
-                physical_size.height,
-                scale_factor
-            );
+        let metrics = WindowMetrics {
+            size: physical_size,
+            pixel_ratio: scale_factor,
+            display_id,
+        };
 
-            engine.send_window_metrics_event(
-                self.inner.id,
-                usize::try_from(physical_size.width.get()).unwrap(),
-                usize::try_from(physical_size.height.get()).unwrap(),
-                scale_factor,
-                display_id,
-            );
+        match self.metrics_debouncer.push(metrics) {
+            WindowMetricsDebounce::SendNow(metrics) => {
+                trace!(
+                    "[configure event] sending window metrics event: {}x{} (scale: {})",
+                    physical_size.width,
+                    physical_size.height,
+                    scale_factor
+                );
+                self.inner.send_window_metrics_event(metrics);
+                None
+            }
+            WindowMetricsDebounce::FlushAfter(interval) => Some(interval),
+            WindowMetricsDebounce::Coalesced => None,
+        }
+    }
+
+    /// Sends the latest debounced window metrics (if any) to the engine. This
+    /// is expected to be called once the interval returned by `configure`
+    /// has elapsed.
+    pub(crate) fn flush_pending_window_metrics(&mut self) {
+        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+
+        let Some(metrics) = self.metrics_debouncer.flush() else {
+            return;
+        };
+
+        trace!(
+            "[configure event] sending debounced window metrics event: {}x{} (scale: {})",
+            metrics.size.width,
+            metrics.size.height,
+            metrics.pixel_ratio
+        );
+        self.inner.send_window_metrics_event(metrics);
+    }
+
+    /// Recovers from a resize which didn't complete within
+    /// [`RESIZE_RECOVERY_TIMEOUT`] (e.g.: because a frame of the wrong size
+    /// was presented after the resize started), which would otherwise leave
+    /// the window unresponsive to resizing. This is expected to be called once
+    /// the timeout has elapsed after starting a resize.
+    pub(crate) fn recover_stuck_resize(&mut self) {
+        let _resize_mutex = self.inner.resize_mutex.lock().unwrap();
+
+        let is_stuck = self
+            .inner
+            .resize_watchdog
+            .lock()
+            .unwrap()
+            .is_stuck(Instant::now(), RESIZE_RECOVERY_TIMEOUT);
+        if !is_stuck || self.inner.load_resize_status() == ResizeState::Done {
+            return;
         }
+
+        let Some(physical_size) = self.inner.non_zero_physical_size() else {
+            error!("Invalid physical size while recovering from a stuck resize");
+            return;
+        };
+
+        warn!(
+            "[{}] resize did not complete within {:?}: re-sending window metrics event: {}x{}",
+            self.inner.id, RESIZE_RECOVERY_TIMEOUT, physical_size.width, physical_size.height
+        );
+
+        // Stop gating frames on the stuck resize, and let the engine render a
+        // frame for the current size.
+        self.inner.store_resize_status(ResizeState::Done);
+        self.inner.store_pending_size(None);
+        self.inner.resize_watchdog.lock().unwrap().resize_done();
+
+        self.inner.renderer.resize(physical_size);
+
+        let display_id = self.inner.get_display_id().unwrap_or_default();
+
+        self.metrics_debouncer.clear_pending();
+        self.inner.send_window_metrics_event(WindowMetrics {
+            size: physical_size,
+            pixel_ratio: self.inner.load_current_scale_factor(),
+            display_id,
+        });
     }
 
     pub(crate) fn surface_outputs_changed(&mut self, _conn: &Connection, _surface: &WlSurface) {
@@ -412,15 +798,12 @@ impl SctkFlutterWindow {
 
         let display_id = self.inner.get_display_id().unwrap_or_default();
 
-        if let Some(engine) = self.inner.engine.upgrade() {
-            engine.send_window_metrics_event(
-                self.inner.id,
-                usize::try_from(physical_size.width.get()).unwrap(),
-                usize::try_from(physical_size.height.get()).unwrap(),
-                scale_factor,
-                display_id,
-            );
-        }
+        self.metrics_debouncer.clear_pending();
+        self.inner.send_window_metrics_event(WindowMetrics {
+            size: physical_size,
+            pixel_ratio: scale_factor,
+            display_id,
+        });
     }
 
     pub(crate) fn pointer_event(
@@ -429,23 +812,29 @@ impl SctkFlutterWindow {
         pointer: &WlPointer,
         event: &PointerEvent,
     ) {
-        let sctk_pointer_event = {
+        if !self.inner.is_view_added() {
+            trace!(
+                "[{}] ignoring pointer event for pending view",
+                self.inner.id
+            );
+            return;
+        }
+
+        let events = {
             let mut pointers = self.inner.pointers.write().unwrap();
             let pointer = pointers
                 .entry(pointer.id())
                 .or_insert_with(|| Pointer::new(pointer.id().protocol_id() as i32));
 
-            match event.kind {
-                PointerEventKind::Press { .. } => pointer.increment_pressed(),
-                PointerEventKind::Release { .. } => pointer.decrement_pressed(),
-                _ => {}
+            if !pointer.track_presence(&event.kind) {
+                trace!("[{}] ignoring redundant pointer enter/leave", self.inner.id);
+                return;
             }
 
-            let scale_factor = self.inner.load_current_scale_factor();
-            SctkPointerEvent::new(self.inner.id, event.clone(), *pointer, scale_factor)
+            pointer.track_event(self.inner.id, event)
         };
 
-        let Ok(event) = FlutterPointerEvent::try_from(sctk_pointer_event) else {
+        let Ok(events) = events else {
             error!("Unable to convert wayland pointer event to flutter pointer event");
             return;
         };
@@ -455,6 +844,60 @@ impl SctkFlutterWindow {
             return;
         };
 
+        for event in events {
+            engine.send_pointer_event(event);
+        }
+    }
+
+    /// Sends a pan-zoom event for a pinch gesture performed with `pointer`, at
+    /// the latest position of the pointer.
+    pub(crate) fn pinch_gesture_event(
+        &mut self,
+        pointer: &WlPointer,
+        phase: FlutterPointerPhase,
+        gesture: PinchGesture,
+    ) {
+        if !self.inner.is_view_added() {
+            trace!(
+                "[{}] ignoring pinch gesture for pending view",
+                self.inner.id
+            );
+            return;
+        }
+
+        let Some(pointer) = self
+            .inner
+            .pointers
+            .read()
+            .unwrap()
+            .get(&pointer.id())
+            .copied()
+        else {
+            trace!(
+                "[{}] ignoring pinch gesture of unknown pointer",
+                self.inner.id
+            );
+            return;
+        };
+
+        // In logical pixels, like the other pointer events.
+        let event = FlutterPointerEvent::new(
+            pointer.pan_zoom_device(),
+            phase,
+            pointer.position,
+            FlutterPointerSignalKind::None,
+            (0.0, 0.0),
+            FlutterPointerDeviceKind::Trackpad,
+            FlutterPointerMouseButtons::empty(),
+            self.inner.id,
+        )
+        .with_pan_zoom(gesture.pan, gesture.scale, gesture.rotation);
+
+        let Some(engine) = self.inner.engine.upgrade() else {
+            error!("Unable to upgrade weak engine while sending pinch gesture");
+            return;
+        };
+
         engine.send_pointer_event(event);
     }
 }
@@ -463,6 +906,106 @@ impl SctkFlutterWindow {
 pub enum SctkFlutterWindowCreateError {
     #[error("Failed to create Wayland EGL context")]
     CreateWaylandContextError(#[from] CreateWaylandContextError),
+    #[error("Failed to create shm pool for software rendering")]
+    CreateShmPoolError(#[from] CreatePoolError),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct WindowMetrics {
+    size: PhysicalSize<NonZeroU32>,
+    pixel_ratio: f64,
+    display_id: FlutterEngineDisplayId,
+}
+
+/// Maps the pointer coordinates reported on the window surface (in logical
+/// pixels) to the view, laid out with `metrics`. The view is never rotated on
+/// the surface: compositors apply the transform of the output to the whole
+/// surface, and report pointer coordinates in the (unrotated) surface space.
+fn pointer_transform(metrics: &WindowMetrics) -> FlutterPointerTransform {
+    FlutterPointerTransform {
+        scale_factor: metrics.pixel_ratio,
+        rotation: FlutterViewRotation::Normal,
+        view_size: PhysicalSize::new(
+            metrics.size.width.get().into(),
+            metrics.size.height.get().into(),
+        ),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum WindowMetricsDebounce {
+    /// Debouncing is disabled and the metrics should be sent right away.
+    SendNow(WindowMetrics),
+    /// The metrics have been stored and need to be flushed after the given
+    /// interval.
+    FlushAfter(Duration),
+    /// The metrics replaced previously stored metrics which are already
+    /// scheduled to be flushed.
+    Coalesced,
+}
+
+/// Coalesces window metrics events so that only the latest metrics within the
+/// debounce interval are sent to the engine.
+#[derive(Debug, Default)]
+pub(crate) struct WindowMetricsDebouncer {
+    interval: Option<Duration>,
+    pending: Option<WindowMetrics>,
+    flush_scheduled: bool,
+}
+
+impl WindowMetricsDebouncer {
+    pub(crate) fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn push(&mut self, metrics: WindowMetrics) -> WindowMetricsDebounce {
+        let Some(interval) = self.interval else {
+            return WindowMetricsDebounce::SendNow(metrics);
+        };
+
+        self.pending = Some(metrics);
+
+        if self.flush_scheduled {
+            return WindowMetricsDebounce::Coalesced;
+        }
+
+        self.flush_scheduled = true;
+        WindowMetricsDebounce::FlushAfter(interval)
+    }
+
+    pub(crate) fn flush(&mut self) -> Option<WindowMetrics> {
+        self.flush_scheduled = false;
+        self.pending.take()
+    }
+
+    pub(crate) fn clear_pending(&mut self) {
+        self.pending = None;
+    }
+}
+
+/// Tracks how long the current resize has been in progress.
+#[derive(Debug, Default)]
+pub(crate) struct ResizeWatchdog {
+    started_at: Option<Instant>,
+}
+
+impl ResizeWatchdog {
+    pub(crate) fn resize_started(&mut self, now: Instant) {
+        self.started_at = Some(now);
+    }
+
+    pub(crate) fn resize_done(&mut self) {
+        self.started_at = None;
+    }
+
+    /// Whether the current resize has been in progress for at least `timeout`.
+    pub(crate) fn is_stuck(&self, now: Instant, timeout: Duration) -> bool {
+        self.started_at
+            .is_some_and(|started_at| now.saturating_duration_since(started_at) >= timeout)
+    }
 }
 
 type ConfigureSize = (Option<NonZeroU32>, Option<NonZeroU32>);
@@ -492,3 +1035,153 @@ pub enum SizeConversionError {
     #[error("Invalid size")]
     Invalid,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(width: u32, height: u32) -> WindowMetrics {
+        WindowMetrics {
+            size: PhysicalSize::new(
+                NonZeroU32::new(width).unwrap(),
+                NonZeroU32::new(height).unwrap(),
+            ),
+            pixel_ratio: 1.0,
+            display_id: 0,
+        }
+    }
+
+    #[test]
+    fn failing_to_add_the_view_resets_view_added() {
+        let view_added = Arc::new(AtomicBool::new(true));
+
+        reset_on_failure(view_added.clone())(true);
+        assert!(view_added.load(Ordering::Acquire));
+
+        reset_on_failure(view_added.clone())(false);
+        assert!(!view_added.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn pointer_positions_are_scaled_with_the_metrics() {
+        let transform = pointer_transform(&WindowMetrics {
+            pixel_ratio: 1.5,
+            ..metrics(1200, 900)
+        });
+
+        assert_eq!(transform.apply_to_position((10.0, 20.0)), (15.0, 30.0));
+        assert_eq!(transform.apply_to_delta((0.0, -2.0)), (0.0, -3.0));
+    }
+
+    #[test]
+    fn window_metrics_are_sent_immediately_without_interval() {
+        let mut debouncer = WindowMetricsDebouncer::new(None);
+
+        assert_eq!(
+            debouncer.push(metrics(800, 600)),
+            WindowMetricsDebounce::SendNow(metrics(800, 600))
+        );
+        assert_eq!(debouncer.flush(), None);
+    }
+
+    #[test]
+    fn rapid_window_metrics_collapse_to_final_event() {
+        let interval = Duration::from_millis(16);
+        let mut debouncer = WindowMetricsDebouncer::new(Some(interval));
+
+        assert_eq!(
+            debouncer.push(metrics(800, 600)),
+            WindowMetricsDebounce::FlushAfter(interval)
+        );
+        assert_eq!(
+            debouncer.push(metrics(810, 610)),
+            WindowMetricsDebounce::Coalesced
+        );
+        assert_eq!(
+            debouncer.push(metrics(820, 620)),
+            WindowMetricsDebounce::Coalesced
+        );
+
+        assert_eq!(debouncer.flush(), Some(metrics(820, 620)));
+        assert_eq!(debouncer.flush(), None);
+
+        assert_eq!(
+            debouncer.push(metrics(830, 630)),
+            WindowMetricsDebounce::FlushAfter(interval)
+        );
+    }
+
+    #[test]
+    fn unsynchronized_resizes_never_reject_frames() {
+        let pending_size = Some(metrics(800, 600).size);
+        let stale_size = PhysicalSize::new(640, 480);
+
+        let synchronized = ResizeState::started(true);
+        assert!(!synchronized.accepts_frame(pending_size, stale_size));
+        assert!(synchronized.accepts_frame(pending_size, PhysicalSize::new(800, 600)));
+
+        let unsynchronized = ResizeState::started(false);
+        assert!(unsynchronized.accepts_frame(pending_size, stale_size));
+        assert!(unsynchronized.accepts_frame(None, stale_size));
+    }
+
+    #[test]
+    fn stale_frames_are_rejected_until_the_resize_completes() {
+        let pending_size = metrics(800, 600).size;
+        let mut resize = ResizeProgress {
+            state: ResizeState::started(true),
+            pending_size: Some(pending_size),
+        };
+
+        // A frame rendered for the previous size can't complete the resize.
+        assert!(!resize.frame_generated(PhysicalSize::new(640, 480)));
+        assert_eq!(resize.state, ResizeState::ResizeStarted);
+
+        assert!(resize.frame_generated(PhysicalSize::new(800, 600)));
+        assert_eq!(resize.state, ResizeState::FrameGenerated);
+
+        // Without a resize in progress, frames of any size are presented.
+        let mut resize = ResizeProgress::default();
+        assert!(resize.frame_generated(PhysicalSize::new(640, 480)));
+        assert_eq!(resize.state, ResizeState::Done);
+
+        // A resize without a pending size is invalid, so it isn't completed.
+        let mut resize = ResizeProgress {
+            state: ResizeState::ResizeStarted,
+            pending_size: None,
+        };
+        assert!(!resize.frame_generated(PhysicalSize::new(800, 600)));
+        assert_eq!(resize.state, ResizeState::ResizeStarted);
+    }
+
+    #[test]
+    fn stuck_resize_recovers_after_timeout() {
+        let mut watchdog = ResizeWatchdog::default();
+        let started_at = Instant::now();
+        assert!(!watchdog.is_stuck(started_at, RESIZE_RECOVERY_TIMEOUT));
+
+        watchdog.resize_started(started_at);
+        assert!(!watchdog.is_stuck(
+            started_at + Duration::from_millis(100),
+            RESIZE_RECOVERY_TIMEOUT
+        ));
+        assert!(watchdog.is_stuck(
+            started_at + RESIZE_RECOVERY_TIMEOUT,
+            RESIZE_RECOVERY_TIMEOUT
+        ));
+
+        // A new resize restarts the timeout, and recovering completes it.
+        let restarted_at = started_at + Duration::from_millis(400);
+        watchdog.resize_started(restarted_at);
+        assert!(!watchdog.is_stuck(
+            started_at + RESIZE_RECOVERY_TIMEOUT,
+            RESIZE_RECOVERY_TIMEOUT
+        ));
+
+        watchdog.resize_done();
+        assert!(!watchdog.is_stuck(
+            restarted_at + RESIZE_RECOVERY_TIMEOUT,
+            RESIZE_RECOVERY_TIMEOUT
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::engine_library::{host_library_name, EngineLibraryCache};
+use crate::Build;
+use std::path::PathBuf;
+
+/// Directory containing the data files of a bundle.
+const DATA_DIR: &str = "data";
+/// Directory containing the libraries of a bundle.
+const LIB_DIR: &str = "lib";
+
+const ASSETS_DIR_NAME: &str = "flutter_assets";
+const ICU_DATA_FILE_NAME: &str = "icudtl.dat";
+const KERNEL_BLOB_FILE_NAME: &str = "kernel_blob.bin";
+const AOT_LIBRARY_FILE_NAME: &str = "libapp.so";
+
+/// A piece of an app bundle which is missing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BundleIssue {
+    MissingAssets(PathBuf),
+    MissingKernelBlob(PathBuf),
+    MissingAotLibrary(PathBuf),
+    MissingIcuData(PathBuf),
+    MissingEngineLibrary { version: String, build: Build },
+}
+
+impl std::fmt::Display for BundleIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BundleIssue::MissingAssets(path) => {
+                write!(f, "Missing flutter assets: {}", path.display())
+            }
+            BundleIssue::MissingKernelBlob(path) => write!(
+                f,
+                "Missing kernel blob required in debug mode: {}",
+                path.display()
+            ),
+            BundleIssue::MissingAotLibrary(path) => write!(
+                f,
+                "Missing AOT library required in profile and release modes: {}",
+                path.display()
+            ),
+            BundleIssue::MissingIcuData(path) => {
+                write!(f, "Missing ICU data: {}", path.display())
+            }
+            BundleIssue::MissingEngineLibrary { version, build } => write!(
+                f,
+                "Missing engine library: {} is neither bundled nor installed for version {} ({})",
+                host_library_name(),
+                version,
+                build.mode()
+            ),
+        }
+    }
+}
+
+/// An app bundle, following the layout of Flutter's Linux bundles:
+///
+/// ```text
+/// <bundle>/data/flutter_assets/   (including kernel_blob.bin in debug mode)
+/// <bundle>/data/icudtl.dat
+/// <bundle>/lib/libapp.so          (profile and release modes only)
+/// <bundle>/lib/<engine library>   (optional, the cache is used otherwise)
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Bundle {
+    root: PathBuf,
+}
+
+impl Bundle {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn assets_path(&self) -> PathBuf {
+        self.root.join(DATA_DIR).join(ASSETS_DIR_NAME)
+    }
+
+    pub fn icu_data_path(&self) -> PathBuf {
+        self.root.join(DATA_DIR).join(ICU_DATA_FILE_NAME)
+    }
+
+    pub fn aot_library_path(&self) -> PathBuf {
+        self.root.join(LIB_DIR).join(AOT_LIBRARY_FILE_NAME)
+    }
+
+    pub fn engine_library_path(&self) -> PathBuf {
+        self.root.join(LIB_DIR).join(host_library_name())
+    }
+
+    /// Checks that the bundle contains everything needed for running in the
+    /// given build mode, with the engine library of `engine_version` either
+    /// bundled or installed in `cache`. Returns every missing piece.
+    pub fn verify(
+        &self,
+        cache: &EngineLibraryCache,
+        engine_version: &str,
+        build: Build,
+    ) -> Vec<BundleIssue> {
+        let mut issues = Vec::new();
+
+        let assets_path = self.assets_path();
+        if !assets_path.is_dir() {
+            issues.push(BundleIssue::MissingAssets(assets_path.clone()));
+        }
+
+        match build {
+            Build::Debug => {
+                let kernel_blob_path = assets_path.join(KERNEL_BLOB_FILE_NAME);
+                if assets_path.is_dir() && !kernel_blob_path.is_file() {
+                    issues.push(BundleIssue::MissingKernelBlob(kernel_blob_path));
+                }
+            }
+            Build::Profile | Build::Release => {
+                let aot_library_path = self.aot_library_path();
+                if !aot_library_path.is_file() {
+                    issues.push(BundleIssue::MissingAotLibrary(aot_library_path));
+                }
+            }
+        }
+
+        let icu_data_path = self.icu_data_path();
+        if !icu_data_path.is_file() {
+            issues.push(BundleIssue::MissingIcuData(icu_data_path));
+        }
+
+        let has_engine_library = self.engine_library_path().is_file()
+            || cache
+                .find_canonical_path_for_installed_version(engine_version, build)
+                .is_some();
+        if !has_engine_library {
+            issues.push(BundleIssue::MissingEngineLibrary {
+                version: engine_version.to_owned(),
+                build,
+            });
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::path::Path;
+
+    fn write_file(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn complete_bundle_has_no_issues() {
+        let dir = TempDir::new("flust-tools-bundle");
+        let root = dir.path();
+        let bundle = Bundle::new(root.join("bundle"));
+        let cache = EngineLibraryCache::new_from_path(root.join("cache"));
+
+        write_file(&bundle.assets_path().join(KERNEL_BLOB_FILE_NAME));
+        write_file(&bundle.icu_data_path());
+        write_file(&cache.library_path("1234abcd", Build::Debug));
+
+        assert_eq!(bundle.verify(&cache, "1234abcd", Build::Debug), vec![]);
+    }
+
+    #[test]
+    fn incomplete_bundle_reports_each_missing_piece() {
+        let dir = TempDir::new("flust-tools-bundle");
+        let root = dir.path();
+        let bundle = Bundle::new(root.join("bundle"));
+        let cache = EngineLibraryCache::new_from_path(root.join("cache"));
+
+        write_file(&bundle.assets_path().join("AssetManifest.json"));
+
+        assert_eq!(
+            bundle.verify(&cache, "1234abcd", Build::Release),
+            vec![
+                BundleIssue::MissingAotLibrary(bundle.aot_library_path()),
+                BundleIssue::MissingIcuData(bundle.icu_data_path()),
+                BundleIssue::MissingEngineLibrary {
+                    version: "1234abcd".to_owned(),
+                    build: Build::Release,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{download, host_target, library_name, unzip, Build, Engine, Error, Flutter};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE_NAME: &str = ".lock";
+
+/// On-disk cache of Flutter engine libraries.
+///
+/// Libraries are organized by engine version and build mode:
+/// `<root>/by-engine-version/<engine_version>/<build_mode>/<library_name>`.
+/// The libraries of another target than the host (see
+/// [`EngineLibraryCache::with_target`]) are kept in a `<target>` directory
+/// of the build mode directory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EngineLibraryCache {
+    root: PathBuf,
+    /// The target libraries are installed for, `None` for the host.
+    target: Option<String>,
+    library_name: &'static str,
+}
+
+impl EngineLibraryCache {
+    pub fn new() -> Result<Self, Error> {
+        let root = dirs::cache_dir()
+            .ok_or(Error::CacheDirNotFound)?
+            .join("flutter-engine-lib");
+        Ok(Self::new_from_path(root))
+    }
+
+    pub fn new_from_path(root: PathBuf) -> Self {
+        Self {
+            root,
+            target: None,
+            library_name: host_library_name(),
+        }
+    }
+
+    /// Installs and looks up the libraries of `target` (e.g.:
+    /// `"aarch64-linux-android"`) instead of the ones of the host. Fails with
+    /// [`Error::UnsupportedTarget`] if there are no engine builds for it.
+    pub fn with_target(mut self, target: impl Into<String>) -> Result<Self, Error> {
+        let target = target.into();
+        self.library_name = library_name(&target)?;
+        self.target = match host_target() {
+            Ok(host_target) if host_target == target => None,
+            _ => Some(target),
+        };
+        Ok(self)
+    }
+
+    /// The target libraries are installed for, see [`host_target`].
+    pub fn target(&self) -> Result<&str, Error> {
+        match &self.target {
+            Some(target) => Ok(target),
+            None => host_target(),
+        }
+    }
+
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn by_engine_version_dir(&self) -> PathBuf {
+        self.root.join("by-engine-version")
+    }
+
+    pub fn library_dir(&self, engine_version: &str, build: Build) -> PathBuf {
+        let dir = self
+            .by_engine_version_dir()
+            .join(engine_version)
+            .join(build.mode());
+        match &self.target {
+            Some(target) => dir.join(target),
+            None => dir,
+        }
+    }
+
+    pub fn library_path(&self, engine_version: &str, build: Build) -> PathBuf {
+        self.library_dir(engine_version, build)
+            .join(self.library_name)
+    }
+
+    /// Returns the canonical path of the engine library installed for the
+    /// given version and build mode, or `None` if it is not installed.
+    pub fn find_canonical_path_for_installed_version(
+        &self,
+        engine_version: &str,
+        build: Build,
+    ) -> Option<PathBuf> {
+        std::fs::canonicalize(self.library_path(engine_version, build)).ok()
+    }
+
+    /// Returns the engine libraries installed in the cache, sorted by version
+    /// and build mode.
+    pub fn installed_libraries(&self) -> Result<Vec<InstalledLibrary>, Error> {
+        let by_engine_version_dir = self.by_engine_version_dir();
+        if !by_engine_version_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut libraries = Vec::new();
+        for entry in std::fs::read_dir(by_engine_version_dir)? {
+            let entry = entry?;
+            let Some(version) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+
+            for entry in std::fs::read_dir(entry.path())? {
+                let Some(build) = entry?.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                    continue;
+                };
+
+                if let Some(path) = self.find_canonical_path_for_installed_version(&version, build)
+                {
+                    libraries.push(InstalledLibrary {
+                        version: version.clone(),
+                        build,
+                        path,
+                    });
+                }
+            }
+        }
+
+        libraries.sort_by(|a, b| (&a.version, a.build.mode()).cmp(&(&b.version, b.build.mode())));
+        Ok(libraries)
+    }
+
+    pub fn lock_path(&self) -> PathBuf {
+        self.root.join(LOCK_FILE_NAME)
+    }
+
+    /// Acquires an exclusive lock of the cache, blocking until operations of
+    /// other processes (e.g.: concurrent builds installing the engine library)
+    /// are done. The lock is released once the returned guard is dropped.
+    pub fn lock(&self) -> Result<EngineLibraryCacheLock, Error> {
+        std::fs::create_dir_all(&self.root)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.lock_path())?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                println!("Waiting for another operation on the engine library cache to finish...");
+                file.lock()?;
+            }
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+
+        Ok(EngineLibraryCacheLock { _file: file })
+    }
+
+    /// Downloads and extracts the engine library for the given version and
+    /// build mode. Returns the directory containing the library.
+    pub fn install_version(&self, engine_version: &str, build: Build) -> Result<PathBuf, Error> {
+        let _lock = self.lock()?;
+        self.install_version_locked(engine_version, build)
+    }
+
+    fn install_version_locked(&self, engine_version: &str, build: Build) -> Result<PathBuf, Error> {
+        let engine = Engine::new(engine_version.to_owned(), self.target()?.to_owned(), build);
+        let dir = self.library_dir(engine_version, build);
+
+        std::fs::create_dir_all(&dir)?;
+
+        let download_file = dir.join("engine.zip");
+        download(&engine.try_download_url()?, &download_file)?;
+        unzip(&download_file, &dir)?;
+        std::fs::remove_file(&download_file)?;
+
+        Ok(dir)
+    }
+
+    /// Returns the directory containing the engine library for the given
+    /// version and build mode, installing it first if it is not already
+    /// present in the cache.
+    pub fn ensure_installed(&self, engine_version: &str, build: Build) -> Result<PathBuf, Error> {
+        if let Some(dir) = self.installed_library_dir(engine_version, build) {
+            return Ok(dir);
+        }
+
+        let _lock = self.lock()?;
+
+        // Another process might have installed it while waiting for the lock.
+        match self.installed_library_dir(engine_version, build) {
+            Some(dir) => Ok(dir),
+            None => self.install_version_locked(engine_version, build),
+        }
+    }
+
+    fn installed_library_dir(&self, engine_version: &str, build: Build) -> Option<PathBuf> {
+        let path = self.find_canonical_path_for_installed_version(engine_version, build)?;
+        Some(
+            path.parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| self.library_dir(engine_version, build)),
+        )
+    }
+}
+
+/// An engine library installed in an [`EngineLibraryCache`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstalledLibrary {
+    pub version: String,
+    pub build: Build,
+    /// Canonical path of the library.
+    pub path: PathBuf,
+}
+
+impl InstalledLibrary {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version": self.version,
+            "build": self.build.mode(),
+            "path": self.path,
+        })
+    }
+}
+
+/// Exclusive lock of an [`EngineLibraryCache`], see [`EngineLibraryCache::lock`].
+#[derive(Debug)]
+pub struct EngineLibraryCacheLock {
+    _file: File,
+}
+
+/// Makes sure the engine library required by the current project is installed
+/// and returns the directory that should be added to the link search path (and
+/// rpath).
+///
+/// This is meant to be called from an application's `build.rs`. The engine
+/// version is resolved using [`detect_engine_version`]. The library of the
+/// host is installed, unless another target is set by the
+/// `FLUTTER_ENGINE_TARGET` environment variable (e.g.: when cross-compiling).
+pub fn ensure_engine_library(build: Build) -> Result<PathBuf, Error> {
+    let engine_version = detect_engine_version()?;
+    let mut cache = EngineLibraryCache::new()?;
+    if let Ok(target) = std::env::var("FLUTTER_ENGINE_TARGET") {
+        cache = cache.with_target(target)?;
+    }
+    cache.ensure_installed(&engine_version, build)
+}
+
+/// Resolves the engine version required by the current project.
+///
+/// The `FLUTTER_ENGINE_VERSION` environment variable takes precedence, followed
+/// by the `package.metadata.flutter.engine_version` key of the crate being
+/// built. Otherwise, the version of the auto-detected Flutter SDK is used.
+pub fn detect_engine_version() -> Result<String, Error> {
+    if let Ok(engine_version) = std::env::var("FLUTTER_ENGINE_VERSION") {
+        return Ok(engine_version);
+    }
+
+    if let Some(engine_version) = engine_version_from_cargo_metadata() {
+        return Ok(engine_version);
+    }
+
+    Flutter::auto_detect()?.engine_version()
+}
+
+fn engine_version_from_cargo_metadata() -> Option<String> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").ok()?;
+    let manifest = std::fs::read_to_string(Path::new(&manifest_dir).join("Cargo.toml")).ok()?;
+    let manifest: toml::Table = manifest.parse().ok()?;
+
+    manifest
+        .get("package")?
+        .get("metadata")?
+        .get("flutter")?
+        .get("engine_version")?
+        .as_str()
+        .map(str::to_owned)
+}
+
+pub fn host_library_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "flutter_engine.dll"
+    } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+        "libflutter_engine.dylib"
+    } else {
+        "libflutter_engine.so"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn ensure_installed_is_idempotent_when_already_installed() {
+        let dir = TempDir::new("flust-tools-engine-library");
+        let cache = EngineLibraryCache::new_from_path(dir.path().to_owned());
+
+        let library_path = cache.library_path("1234abcd", Build::Debug);
+        std::fs::create_dir_all(library_path.parent().unwrap()).unwrap();
+        std::fs::write(&library_path, b"").unwrap();
+
+        let first = cache.ensure_installed("1234abcd", Build::Debug).unwrap();
+        let second = cache.ensure_installed("1234abcd", Build::Debug).unwrap();
+
+        assert_eq!(first, second);
+        assert!(first.join(host_library_name()).exists());
+    }
+
+    #[test]
+    fn host_target_is_detected() {
+        let target = host_target().unwrap();
+        assert!(target.starts_with(std::env::consts::ARCH));
+        assert_eq!(library_name(target).unwrap(), host_library_name());
+
+        assert_eq!(
+            crate::host_target_for("macos", "x86_64"),
+            Some("x86_64-apple-darwin")
+        );
+        assert_eq!(crate::host_target_for("freebsd", "x86_64"), None);
+    }
+
+    #[test]
+    fn libraries_of_other_targets_are_kept_apart() {
+        let root = PathBuf::from("cache");
+        let host = EngineLibraryCache::new_from_path(root.clone());
+        assert_eq!(
+            host.clone().with_target(host_target().unwrap()).unwrap(),
+            host
+        );
+
+        let android = host.clone().with_target("aarch64-linux-android").unwrap();
+        assert_eq!(android.target().unwrap(), "aarch64-linux-android");
+        assert_eq!(
+            android.library_path("1234abcd", Build::Release),
+            android
+                .by_engine_version_dir()
+                .join("1234abcd")
+                .join("release")
+                .join("aarch64-linux-android")
+                .join("libflutter_engine.so")
+        );
+
+        assert!(matches!(
+            host.with_target("riscv64gc-unknown-linux-gnu"),
+            Err(Error::UnsupportedTarget(target)) if target == "riscv64gc-unknown-linux-gnu"
+        ));
+        let engine = Engine::new(
+            "1234abcd".to_owned(),
+            "riscv64gc-unknown-linux-gnu".to_owned(),
+            Build::Release,
+        );
+        assert!(matches!(
+            engine.try_download_url(),
+            Err(Error::UnsupportedTarget(_))
+        ));
+    }
+
+    #[test]
+    fn installed_libraries_are_listed_as_json() {
+        let dir = TempDir::new("flust-tools-engine-library-list");
+        let cache = EngineLibraryCache::new_from_path(dir.path().to_owned());
+
+        let installed = [
+            ("1234abcd", Build::Debug),
+            ("1234abcd", Build::Release),
+            ("5678efgh", Build::Profile),
+        ];
+        for (version, build) in installed {
+            let library_path = cache.library_path(version, build);
+            std::fs::create_dir_all(library_path.parent().unwrap()).unwrap();
+            std::fs::write(&library_path, b"").unwrap();
+        }
+        // A build mode directory without a library isn't installed.
+        std::fs::create_dir_all(cache.library_dir("5678efgh", Build::Debug)).unwrap();
+
+        let json: Vec<serde_json::Value> = cache
+            .installed_libraries()
+            .unwrap()
+            .iter()
+            .map(InstalledLibrary::to_json)
+            .collect();
+
+        let expected: Vec<serde_json::Value> = installed
+            .iter()
+            .map(|(version, build)| {
+                serde_json::json!({
+                    "version": version,
+                    "build": build.mode(),
+                    "path": std::fs::canonicalize(cache.library_path(version, *build)).unwrap(),
+                })
+            })
+            .collect();
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn concurrent_cache_operations_are_serialized() {
+        let dir = TempDir::new("flust-tools-engine-library-lock");
+        let cache = EngineLibraryCache::new_from_path(dir.path().to_owned());
+        let log_path = dir.path().join("operations.log");
+
+        let installs: Vec<_> = (0..2)
+            .map(|i| {
+                let cache = cache.clone();
+                let log_path = log_path.clone();
+                std::thread::spawn(move || {
+                    let _lock = cache.lock().unwrap();
+                    let append = |entry: String| {
+                        let mut log = std::fs::read_to_string(&log_path).unwrap_or_default();
+                        log.push_str(&entry);
+                        std::fs::write(&log_path, log).unwrap();
+                    };
+
+                    append(format!("begin {}\n", i));
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    append(format!("end {}\n", i));
+                })
+            })
+            .collect();
+
+        for install in installs {
+            install.join().unwrap();
+        }
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let entries: Vec<&str> = log.lines().collect();
+        assert_eq!(entries.len(), 4);
+        for operation in entries.chunks(2) {
+            assert_eq!(
+                operation[0].strip_prefix("begin "),
+                operation[1].strip_prefix("end ")
+            );
+        }
+    }
+}
@@ -7,11 +7,20 @@ use std::sync::Arc;
 use zip::result::ZipError;
 use zip::ZipArchive;
 
+pub mod bundle;
+pub mod engine_library;
+#[cfg(test)]
+mod temp_dir;
+
 #[derive(Debug)]
 pub enum Error {
     FlutterNotFound,
     DownloadNotFound,
     DartNotFound,
+    CacheDirNotFound,
+    InvalidBuildMode(String),
+    UnsupportedHost(String),
+    UnsupportedTarget(String),
     Io(std::io::Error),
     Which(which::Error),
     Curl(curl::Error),
@@ -47,6 +56,20 @@ You'll find the available builds on our github releases page [0].
 - [0] https://github.com/flutter-rs/engine-builds/releases"#,
             ),
             Error::DartNotFound => write!(f, "Could't find dart"),
+            Error::CacheDirNotFound => write!(f, "Cannot get cache dir"),
+            Error::InvalidBuildMode(mode) => write!(
+                f,
+                "Invalid build mode '{}' (expected one of: debug, profile, release)",
+                mode
+            ),
+            Error::UnsupportedHost(host) => write!(
+                f,
+                "No engine builds for the host platform '{}', its target must be specified",
+                host
+            ),
+            Error::UnsupportedTarget(target) => {
+                write!(f, "No engine builds for the target '{}'", target)
+            }
             Error::Which(error) => error.fmt(f),
             Error::Io(error) => error.fmt(f),
             Error::Curl(error) => error.fmt(f),
@@ -149,6 +172,27 @@ impl Build {
             Self::Profile => "profile",
         }
     }
+
+    pub fn mode(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Release => "release",
+            Self::Profile => "profile",
+        }
+    }
+}
+
+impl std::str::FromStr for Build {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(Self::Debug),
+            "release" => Ok(Self::Release),
+            "profile" => Ok(Self::Profile),
+            _ => Err(Error::InvalidBuildMode(s.to_owned())),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -167,7 +211,22 @@ impl Engine {
         }
     }
 
+    /// The engine for the host the tools are running on, see [`host_target`].
+    pub fn for_host(version: String, build: Build) -> Result<Self, Error> {
+        Ok(Self::new(version, host_target()?.to_owned(), build))
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Like [`Engine::try_download_url`], but panics if the target has no
+    /// engine builds.
     pub fn download_url(&self) -> String {
+        self.try_download_url().expect("unsupported platform")
+    }
+
+    pub fn try_download_url(&self) -> Result<String, Error> {
         let build = self.build.build();
         let platform = match self.target.as_str() {
             "x86_64-unknown-linux-gnu" => format!("linux_x64-host_{}", build),
@@ -179,27 +238,22 @@ impl Engine {
             "armv7-apple-ios" => format!("macosx_x64-ios_{}_arm", build),
             "aarch64-apple-ios" => format!("macosx_x64-ios_{}", build),
             "x86_64-pc-windows-msvc" => format!("windows_x64-host_{}", build),
-            _ => panic!("unsupported platform"),
+            _ => return Err(Error::UnsupportedTarget(self.target.clone())),
         };
-        format!(
+        Ok(format!(
             "https://github.com/flutter-rs/engine-builds/releases/download/f-{0}/{1}.zip",
             &self.version, platform
-        )
+        ))
     }
 
+    /// Like [`Engine::try_library_name`], but panics if the target has no
+    /// engine builds.
     pub fn library_name(&self) -> &'static str {
-        match self.target.as_str() {
-            "x86_64-unknown-linux-gnu" => "libflutter_engine.so",
-            "armv7-linux-androideabi" => "libflutter_engine.so",
-            "aarch64-linux-android" => "libflutter_engine.so",
-            "i686-linux-android" => "libflutter_engine.so",
-            "x86_64-linux-android" => "libflutter_engine.so",
-            "x86_64-apple-darwin" => "libflutter_engine.dylib",
-            "armv7-apple-ios" => "libflutter_engine.dylib",
-            "aarch64-apple-ios" => "libflutter_engine.dylib",
-            "x86_64-pc-windows-msvc" => "flutter_engine.dll",
-            _ => panic!("unsupported platform"),
-        }
+        self.try_library_name().expect("unsupported platform")
+    }
+
+    pub fn try_library_name(&self) -> Result<&'static str, Error> {
+        library_name(&self.target)
     }
 
     pub fn engine_dir(&self) -> PathBuf {
@@ -211,13 +265,19 @@ impl Engine {
             .join(self.build.build())
     }
 
+    /// Like [`Engine::try_library_path`], but panics if the target has no
+    /// engine builds.
     pub fn library_path(&self) -> PathBuf {
-        self.engine_dir().join(self.library_name())
+        self.try_library_path().expect("unsupported platform")
+    }
+
+    pub fn try_library_path(&self) -> Result<PathBuf, Error> {
+        Ok(self.engine_dir().join(self.try_library_name()?))
     }
 
     pub fn download(&self) -> Result<(), Error> {
-        let url = self.download_url();
-        let path = self.library_path();
+        let url = self.try_download_url()?;
+        let path = self.try_library_path()?;
         let dir = path.parent().unwrap().to_owned();
 
         if path.exists() {
@@ -243,7 +303,40 @@ impl Engine {
     }
 }
 
-fn download(url: &str, target: &Path) -> Result<(), Error> {
+/// Detects the target triple of the host the tools are running on, from its
+/// operating system and architecture. Only hosts with engine builds are
+/// detected, other targets (e.g.: when cross-installing) must be specified.
+pub fn host_target() -> Result<&'static str, Error> {
+    let (os, arch) = (std::env::consts::OS, std::env::consts::ARCH);
+    host_target_for(os, arch).ok_or_else(|| Error::UnsupportedHost(format!("{}-{}", arch, os)))
+}
+
+pub(crate) fn host_target_for(os: &str, arch: &str) -> Option<&'static str> {
+    match (os, arch) {
+        ("linux", "x86_64") => Some("x86_64-unknown-linux-gnu"),
+        ("macos", "x86_64") => Some("x86_64-apple-darwin"),
+        ("windows", "x86_64") => Some("x86_64-pc-windows-msvc"),
+        _ => None,
+    }
+}
+
+/// The name of the engine library of `target`, which must have engine builds.
+pub(crate) fn library_name(target: &str) -> Result<&'static str, Error> {
+    match target {
+        "x86_64-unknown-linux-gnu" => Ok("libflutter_engine.so"),
+        "armv7-linux-androideabi" => Ok("libflutter_engine.so"),
+        "aarch64-linux-android" => Ok("libflutter_engine.so"),
+        "i686-linux-android" => Ok("libflutter_engine.so"),
+        "x86_64-linux-android" => Ok("libflutter_engine.so"),
+        "x86_64-apple-darwin" => Ok("libflutter_engine.dylib"),
+        "armv7-apple-ios" => Ok("libflutter_engine.dylib"),
+        "aarch64-apple-ios" => Ok("libflutter_engine.dylib"),
+        "x86_64-pc-windows-msvc" => Ok("flutter_engine.dll"),
+        _ => Err(Error::UnsupportedTarget(target.to_owned())),
+    }
+}
+
+pub(crate) fn download(url: &str, target: &Path) -> Result<(), Error> {
     println!("Starting download from {}", url);
     let mut file = File::create(target)?;
     let mut last_done = 0.0;
@@ -280,7 +373,7 @@ fn download(url: &str, target: &Path) -> Result<(), Error> {
     Ok(())
 }
 
-fn unzip(archive: &Path, dir: &Path) -> Result<(), Error> {
+pub(crate) fn unzip(archive: &Path, dir: &Path) -> Result<(), Error> {
     println!("Extracting {:?}...", archive.file_name().unwrap());
 
     let file = File::open(archive)?;
@@ -0,0 +1,191 @@
+use clap::{Parser, Subcommand};
+use flust_tools::bundle::Bundle;
+use flust_tools::engine_library::{detect_engine_version, EngineLibraryCache, InstalledLibrary};
+use flust_tools::Build;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "flust-tools", about = "Flust development tools")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Manage the engine libraries cache
+    EngineLibrary {
+        #[command(subcommand)]
+        command: EngineLibraryCommands,
+    },
+    /// Check that an app bundle contains everything needed for running it
+    Verify {
+        /// Bundle directory
+        #[arg(long)]
+        bundle: PathBuf,
+
+        /// Build mode (debug, profile or release)
+        #[arg(long, default_value = "debug")]
+        build: Build,
+
+        /// Flutter engine version (detected from the project or the Flutter
+        /// SDK by default)
+        #[arg(long)]
+        engine_version: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum EngineLibraryCommands {
+    /// List the installed engine libraries
+    List {
+        /// Print machine-readable output
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the path of an installed engine library
+    Path {
+        /// Flutter engine version
+        #[arg(long)]
+        version: String,
+
+        /// Build mode (debug, profile or release)
+        #[arg(long, default_value = "debug")]
+        build: Build,
+
+        /// Target triple of the library (the host's by default)
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Print machine-readable output
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::EngineLibrary { command } => match command {
+            EngineLibraryCommands::List { json } => list_libraries(json),
+            EngineLibraryCommands::Path {
+                version,
+                build,
+                target,
+                json,
+            } => print_library_path(&version, build, target, json),
+        },
+        Commands::Verify {
+            bundle,
+            build,
+            engine_version,
+        } => verify_bundle(bundle, build, engine_version),
+    }
+}
+
+fn list_libraries(json: bool) -> ExitCode {
+    let libraries = match EngineLibraryCache::new().and_then(|cache| cache.installed_libraries()) {
+        Ok(libraries) => libraries,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if json {
+        let output: Vec<_> = libraries.iter().map(InstalledLibrary::to_json).collect();
+        println!("{}", serde_json::Value::Array(output));
+        return ExitCode::SUCCESS;
+    }
+
+    let version_width = libraries
+        .iter()
+        .map(|library| library.version.len())
+        .chain(["VERSION".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!("{:<version_width$}  {:<7}  PATH", "VERSION", "BUILD");
+    for library in &libraries {
+        println!(
+            "{:<version_width$}  {:<7}  {}",
+            library.version,
+            library.build.mode(),
+            library.path.display()
+        );
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn print_library_path(version: &str, build: Build, target: Option<String>, json: bool) -> ExitCode {
+    let cache = EngineLibraryCache::new().and_then(|cache| match target {
+        Some(target) => cache.with_target(target),
+        None => Ok(cache),
+    });
+    let cache = match cache {
+        Ok(cache) => cache,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(path) = cache.find_canonical_path_for_installed_version(version, build) else {
+        eprintln!(
+            "Engine library version {} ({}) is not installed",
+            version,
+            build.mode()
+        );
+        return ExitCode::FAILURE;
+    };
+
+    if json {
+        let output = serde_json::json!({
+            "version": version,
+            "build": build.mode(),
+            "path": path,
+        });
+        println!("{}", output);
+    } else {
+        println!("{}", path.display());
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn verify_bundle(bundle: PathBuf, build: Build, engine_version: Option<String>) -> ExitCode {
+    let engine_version = match engine_version.map_or_else(detect_engine_version, Ok) {
+        Ok(engine_version) => engine_version,
+        Err(err) => {
+            eprintln!("Unable to detect the engine version: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let cache = match EngineLibraryCache::new() {
+        Ok(cache) => cache,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let issues = Bundle::new(bundle.clone()).verify(&cache, &engine_version, build);
+    if !issues.is_empty() {
+        for issue in &issues {
+            eprintln!("{}", issue);
+        }
+        return ExitCode::FAILURE;
+    }
+
+    println!(
+        "Bundle {} is complete ({}, engine version {})",
+        bundle.display(),
+        build.mode(),
+        engine_version
+    );
+    ExitCode::SUCCESS
+}
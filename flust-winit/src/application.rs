@@ -2,6 +2,7 @@ use ashpd::desktop::settings::{ColorScheme, Settings};
 use async_executor::LocalExecutor;
 use flust_runner_api::ApplicationAttributes;
 use flust_engine::builder::FlutterEngineBuilder;
+use flust_engine::ffi::IMPLICIT_VIEW_ID;
 use flust_engine::plugins::Plugin;
 use flust_engine::{CreateError, FlutterEngine, FlutterEngineError};
 use flust_plugins::localization::LocalizationPlugin;
@@ -16,12 +17,14 @@ use winit::error::EventLoopError;
 use winit::event::WindowEvent;
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::platform::wayland::WindowAttributesExtWayland;
+use winit::platform::x11::WindowAttributesExtX11;
 use winit::window::{WindowAttributes, WindowId};
 
+use crate::egl::create_window_contexts;
 use crate::pointer::Pointers;
 use crate::view::WinitControllerError;
 use crate::window::{resize, FlutterEvent};
-use crate::{FlutterViewWinit, WinitPlatformTaskHandler};
+use crate::{FlutterViewWinit, FlutterWindow, WinitPlatformTaskHandler};
 
 pub struct WinitApplication {
     event_loop: EventLoop<FlutterEvent>,
@@ -32,6 +35,7 @@ pub struct WinitApplicationState {
     implicit_view: FlutterViewWinit,
     engine: FlutterEngine,
     pointers: Pointers,
+    first_frame_callback: Option<Box<dyn FnOnce() + Send>>,
 }
 
 impl WinitApplication {
@@ -45,6 +49,13 @@ impl WinitApplication {
         let platform_task_handler =
             Arc::new(WinitPlatformTaskHandler::new(event_loop.create_proxy()));
 
+        // The window is created before the engine, so that its context can
+        // be checked for the requirements of Impeller.
+        let (window, context, resource_context) =
+            create_window_contexts(window_attributes, &event_loop)
+                .map_err(WinitControllerError::from)?;
+        let context = Arc::new(std::sync::Mutex::new(context));
+
         let engine = FlutterEngineBuilder::new()
             .with_platform_handler(platform_task_handler)
             .with_aot_library_path(attributes.aot_library_path)
@@ -52,10 +63,29 @@ impl WinitApplication {
             .with_icu_data_path(attributes.icu_data_path)
             .with_persistent_cache_path(attributes.persistent_cache_path.clone())
             .with_args(attributes.args)
+            .with_enable_impeller(attributes.enable_impeller)
+            .with_impeller_requirements_check({
+                let context = context.clone();
+                move || {
+                    context
+                        .lock()
+                        .unwrap()
+                        .check_impeller_requirements()
+                        .map_err(|err| err.to_string())
+                }
+            })
             .build()?;
 
-        let implicit_view =
-            FlutterViewWinit::new_implicit(&event_loop, engine.clone(), window_attributes)?;
+        let window = FlutterWindow::with_contexts(
+            IMPLICIT_VIEW_ID,
+            &event_loop,
+            engine.clone(),
+            window,
+            context,
+            resource_context,
+        )
+        .map_err(WinitControllerError::from)?;
+        let implicit_view = FlutterViewWinit::new(IMPLICIT_VIEW_ID, window);
 
         let pointers = Pointers::new(engine.clone());
 
@@ -65,6 +95,7 @@ impl WinitApplication {
             implicit_view,
             engine,
             pointers,
+            first_frame_callback: None,
         };
 
         Ok(WinitApplication { event_loop, state })
@@ -79,6 +110,10 @@ impl WinitApplication {
         // `FlutterEngineRun` comment in `embedder.h` for additional context.
         state.engine.run()?;
 
+        if let Some(callback) = state.first_frame_callback.take() {
+            state.engine.set_next_frame_callback(move |_| callback())?;
+        }
+
         let window = state.implicit_view.window();
         let context = window.context();
 
@@ -126,6 +161,16 @@ impl WinitApplication {
     {
         self.state.implicit_view.add_plugin(plugin);
     }
+
+    /// Sets a one-shot callback called once Flutter has rendered its first
+    /// frame (e.g.: for hiding a native splash screen). The callback is called
+    /// on the platform thread.
+    pub fn on_first_frame<F>(&mut self, callback: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.state.first_frame_callback = Some(Box::new(callback));
+    }
 }
 
 impl ApplicationHandler<FlutterEvent> for WinitApplicationState {
@@ -204,9 +249,11 @@ impl From<ApplicationAttributes> for WinitWindowAttributes {
             .into()
         });
 
-        let attributes = value.app_id.map_or(attributes.clone(), |app_id| {
-            attributes.with_name(app_id, "")
-        });
+        // Used as the Wayland app id and as both the class and instance parts
+        // of the X11 `WM_CLASS`.
+        let app_id = value.app_id_or_default();
+        let attributes = WindowAttributesExtWayland::with_name(attributes, &app_id, &app_id);
+        let attributes = WindowAttributesExtX11::with_name(attributes, &app_id, &app_id);
 
         Self(attributes)
     }
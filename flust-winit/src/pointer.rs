@@ -7,25 +7,55 @@ use winit::event::{DeviceId, ElementState, MouseButton, TouchPhase};
 
 pub struct Pointer {
     device_id: DeviceId,
-    touch: bool,
     position: (f64, f64),
-    pressed: u32,
+    buttons: FlutterPointerMouseButtons,
 }
 
 impl Pointer {
-    pub fn new(device_id: DeviceId, touch: bool) -> Self {
+    pub fn new(device_id: DeviceId) -> Self {
         Self {
             device_id,
-            touch,
             position: (0.0, 0.0),
-            pressed: 0,
+            buttons: FlutterPointerMouseButtons::empty(),
         }
     }
+
+    fn move_phase(&self) -> FlutterPointerPhase {
+        if self.buttons.is_empty() {
+            FlutterPointerPhase::Hover
+        } else {
+            FlutterPointerPhase::Move
+        }
+    }
+
+    /// Releases the buttons of the pointer leaving the window, returning the
+    /// engine phases to send. Leaving mid-drag (e.g.: when the window is
+    /// alt-tabbed away) aborts the gestures in progress, as the release won't
+    /// be delivered.
+    fn leave_phases(&mut self) -> &'static [FlutterPointerPhase] {
+        use FlutterPointerPhase::*;
+
+        if self.buttons.is_empty() {
+            return &[Remove];
+        }
+
+        self.buttons = FlutterPointerMouseButtons::empty();
+        &[Cancel, Remove]
+    }
 }
 
+/// Offset of the engine device ids of mice, which are assigned in the order
+/// the mice are first used and stay the same for the lifetime of the app.
+const MOUSE_DEVICE_ID_OFFSET: i32 = 10;
+
+/// Offset of the engine device ids of touches (see [`TouchDevices`]), placed
+/// well above the mouse device ids so that the two never overlap.
+const TOUCH_DEVICE_ID_OFFSET: i32 = 1 << 16;
+
 pub struct Pointers {
     engine: FlutterEngine,
     pointers: Vec<Pointer>,
+    touches: TouchDevices,
 }
 
 impl Pointers {
@@ -33,70 +63,64 @@ impl Pointers {
         Self {
             engine,
             pointers: Default::default(),
+            touches: Default::default(),
         }
     }
 
-    pub fn index(&mut self, device_id: DeviceId, touch: bool) -> usize {
-        if let Some(index) = self
-            .pointers
-            .iter()
-            .position(|p| p.device_id == device_id && p.touch == touch)
-        {
+    pub fn index(&mut self, device_id: DeviceId) -> usize {
+        if let Some(index) = self.pointers.iter().position(|p| p.device_id == device_id) {
             index
         } else {
             let index = self.pointers.len();
-            self.pointers.push(Pointer::new(device_id, touch));
+            self.pointers.push(Pointer::new(device_id));
             index
         }
     }
 
     pub fn enter(&mut self, view_id: FlutterViewId, device_id: DeviceId) {
-        let device = self.index(device_id, false);
+        let device = self.index(device_id);
         let pointer = &self.pointers[device];
         self.engine.send_pointer_event(FlutterPointerEvent::new(
-            device as i32 + 10,
+            device as i32 + MOUSE_DEVICE_ID_OFFSET,
             FlutterPointerPhase::Add,
             pointer.position,
             FlutterPointerSignalKind::None,
             (0.0, 0.0),
             FlutterPointerDeviceKind::Mouse,
-            FlutterPointerMouseButtons::Primary,
+            FlutterPointerMouseButtons::empty(),
             view_id,
         ));
     }
 
     pub fn leave(&mut self, view_id: FlutterViewId, device_id: DeviceId) {
-        let device = self.index(device_id, false);
-        let pointer = &self.pointers[device];
-        self.engine.send_pointer_event(FlutterPointerEvent::new(
-            device as i32 + 10,
-            FlutterPointerPhase::Remove,
-            pointer.position,
-            FlutterPointerSignalKind::None,
-            (0.0, 0.0),
-            FlutterPointerDeviceKind::Mouse,
-            FlutterPointerMouseButtons::Primary,
-            view_id,
-        ));
+        let device = self.index(device_id);
+        let pointer = &mut self.pointers[device];
+        for &phase in pointer.leave_phases() {
+            self.engine.send_pointer_event(FlutterPointerEvent::new(
+                device as i32 + MOUSE_DEVICE_ID_OFFSET,
+                phase,
+                pointer.position,
+                FlutterPointerSignalKind::None,
+                (0.0, 0.0),
+                FlutterPointerDeviceKind::Mouse,
+                FlutterPointerMouseButtons::empty(),
+                view_id,
+            ));
+        }
     }
 
     pub fn moved(&mut self, view_id: FlutterViewId, device_id: DeviceId, position: (f64, f64)) {
-        let device = self.index(device_id, false);
+        let device = self.index(device_id);
         self.pointers[device].position = position;
         let pointer = &self.pointers[device];
-        let phase = if pointer.pressed == 0 {
-            FlutterPointerPhase::Hover
-        } else {
-            FlutterPointerPhase::Move
-        };
         self.engine.send_pointer_event(FlutterPointerEvent::new(
-            device as i32 + 10,
-            phase,
+            device as i32 + MOUSE_DEVICE_ID_OFFSET,
+            pointer.move_phase(),
             pointer.position,
             FlutterPointerSignalKind::None,
             (0.0, 0.0),
             FlutterPointerDeviceKind::Mouse,
-            FlutterPointerMouseButtons::Primary,
+            pointer.buttons,
             view_id,
         ));
     }
@@ -108,52 +132,41 @@ impl Pointers {
         state: ElementState,
         button: MouseButton,
     ) {
-        let device = self.index(device_id, false);
-        match state {
-            ElementState::Pressed => self.pointers[device].pressed += 1,
-            ElementState::Released => self.pointers[device].pressed -= 1,
-        }
-        let pointer = &self.pointers[device];
-        let phase = match state {
-            ElementState::Pressed => FlutterPointerPhase::Down,
-            ElementState::Released => FlutterPointerPhase::Up,
-        };
+        let device = self.index(device_id);
         let button = match button {
-            MouseButton::Left => FlutterPointerMouseButtons::Primary,
-            MouseButton::Right => FlutterPointerMouseButtons::Secondary,
-            MouseButton::Middle => FlutterPointerMouseButtons::Middle,
-            MouseButton::Other(4) => FlutterPointerMouseButtons::Back,
-            MouseButton::Other(5) => FlutterPointerMouseButtons::Forward,
-            _ => FlutterPointerMouseButtons::Primary,
+            MouseButton::Left => FlutterPointerMouseButtons::PRIMARY,
+            MouseButton::Right => FlutterPointerMouseButtons::SECONDARY,
+            MouseButton::Middle => FlutterPointerMouseButtons::MIDDLE,
+            MouseButton::Other(4) => FlutterPointerMouseButtons::BACK,
+            MouseButton::Other(5) => FlutterPointerMouseButtons::FORWARD,
+            _ => FlutterPointerMouseButtons::PRIMARY,
         };
+        let pointer = &mut self.pointers[device];
+        let before = pointer.buttons;
+        pointer.buttons.set(button, state == ElementState::Pressed);
         self.engine.send_pointer_event(FlutterPointerEvent::new(
-            device as i32 + 10,
-            phase,
+            device as i32 + MOUSE_DEVICE_ID_OFFSET,
+            FlutterPointerPhase::from_buttons_change(before, pointer.buttons),
             pointer.position,
             FlutterPointerSignalKind::None,
             (0.0, 0.0),
             FlutterPointerDeviceKind::Mouse,
-            button,
+            pointer.buttons,
             view_id,
         ));
     }
 
     pub fn wheel(&mut self, view_id: FlutterViewId, device_id: DeviceId, delta: (f64, f64)) {
-        let device = self.index(device_id, false);
+        let device = self.index(device_id);
         let pointer = &self.pointers[device];
-        let phase = if pointer.pressed == 0 {
-            FlutterPointerPhase::Hover
-        } else {
-            FlutterPointerPhase::Move
-        };
         self.engine.send_pointer_event(FlutterPointerEvent::new(
-            device as i32 + 10,
-            phase,
+            device as i32 + MOUSE_DEVICE_ID_OFFSET,
+            pointer.move_phase(),
             pointer.position,
             FlutterPointerSignalKind::Scroll,
             delta,
             FlutterPointerDeviceKind::Mouse,
-            FlutterPointerMouseButtons::Primary,
+            pointer.buttons,
             view_id,
         ));
     }
@@ -161,32 +174,120 @@ impl Pointers {
     pub fn touch(
         &mut self,
         view_id: FlutterViewId,
-        device_id: DeviceId,
+        id: u64,
         phase: TouchPhase,
         position: (f64, f64),
     ) {
-        let device = self.index(device_id, true);
-        let phase = match phase {
-            TouchPhase::Started => {
-                self.pointers[device].pressed += 1;
-                FlutterPointerPhase::Down
-            }
-            TouchPhase::Moved => FlutterPointerPhase::Move,
-            TouchPhase::Ended => FlutterPointerPhase::Up,
-            TouchPhase::Cancelled => FlutterPointerPhase::Cancel,
+        let Some((device, phases)) = self.touches.phases(id, phase) else {
+            return;
         };
-        self.engine.send_pointer_event(FlutterPointerEvent::new(
-            self.pointers[device].pressed as i32 - 1,
-            phase,
-            position,
-            FlutterPointerSignalKind::None,
-            (0.0, 0.0),
-            FlutterPointerDeviceKind::Touch,
-            FlutterPointerMouseButtons::Primary,
-            view_id,
-        ));
-        if phase == FlutterPointerPhase::Up || phase == FlutterPointerPhase::Cancel {
-            self.pointers[device].pressed -= 1;
+        for &phase in phases {
+            self.engine.send_pointer_event(FlutterPointerEvent::new(
+                device,
+                phase,
+                position,
+                FlutterPointerSignalKind::None,
+                (0.0, 0.0),
+                FlutterPointerDeviceKind::Touch,
+                FlutterPointerMouseButtons::PRIMARY,
+                view_id,
+            ));
+        }
+    }
+}
+
+/// Engine device ids of the active touches.
+///
+/// Like the other Flutter desktop embedders, every touch is added to the
+/// engine when it starts and removed when it ends, so the device id of a touch
+/// is stable within a down-up sequence and only reused for later touches
+/// after the previous one was removed. The engine assigns a fresh pointer
+/// identifier to every down-up sequence, so two consecutive taps with the
+/// same finger (or reused device id) are never merged into a drag.
+#[derive(Default)]
+struct TouchDevices {
+    /// The winit touch id and the engine device id of every active touch.
+    active: Vec<(u64, i32)>,
+}
+
+impl TouchDevices {
+    /// Returns the device id of touch `id` and the engine phases to send for
+    /// `phase`, or `None` for events of touches that aren't active.
+    fn phases(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+    ) -> Option<(i32, &'static [FlutterPointerPhase])> {
+        use FlutterPointerPhase::*;
+
+        let active = self.active.iter().position(|&(active, _)| active == id);
+        match (phase, active) {
+            (TouchPhase::Started, Some(index)) => Some((self.active[index].1, &[Down])),
+            (TouchPhase::Started, None) => {
+                let device = (TOUCH_DEVICE_ID_OFFSET..)
+                    .find(|device| self.active.iter().all(|&(_, active)| active != *device))
+                    .unwrap();
+                self.active.push((id, device));
+                Some((device, &[Add, Down]))
+            }
+            (TouchPhase::Moved, Some(index)) => Some((self.active[index].1, &[Move])),
+            (TouchPhase::Ended, Some(index)) => Some((self.active.remove(index).1, &[Up, Remove])),
+            (TouchPhase::Cancelled, Some(index)) => {
+                Some((self.active.remove(index).1, &[Cancel, Remove]))
+            }
+            (_, None) => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FlutterPointerPhase::*;
+
+    #[test]
+    fn touch_sequences_are_added_and_removed() {
+        let mut touches = TouchDevices::default();
+
+        let events: Vec<_> = [
+            (1, TouchPhase::Started),
+            (1, TouchPhase::Ended),
+            (2, TouchPhase::Started),
+            (3, TouchPhase::Started),
+            (2, TouchPhase::Moved),
+            (2, TouchPhase::Cancelled),
+            (3, TouchPhase::Ended),
+            (3, TouchPhase::Moved),
+        ]
+        .into_iter()
+        .filter_map(|(id, phase)| touches.phases(id, phase))
+        .collect();
+
+        let first = TOUCH_DEVICE_ID_OFFSET;
+        let second = TOUCH_DEVICE_ID_OFFSET + 1;
+        assert_eq!(
+            events,
+            vec![
+                (first, &[Add, Down][..]),
+                (first, &[Up, Remove][..]),
+                (first, &[Add, Down][..]),
+                (second, &[Add, Down][..]),
+                (first, &[Move][..]),
+                (first, &[Cancel, Remove][..]),
+                (second, &[Up, Remove][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaving_mid_drag_cancels_before_removing() {
+        // The dummy id is only compared, it never reaches winit.
+        let mut pointer = Pointer::new(unsafe { DeviceId::dummy() });
+        assert_eq!(pointer.leave_phases(), [Remove]);
+
+        pointer.buttons = FlutterPointerMouseButtons::PRIMARY;
+        assert_eq!(pointer.leave_phases(), [Cancel, Remove]);
+        assert!(pointer.buttons.is_empty());
+        assert_eq!(pointer.leave_phases(), [Remove]);
+    }
+}
@@ -17,6 +17,7 @@ use flust_plugins::localization::LocalizationPlugin;
 use flust_plugins::navigation::NavigationPlugin;
 use flust_plugins::platform::PlatformPlugin;
 use flust_plugins::settings::SettingsPlugin;
+use flust_plugins::spellcheck::SpellCheckPlugin;
 use flust_plugins::system::SystemPlugin;
 use flust_plugins::textinput::TextInputPlugin;
 use flust_plugins::window::WindowPlugin;
@@ -59,6 +60,26 @@ impl FlutterWindow {
     ) -> Result<Self, Box<dyn Error>> {
         let (window, context, resource_context) = create_window_contexts(attributes, event_loop)?;
         let context = Arc::new(std::sync::Mutex::new(context));
+        Self::with_contexts(
+            view_id,
+            event_loop,
+            engine,
+            window,
+            context,
+            resource_context,
+        )
+    }
+
+    /// Creates a window from a window created beforehand, together with its
+    /// contexts (see [`create_window_contexts`]).
+    pub(crate) fn with_contexts(
+        view_id: FlutterViewId,
+        event_loop: &EventLoop<FlutterEvent>,
+        engine: FlutterEngine,
+        window: Window,
+        context: Arc<std::sync::Mutex<Context>>,
+        resource_context: ResourceContext,
+    ) -> Result<Self, Box<dyn Error>> {
         let resource_context = Arc::new(std::sync::Mutex::new(resource_context));
         let window = Arc::new(Mutex::new(window));
 
@@ -82,6 +103,7 @@ impl FlutterWindow {
         plugins.add_plugin(&engine, NavigationPlugin::default());
         plugins.add_plugin(&engine, PlatformPlugin::new(platform_handler));
         plugins.add_plugin(&engine, SettingsPlugin::default());
+        plugins.add_plugin(&engine, SpellCheckPlugin::default());
         plugins.add_plugin(&engine, SystemPlugin::default());
         plugins.add_plugin(&engine, TextInputPlugin::new(textinput_handler));
         plugins.add_plugin(&engine, WindowPlugin::new(window_handler));
@@ -218,12 +240,12 @@ impl FlutterWindow {
                 pointers.wheel(self.view_id, device_id, delta);
             }
             WindowEvent::Touch(Touch {
-                device_id,
+                id,
                 phase,
                 location,
                 ..
             }) => {
-                pointers.touch(self.view_id, device_id, phase, location.into());
+                pointers.touch(self.view_id, id, phase, location.into());
             }
             WindowEvent::KeyboardInput {
                 event: KeyEvent {
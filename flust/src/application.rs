@@ -69,6 +69,21 @@ impl Application {
             Self::Winit(app) => app.add_plugin(plugin),
         }
     }
+
+    /// Sets a one-shot callback called once Flutter has rendered its first
+    /// frame (e.g.: for hiding a native splash screen).
+    pub fn on_first_frame<F>(&mut self, callback: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match self {
+            #[cfg(feature = "flust-sctk")]
+            Self::Sctk(app) => app.on_first_frame(callback),
+
+            #[cfg(feature = "flust-winit")]
+            Self::Winit(app) => app.on_first_frame(callback),
+        }
+    }
 }
 
 /// Configure application before creation.
@@ -122,6 +137,11 @@ impl ApplicationBuilder {
         self
     }
 
+    pub fn with_enable_impeller(mut self, enabled: bool) -> Self {
+        self.attributes.enable_impeller = enabled;
+        self
+    }
+
     pub fn with_aot_library_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.attributes.aot_library_path = path.into();
         self
@@ -155,9 +175,10 @@ impl ApplicationBuilder {
                 .unwrap_or_default();
         }
 
+        // The AOT library is detected by the engine from the assets path
+        // (i.e.: `lib/libapp.so` in the bundle) when not set.
         if !&self.attributes.assets_path.as_os_str().is_empty()
             && !&self.attributes.icu_data_path.as_os_str().is_empty()
-            && !&self.attributes.aot_library_path.as_os_str().is_empty()
         {
             return;
         }
@@ -167,10 +188,6 @@ impl ApplicationBuilder {
             return;
         };
 
-        if self.attributes.aot_library_path.as_os_str().is_empty() {
-            self.attributes.aot_library_path = executable_dir.join("lib").join("libapp.so");
-        }
-
         if self.attributes.assets_path.as_os_str().is_empty() {
             self.attributes.assets_path = executable_dir.join("data").join("flutter_assets");
         }